@@ -0,0 +1,11 @@
+create table idempotency (
+    idempotency_key text not null,
+    identity text not null,
+    request_hash text not null,
+    subject text,
+    tx_id text,
+    created_time timestamp not null,
+    primary key(idempotency_key, identity)
+);
+
+create index idempotency_tx_id_idx on idempotency(tx_id);
@@ -0,0 +1,10 @@
+create table txreceipt (
+    tx_id text primary key,
+    stage text not null,
+    block_id text,
+    identity text,
+    delta text,
+    contradiction text,
+    error text,
+    updated_time timestamp not null
+);
@@ -0,0 +1,2 @@
+drop table outboxsubject;
+drop table outbox;
@@ -0,0 +1,22 @@
+create table outbox (
+    id serial primary key,
+    subject text not null,
+    transaction text not null,
+    status text not null,
+    attempts integer not null default 0,
+    last_error text,
+    tx_id text,
+    queued_time timestamp not null,
+    next_attempt_time timestamp not null
+);
+
+create index outbox_status_idx on outbox(status, id);
+
+create table outboxsubject (
+    outbox_id integer not null,
+    namespace text not null,
+    subject text not null,
+    primary key (outbox_id, namespace, subject)
+);
+
+create index outboxsubject_subject_idx on outboxsubject(subject, namespace);
@@ -0,0 +1,3 @@
+alter table txreceipt drop column key_version;
+
+drop table chronicle_key;
@@ -0,0 +1 @@
+drop table opa_decision;
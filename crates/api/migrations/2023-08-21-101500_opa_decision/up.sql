@@ -0,0 +1,14 @@
+create table opa_decision (
+    decision_id text primary key not null,
+    decision_time timestamp not null,
+    identity text not null,
+    policy text not null,
+    revision text not null,
+    path text not null,
+    decision text,
+    error text,
+    input text not null,
+    eval_ns bigint not null
+);
+
+create index opa_decision_identity_idx on opa_decision(identity, decision_time);
@@ -0,0 +1,21 @@
+drop table outboxsubject;
+drop table outbox;
+drop table txreceipt;
+drop table idempotency;
+drop table activity_attribute;
+drop table agent_attribute;
+drop table entity_attribute;
+drop table hadidentity;
+drop table attribution;
+drop table wasinformedby;
+drop table usage;
+drop table association;
+drop table generation;
+drop table derivation;
+drop table delegation;
+drop table entity;
+drop table activity;
+drop table agent;
+drop table identity;
+drop table ledgersync;
+drop table namespace;
@@ -0,0 +1,207 @@
+create table namespace (
+    id integer primary key autoincrement,
+    external_id text not null,
+    uuid text not null,
+    unique(external_id)
+);
+
+create unique index namespace_idx on namespace(external_id,uuid);
+
+create table ledgersync (
+    tx_id text primary key not null,
+    bc_offset text,
+    sync_time timestamp
+);
+
+create index ledger_index on ledgersync(sync_time,bc_offset);
+
+create table identity (
+    id integer primary key autoincrement,
+    namespace_id integer not null,
+    public_key text not null,
+    foreign key(namespace_id) references namespace(id)
+);
+
+create index identity_public_key_idx on identity(public_key);
+
+create table agent (
+    id integer primary key autoincrement,
+    external_id text not null,
+    namespace_id integer not null,
+    domaintype text,
+    current integer not null,
+    identity_id integer,
+    foreign key(identity_id) references identity(id),
+    foreign key(namespace_id) references namespace(id),
+    unique(external_id,namespace_id)
+);
+
+create index agent_external_id_idx on agent(external_id,namespace_id);
+
+create table activity (
+    id integer primary key autoincrement,
+    external_id text not null,
+    namespace_id integer not null,
+    domaintype text,
+    started timestamp,
+    ended timestamp,
+    foreign key(namespace_id) references namespace(id),
+    unique(external_id,namespace_id)
+);
+
+create table entity (
+    id integer primary key autoincrement,
+    external_id text not null,
+    namespace_id integer not null,
+    domaintype text,
+    foreign key(namespace_id) references namespace(id),
+    unique(external_id,namespace_id)
+);
+
+create table delegation (
+    delegate_id integer not null,
+    responsible_id integer not null,
+    activity_id integer not null default -1,
+    role text not null default '',
+    foreign key(delegate_id) references agent(id),
+    foreign key(responsible_id) references agent(id),
+    foreign key(activity_id) references activity(id),
+    primary key(responsible_id,delegate_id,activity_id,role)
+);
+
+create table derivation (
+    activity_id integer not null default -1,
+    generated_entity_id integer not null,
+    used_entity_id integer not null,
+    typ integer not null default -1,
+    foreign key(activity_id) references activity(id),
+    foreign key(generated_entity_id) references entity(id),
+    foreign key(used_entity_id) references entity(id),
+    primary key(activity_id,used_entity_id,generated_entity_id,typ)
+);
+
+create table generation (
+    activity_id integer not null,
+    generated_entity_id integer not null,
+    foreign key(activity_id) references activity(id),
+    foreign key(generated_entity_id) references entity(id),
+    primary key(activity_id,generated_entity_id)
+);
+
+create table association (
+    agent_id integer not null,
+    activity_id integer not null,
+    role text not null default '',
+    foreign key(agent_id) references agent(id),
+    foreign key(activity_id) references activity(id),
+    primary key(agent_id, activity_id, role)
+);
+
+create table usage (
+    activity_id integer not null,
+    entity_id integer not null,
+    foreign key(entity_id) references entity(id),
+    foreign key(activity_id) references activity(id),
+    primary key(activity_id,entity_id)
+);
+
+create table wasinformedby (
+    activity_id integer not null,
+    informing_activity_id integer not null,
+    foreign key(activity_id) references activity(id),
+    foreign key(informing_activity_id) references activity(id),
+    primary key(activity_id,informing_activity_id)
+);
+
+create table attribution (
+    agent_id integer not null,
+    entity_id integer not null,
+    role text not null default '',
+    foreign key(agent_id) references agent(id),
+    foreign key(entity_id) references entity(id),
+    primary key(agent_id, entity_id, role)
+);
+
+create table hadidentity (
+    agent_id integer not null,
+    identity_id integer not null,
+    foreign key(agent_id) references agent(id),
+    foreign key(identity_id) references identity(id),
+    primary key(agent_id,identity_id)
+);
+
+create table entity_attribute (
+    entity_id integer not null,
+    typename text not null,
+    value text not null,
+    foreign key(entity_id) references entity(id),
+    primary key(entity_id,typename)
+);
+
+create table agent_attribute (
+    agent_id integer not null,
+    typename text not null,
+    value text not null,
+    foreign key(agent_id) references agent(id),
+    primary key(agent_id,typename)
+);
+
+create table activity_attribute (
+    activity_id integer not null,
+    typename text not null,
+    value text not null,
+    foreign key(activity_id) references activity(id),
+    primary key(activity_id,typename)
+);
+
+create table idempotency (
+    idempotency_key text not null,
+    identity text not null,
+    request_hash text not null,
+    subject text,
+    tx_id text,
+    created_time timestamp not null,
+    primary key(idempotency_key, identity)
+);
+
+create index idempotency_tx_id_idx on idempotency(tx_id);
+
+create table txreceipt (
+    tx_id text primary key not null,
+    stage text not null,
+    block_id text,
+    identity text,
+    delta text,
+    contradiction text,
+    error text,
+    updated_time timestamp not null
+);
+
+create table outbox (
+    id integer primary key autoincrement,
+    subject text not null,
+    "transaction" text not null,
+    status text not null,
+    attempts integer not null default 0,
+    last_error text,
+    tx_id text,
+    queued_time timestamp not null,
+    next_attempt_time timestamp not null
+);
+
+create index outbox_status_idx on outbox(status, id);
+
+create table outboxsubject (
+    outbox_id integer not null,
+    namespace text not null,
+    subject text not null,
+    primary key (outbox_id, namespace, subject)
+);
+
+create index outboxsubject_subject_idx on outboxsubject(subject, namespace);
+
+insert into namespace(id, external_id, uuid)
+    values (-1, 'hidden entry for Option None', '00000000-0000-0000-0000-000000000000');
+
+insert into activity(id, external_id, namespace_id)
+    values (-1, 'hidden entry for Option None', -1);
@@ -0,0 +1,7 @@
+create table chronicle_key (
+    version integer primary key not null,
+    public_key text not null,
+    block_id text not null
+);
+
+alter table txreceipt add column key_version integer;
@@ -0,0 +1,7 @@
+create table txsubject (
+    tx_id text not null,
+    subject text not null,
+    primary key (tx_id, subject)
+);
+
+create index txsubject_subject_idx on txsubject(subject);
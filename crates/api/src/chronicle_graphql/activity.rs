@@ -1,6 +1,9 @@
 use super::{Activity, Agent, Entity, Namespace, Store};
 use async_graphql::Context;
-use common::prov::Role;
+use common::{
+    commands::OutboxStatus,
+    prov::{ActivityId, Role},
+};
 use diesel::prelude::*;
 use std::collections::HashMap;
 
@@ -21,7 +24,11 @@ pub async fn namespace<'a>(
 pub async fn was_associated_with<'a>(
     id: i32,
     ctx: &Context<'a>,
-) -> async_graphql::Result<Vec<(Agent, Option<Role>, Option<Agent>, Option<Role>)>> {
+) -> async_graphql::Result<Option<Vec<(Agent, Option<Role>, Option<Agent>, Option<Role>)>>> {
+    if super::masking::is_masked(ctx, "activity", id, "wasAssociatedWith").await? {
+        return Ok(None);
+    }
+
     use crate::persistence::schema::{agent, association, delegation};
 
     #[derive(Queryable)]
@@ -100,10 +107,14 @@ pub async fn was_associated_with<'a>(
         })
         .collect();
 
-    Ok(res)
+    Ok(Some(res))
 }
 
-pub async fn used<'a>(id: i32, ctx: &Context<'a>) -> async_graphql::Result<Vec<Entity>> {
+pub async fn used<'a>(id: i32, ctx: &Context<'a>) -> async_graphql::Result<Option<Vec<Entity>>> {
+    if super::masking::is_masked(ctx, "activity", id, "used").await? {
+        return Ok(None);
+    }
+
     use crate::persistence::schema::usage::{self, dsl};
 
     let store = ctx.data_unchecked::<Store>();
@@ -117,13 +128,17 @@ pub async fn used<'a>(id: i32, ctx: &Context<'a>) -> async_graphql::Result<Vec<E
         .select(Entity::as_select())
         .load::<Entity>(&mut connection)?;
 
-    Ok(res)
+    Ok(Some(res))
 }
 
 pub async fn was_informed_by<'a>(
     id: i32,
     ctx: &Context<'a>,
-) -> async_graphql::Result<Vec<Activity>> {
+) -> async_graphql::Result<Option<Vec<Activity>>> {
+    if super::masking::is_masked(ctx, "activity", id, "wasInformedBy").await? {
+        return Ok(None);
+    }
+
     use crate::persistence::schema::wasinformedby::{self, dsl};
 
     let store = ctx.data_unchecked::<Store>();
@@ -140,10 +155,17 @@ pub async fn was_informed_by<'a>(
             .select(Activity::as_select())
             .load::<Activity>(&mut connection)?;
 
-    Ok(res)
+    Ok(Some(res))
 }
 
-pub async fn generated<'a>(id: i32, ctx: &Context<'a>) -> async_graphql::Result<Vec<Entity>> {
+pub async fn generated<'a>(
+    id: i32,
+    ctx: &Context<'a>,
+) -> async_graphql::Result<Option<Vec<Entity>>> {
+    if super::masking::is_masked(ctx, "activity", id, "generated").await? {
+        return Ok(None);
+    }
+
     use crate::persistence::schema::generation::{self, dsl};
 
     let store = ctx.data_unchecked::<Store>();
@@ -156,7 +178,7 @@ pub async fn generated<'a>(id: i32, ctx: &Context<'a>) -> async_graphql::Result<
         .select(Entity::as_select())
         .load::<Entity>(&mut connection)?;
 
-    Ok(res)
+    Ok(Some(res))
 }
 
 pub async fn load_attribute<'a>(
@@ -164,6 +186,10 @@ pub async fn load_attribute<'a>(
     external_id: &str,
     ctx: &Context<'a>,
 ) -> async_graphql::Result<Option<serde_json::Value>> {
+    if super::masking::is_masked(ctx, "activity", id, external_id).await? {
+        return Ok(None);
+    }
+
     use crate::persistence::schema::activity_attribute;
 
     let store = ctx.data_unchecked::<Store>();
@@ -183,3 +209,17 @@ pub async fn load_attribute<'a>(
         .map(serde_json::from_str)
         .transpose()?)
 }
+
+/// Whether operations on the activity are queued in the outbox and yet to be committed
+pub async fn outbox_status<'a>(
+    namespace_id: i32,
+    external_id: &str,
+    ctx: &Context<'a>,
+) -> async_graphql::Result<Option<OutboxStatus>> {
+    super::outbox_status(
+        ctx,
+        namespace_id,
+        ActivityId::from_external_id(external_id).into(),
+    )
+    .await
+}
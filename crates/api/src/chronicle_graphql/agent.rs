@@ -2,7 +2,10 @@ use crate::chronicle_graphql::Entity;
 
 use super::{Agent, Identity, Namespace, Store};
 use async_graphql::Context;
-use common::prov::Role;
+use common::{
+    commands::OutboxStatus,
+    prov::{AgentId, Role},
+};
 use diesel::prelude::*;
 
 pub async fn namespace<'a>(
@@ -41,7 +44,11 @@ pub async fn identity<'a>(
 pub async fn acted_on_behalf_of<'a>(
     id: i32,
     ctx: &Context<'a>,
-) -> async_graphql::Result<Vec<(Agent, Option<Role>)>> {
+) -> async_graphql::Result<Option<Vec<(Agent, Option<Role>)>>> {
+    if super::masking::is_masked(ctx, "agent", id, "actedOnBehalfOf").await? {
+        return Ok(None);
+    }
+
     use crate::persistence::schema::{
         agent as agentdsl,
         delegation::{self, dsl},
@@ -51,22 +58,28 @@ pub async fn acted_on_behalf_of<'a>(
 
     let mut connection = store.pool.get()?;
 
-    Ok(delegation::table
-        .filter(dsl::delegate_id.eq(id))
-        .inner_join(agentdsl::table.on(dsl::responsible_id.eq(agentdsl::id)))
-        .order(agentdsl::external_id)
-        .select((Agent::as_select(), dsl::role))
-        .load::<(Agent, Role)>(&mut connection)?
-        .into_iter()
-        .map(|(a, r)| (a, if r.0.is_empty() { None } else { Some(r) }))
-        .collect())
+    Ok(Some(
+        delegation::table
+            .filter(dsl::delegate_id.eq(id))
+            .inner_join(agentdsl::table.on(dsl::responsible_id.eq(agentdsl::id)))
+            .order(agentdsl::external_id)
+            .select((Agent::as_select(), dsl::role))
+            .load::<(Agent, Role)>(&mut connection)?
+            .into_iter()
+            .map(|(a, r)| (a, if r.0.is_empty() { None } else { Some(r) }))
+            .collect(),
+    ))
 }
 
 /// Return the entities an agent has attributed to it along with the roles in which they were attributed
 pub async fn attribution<'a>(
     id: i32,
     ctx: &Context<'a>,
-) -> async_graphql::Result<Vec<(Entity, Option<Role>)>> {
+) -> async_graphql::Result<Option<Vec<(Entity, Option<Role>)>>> {
+    if super::masking::is_masked(ctx, "agent", id, "attribution").await? {
+        return Ok(None);
+    }
+
     use crate::persistence::schema::{
         attribution::{self, dsl},
         entity as entity_dsl,
@@ -76,15 +89,17 @@ pub async fn attribution<'a>(
 
     let mut connection = store.pool.get()?;
 
-    Ok(attribution::table
-        .filter(dsl::agent_id.eq(id))
-        .inner_join(entity_dsl::table.on(dsl::entity_id.eq(entity_dsl::id)))
-        .order(entity_dsl::external_id)
-        .select((Entity::as_select(), dsl::role))
-        .load::<(Entity, Role)>(&mut connection)?
-        .into_iter()
-        .map(|(entity, role)| (entity, if role.0.is_empty() { None } else { Some(role) }))
-        .collect())
+    Ok(Some(
+        attribution::table
+            .filter(dsl::agent_id.eq(id))
+            .inner_join(entity_dsl::table.on(dsl::entity_id.eq(entity_dsl::id)))
+            .order(entity_dsl::external_id)
+            .select((Entity::as_select(), dsl::role))
+            .load::<(Entity, Role)>(&mut connection)?
+            .into_iter()
+            .map(|(entity, role)| (entity, if role.0.is_empty() { None } else { Some(role) }))
+            .collect(),
+    ))
 }
 
 pub async fn load_attribute<'a>(
@@ -92,6 +107,10 @@ pub async fn load_attribute<'a>(
     external_id: &str,
     ctx: &Context<'a>,
 ) -> async_graphql::Result<Option<serde_json::Value>> {
+    if super::masking::is_masked(ctx, "agent", id, external_id).await? {
+        return Ok(None);
+    }
+
     use crate::persistence::schema::agent_attribute;
 
     let store = ctx.data_unchecked::<Store>();
@@ -111,3 +130,17 @@ pub async fn load_attribute<'a>(
         .map(serde_json::from_str)
         .transpose()?)
 }
+
+/// Whether operations on the agent are queued in the outbox and yet to be committed
+pub async fn outbox_status<'a>(
+    namespace_id: i32,
+    external_id: &str,
+    ctx: &Context<'a>,
+) -> async_graphql::Result<Option<OutboxStatus>> {
+    super::outbox_status(
+        ctx,
+        namespace_id,
+        AgentId::from_external_id(external_id).into(),
+    )
+    .await
+}
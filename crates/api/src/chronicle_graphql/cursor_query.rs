@@ -2,10 +2,11 @@ use async_graphql::{
     connection::{Edge, EmptyFields},
     OutputType,
 };
-use diesel::{pg::Pg, prelude::*, query_builder::*, r2d2::ConnectionManager, sql_types::BigInt};
+use common::database::{StoreBackend, StoreConnection};
+use diesel::{prelude::*, query_builder::*, r2d2::ConnectionManager, sql_types::BigInt};
 use r2d2::PooledConnection;
 
-type Conn = PooledConnection<ConnectionManager<PgConnection>>;
+type Conn = PooledConnection<ConnectionManager<StoreConnection>>;
 
 const DEFAULT_PAGE_SIZE: i32 = 10;
 
@@ -54,6 +55,25 @@ where
     gql
 }
 
+/// The offset and length of the page a connection's arguments select
+pub fn page(
+    after: Option<i32>,
+    before: Option<i32>,
+    first: Option<usize>,
+    last: Option<usize>,
+) -> (i64, i64) {
+    let mut start = after.map(|after| after + 1).unwrap_or(0) as usize;
+    let mut end = before.unwrap_or(DEFAULT_PAGE_SIZE) as usize;
+    if let Some(first) = first {
+        end = start + first
+    }
+    if let Some(last) = last {
+        start = if last > end - start { end } else { end - last };
+    };
+
+    (start as _, (end - start) as _)
+}
+
 impl<T> Cursorize for T {
     fn cursor(
         self,
@@ -62,28 +82,21 @@ impl<T> Cursorize for T {
         first: Option<usize>,
         last: Option<usize>,
     ) -> CursorPosition<Self> {
-        let mut start = after.map(|after| after + 1).unwrap_or(0) as usize;
-        let mut end = before.unwrap_or(DEFAULT_PAGE_SIZE) as usize;
-        if let Some(first) = first {
-            end = start + first
-        }
-        if let Some(last) = last {
-            start = if last > end - start { end } else { end - last };
-        };
+        let (start, limit) = page(after, before, first, last);
 
         CursorPosition {
             query: self,
-            start: start as _,
-            limit: (end - start) as _,
+            start,
+            limit,
         }
     }
 }
 
-impl<T> QueryFragment<Pg> for CursorPosition<T>
+impl<T> QueryFragment<StoreBackend> for CursorPosition<T>
 where
-    T: QueryFragment<Pg>,
+    T: QueryFragment<StoreBackend>,
 {
-    fn walk_ast<'a>(&'a self, mut out: AstPass<'_, 'a, Pg>) -> QueryResult<()> {
+    fn walk_ast<'a>(&'a self, mut out: AstPass<'_, 'a, StoreBackend>) -> QueryResult<()> {
         out.push_sql("SELECT *, COUNT(*) OVER () FROM (");
         self.query.walk_ast(out.reborrow())?;
         out.push_sql(") t LIMIT ");
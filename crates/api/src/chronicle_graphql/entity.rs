@@ -1,6 +1,9 @@
 use super::{Activity, Agent, Entity, Namespace, Store};
 use async_graphql::Context;
-use common::prov::{operations::DerivationType, Role};
+use common::{
+    commands::OutboxStatus,
+    prov::{operations::DerivationType, EntityId, Role},
+};
 use diesel::prelude::*;
 
 async fn typed_derivation<'a>(
@@ -45,7 +48,11 @@ pub async fn namespace<'a>(
 pub async fn was_attributed_to<'a>(
     id: i32,
     ctx: &Context<'a>,
-) -> async_graphql::Result<Vec<(Agent, Option<Role>)>> {
+) -> async_graphql::Result<Option<Vec<(Agent, Option<Role>)>>> {
+    if super::masking::is_masked(ctx, "entity", id, "wasAttributedTo").await? {
+        return Ok(None);
+    }
+
     use crate::persistence::schema::{agent, attribution};
 
     let store = ctx.data_unchecked::<Store>();
@@ -64,13 +71,17 @@ pub async fn was_attributed_to<'a>(
         })
         .collect();
 
-    Ok(res)
+    Ok(Some(res))
 }
 
 pub async fn was_generated_by<'a>(
     id: i32,
     ctx: &Context<'a>,
-) -> async_graphql::Result<Vec<Activity>> {
+) -> async_graphql::Result<Option<Vec<Activity>>> {
+    if super::masking::is_masked(ctx, "entity", id, "wasGeneratedBy").await? {
+        return Ok(None);
+    }
+
     use crate::persistence::schema::generation::{self, dsl};
 
     let store = ctx.data_unchecked::<Store>();
@@ -83,13 +94,17 @@ pub async fn was_generated_by<'a>(
         .select(Activity::as_select())
         .load::<Activity>(&mut connection)?;
 
-    Ok(res)
+    Ok(Some(res))
 }
 
 pub async fn was_derived_from<'a>(
     id: i32,
     ctx: &Context<'a>,
-) -> async_graphql::Result<Vec<Entity>> {
+) -> async_graphql::Result<Option<Vec<Entity>>> {
+    if super::masking::is_masked(ctx, "entity", id, "wasDerivedFrom").await? {
+        return Ok(None);
+    }
+
     use crate::persistence::schema::{
         derivation::{self, dsl},
         entity as entitydsl,
@@ -105,21 +120,45 @@ pub async fn was_derived_from<'a>(
         .select(Entity::as_select())
         .load::<Entity>(&mut connection)?;
 
-    Ok(res)
+    Ok(Some(res))
 }
 
 pub async fn had_primary_source<'a>(
     id: i32,
     ctx: &Context<'a>,
-) -> async_graphql::Result<Vec<Entity>> {
-    typed_derivation(id, ctx, DerivationType::PrimarySource).await
+) -> async_graphql::Result<Option<Vec<Entity>>> {
+    if super::masking::is_masked(ctx, "entity", id, "hadPrimarySource").await? {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        typed_derivation(id, ctx, DerivationType::PrimarySource).await?,
+    ))
 }
 
-pub async fn was_revision_of<'a>(id: i32, ctx: &Context<'a>) -> async_graphql::Result<Vec<Entity>> {
-    typed_derivation(id, ctx, DerivationType::Revision).await
+pub async fn was_revision_of<'a>(
+    id: i32,
+    ctx: &Context<'a>,
+) -> async_graphql::Result<Option<Vec<Entity>>> {
+    if super::masking::is_masked(ctx, "entity", id, "wasRevisionOf").await? {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        typed_derivation(id, ctx, DerivationType::Revision).await?,
+    ))
 }
-pub async fn was_quoted_from<'a>(id: i32, ctx: &Context<'a>) -> async_graphql::Result<Vec<Entity>> {
-    typed_derivation(id, ctx, DerivationType::Quotation).await
+pub async fn was_quoted_from<'a>(
+    id: i32,
+    ctx: &Context<'a>,
+) -> async_graphql::Result<Option<Vec<Entity>>> {
+    if super::masking::is_masked(ctx, "entity", id, "wasQuotedFrom").await? {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        typed_derivation(id, ctx, DerivationType::Quotation).await?,
+    ))
 }
 
 pub async fn load_attribute<'a>(
@@ -127,6 +166,10 @@ pub async fn load_attribute<'a>(
     external_id: &str,
     ctx: &Context<'a>,
 ) -> async_graphql::Result<Option<serde_json::Value>> {
+    if super::masking::is_masked(ctx, "entity", id, external_id).await? {
+        return Ok(None);
+    }
+
     use crate::persistence::schema::entity_attribute;
 
     let store = ctx.data_unchecked::<Store>();
@@ -146,3 +189,17 @@ pub async fn load_attribute<'a>(
         .map(serde_json::from_str)
         .transpose()?)
 }
+
+/// Whether operations on the entity are queued in the outbox and yet to be committed
+pub async fn outbox_status<'a>(
+    namespace_id: i32,
+    external_id: &str,
+    ctx: &Context<'a>,
+) -> async_graphql::Result<Option<OutboxStatus>> {
+    super::outbox_status(
+        ctx,
+        namespace_id,
+        EntityId::from_external_id(external_id).into(),
+    )
+    .await
+}
@@ -0,0 +1,185 @@
+//! Masking of the attributes and relations that policies withhold from a reader, applied alike
+//! to GraphQL fields and to the JSON-LD served at `/data`
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use async_graphql::Context;
+use common::{
+    identity::AuthId,
+    opa::ExecutorContext,
+    prov::{ActivityId, AgentId, ChronicleIri, EntityId, ExternalId, ExternalIdPart},
+};
+use diesel::prelude::*;
+use serde_json::{Map, Value};
+use tokio::sync::OnceCell;
+
+use super::{subscription::read_data_context, Store};
+
+/// The attributes and relations of `subject` in `namespace` that the policy masks from
+/// `identity`
+async fn masked_from(
+    opa: &ExecutorContext,
+    identity: &AuthId,
+    namespace: &ExternalId,
+    subject: &ChronicleIri,
+) -> BTreeSet<String> {
+    match read_data_context(identity, namespace, subject) {
+        Some(opa_data) => {
+            opa.for_namespace(namespace.as_str())
+                .masked(identity, &opa_data)
+                .await
+        }
+        None => BTreeSet::new(),
+    }
+}
+
+/// Null the masked attributes, under `value`, and relations of a compacted JSON-LD node.
+/// Relations outside Chronicle's context compact to `prov:` terms.
+fn mask_node(node: &mut Map<String, Value>, masked: &BTreeSet<String>) {
+    if let Some(Value::Object(attributes)) = node.get_mut("value") {
+        for (name, value) in attributes.iter_mut() {
+            if masked.contains(name) {
+                *value = Value::Null;
+            }
+        }
+    }
+
+    for (key, value) in node.iter_mut() {
+        if masked.contains(key.strip_prefix("prov:").unwrap_or(key)) {
+            *value = Value::Null;
+        }
+    }
+}
+
+/// Mask each agent, activity and entity in compacted JSON-LD as the policy masks it from
+/// `identity`, whether the JSON-LD is a single node or a `@graph` of them
+pub async fn mask_json_ld(opa: &ExecutorContext, identity: &AuthId, json: &mut Value) {
+    if !opa.masks() {
+        return;
+    }
+
+    let nodes = match json {
+        Value::Object(node) if !node.contains_key("@graph") => vec![node],
+        Value::Object(document) => match document.get_mut("@graph") {
+            Some(Value::Array(nodes)) => {
+                nodes.iter_mut().filter_map(Value::as_object_mut).collect()
+            }
+            _ => vec![],
+        },
+        _ => vec![],
+    };
+
+    for node in nodes {
+        let iri = |key: &str| {
+            node.get(key)
+                .and_then(Value::as_str)
+                .and_then(|iri| ChronicleIri::from_str(iri).ok())
+        };
+        let (subject, namespace) = match (iri("@id"), iri("namespace")) {
+            (Some(subject), Some(ChronicleIri::Namespace(namespace))) => (subject, namespace),
+            _ => continue,
+        };
+
+        let masked = masked_from(opa, identity, namespace.external_id_part(), &subject).await;
+        mask_node(node, &masked);
+    }
+}
+
+/// The attributes and relations masked from the requester of each agent, activity and entity
+/// that a request resolves, by prov type and database id, so that each is looked up and evaluated
+/// once however many of its fields are selected
+#[derive(Default)]
+pub struct MaskedFields(Mutex<HashMap<(&'static str, i32), MaskedOnce>>);
+
+type MaskedOnce = Arc<OnceCell<BTreeSet<String>>>;
+
+/// The attributes and relations of the agent, activity or entity with database `id` that are
+/// masked from the requester
+async fn masked_fields<'a>(
+    ctx: &Context<'a>,
+    opa: &ExecutorContext,
+    prov_type: &str,
+    id: i32,
+) -> async_graphql::Result<BTreeSet<String>> {
+    use crate::persistence::schema::{activity, agent, entity, namespace};
+
+    let store = ctx.data_unchecked::<Store>();
+    let mut connection = store.pool.get()?;
+
+    let subject = match prov_type {
+        "agent" => agent::table
+            .inner_join(namespace::table)
+            .filter(agent::id.eq(id))
+            .select((agent::external_id, namespace::external_id))
+            .first::<(String, String)>(&mut connection)
+            .map(|(external_id, ns)| {
+                (
+                    ChronicleIri::from(AgentId::from_external_id(external_id)),
+                    ns,
+                )
+            }),
+        "activity" => activity::table
+            .inner_join(namespace::table)
+            .filter(activity::id.eq(id))
+            .select((activity::external_id, namespace::external_id))
+            .first::<(String, String)>(&mut connection)
+            .map(|(external_id, ns)| {
+                (
+                    ChronicleIri::from(ActivityId::from_external_id(external_id)),
+                    ns,
+                )
+            }),
+        _ => entity::table
+            .inner_join(namespace::table)
+            .filter(entity::id.eq(id))
+            .select((entity::external_id, namespace::external_id))
+            .first::<(String, String)>(&mut connection)
+            .map(|(external_id, ns)| {
+                (
+                    ChronicleIri::from(EntityId::from_external_id(external_id)),
+                    ns,
+                )
+            }),
+    };
+    let (subject, namespace) = subject?;
+
+    let identity = ctx
+        .data_opt::<AuthId>()
+        .cloned()
+        .unwrap_or_else(AuthId::anonymous);
+
+    Ok(masked_from(opa, &identity, &ExternalId::from(namespace), &subject).await)
+}
+
+/// Whether the attribute or relation `name` of the agent, activity or entity with database `id`
+/// is masked from the requester, `prov_type` being which of them it is
+pub async fn is_masked<'a>(
+    ctx: &Context<'a>,
+    prov_type: &'static str,
+    id: i32,
+    name: &str,
+) -> async_graphql::Result<bool> {
+    let opa = match ctx.data_opt::<ExecutorContext>() {
+        Some(opa) if opa.masks() => opa,
+        _ => return Ok(false),
+    };
+
+    let cached = match ctx.data_opt::<MaskedFields>() {
+        Some(MaskedFields(cache)) => cache
+            .lock()
+            .unwrap()
+            .entry((prov_type, id))
+            .or_default()
+            .clone(),
+        None => return Ok(masked_fields(ctx, opa, prov_type, id).await?.contains(name)),
+    };
+
+    Ok(cached
+        .get_or_try_init(|| masked_fields(ctx, opa, prov_type, id))
+        .await?
+        .contains(name))
+}
@@ -1,29 +1,30 @@
 use async_graphql::{
     extensions::OpenTelemetry,
     http::{playground_source, GraphQLPlaygroundConfig, ALL_WEBSOCKET_PROTOCOLS},
+    parser::types::{ExecutableDocument, Selection},
     scalar, Context, Enum, Error, ErrorExtensions, Object, ObjectType, Schema, ServerError,
     SimpleObject, Subscription, SubscriptionType,
 };
 use async_graphql_poem::{
-    GraphQL, GraphQLBatchRequest, GraphQLBatchResponse, GraphQLProtocol, GraphQLSubscription,
+    GraphQLBatchRequest, GraphQLBatchResponse, GraphQLProtocol, GraphQLSubscription,
     GraphQLWebSocket,
 };
-use chrono::NaiveDateTime;
+use chronicle_signing::{ChronicleSigning, TaggedVerifyingKey};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use common::{
+    commands::{OutboxStatus, TransactionStage},
+    credential::{ContributingTransaction, ProvenanceCredential},
+    database::{ConnectionPool, StoreConnection},
     identity::{AuthId, IdentityError, JwtClaims, OpaData, SignedIdentity},
     ledger::{SubmissionError, SubmissionStage},
     opa::{ExecutorContext, OpaExecutorError},
     prov::{
-        to_json_ld::ToJson, ChronicleIri, ChronicleTransactionId, ExternalId, ExternalIdPart,
-        ProvModel,
+        to_json_ld::ToJson, ChronicleIri, ChronicleTransactionId, CompactionError, Contradiction,
+        DomaintypeId, ExternalId, ExternalIdPart, ProvModel,
     },
 };
 use derivative::*;
-use diesel::{
-    prelude::*,
-    r2d2::{ConnectionManager, Pool},
-    PgConnection, Queryable,
-};
+use diesel::{prelude::*, r2d2::ConnectionManager, Queryable};
 use futures::Stream;
 use lazy_static::lazy_static;
 use poem::{
@@ -52,8 +53,11 @@ use tokio::sync::{broadcast::error::RecvError, Semaphore};
 use tracing::{debug, error, instrument, warn};
 use url::Url;
 
-use self::authorization::TokenChecker;
-use crate::{ApiDispatch, ApiError, StoreError};
+use self::{
+    authorization::TokenChecker,
+    subscription::{CommitNotificationFilter, OperationKind},
+};
+use crate::{persistence::TransactionReceipt, ApiDispatch, ApiError, BlockWriteProofs, StoreError};
 
 #[macro_use]
 pub mod activity;
@@ -61,8 +65,10 @@ pub mod agent;
 mod authorization;
 mod cursor_query;
 pub mod entity;
+mod masking;
 pub mod mutation;
 pub mod query;
+pub mod subscription;
 
 pub type AuthorizationError = authorization::Error;
 
@@ -150,11 +156,15 @@ impl Namespace {
 /// * `submission_result` - result type of an operation
 ///
 /// * `tx_id` - transaction id for a submitted operation; returns `null` if `submission_result`
-/// is `SubmissionResult::AlreadyRecorded`
+///   is `SubmissionResult::AlreadyRecorded` or `SubmissionResult::Queued`
+///
+/// * `stage` - how far the transaction has progressed, when last known; a request retried with
+///   the same `Idempotency-Key` header returns the original submission with its current stage
 pub struct Submission {
     context: String,
     submission_result: SubmissionResult,
     tx_id: Option<String>,
+    stage: Option<TransactionStage>,
 }
 
 #[derive(Enum, PartialEq, Eq, Clone, Copy)]
@@ -164,9 +174,12 @@ pub struct Submission {
 ///
 /// * `Submission` - operation has been submitted
 /// * `AlreadyRecorded` - operation will not result in data changes and has not been submitted
+/// * `Queued` - the ledger could not be reached, so the operation has been queued in the outbox
+///   for later submission
 pub enum SubmissionResult {
     Submission,
     AlreadyRecorded,
+    Queued,
 }
 
 impl Submission {
@@ -175,6 +188,7 @@ impl Submission {
             context: subject.to_string(),
             submission_result: SubmissionResult::Submission,
             tx_id: Some(tx_id.to_string()),
+            stage: Some(TransactionStage::Submitted),
         }
     }
 
@@ -183,10 +197,42 @@ impl Submission {
             context: subject.to_string(),
             submission_result: SubmissionResult::AlreadyRecorded,
             tx_id: None,
+            stage: None,
+        }
+    }
+
+    pub fn from_queued(subject: &ChronicleIri) -> Self {
+        Submission {
+            context: subject.to_string(),
+            submission_result: SubmissionResult::Queued,
+            tx_id: None,
+            stage: None,
+        }
+    }
+
+    pub fn from_prior_submission(
+        subject: &ChronicleIri,
+        tx_id: Option<&ChronicleTransactionId>,
+        stage: Option<TransactionStage>,
+    ) -> Self {
+        Submission {
+            context: subject.to_string(),
+            submission_result: if tx_id.is_some() {
+                SubmissionResult::Submission
+            } else {
+                SubmissionResult::AlreadyRecorded
+            },
+            tx_id: tx_id.map(|tx_id| tx_id.to_string()),
+            stage,
         }
     }
 }
 
+/// A client supplied key, from the `Idempotency-Key` request header, identifying a request that
+/// may be retried
+#[derive(Debug, Clone)]
+pub struct IdempotencyKey(pub String);
+
 /// # `TimelineOrder`
 ///
 /// Specify the order in which multiple results of query data are returned
@@ -238,10 +284,8 @@ impl ErrorExtensions for GraphQlError {
     fn extend(&self) -> Error {
         Error::new(self.to_string()).extend_with(|_err, e| {
             if let Some(reasons) = Self::error_sources(custom_error::Error::source(&self)) {
-                let mut i = 1;
-                for reason in reasons {
+                for (i, reason) in (1..).zip(reasons) {
                     e.set(format!("reason {i}"), reason);
-                    i += 1;
                 }
             }
         })
@@ -252,11 +296,11 @@ impl ErrorExtensions for GraphQlError {
 #[derivative(Debug)]
 pub struct Store {
     #[derivative(Debug = "ignore")]
-    pub pool: Pool<ConnectionManager<PgConnection>>,
+    pub pool: ConnectionPool,
 }
 
 impl Store {
-    pub fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+    pub fn new(pool: ConnectionPool) -> Self {
         Store { pool }
     }
 }
@@ -270,7 +314,7 @@ pub struct Rejection {
     pub reason: String,
 }
 
-#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+#[derive(Enum, PartialEq, Eq, Clone, Copy, Debug)]
 pub enum Stage {
     Submit,
     Commit,
@@ -280,6 +324,19 @@ pub enum Stage {
 pub struct Delta(async_graphql::Value);
 scalar!(Delta);
 
+impl Delta {
+    async fn from_prov(prov: &ProvModel) -> Result<Option<Self>, async_graphql::Error> {
+        Ok(prov
+            .to_json()
+            .compact_stable_order()
+            .await
+            .ok()
+            .map(async_graphql::Value::from_json)
+            .transpose()?
+            .map(Delta))
+    }
+}
+
 #[derive(SimpleObject)]
 pub struct CommitIdentity {
     identity: String,
@@ -297,7 +354,7 @@ impl From<SignedIdentity> for CommitIdentity {
                 .unwrap_or_default(),
             verifying_key: identity
                 .verifying_key
-                .map(|x| hex::encode(x.to_bytes()))
+                .map(|x| x.to_string())
                 .unwrap_or_default(),
         }
     }
@@ -347,28 +404,116 @@ impl CommitNotification {
         }
     }
 
+    /// Convert a committed delta to a notification, or `None` if it does not meet `filter`
     pub async fn from_committed(
         tx_id: &ChronicleTransactionId,
         delta: Box<ProvModel>,
         id: SignedIdentity,
-    ) -> Result<Self, async_graphql::Error> {
-        Ok(CommitNotification {
+        filter: &CommitNotificationFilter,
+    ) -> Result<Option<Self>, async_graphql::Error> {
+        if !filter.matches_delta(&delta, &id) {
+            return Ok(None);
+        }
+
+        Ok(Some(CommitNotification {
             stage: Stage::Commit,
             tx_id: tx_id.to_string(),
             error: None,
-            delta: delta
-                .to_json()
-                .compact_stable_order()
-                .await
-                .ok()
-                .map(async_graphql::Value::from_json)
-                .transpose()?
-                .map(Delta),
+            delta: Delta::from_prov(&delta).await?,
             id: Some(id.into()),
+        }))
+    }
+}
+
+/// # `Transaction`
+///
+/// The progress of a Chronicle transaction, recorded by Chronicle on submission and from the
+/// ledger so that it can be queried after the fact
+#[derive(SimpleObject)]
+pub struct Transaction {
+    pub tx_id: String,
+    pub stage: TransactionStage,
+    /// The block in which the transaction was committed or contradicted
+    pub block_id: Option<String>,
+    pub identity: Option<CommitIdentity>,
+    /// The provenance recorded by a committed transaction
+    pub delta: Option<Delta>,
+    /// Why a contradicted transaction was rejected by the ledger
+    pub contradiction: Option<async_graphql::Json<Contradiction>>,
+    pub error: Option<String>,
+    pub updated: DateTime<Utc>,
+}
+
+impl Transaction {
+    pub async fn from_receipt(receipt: TransactionReceipt) -> Result<Self, async_graphql::Error> {
+        Ok(Transaction {
+            tx_id: receipt.tx_id.to_string(),
+            stage: receipt.stage,
+            block_id: receipt.block_id.map(|block_id| block_id.to_string()),
+            identity: receipt.identity.map(CommitIdentity::from),
+            delta: match receipt.delta {
+                Some(delta) => Delta::from_prov(&delta).await?,
+                None => None,
+            },
+            contradiction: receipt.contradiction.map(async_graphql::Json),
+            error: receipt.error,
+            updated: DateTime::from_naive_utc_and_offset(receipt.updated_time, Utc),
+        })
+    }
+}
+
+/// # `OutboxItem`
+///
+/// A transaction queued while the ledger was unreachable. The effect of a `PENDING` transaction is
+/// already reflected in query results, but has yet to be recorded on the ledger. So is that of a
+/// `FAILED` transaction, which holds up those queued after it until it is retried, and that of a
+/// `CONTRADICTED` transaction, which the ledger rejected.
+#[derive(SimpleObject)]
+pub struct OutboxItem {
+    pub id: i32,
+    /// The agent, activity or entity the queued operation was made on
+    pub subject: String,
+    pub status: OutboxStatus,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    /// The transaction id, once submitted
+    pub tx_id: Option<String>,
+    pub delta: Option<Delta>,
+    pub queued: DateTime<Utc>,
+    pub next_attempt: DateTime<Utc>,
+}
+
+impl OutboxItem {
+    pub async fn from_item(
+        item: common::commands::OutboxItem,
+    ) -> Result<Self, async_graphql::Error> {
+        Ok(OutboxItem {
+            id: item.id,
+            subject: item.subject.to_string(),
+            status: item.status,
+            attempts: item.attempts,
+            last_error: item.last_error,
+            tx_id: item.tx_id.map(|tx_id| tx_id.to_string()),
+            delta: Delta::from_prov(&item.prov).await?,
+            queued: item.queued,
+            next_attempt: item.next_attempt,
         })
     }
 }
 
+/// The status of transactions queued in the outbox with operations on an agent, activity or
+/// entity that are yet to be committed, see `outboxStatus`
+async fn outbox_status<'a>(
+    ctx: &Context<'a>,
+    namespace_id: i32,
+    subject: ChronicleIri,
+) -> async_graphql::Result<Option<OutboxStatus>> {
+    let store = crate::persistence::Store::new(ctx.data_unchecked::<Store>().pool.clone())?;
+    let mut connection = store.connection()?;
+
+    Ok(store.outbox_status(&mut connection, namespace_id, &subject)?)
+}
+
 pub struct Subscription;
 
 #[Subscription]
@@ -377,37 +522,87 @@ pub struct Subscription;
 ///
 /// [^note](https://graphql.org/blog/subscriptions-in-graphql-and-relay/)
 impl Subscription {
+    /// Notifications may be restricted to those of a `namespace`, about any of the agents,
+    /// activities or entities in `subjects`, of any of `domainTypes`, including effects of any of
+    /// `operationKinds`, at `stage`, or submitted by `identity`. Committed operations are only
+    /// notified to subscribers that may read every resource they affect.
+    #[allow(clippy::too_many_arguments)]
     async fn commit_notifications<'a>(
         &self,
         ctx: &Context<'a>,
-    ) -> impl Stream<Item = CommitNotification> {
+        namespace: Option<String>,
+        subjects: Option<Vec<String>>,
+        domain_types: Option<Vec<DomaintypeId>>,
+        operation_kinds: Option<Vec<OperationKind>>,
+        stage: Option<Stage>,
+        identity: Option<String>,
+    ) -> async_graphql::Result<impl Stream<Item = CommitNotification>> {
+        let subjects = subjects
+            .map(|subjects| {
+                subjects
+                    .iter()
+                    .map(|subject| ChronicleIri::from_str(subject))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+
+        let filter = CommitNotificationFilter {
+            namespace,
+            subjects,
+            domain_types,
+            operation_kinds,
+            stage,
+            identity,
+        };
+
+        let subscriber = ctx.data_unchecked::<AuthId>().clone();
+        let opa = ctx.data_opt::<ExecutorContext>().cloned();
         let api = ctx.data_unchecked::<ApiDispatch>().clone();
         let mut rx = api.notify_commit.subscribe();
-        async_stream::stream! {
+        Ok(async_stream::stream! {
             loop {
                 match rx.recv().await {
-                    Ok(SubmissionStage::Submitted(Ok(submission))) =>
-                      yield CommitNotification::from_submission(&submission),
+                    Ok(SubmissionStage::Submitted(Ok(submission))) => {
+                      if filter.matches_submission() {
+                        yield CommitNotification::from_submission(&submission);
+                      }
+                    }
                     Ok(SubmissionStage::Committed(commit, id)) => {
-                      let notify = CommitNotification::from_committed(&commit.tx_id, commit.delta, *id).await;
-                      if let Ok(notify) = notify {
-                        yield notify;
-                      } else {
-                        error!("Failed to convert commit to notification: {:?}", notify.err());
+                      if let Some(opa) = &opa {
+                        if !subscription::may_read_delta(opa, &subscriber, &commit.delta).await {
+                          continue;
+                        }
+                      }
+                      let notify = CommitNotification::from_committed(&commit.tx_id, commit.delta, *id, &filter).await;
+                      match notify {
+                        Ok(Some(notify)) => yield notify,
+                        Ok(None) => {}
+                        Err(e) => error!("Failed to convert commit to notification: {:?}", e),
+                      }
+                    }
+                    Ok(SubmissionStage::NotCommitted((commit,contradiction, id))) => {
+                      if !filter.matches_contradiction(&contradiction, &id) {
+                        continue;
                       }
+                      if let Some(opa) = &opa {
+                        if !subscription::may_read_contradiction(opa, &subscriber, &contradiction).await {
+                          continue;
+                        }
+                      }
+                      yield CommitNotification::from_contradiction(&commit, &contradiction.to_string(), *id)
                     }
-                    Ok(SubmissionStage::NotCommitted((commit,contradiction, id))) =>
-                      yield CommitNotification::from_contradiction(&commit, &contradiction.to_string(), *id),
                     Ok(SubmissionStage::Submitted(Err(e))) => {
                       error!("Failed to submit: {:?}", e);
-                      yield CommitNotification::from_submission_failed(&e);
+                      if filter.matches_submission() {
+                        yield CommitNotification::from_submission_failed(&e);
+                      }
                     }
                     Err(RecvError::Lagged(_)) => {
                     }
                     Err(_) => break
                 }
             }
-        }
+        })
     }
 }
 
@@ -529,7 +724,7 @@ impl SecurityConf {
 pub trait ChronicleApiServer {
     async fn serve_api(
         &self,
-        pool: Pool<ConnectionManager<PgConnection>>,
+        pool: ConnectionPool,
         api: ApiDispatch,
         addresses: Vec<SocketAddr>,
         security_conf: SecurityConf,
@@ -621,18 +816,22 @@ async fn check_claims(
     }
 }
 
+/// The identity making a request, `Anonymous` if there are no claims or no external_id can be
+/// got from them
+fn requester_identity(claim_parser: &Option<AuthFromJwt>, claims: Option<&JwtClaims>) -> AuthId {
+    match (claims, claim_parser) {
+        (Some(claims), Some(parser)) => parser.identity(claims).unwrap_or(AuthId::anonymous()),
+        _ => AuthId::anonymous(),
+    }
+}
+
 async fn execute_opa_check(
     opa_executor: &ExecutorContext,
     claim_parser: &Option<AuthFromJwt>,
     claims: Option<&JwtClaims>,
     construct_data: impl FnOnce(&AuthId) -> OpaData,
 ) -> Result<(), OpaExecutorError> {
-    // If unable to get an external_id from the JwtClaims or no claims found,
-    // identity will be `Anonymous`
-    let identity = match (claims, claim_parser) {
-        (Some(claims), Some(parser)) => parser.identity(claims).unwrap_or(AuthId::anonymous()),
-        _ => AuthId::anonymous(),
-    };
+    let identity = requester_identity(claim_parser, claims);
 
     // Create OPA context data for the user identity
     let opa_data = construct_data(&identity);
@@ -675,7 +874,7 @@ impl EndpointSecurityConfiguration {
 }
 
 struct QueryEndpoint<Q, M, S> {
-    secconf: EndpointSecurityConfiguration,
+    secconf: Option<EndpointSecurityConfiguration>,
     schema: Schema<Q, M, S>,
 }
 
@@ -708,12 +907,24 @@ where
     type Output = poem::Response;
 
     async fn call(&self, req: poem::Request) -> poem::Result<Self::Output> {
-        let checked_claims = check_claims(&self.secconf, &req).await?;
+        let checked_claims = if let Some(secconf) = &self.secconf {
+            check_claims(secconf, &req).await?
+        } else {
+            None
+        };
+        let idempotency_key = req
+            .header("Idempotency-Key")
+            .map(|key| IdempotencyKey(key.to_owned()));
         self.respond(req, |api_req| {
-            if let Some(claims) = checked_claims {
+            let api_req = if let Some(claims) = checked_claims {
                 api_req.0.data(claims)
             } else {
                 api_req.0
+            };
+            if let Some(idempotency_key) = idempotency_key {
+                api_req.data(idempotency_key)
+            } else {
+                api_req
             }
         })
         .await
@@ -783,22 +994,81 @@ struct IriEndpoint {
     store: super::persistence::Store,
     opa_executor: ExecutorContext,
     claim_parser: Option<AuthFromJwt>,
+    /// Sign responses as verifiable credentials with the Chronicle key
+    credential: Option<ChronicleSigning>,
 }
 
 impl IriEndpoint {
-    async fn response_for_query<ID: Display + ExternalIdPart, X: ToJson>(
+    /// Wrap the provenance of `id` in a credential, along with the identities that recorded it
+    async fn credential_response(
+        &self,
+        signing: &ChronicleSigning,
+        model: &ProvModel,
+        id: String,
+        ns: &ExternalId,
+        provenance: serde_json::Value,
+    ) -> poem::Response {
+        let transactions = self.store.connection().and_then(|mut connection| {
+            let mut transactions = vec![];
+            for receipt in self
+                .store
+                .contributing_transactions(&mut connection, model)?
+            {
+                let signing_key = match receipt.key_version {
+                    Some(version) => self.store.chronicle_key(&mut connection, version)?,
+                    None => None,
+                };
+                if let Some(identity) = receipt.identity {
+                    transactions.push(ContributingTransaction {
+                        tx_id: receipt.tx_id.to_string(),
+                        block_id: receipt.block_id.map(|block_id| block_id.to_string()),
+                        identity,
+                        signing_key: signing_key
+                            .and_then(|key| TaggedVerifyingKey::from_public_key_pem(&key).ok())
+                            .map(|key| key.to_string()),
+                    });
+                }
+            }
+            Ok(transactions)
+        });
+
+        let transactions = match transactions {
+            Ok(transactions) => transactions,
+            Err(error) => {
+                tracing::error!("failed to retrieve from database: {error}");
+                return poem::Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body("failed to fetch from backend storage");
+            }
+        };
+
+        match ProvenanceCredential::issue(signing, id, ns.to_string(), provenance, transactions)
+            .await
+        {
+            Ok(credential) => IntoResponse::into_response(poem::web::Json(credential)),
+            Err(error) => {
+                tracing::error!("failed to sign credential: {error}");
+                poem::Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body("failed to sign credential")
+            }
+        }
+    }
+
+    async fn response_for_query<ID: Display + ExternalIdPart>(
         &self,
         claims: Option<&JwtClaims>,
         prov_type: &str,
         id: &ID,
         ns: &ExternalId,
         retrieve: impl FnOnce(
-            PooledConnection<ConnectionManager<PgConnection>>,
+            PooledConnection<ConnectionManager<StoreConnection>>,
             &ID,
             &ExternalId,
-        ) -> Result<X, StoreError>,
+        ) -> Result<ProvModel, StoreError>,
     ) -> poem::Result<poem::Response> {
-        match execute_opa_check(&self.opa_executor, &self.claim_parser, claims, |identity| {
+        let opa_executor = self.opa_executor.for_namespace(ns.as_str());
+        match execute_opa_check(&opa_executor, &self.claim_parser, claims, |identity| {
             OpaData::operation(
                 identity,
                 &json!("ReadData"),
@@ -815,15 +1085,28 @@ impl IriEndpoint {
                 Ok(connection) => match retrieve(connection, id, ns) {
                     Ok(data) => match data.to_json().compact().await {
                         Ok(mut json) => {
-                            use serde_json::Value;
-                            if let Value::Object(mut map) = json {
-                                map.insert(
-                                    "@context".to_string(),
-                                    Value::String("/context".to_string()),
-                                );
-                                json = Value::Object(map);
+                            masking::mask_json_ld(
+                                &self.opa_executor,
+                                &requester_identity(&self.claim_parser, claims),
+                                &mut json,
+                            )
+                            .await;
+                            match &self.credential {
+                                Some(signing) => Ok(self
+                                    .credential_response(signing, &data, id.to_string(), ns, json)
+                                    .await),
+                                None => {
+                                    use serde_json::Value;
+                                    if let Value::Object(mut map) = json {
+                                        map.insert(
+                                            "@context".to_string(),
+                                            Value::String("/context".to_string()),
+                                        );
+                                        json = Value::Object(map);
+                                    }
+                                    Ok(IntoResponse::into_response(poem::web::Json(json)))
+                                }
                             }
-                            Ok(IntoResponse::into_response(poem::web::Json(json)))
                         }
                         Err(error) => {
                             tracing::error!("JSON failed compaction: {error}");
@@ -953,6 +1236,175 @@ impl Endpoint for IriEndpoint {
     }
 }
 
+struct TxEndpoint {
+    secconf: Option<EndpointSecurityConfiguration>,
+    store: super::persistence::Store,
+    opa_executor: ExecutorContext,
+    claim_parser: Option<AuthFromJwt>,
+    /// Respond with proofs of the state the transaction committed, rather than its receipt
+    proofs: Option<Option<Arc<dyn BlockWriteProofs>>>,
+}
+
+impl TxEndpoint {
+    async fn receipt_json(
+        receipt: TransactionReceipt,
+    ) -> Result<serde_json::Value, CompactionError> {
+        let delta = match &receipt.delta {
+            Some(delta) => Some(delta.to_json().compact_stable_order().await?),
+            None => None,
+        };
+
+        Ok(json!({
+            "txId": receipt.tx_id.to_string(),
+            "stage": receipt.stage,
+            "blockId": receipt.block_id.map(|block_id| block_id.to_string()),
+            "identity": receipt.identity,
+            "delta": delta,
+            "contradiction": receipt.contradiction,
+            "error": receipt.error,
+            "updated": DateTime::<Utc>::from_naive_utc_and_offset(receipt.updated_time, Utc),
+        }))
+    }
+
+    /// Proofs of each state entry written in the block that committed the transaction
+    fn proof_response(
+        proofs: Option<&dyn BlockWriteProofs>,
+        receipt: Result<Option<TransactionReceipt>, StoreError>,
+    ) -> poem::Response {
+        let proofs = match proofs {
+            Some(proofs) => proofs,
+            None => {
+                return poem::Response::builder()
+                    .status(StatusCode::NOT_IMPLEMENTED)
+                    .body("only a ledger file proves the state its blocks wrote")
+            }
+        };
+
+        let (tx_id, block_id) = match receipt {
+            Ok(Some(TransactionReceipt {
+                tx_id,
+                stage: TransactionStage::Committed,
+                block_id: Some(block_id),
+                ..
+            })) => (tx_id, block_id.to_string()),
+            Ok(_) => {
+                return poem::Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body("the specified transaction is not known to be committed")
+            }
+            Err(error) => {
+                tracing::error!("failed to retrieve from database: {error}");
+                return poem::Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body("failed to fetch from backend storage");
+            }
+        };
+
+        match proofs.prove_block(&block_id) {
+            Ok(Some(proofs)) => IntoResponse::into_response(poem::web::Json(json!({
+                "txId": tx_id.to_string(),
+                "blockId": block_id,
+                "proofs": proofs,
+            }))),
+            Ok(None) => poem::Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body("the ledger has no proof for the block that committed the transaction"),
+            Err(error) => {
+                tracing::error!("failed to prove block {block_id}: {error}");
+                poem::Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body("failed to prove the transaction")
+            }
+        }
+    }
+
+    #[instrument(level = "trace", skip(self, req), ret(Debug))]
+    async fn respond(
+        &self,
+        req: poem::Request,
+        claims: Option<&JwtClaims>,
+    ) -> poem::Result<poem::Response> {
+        use poem::{web::Path, FromRequest};
+
+        let (req, mut body) = req.split();
+        let Path(tx_id): Path<String> = FromRequest::from_request(&req, &mut body).await?;
+
+        if execute_opa_check(&self.opa_executor, &self.claim_parser, claims, |identity| {
+            OpaData::operation(identity, &json!("ReadTransaction"), &json!({ "id": tx_id }))
+        })
+        .await
+        .is_err()
+        {
+            return Ok(poem::Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body("violation of policy rules"));
+        }
+
+        let mut receipt = self.store.connection().and_then(|mut connection| {
+            self.store
+                .transaction_receipt(&mut connection, &ChronicleTransactionId::from(&*tx_id))
+        });
+
+        // As for commit notifications, what the transaction recorded is only shown to a reader
+        // who may read all of it
+        let withheld = match &mut receipt {
+            Ok(Some(receipt)) => {
+                subscription::withhold_unreadable(
+                    &self.opa_executor,
+                    &requester_identity(&self.claim_parser, claims),
+                    receipt,
+                )
+                .await
+            }
+            _ => false,
+        };
+
+        if let Some(proofs) = &self.proofs {
+            if withheld {
+                return Ok(poem::Response::builder()
+                    .status(StatusCode::FORBIDDEN)
+                    .body("violation of policy rules"));
+            }
+            return Ok(Self::proof_response(proofs.as_deref(), receipt));
+        }
+
+        match receipt {
+            Ok(Some(receipt)) => match Self::receipt_json(receipt).await {
+                Ok(json) => Ok(IntoResponse::into_response(poem::web::Json(json))),
+                Err(error) => {
+                    tracing::error!("JSON failed compaction: {error}");
+                    Ok(poem::Response::builder()
+                        .status(StatusCode::INTERNAL_SERVER_ERROR)
+                        .body("failed to compact JSON response"))
+                }
+            },
+            Ok(None) => Ok(poem::Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body("the specified transaction is not known")),
+            Err(error) => {
+                tracing::error!("failed to retrieve from database: {error}");
+                Ok(poem::Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body("failed to fetch from backend storage"))
+            }
+        }
+    }
+}
+
+#[poem::async_trait]
+impl Endpoint for TxEndpoint {
+    type Output = poem::Response;
+
+    async fn call(&self, req: poem::Request) -> poem::Result<Self::Output> {
+        let checked_claims = if let Some(secconf) = &self.secconf {
+            check_claims(secconf, &req).await?
+        } else {
+            None
+        };
+        self.respond(req, checked_claims.as_ref()).await
+    }
+}
+
 struct LdContextEndpoint;
 
 #[poem::async_trait]
@@ -965,6 +1417,26 @@ impl Endpoint for LdContextEndpoint {
     }
 }
 
+/// The policy requests are checked against, which changes as it is set on the ledger
+struct ActivePolicyEndpoint {
+    secconf: Option<EndpointSecurityConfiguration>,
+    opa: ExecutorContext,
+}
+
+#[poem::async_trait]
+impl Endpoint for ActivePolicyEndpoint {
+    type Output = poem::Response;
+
+    async fn call(&self, req: poem::Request) -> poem::Result<Self::Output> {
+        if let Some(secconf) = &self.secconf {
+            check_claims(secconf, &req).await?;
+        }
+        Ok(IntoResponse::into_response(poem::web::Json(
+            self.opa.active_policy().await,
+        )))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct AuthFromJwt {
     id_claims: BTreeSet<String>,
@@ -1020,8 +1492,68 @@ pub struct OpaCheck {
     pub claim_parser: Option<AuthFromJwt>,
 }
 
+/// The namespaces the top-level fields of a request's operations are in, by response key. Fields
+/// whose namespace is not an argument, or is not a string, are left out.
+fn requested_namespaces(
+    document: &ExecutableDocument,
+    variables: &async_graphql::Variables,
+) -> HashMap<String, String> {
+    document
+        .operations
+        .iter()
+        .flat_map(|(_, operation)| operation.node.selection_set.node.items.iter())
+        .filter_map(|selection| match &selection.node {
+            Selection::Field(field) => Some(&field.node),
+            _ => None,
+        })
+        .filter_map(|field| {
+            let namespace = field
+                .get_argument("namespace")?
+                .node
+                .clone()
+                .into_const_with(|name| variables.get(&name).cloned().ok_or(()))
+                .ok()?;
+            match namespace {
+                async_graphql::Value::String(namespace) => {
+                    Some((field.response_key().node.to_string(), namespace))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Checks a request's fields against the policy of the namespace they are in, and holds what the
+/// policy masks of the agents, activities and entities the request resolves
+struct OpaCheckExtension {
+    claim_parser: Option<AuthFromJwt>,
+    namespaces: std::sync::Mutex<HashMap<String, String>>,
+}
+
 #[async_trait::async_trait]
-impl async_graphql::extensions::Extension for OpaCheck {
+impl async_graphql::extensions::Extension for OpaCheckExtension {
+    async fn prepare_request(
+        &self,
+        ctx: &async_graphql::extensions::ExtensionContext<'_>,
+        request: async_graphql::Request,
+        next: async_graphql::extensions::NextPrepareRequest<'_>,
+    ) -> async_graphql::ServerResult<async_graphql::Request> {
+        next.run(ctx, request.data(masking::MaskedFields::default()))
+            .await
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &async_graphql::extensions::ExtensionContext<'_>,
+        query: &str,
+        variables: &async_graphql::Variables,
+        next: async_graphql::extensions::NextParseQuery<'_>,
+    ) -> async_graphql::ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        *self.namespaces.lock().unwrap() = requested_namespaces(&document, variables);
+        Ok(document)
+    }
+
     #[instrument(level = "trace", skip_all, ret(Debug))]
     async fn resolve(
         &self,
@@ -1032,27 +1564,39 @@ impl async_graphql::extensions::Extension for OpaCheck {
         use async_graphql::ServerError;
         use serde_json::Value;
         if let Some(opa_executor) = ctx.data_opt::<ExecutorContext>() {
+            let path = info.path_node.to_string_vec();
+            // Fields are in the namespace of the top-level field they are selected from
+            let namespace = path
+                .first()
+                .and_then(|field| self.namespaces.lock().unwrap().get(field).cloned())
+                .unwrap_or_else(|| "default".to_owned());
             match execute_opa_check(
-                opa_executor,
+                &opa_executor.for_namespace(&namespace),
                 &self.claim_parser,
                 ctx.data_opt::<JwtClaims>(),
                 |identity| {
                     OpaData::graphql(
                         identity,
                         &Value::String(info.parent_type.to_string()),
-                        &Value::Array(
-                            info.path_node
-                                .to_string_vec()
-                                .into_iter()
-                                .map(Value::String)
-                                .collect(),
-                        ),
+                        &Value::Array(path.into_iter().map(Value::String).collect()),
                     )
                 },
             )
             .await
             {
                 Ok(()) => next.run(ctx, info).await,
+                // Reasons are only given if the OPA executor is explaining denials
+                Err(OpaExecutorError::AccessDenied { reasons }) if !reasons.is_empty() => {
+                    let mut error = ServerError::new("violation of policy rules", None);
+                    let mut extensions = async_graphql::ErrorExtensionValues::default();
+                    extensions.set(
+                        "denyReasons",
+                        async_graphql::Value::from_json(Value::Array(reasons))
+                            .unwrap_or(async_graphql::Value::Null),
+                    );
+                    error.extensions = Some(extensions);
+                    Err(error)
+                }
                 Err(_) => Err(ServerError::new("violation of policy rules", None)),
             }
         } else {
@@ -1064,8 +1608,9 @@ impl async_graphql::extensions::Extension for OpaCheck {
 #[async_trait::async_trait]
 impl async_graphql::extensions::ExtensionFactory for OpaCheck {
     fn create(&self) -> Arc<dyn async_graphql::extensions::Extension> {
-        Arc::new(OpaCheck {
+        Arc::new(OpaCheckExtension {
             claim_parser: self.claim_parser.clone(),
+            namespaces: Default::default(),
         })
     }
 }
@@ -1090,7 +1635,7 @@ where
 {
     async fn serve_api(
         &self,
-        pool: Pool<ConnectionManager<PgConnection>>,
+        pool: ConnectionPool,
         api: ApiDispatch,
         addresses: Vec<SocketAddr>,
         sec: SecurityConf,
@@ -1101,6 +1646,8 @@ where
             id_claims,
             allow_anonymous: sec.allow_anonymous,
         });
+        let proofs = api.proofs.clone();
+        let signing = api.signing.clone();
         let mut schema = Schema::build(self.query, self.mutation, Subscription)
             .extension(OpenTelemetry::new(opentelemetry::global::tracer(
                 "chronicle-api-gql",
@@ -1118,11 +1665,25 @@ where
             .data(AuthId::anonymous())
             .finish();
 
-        let iri_endpoint = |secconf| IriEndpoint {
+        let iri_endpoint = |secconf, credential: bool| IriEndpoint {
             secconf,
             store: super::persistence::Store::new(pool.clone()).unwrap(),
             opa_executor: sec.opa.clone(),
             claim_parser: claim_parser.clone(),
+            credential: credential.then(|| signing.clone()),
+        };
+
+        let tx_endpoint = |secconf, proof: bool| TxEndpoint {
+            secconf,
+            store: super::persistence::Store::new(pool.clone()).unwrap(),
+            opa_executor: sec.opa.clone(),
+            claim_parser: claim_parser.clone(),
+            proofs: proof.then(|| proofs.clone()),
+        };
+
+        let policy_endpoint = |secconf| ActivePolicyEndpoint {
+            secconf,
+            opa: sec.opa.clone(),
         };
 
         let mut app = Route::new();
@@ -1131,16 +1692,28 @@ where
             (None, None) => {
                 tracing::warn!("API endpoint uses no authentication");
 
+                app = app.at("/opa/policy", get(policy_endpoint(None)));
+
                 if serve_graphql {
                     app = app
-                        .at("/", get(gql_playground).post(GraphQL::new(schema.clone())))
+                        .at(
+                            "/",
+                            get(gql_playground).post(QueryEndpoint {
+                                secconf: None,
+                                schema: schema.clone(),
+                            }),
+                        )
                         .at("/ws", get(GraphQLSubscription::new(schema)))
                 };
                 if serve_data {
                     app = app
                         .at("/context", get(LdContextEndpoint))
-                        .at("/data/:iri", get(iri_endpoint(None)))
-                        .at("/data/:ns/:iri", get(iri_endpoint(None)))
+                        .at("/data/:iri", get(iri_endpoint(None, false)))
+                        .at("/data/:ns/:iri", get(iri_endpoint(None, false)))
+                        .at("/credential/:iri", get(iri_endpoint(None, true)))
+                        .at("/credential/:ns/:iri", get(iri_endpoint(None, true)))
+                        .at("/tx/:id", get(tx_endpoint(None, false)))
+                        .at("/tx/:id/file-ledger-proof", get(tx_endpoint(None, true)))
                 };
             }
             (jwks_uri, userinfo_uri) => {
@@ -1166,12 +1739,14 @@ where
 
                 secconf().check_status().await?;
 
+                app = app.at("/opa/policy", get(policy_endpoint(Some(secconf()))));
+
                 if serve_graphql {
                     app = app
                         .at(
                             "/",
                             post(QueryEndpoint {
-                                secconf: secconf(),
+                                secconf: Some(secconf()),
                                 schema: schema.clone(),
                             }),
                         )
@@ -1186,8 +1761,18 @@ where
                 if serve_data {
                     app = app
                         .at("/context", get(LdContextEndpoint))
-                        .at("/data/:iri", get(iri_endpoint(Some(secconf()))))
-                        .at("/data/:ns/:iri", get(iri_endpoint(Some(secconf()))))
+                        .at("/data/:iri", get(iri_endpoint(Some(secconf()), false)))
+                        .at("/data/:ns/:iri", get(iri_endpoint(Some(secconf()), false)))
+                        .at("/credential/:iri", get(iri_endpoint(Some(secconf()), true)))
+                        .at(
+                            "/credential/:ns/:iri",
+                            get(iri_endpoint(Some(secconf()), true)),
+                        )
+                        .at("/tx/:id", get(tx_endpoint(Some(secconf()), false)))
+                        .at(
+                            "/tx/:id/file-ledger-proof",
+                            get(tx_endpoint(Some(secconf()), true)),
+                        )
                 };
             }
         }
@@ -4,14 +4,16 @@ use async_graphql::Context;
 use chrono::{DateTime, Utc};
 use common::{
     attributes::Attributes,
-    commands::{ActivityCommand, AgentCommand, ApiCommand, ApiResponse, EntityCommand},
-    identity::AuthId,
+    commands::{
+        ActivityCommand, AgentCommand, AgentSignedCommand, ApiCommand, ApiResponse, EntityCommand,
+    },
+    identity::{AgentSignedOperations, AuthId},
     prov::{operations::DerivationType, ActivityId, AgentId, EntityId, Role},
 };
 
 use crate::ApiDispatch;
 
-use super::Submission;
+use super::{IdempotencyKey, Submission};
 async fn transaction_context<'a>(
     res: ApiResponse,
     _ctx: &Context<'a>,
@@ -23,10 +25,30 @@ async fn transaction_context<'a>(
         ApiResponse::AlreadyRecorded { subject, .. } => {
             Ok(Submission::from_already_recorded(&subject))
         }
+        ApiResponse::Queued { subject, .. } => Ok(Submission::from_queued(&subject)),
+        ApiResponse::PriorSubmission {
+            subject,
+            tx_id,
+            stage,
+        } => Ok(Submission::from_prior_submission(
+            &subject,
+            tx_id.as_ref(),
+            stage,
+        )),
         _ => unreachable!(),
     }
 }
 
+/// The request's idempotency key, qualified by the path to this mutation so that each mutation
+/// in a request is distinct
+fn idempotency_key(ctx: &Context<'_>) -> Option<String> {
+    ctx.data_opt::<IdempotencyKey>()
+        .map(|IdempotencyKey(key)| match ctx.path_node {
+            Some(path) => format!("{key}:{path}"),
+            None => key.to_owned(),
+        })
+}
+
 async fn derivation<'a>(
     ctx: &Context<'a>,
     namespace: Option<String>,
@@ -41,7 +63,7 @@ async fn derivation<'a>(
     let namespace = namespace.unwrap_or_else(|| "default".into()).into();
 
     let res = api
-        .dispatch(
+        .dispatch_with_idempotency_key(
             ApiCommand::Entity(EntityCommand::Derive {
                 id: generated_entity,
                 namespace,
@@ -50,6 +72,7 @@ async fn derivation<'a>(
                 derivation,
             }),
             identity,
+            idempotency_key(ctx),
         )
         .await?;
 
@@ -69,13 +92,14 @@ pub async fn agent<'a>(
     let namespace = namespace.unwrap_or_else(|| "default".to_owned());
 
     let res = api
-        .dispatch(
+        .dispatch_with_idempotency_key(
             ApiCommand::Agent(AgentCommand::Create {
                 external_id: external_id.into(),
                 namespace: namespace.into(),
                 attributes,
             }),
             identity,
+            idempotency_key(ctx),
         )
         .await?;
 
@@ -95,13 +119,14 @@ pub async fn activity<'a>(
     let namespace = namespace.unwrap_or_else(|| "default".to_owned());
 
     let res = api
-        .dispatch(
+        .dispatch_with_idempotency_key(
             ApiCommand::Activity(ActivityCommand::Create {
                 external_id: external_id.into(),
                 namespace: namespace.into(),
                 attributes,
             }),
             identity,
+            idempotency_key(ctx),
         )
         .await?;
 
@@ -121,13 +146,14 @@ pub async fn entity<'a>(
     let namespace = namespace.unwrap_or_else(|| "default".to_owned());
 
     let res = api
-        .dispatch(
+        .dispatch_with_idempotency_key(
             ApiCommand::Entity(EntityCommand::Create {
                 external_id: external_id.into(),
                 namespace: namespace.into(),
                 attributes,
             }),
             identity,
+            idempotency_key(ctx),
         )
         .await?;
 
@@ -149,7 +175,7 @@ pub async fn acted_on_behalf_of<'a>(
     let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
 
     let res = api
-        .dispatch(
+        .dispatch_with_idempotency_key(
             ApiCommand::Agent(AgentCommand::Delegate {
                 id: responsible_id,
                 delegate: delegate_id,
@@ -158,6 +184,7 @@ pub async fn acted_on_behalf_of<'a>(
                 role,
             }),
             identity,
+            idempotency_key(ctx),
         )
         .await?;
 
@@ -240,7 +267,7 @@ pub async fn start_activity<'a>(
     let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
 
     let res = api
-        .dispatch(
+        .dispatch_with_idempotency_key(
             ApiCommand::Activity(ActivityCommand::Start {
                 id,
                 namespace,
@@ -248,6 +275,7 @@ pub async fn start_activity<'a>(
                 agent,
             }),
             identity,
+            idempotency_key(ctx),
         )
         .await?;
 
@@ -268,7 +296,7 @@ pub async fn end_activity<'a>(
     let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
 
     let res = api
-        .dispatch(
+        .dispatch_with_idempotency_key(
             ApiCommand::Activity(ActivityCommand::End {
                 id,
                 namespace,
@@ -276,6 +304,7 @@ pub async fn end_activity<'a>(
                 agent,
             }),
             identity,
+            idempotency_key(ctx),
         )
         .await?;
 
@@ -296,7 +325,7 @@ pub async fn instant_activity<'a>(
     let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
 
     let res = api
-        .dispatch(
+        .dispatch_with_idempotency_key(
             ApiCommand::Activity(ActivityCommand::Instant {
                 id,
                 namespace,
@@ -304,6 +333,7 @@ pub async fn instant_activity<'a>(
                 agent,
             }),
             identity,
+            idempotency_key(ctx),
         )
         .await?;
 
@@ -324,7 +354,7 @@ pub async fn was_associated_with<'a>(
     let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
 
     let res = api
-        .dispatch(
+        .dispatch_with_idempotency_key(
             ApiCommand::Activity(ActivityCommand::Associate {
                 id: activity,
                 responsible,
@@ -332,6 +362,7 @@ pub async fn was_associated_with<'a>(
                 namespace,
             }),
             identity,
+            idempotency_key(ctx),
         )
         .await?;
 
@@ -352,7 +383,7 @@ pub async fn was_attributed_to<'a>(
     let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
 
     let res = api
-        .dispatch(
+        .dispatch_with_idempotency_key(
             ApiCommand::Entity(EntityCommand::Attribute {
                 id,
                 namespace,
@@ -360,6 +391,7 @@ pub async fn was_attributed_to<'a>(
                 role,
             }),
             identity,
+            idempotency_key(ctx),
         )
         .await?;
 
@@ -379,13 +411,14 @@ pub async fn used<'a>(
     let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
 
     let res = api
-        .dispatch(
+        .dispatch_with_idempotency_key(
             ApiCommand::Activity(ActivityCommand::Use {
                 id: entity,
                 namespace,
                 activity,
             }),
             identity,
+            idempotency_key(ctx),
         )
         .await?;
 
@@ -405,13 +438,14 @@ pub async fn was_informed_by<'a>(
     let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
 
     let res = api
-        .dispatch(
+        .dispatch_with_idempotency_key(
             ApiCommand::Activity(ActivityCommand::WasInformedBy {
                 id: activity,
                 namespace,
                 informing_activity,
             }),
             identity,
+            idempotency_key(ctx),
         )
         .await?;
 
@@ -431,13 +465,41 @@ pub async fn was_generated_by<'a>(
     let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
 
     let res = api
-        .dispatch(
+        .dispatch_with_idempotency_key(
             ApiCommand::Activity(ActivityCommand::Generate {
                 id: entity,
                 namespace,
                 activity,
             }),
             identity,
+            idempotency_key(ctx),
+        )
+        .await?;
+
+    transaction_context(res, ctx).await
+}
+
+pub async fn submit_agent_signed<'a>(
+    ctx: &Context<'a>,
+    agent: AgentId,
+    payload: String,
+    signature: String,
+) -> async_graphql::Result<Submission> {
+    let api = ctx.data_unchecked::<ApiDispatch>();
+
+    let identity = ctx.data_unchecked::<AuthId>().to_owned();
+
+    let signed = AgentSignedOperations {
+        agent,
+        payload,
+        signature: hex::decode(signature)?,
+    };
+
+    let res = api
+        .dispatch_with_idempotency_key(
+            ApiCommand::AgentSigned(AgentSignedCommand { signed }),
+            identity,
+            idempotency_key(ctx),
         )
         .await?;
 
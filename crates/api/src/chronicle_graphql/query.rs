@@ -3,15 +3,21 @@ use async_graphql::{
     Context, ID,
 };
 use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
-use diesel::{debug_query, pg::Pg, prelude::*};
+use diesel::prelude::*;
 use tracing::{debug, instrument};
 
 use super::{
-    cursor_query::{project_to_nodes, Cursorize},
-    Activity, Agent, Entity, GraphQlError, Store, TimelineOrder,
+    cursor_query::{page, project_to_nodes, Cursorize},
+    subscription, Activity, Agent, Entity, GraphQlError, OutboxItem, Store, TimelineOrder,
+    Transaction,
 };
 use crate::persistence::schema::generation;
-use common::prov::{ActivityId, AgentId, DomaintypeId, EntityId, ExternalIdPart};
+use common::{
+    commands::OutboxStatus,
+    identity::AuthId,
+    opa::ExecutorContext,
+    prov::{ActivityId, AgentId, ChronicleTransactionId, DomaintypeId, EntityId, ExternalIdPart},
+};
 
 #[allow(clippy::too_many_arguments)]
 #[instrument(skip(ctx))]
@@ -132,14 +138,11 @@ pub async fn activity_timeline<'a>(
         first,
         last,
         |after, before, first, last| async move {
-            debug!(
-                "Cursor query {}",
-                debug_query::<Pg, _>(&sql_query).to_string()
-            );
             let rx = sql_query.cursor(after, before, first, last);
 
             let start = rx.start;
             let limit = rx.limit;
+            debug!(start, limit, "Cursor query");
 
             let rx = rx.load::<(Activity, i64)>(&mut connection)?;
 
@@ -150,7 +153,6 @@ pub async fn activity_timeline<'a>(
 }
 
 #[allow(clippy::too_many_arguments)]
-
 pub async fn entities_by_type<'a>(
     ctx: &Context<'a>,
     typ: Option<DomaintypeId>,
@@ -183,14 +185,11 @@ pub async fn entities_by_type<'a>(
         first,
         last,
         |after, before, first, last| async move {
-            debug!(
-                "Cursor query {}",
-                debug_query::<Pg, _>(&sql_query).to_string()
-            );
             let rx = sql_query.cursor(after, before, first, last);
 
             let start = rx.start;
             let limit = rx.limit;
+            debug!(start, limit, "Cursor query");
 
             let rx = rx.load::<(Entity, i64)>(&mut connection)?;
 
@@ -232,14 +231,11 @@ pub async fn activities_by_type<'a>(
         first,
         last,
         |after, before, first, last| async move {
-            debug!(
-                "Cursor query {}",
-                debug_query::<Pg, _>(&sql_query).to_string()
-            );
             let rx = sql_query.cursor(after, before, first, last);
 
             let start = rx.start;
             let limit = rx.limit;
+            debug!(start, limit, "Cursor query");
 
             let rx = rx.load::<(Activity, i64)>(&mut connection)?;
 
@@ -282,14 +278,11 @@ pub async fn agents_by_type<'a>(
         first,
         last,
         |after, before, first, last| async move {
-            debug!(
-                "Cursor query {}",
-                debug_query::<Pg, _>(&sql_query).to_string()
-            );
             let rx = sql_query.cursor(after, before, first, last);
 
             let start = rx.start;
             let limit = rx.limit;
+            debug!(start, limit, "Cursor query");
 
             let rx = rx.load::<(Agent, i64)>(&mut connection)?;
 
@@ -378,3 +371,64 @@ pub async fn entity_by_id<'a>(
         .first::<Entity>(&mut connection)
         .optional()?)
 }
+
+pub async fn transaction<'a>(
+    ctx: &Context<'a>,
+    tx_id: String,
+) -> async_graphql::Result<Option<Transaction>> {
+    let store = crate::persistence::Store::new(ctx.data_unchecked::<Store>().pool.clone())?;
+    let mut connection = store.connection()?;
+
+    match store.transaction_receipt(&mut connection, &ChronicleTransactionId::from(&*tx_id))? {
+        Some(mut receipt) => {
+            if let Some(opa) = ctx.data_opt::<ExecutorContext>() {
+                subscription::withhold_unreadable(
+                    opa,
+                    ctx.data_unchecked::<AuthId>(),
+                    &mut receipt,
+                )
+                .await;
+            }
+            Ok(Some(Transaction::from_receipt(receipt).await?))
+        }
+        None => Ok(None),
+    }
+}
+
+pub async fn outbox<'a>(
+    ctx: &Context<'a>,
+    status: Option<OutboxStatus>,
+    after: Option<String>,
+    before: Option<String>,
+    first: Option<i32>,
+    last: Option<i32>,
+) -> async_graphql::Result<Connection<i32, OutboxItem, EmptyFields, EmptyFields>> {
+    let store = crate::persistence::Store::new(ctx.data_unchecked::<Store>().pool.clone())?;
+
+    query(
+        after,
+        before,
+        first,
+        last,
+        |after, before, first, last| async move {
+            let (start, limit) = page(after, before, first, last);
+            debug!(start, limit, "Outbox page");
+
+            let (total, page) = {
+                let mut connection = store.connection()?;
+                (
+                    store.outbox_len(&mut connection, status)?,
+                    store.outbox_items(&mut connection, status, Some((start, limit)))?,
+                )
+            };
+
+            let mut items = vec![];
+            for item in page {
+                items.push((OutboxItem::from_item(item).await?, total));
+            }
+
+            Ok::<_, async_graphql::Error>(project_to_nodes(items, start, limit))
+        },
+    )
+    .await
+}
@@ -1 +1,342 @@
-// empty for now
+//! Server-side filtering and authorization of `commit_notifications`, and of the transaction
+//! receipts that report the same deltas
+
+use async_graphql::Enum;
+use common::{
+    identity::{AuthId, OpaData, SignedIdentity},
+    opa::ExecutorContext,
+    prov::{
+        ChronicleIri, Contradiction, DomaintypeId, ExternalId, ExternalIdPart, NamespaceId,
+        ProvModel,
+    },
+};
+use serde_json::json;
+use tracing::debug;
+
+use super::Stage;
+use crate::persistence::TransactionReceipt;
+
+/// # `OperationKind`
+///
+/// The kinds of Chronicle operation whose effects can be recognized in a committed delta.
+/// As a delta records resulting state rather than the operations that produced it, a delta
+/// is considered to be of every kind whose effects it contains.
+#[derive(Enum, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum OperationKind {
+    AgentExists,
+    ActivityExists,
+    EntityExists,
+    SetAttributes,
+    RegisterKey,
+    StartActivity,
+    EndActivity,
+    WasAssociatedWith,
+    WasAttributedTo,
+    ActsOnBehalfOf,
+    EntityDerive,
+    WasGeneratedBy,
+    ActivityUses,
+    WasInformedBy,
+}
+
+impl OperationKind {
+    /// The kinds of operation whose effects are present in `delta`
+    pub fn of_delta(delta: &ProvModel) -> Vec<OperationKind> {
+        let mut kinds = vec![];
+
+        if !delta.agents.is_empty() {
+            kinds.push(OperationKind::AgentExists);
+        }
+        if !delta.activities.is_empty() {
+            kinds.push(OperationKind::ActivityExists);
+        }
+        if !delta.entities.is_empty() {
+            kinds.push(OperationKind::EntityExists);
+        }
+        if delta
+            .agents
+            .values()
+            .any(|x| x.domaintypeid.is_some() || !x.attributes.is_empty())
+            || delta
+                .activities
+                .values()
+                .any(|x| x.domaintypeid.is_some() || !x.attributes.is_empty())
+            || delta
+                .entities
+                .values()
+                .any(|x| x.domaintypeid.is_some() || !x.attributes.is_empty())
+        {
+            kinds.push(OperationKind::SetAttributes);
+        }
+        if !delta.identities.is_empty() {
+            kinds.push(OperationKind::RegisterKey);
+        }
+        if delta.activities.values().any(|x| x.started.is_some()) {
+            kinds.push(OperationKind::StartActivity);
+        }
+        if delta.activities.values().any(|x| x.ended.is_some()) {
+            kinds.push(OperationKind::EndActivity);
+        }
+        if !delta.association.is_empty() {
+            kinds.push(OperationKind::WasAssociatedWith);
+        }
+        if !delta.attribution.is_empty() {
+            kinds.push(OperationKind::WasAttributedTo);
+        }
+        if !delta.delegation.is_empty() {
+            kinds.push(OperationKind::ActsOnBehalfOf);
+        }
+        if !delta.derivation.is_empty() {
+            kinds.push(OperationKind::EntityDerive);
+        }
+        if !delta.generation.is_empty() {
+            kinds.push(OperationKind::WasGeneratedBy);
+        }
+        if !delta.usage.is_empty() {
+            kinds.push(OperationKind::ActivityUses);
+        }
+        if !delta.was_informed_by.is_empty() {
+            kinds.push(OperationKind::WasInformedBy);
+        }
+
+        kinds
+    }
+}
+
+/// Criteria supplied by a subscriber to `commit_notifications`, criteria that are not set match
+/// any notification. Submission notifications carry no delta, so are only delivered if no
+/// criteria other than `stage` are set.
+#[derive(Debug, Default, Clone)]
+pub struct CommitNotificationFilter {
+    pub namespace: Option<String>,
+    pub subjects: Option<Vec<ChronicleIri>>,
+    pub domain_types: Option<Vec<DomaintypeId>>,
+    pub operation_kinds: Option<Vec<OperationKind>>,
+    pub stage: Option<Stage>,
+    pub identity: Option<String>,
+}
+
+impl CommitNotificationFilter {
+    /// Whether any criteria can only be evaluated against the content of a delta
+    fn inspects_content(&self) -> bool {
+        self.namespace.is_some()
+            || self.subjects.is_some()
+            || self.domain_types.is_some()
+            || self.operation_kinds.is_some()
+            || self.identity.is_some()
+    }
+
+    fn matches_stage(&self, stage: Stage) -> bool {
+        match self.stage {
+            None => true,
+            Some(wanted) => wanted == stage,
+        }
+    }
+
+    fn matches_identity(&self, identity: &SignedIdentity) -> bool {
+        match &self.identity {
+            None => true,
+            Some(wanted) => AuthId::try_from(identity)
+                .map(|identity| identity.to_string() == *wanted)
+                .unwrap_or(false),
+        }
+    }
+
+    fn matches_namespace(&self, namespace: &NamespaceId) -> bool {
+        match &self.namespace {
+            None => true,
+            Some(wanted) => namespace.external_id_part().as_str() == wanted,
+        }
+    }
+
+    /// Check a notification that has not yet been committed
+    pub fn matches_submission(&self) -> bool {
+        self.matches_stage(Stage::Submit) && !self.inspects_content()
+    }
+
+    /// Check a notification of a contradiction, which names only the contradicted resource
+    pub fn matches_contradiction(
+        &self,
+        contradiction: &Contradiction,
+        identity: &SignedIdentity,
+    ) -> bool {
+        self.matches_stage(Stage::Commit)
+            && self.domain_types.is_none()
+            && self.operation_kinds.is_none()
+            && self.matches_identity(identity)
+            && self.matches_namespace(contradiction.namespace())
+            && match &self.subjects {
+                None => true,
+                Some(wanted) => wanted.contains(contradiction.id()),
+            }
+    }
+
+    /// Check a committed delta, each criterion must be met by at least one part of the delta
+    pub fn matches_delta(&self, delta: &ProvModel, identity: &SignedIdentity) -> bool {
+        self.matches_stage(Stage::Commit)
+            && self.matches_identity(identity)
+            && (self.namespace.is_none()
+                || delta.namespaces.keys().any(|ns| self.matches_namespace(ns)))
+            && match &self.subjects {
+                None => true,
+                Some(wanted) => subjects(delta).any(|subject| wanted.contains(&subject)),
+            }
+            && match &self.domain_types {
+                None => true,
+                Some(wanted) => domain_types(delta).any(|typ| wanted.contains(typ)),
+            }
+            && match &self.operation_kinds {
+                None => true,
+                Some(wanted) => OperationKind::of_delta(delta)
+                    .iter()
+                    .any(|kind| wanted.contains(kind)),
+            }
+    }
+}
+
+/// The agents, activities and entities described by a delta
+fn subjects(delta: &ProvModel) -> impl Iterator<Item = ChronicleIri> + '_ {
+    delta
+        .agents
+        .keys()
+        .map(|(_, id)| ChronicleIri::from(id.clone()))
+        .chain(
+            delta
+                .activities
+                .keys()
+                .map(|(_, id)| ChronicleIri::from(id.clone())),
+        )
+        .chain(
+            delta
+                .entities
+                .keys()
+                .map(|(_, id)| ChronicleIri::from(id.clone())),
+        )
+}
+
+fn domain_types(delta: &ProvModel) -> impl Iterator<Item = &DomaintypeId> + '_ {
+    delta
+        .agents
+        .values()
+        .filter_map(|x| x.domaintypeid.as_ref())
+        .chain(
+            delta
+                .activities
+                .values()
+                .filter_map(|x| x.domaintypeid.as_ref()),
+        )
+        .chain(
+            delta
+                .entities
+                .values()
+                .filter_map(|x| x.domaintypeid.as_ref()),
+        )
+}
+
+/// The `ReadData` policy context for an agent, activity or entity, as used by the `/data` endpoint
+pub(super) fn read_data_context(
+    identity: &AuthId,
+    namespace: &ExternalId,
+    subject: &ChronicleIri,
+) -> Option<OpaData> {
+    let (prov_type, external_id) = match subject {
+        ChronicleIri::Agent(id) => ("agent", id.external_id_part()),
+        ChronicleIri::Activity(id) => ("activity", id.external_id_part()),
+        ChronicleIri::Entity(id) => ("entity", id.external_id_part()),
+        _ => return None,
+    };
+
+    Some(OpaData::operation(
+        identity,
+        &json!("ReadData"),
+        &json!({
+                "type": prov_type,
+                "id": external_id,
+                "namespace": namespace
+        }),
+    ))
+}
+
+async fn may_read(
+    opa: &ExecutorContext,
+    identity: &AuthId,
+    namespace: &NamespaceId,
+    subject: &ChronicleIri,
+) -> bool {
+    let namespace = namespace.external_id_part();
+    if let Some(opa_data) = read_data_context(identity, namespace, subject) {
+        if let Err(error) = opa
+            .for_namespace(namespace.as_str())
+            .evaluate(identity, &opa_data)
+            .await
+        {
+            debug!("{error}: withholding notification about {subject} from identity: {identity}");
+            return false;
+        }
+    }
+    true
+}
+
+/// Whether the subscriber may read every agent, activity and entity in a delta, a delta that
+/// includes any resource the subscriber may not read is withheld in its entirety
+pub async fn may_read_delta(opa: &ExecutorContext, identity: &AuthId, delta: &ProvModel) -> bool {
+    let resources = delta
+        .agents
+        .keys()
+        .map(|(ns, id)| (ns, ChronicleIri::from(id.clone())))
+        .chain(
+            delta
+                .activities
+                .keys()
+                .map(|(ns, id)| (ns, ChronicleIri::from(id.clone()))),
+        )
+        .chain(
+            delta
+                .entities
+                .keys()
+                .map(|(ns, id)| (ns, ChronicleIri::from(id.clone()))),
+        );
+
+    for (namespace, subject) in resources {
+        if !may_read(opa, identity, namespace, &subject).await {
+            return false;
+        }
+    }
+    true
+}
+
+pub async fn may_read_contradiction(
+    opa: &ExecutorContext,
+    identity: &AuthId,
+    contradiction: &Contradiction,
+) -> bool {
+    may_read(opa, identity, contradiction.namespace(), contradiction.id()).await
+}
+
+/// The error reported in place of the description of a contradiction that is withheld
+const CONTRADICTION_WITHHELD: &str = "Transaction contradicts recorded provenance";
+
+/// Withhold the delta and contradiction of a transaction receipt from a reader that may not read
+/// every resource they describe, returning whether anything was withheld. As the error of a
+/// contradicted transaction describes the contradiction, it is withheld along with it.
+pub async fn withhold_unreadable(
+    opa: &ExecutorContext,
+    identity: &AuthId,
+    receipt: &mut TransactionReceipt,
+) -> bool {
+    let mut withheld = false;
+    if let Some(delta) = &receipt.delta {
+        if !may_read_delta(opa, identity, delta).await {
+            receipt.delta = None;
+            withheld = true;
+        }
+    }
+    if let Some(contradiction) = &receipt.contradiction {
+        if !may_read_contradiction(opa, identity, contradiction).await {
+            receipt.contradiction = None;
+            receipt.error = Some(CONTRADICTION_WITHHELD.to_owned());
+            withheld = true;
+        }
+    }
+    withheld
+}
@@ -0,0 +1,1302 @@
+//! A durable ledger for a single node, kept in an append-only file on local disk.
+//!
+//! Transactions are applied by the same `ChronicleTransactionHandler` a Sawtooth validator
+//! runs, one block per batch. Each block records its signed batch, the state it writes and the
+//! events it emits, and is chained to the one before by a SHA-256 hash of its content. Blocks
+//! are synced to disk before their submission is acknowledged. Whenever the file is opened, the
+//! chain and the signatures of its batches are checked, and the batches of each block are applied
+//! again to rebuild its state, which fails unless they write the state and emit the events the
+//! block records. Ledger clients talk to it as they would to a validator.
+
+use async_stl_client::{
+    error::SawtoothCommunicationError,
+    zmq_client::{HighestBlockValidatorSelector, ZmqRequestResponseSawtoothChannel},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chronicle_proof::{prove_entries, InclusionProof};
+use chronicle_protocol::{
+    address::{FAMILY, VERSION},
+    settings::sawtooth_settings_address,
+};
+use chronicle_sawtooth_tp::tp::ChronicleTransactionHandler;
+use common::k256::{
+    ecdsa::{signature::Verifier, Signature, VerifyingKey},
+    sha2::{Digest, Sha256, Sha512},
+};
+use fs2::FileExt;
+use protobuf::Message;
+use sawtooth_sdk::{
+    messages::{
+        batch::{Batch, BatchHeader},
+        block::{Block, BlockHeader},
+        client_batch_submit::{
+            ClientBatchSubmitRequest, ClientBatchSubmitResponse, ClientBatchSubmitResponse_Status,
+        },
+        client_block::{
+            ClientBlockGetByNumRequest, ClientBlockGetResponse, ClientBlockGetResponse_Status,
+            ClientBlockListResponse, ClientBlockListResponse_Status,
+        },
+        client_event::{
+            ClientEventsSubscribeRequest, ClientEventsSubscribeResponse,
+            ClientEventsSubscribeResponse_Status,
+        },
+        client_state::{
+            ClientStateGetRequest, ClientStateGetResponse, ClientStateGetResponse_Status,
+        },
+        events::{Event, EventList, Event_Attribute},
+        processor::TpProcessRequest,
+        setting::{Setting, Setting_Entry},
+        transaction::TransactionHeader,
+        validator::Message_MessageType,
+    },
+    processor::handler::{ApplyError, ContextError, TransactionContext, TransactionHandler},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{debug, error, info, instrument, warn};
+
+use crate::{
+    inmem::{serve_simulated_validator, InMemLedger, SimulatedSawtoothBehavior},
+    ApiError, BlockWriteProofs,
+};
+
+#[derive(Error, Debug)]
+pub enum FileLedgerError {
+    #[error("Ledger file IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Ledger file {0} is in use by another process")]
+    Locked(PathBuf),
+
+    #[error("Ledger file JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Ledger file encoding error: {0}")]
+    Encoding(#[from] base64::DecodeError),
+
+    #[error("Ledger file is corrupt or has been tampered with at block {block_num}: {reason}")]
+    Corrupt { block_num: u64, reason: String },
+
+    #[error("Ledger client error: {0}")]
+    Communication(#[from] SawtoothCommunicationError),
+
+    #[error("Ledger policy error: {0}")]
+    Policy(#[from] ApplyError),
+}
+
+/// An event emitted by a transaction, with its data base64 encoded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct EventRecord {
+    event_type: String,
+    attributes: Vec<(String, String)>,
+    data: String,
+}
+
+/// The hashed content of a block. Batches and state values are base64 encoded, with `None` for
+/// a deletion.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct BlockBody {
+    block_num: u64,
+    previous_block_id: String,
+    state_root: String,
+    transaction_ids: Vec<String>,
+    batches: Vec<String>,
+    state: Vec<(String, Option<String>)>,
+    events: Vec<EventRecord>,
+}
+
+/// A block as it is written to the ledger file, one per line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct BlockRecord {
+    block_id: String,
+    #[serde(flatten)]
+    body: BlockBody,
+}
+
+impl BlockRecord {
+    fn new(body: BlockBody) -> Result<Self, FileLedgerError> {
+        Ok(Self {
+            block_id: block_id(&body)?,
+            body,
+        })
+    }
+
+    fn header(&self) -> Block {
+        let header = BlockHeader {
+            block_num: self.body.block_num,
+            previous_block_id: self.body.previous_block_id.clone(),
+            state_root_hash: self.body.state_root.clone(),
+            batch_ids: self.body.transaction_ids.clone().into(),
+            ..Default::default()
+        };
+
+        Block {
+            header: header.write_to_bytes().unwrap(),
+            header_signature: self.block_id.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// The block's commit event followed by those of its transactions, as a validator sends
+    /// them to subscribers
+    fn event_list(&self) -> Result<Vec<u8>, FileLedgerError> {
+        let commit = Event {
+            event_type: "sawtooth/block-commit".to_owned(),
+            attributes: [
+                ("block_id", self.block_id.clone()),
+                ("block_num", self.body.block_num.to_string()),
+                ("previous_block_id", self.body.previous_block_id.clone()),
+                ("state_root_hash", self.body.state_root.clone()),
+            ]
+            .into_iter()
+            .map(|(key, value)| Event_Attribute {
+                key: key.to_owned(),
+                value,
+                ..Default::default()
+            })
+            .collect(),
+            ..Default::default()
+        };
+
+        let mut events = vec![commit];
+        for event in &self.body.events {
+            events.push(Event {
+                event_type: event.event_type.clone(),
+                attributes: event
+                    .attributes
+                    .iter()
+                    .map(|(key, value)| Event_Attribute {
+                        key: key.clone(),
+                        value: value.clone(),
+                        ..Default::default()
+                    })
+                    .collect(),
+                data: STANDARD.decode(&event.data)?,
+                ..Default::default()
+            });
+        }
+
+        Ok(EventList {
+            events: events.into(),
+            ..Default::default()
+        }
+        .write_to_bytes()
+        .unwrap())
+    }
+}
+
+impl BlockBody {
+    /// The state entries the block wrote, in the order they are committed
+    fn written(&self) -> Result<Vec<(&str, Vec<u8>)>, FileLedgerError> {
+        self.state
+            .iter()
+            .filter_map(|(address, value)| value.as_ref().map(|value| (address, value)))
+            .map(|(address, value)| Ok((address.as_str(), STANDARD.decode(value)?)))
+            .collect()
+    }
+
+    /// The header proofs of the block's state entries are checked against, whose hash is the
+    /// block's id
+    fn proof_header(&self) -> Result<chronicle_proof::BlockHeader, FileLedgerError> {
+        Ok(chronicle_proof::BlockHeader::new(
+            self.block_num,
+            self.previous_block_id.clone(),
+            self.state_root.clone(),
+            hex::encode(Sha256::digest(serde_json::to_vec(self)?)),
+        ))
+    }
+}
+
+fn block_id(body: &BlockBody) -> Result<String, FileLedgerError> {
+    Ok(body.proof_header()?.block_id)
+}
+
+/// Check a hex encoded secp256k1 signature of `message` by a hex encoded public key
+fn verify_signature(public_key: &str, message: &[u8], signature: &str) -> Result<(), String> {
+    let key = hex::decode(public_key)
+        .ok()
+        .and_then(|key| VerifyingKey::from_sec1_bytes(&key).ok())
+        .ok_or_else(|| format!("invalid public key {public_key}"))?;
+    let decoded = hex::decode(signature)
+        .ok()
+        .and_then(|decoded| Signature::try_from(decoded.as_slice()).ok())
+        .ok_or_else(|| format!("invalid signature {signature}"))?;
+
+    key.verify(message, &decoded)
+        .map_err(|_| format!("signature {signature} does not match"))
+}
+
+/// Check the signature of a batch and those of its transactions, as a validator does before
+/// applying them
+fn verify_batch(batch: &Batch) -> Result<(), String> {
+    let header = BatchHeader::parse_from_bytes(batch.get_header())
+        .map_err(|e| format!("invalid batch header: {e}"))?;
+    verify_signature(
+        header.get_signer_public_key(),
+        batch.get_header(),
+        batch.get_header_signature(),
+    )?;
+
+    if !header.get_transaction_ids().iter().eq(batch
+        .get_transactions()
+        .iter()
+        .map(|tx| tx.get_header_signature()))
+    {
+        return Err("batch transactions do not match its header".to_owned());
+    }
+
+    for tx in batch.get_transactions() {
+        let tx_header = TransactionHeader::parse_from_bytes(tx.get_header())
+            .map_err(|e| format!("invalid transaction header: {e}"))?;
+        verify_signature(
+            tx_header.get_signer_public_key(),
+            tx.get_header(),
+            tx.get_header_signature(),
+        )?;
+
+        if tx_header.get_batcher_public_key() != header.get_signer_public_key() {
+            return Err(format!(
+                "transaction {} was not signed for its batcher",
+                tx.get_header_signature()
+            ));
+        }
+        if tx_header.get_payload_sha512() != hex::encode(Sha512::digest(tx.get_payload())) {
+            return Err(format!(
+                "transaction {} payload does not match its header",
+                tx.get_header_signature()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The block the genesis block follows
+fn null_block_id() -> String {
+    hex::encode([0; 32])
+}
+
+/// Where a block is in the ledger file, with its place in the chain
+#[derive(Debug, Clone)]
+struct BlockIndex {
+    block_id: String,
+    offset: u64,
+}
+
+/// The blocks in the ledger file and the state they have written
+struct Chain {
+    path: PathBuf,
+    file: File,
+    blocks: Vec<BlockIndex>,
+    state: BTreeMap<String, Vec<u8>>,
+}
+
+impl Chain {
+    /// Open the ledger file, creating it with a genesis block if need be, and check its chain.
+    /// The batches of each block are applied again with `executor`, if given, and must write the
+    /// state and emit the events the block records. The file is locked for as long as the chain
+    /// is open, so fails if another has it open.
+    #[instrument(skip(executor))]
+    fn open(path: &Path, executor: Option<&BlockExecutor>) -> Result<Self, FileLedgerError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        file.try_lock_exclusive().map_err(|e| {
+            if e.raw_os_error() == fs2::lock_contended_error().raw_os_error() {
+                FileLedgerError::Locked(path.to_owned())
+            } else {
+                e.into()
+            }
+        })?;
+
+        let mut chain = Self {
+            path: path.to_owned(),
+            file,
+            blocks: vec![],
+            state: BTreeMap::new(),
+        };
+
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut offset = 0;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line)?;
+            if read == 0 {
+                break;
+            }
+
+            // Blocks are only acknowledged once their line is complete and synced, so an
+            // unterminated last line is an append interrupted by a crash
+            if !line.ends_with('\n') {
+                warn!(
+                    offset,
+                    "Discarding incomplete block at the end of the ledger file"
+                );
+                chain.file.set_len(offset)?;
+                break;
+            }
+
+            let block = serde_json::from_str::<BlockRecord>(&line).map_err(|e| {
+                FileLedgerError::Corrupt {
+                    block_num: chain.blocks.len() as u64,
+                    reason: e.to_string(),
+                }
+            })?;
+            let batches = chain.verify(&block)?;
+            if let Some(executor) = executor {
+                chain.replay(executor, &block, &batches)?;
+            }
+            chain.apply(&block, offset)?;
+
+            offset += read as u64;
+        }
+
+        if chain.blocks.is_empty() {
+            chain.append(&[], BTreeMap::new(), vec![])?;
+        }
+
+        info!(path = ?chain.path, height = chain.height(), "Opened ledger file");
+
+        Ok(chain)
+    }
+
+    fn height(&self) -> u64 {
+        self.blocks.len() as u64 - 1
+    }
+
+    fn head_id(&self) -> String {
+        self.blocks
+            .last()
+            .map(|block| block.block_id.clone())
+            .unwrap_or_else(null_block_id)
+    }
+
+    /// Check that `block` is the next in the chain, has not been altered, and holds the batches
+    /// its transactions were signed in, returning them
+    fn verify(&self, block: &BlockRecord) -> Result<Vec<Batch>, FileLedgerError> {
+        let corrupt = |reason: &str| FileLedgerError::Corrupt {
+            block_num: self.blocks.len() as u64,
+            reason: reason.to_owned(),
+        };
+
+        if block.body.block_num != self.blocks.len() as u64 {
+            return Err(corrupt("block number out of sequence"));
+        }
+        if block.body.previous_block_id != self.head_id() {
+            return Err(corrupt("previous block id does not match"));
+        }
+        if block_id(&block.body)? != block.block_id {
+            return Err(corrupt("block content does not match its id"));
+        }
+
+        let mut batches = vec![];
+        for batch in &block.body.batches {
+            let batch = Batch::parse_from_bytes(&STANDARD.decode(batch)?)
+                .map_err(|e| corrupt(&format!("invalid batch: {e}")))?;
+            verify_batch(&batch).map_err(|reason| corrupt(&reason))?;
+            batches.push(batch);
+        }
+        if !batches
+            .iter()
+            .flat_map(|batch| batch.get_transactions())
+            .map(|tx| tx.get_header_signature())
+            .eq(block.body.transaction_ids.iter().map(String::as_str))
+        {
+            return Err(corrupt("transaction ids do not match the block's batches"));
+        }
+
+        let written = block.body.written()?;
+        if chronicle_proof::write_root(
+            written
+                .iter()
+                .map(|(address, value)| (*address, value.as_slice())),
+        ) != block.body.state_root
+        {
+            return Err(corrupt("state root does not match the state written"));
+        }
+
+        Ok(batches)
+    }
+
+    /// Check that applying the `batches` of `block` over the state of the chain writes the state
+    /// and emits the events the block records, so that neither can have been rewritten along
+    /// with the hashes of the chain
+    fn replay(
+        &self,
+        executor: &BlockExecutor,
+        block: &BlockRecord,
+        batches: &[Batch],
+    ) -> Result<(), FileLedgerError> {
+        let corrupt = |reason: String| FileLedgerError::Corrupt {
+            block_num: self.blocks.len() as u64,
+            reason,
+        };
+
+        let (writes, events) =
+            executor
+                .execute(&self.state, batches)
+                .map_err(|(transaction_id, e)| {
+                    corrupt(format!("transaction {transaction_id} does not apply: {e}"))
+                })?;
+
+        let state = writes
+            .into_iter()
+            .map(|(address, value)| (address, value.map(|value| STANDARD.encode(value))))
+            .collect::<Vec<_>>();
+        if state != block.body.state {
+            return Err(corrupt(
+                "state does not match that written by its transactions".to_owned(),
+            ));
+        }
+        if events != block.body.events {
+            return Err(corrupt(
+                "events do not match those emitted by its transactions".to_owned(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn apply(&mut self, block: &BlockRecord, offset: u64) -> Result<(), FileLedgerError> {
+        for (address, value) in &block.body.state {
+            match value {
+                Some(value) => {
+                    self.state.insert(address.clone(), STANDARD.decode(value)?);
+                }
+                None => {
+                    self.state.remove(address);
+                }
+            }
+        }
+
+        self.blocks.push(BlockIndex {
+            block_id: block.block_id.clone(),
+            offset,
+        });
+
+        Ok(())
+    }
+
+    /// Write a block of `batches` to the end of the chain, returning once it is on disk
+    fn append(
+        &mut self,
+        batches: &[Batch],
+        writes: BTreeMap<String, Option<Vec<u8>>>,
+        events: Vec<EventRecord>,
+    ) -> Result<BlockRecord, FileLedgerError> {
+        let state_root = chronicle_proof::write_root(
+            writes
+                .iter()
+                .filter_map(|(address, value)| Some((address.as_str(), value.as_deref()?))),
+        );
+
+        let block = BlockRecord::new(BlockBody {
+            block_num: self.blocks.len() as u64,
+            previous_block_id: self.head_id(),
+            state_root,
+            transaction_ids: batches
+                .iter()
+                .flat_map(|batch| batch.get_transactions())
+                .map(|tx| tx.get_header_signature().to_owned())
+                .collect(),
+            batches: batches
+                .iter()
+                .map(|batch| STANDARD.encode(batch.write_to_bytes().unwrap()))
+                .collect(),
+            state: writes
+                .into_iter()
+                .map(|(address, value)| (address, value.map(|value| STANDARD.encode(value))))
+                .collect(),
+            events,
+        })?;
+
+        let mut line = serde_json::to_vec(&block)?;
+        line.push(b'\n');
+
+        let offset = self.file.metadata()?.len();
+        if let Err(e) = self
+            .file
+            .write_all(&line)
+            .and_then(|_| self.file.sync_data())
+        {
+            // Leave no partial block for the next one to follow
+            self.file.set_len(offset).ok();
+            return Err(e.into());
+        }
+
+        self.apply(&block, offset)?;
+
+        Ok(block)
+    }
+
+    fn read(&self, block_num: u64) -> Result<Option<BlockRecord>, FileLedgerError> {
+        let index = match self.blocks.get(block_num as usize) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(index.offset))?;
+        let mut line = String::new();
+        BufReader::new(file).read_line(&mut line)?;
+
+        Ok(Some(serde_json::from_str(&line)?))
+    }
+
+    /// Proofs of the state entries written by a block, if it is known
+    fn prove(&self, block_id: &str) -> Result<Option<Vec<InclusionProof>>, FileLedgerError> {
+        let block = match self.position(block_id) {
+            Some(position) => self.read(position)?,
+            None => return Ok(None),
+        };
+
+        block
+            .map(|block| {
+                let written = block.body.written()?;
+                Ok::<_, FileLedgerError>(prove_entries(
+                    &block.body.proof_header()?,
+                    written
+                        .iter()
+                        .map(|(address, value)| (*address, value.as_slice())),
+                ))
+            })
+            .transpose()
+    }
+
+    fn position(&self, block_id: &str) -> Option<u64> {
+        self.blocks
+            .iter()
+            .position(|block| block.block_id == block_id)
+            .map(|position| position as u64)
+    }
+}
+
+/// The state writes and events of a block being built, over the state of the chain
+struct BlockContext<'a> {
+    state: &'a BTreeMap<String, Vec<u8>>,
+    settings: &'a BTreeMap<String, Vec<u8>>,
+    writes: RefCell<BTreeMap<String, Option<Vec<u8>>>>,
+    events: RefCell<Vec<EventRecord>>,
+}
+
+impl<'a> BlockContext<'a> {
+    fn new(state: &'a BTreeMap<String, Vec<u8>>, settings: &'a BTreeMap<String, Vec<u8>>) -> Self {
+        Self {
+            state,
+            settings,
+            writes: RefCell::new(BTreeMap::new()),
+            events: RefCell::new(vec![]),
+        }
+    }
+
+    fn get(&self, address: &str) -> Option<Vec<u8>> {
+        match self.writes.borrow().get(address) {
+            Some(value) => value.clone(),
+            None => self
+                .state
+                .get(address)
+                .or_else(|| self.settings.get(address))
+                .cloned(),
+        }
+    }
+}
+
+impl TransactionContext for BlockContext<'_> {
+    fn get_state_entries(
+        &self,
+        addresses: &[String],
+    ) -> Result<Vec<(String, Vec<u8>)>, ContextError> {
+        Ok(addresses
+            .iter()
+            .filter_map(|address| self.get(address).map(|value| (address.clone(), value)))
+            .collect())
+    }
+
+    fn set_state_entries(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), ContextError> {
+        let mut writes = self.writes.borrow_mut();
+        for (address, value) in entries {
+            writes.insert(address, Some(value));
+        }
+
+        Ok(())
+    }
+
+    fn delete_state_entries(&self, addresses: &[String]) -> Result<Vec<String>, ContextError> {
+        let deleted = addresses
+            .iter()
+            .filter(|address| self.get(address).is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut writes = self.writes.borrow_mut();
+        for address in &deleted {
+            writes.insert(address.clone(), None);
+        }
+
+        Ok(deleted)
+    }
+
+    fn add_receipt_data(&self, _data: &[u8]) -> Result<(), ContextError> {
+        Ok(())
+    }
+
+    fn add_event(
+        &self,
+        event_type: String,
+        attributes: Vec<(String, String)>,
+        data: &[u8],
+    ) -> Result<(), ContextError> {
+        self.events.borrow_mut().push(EventRecord {
+            event_type,
+            attributes,
+            data: STANDARD.encode(data),
+        });
+
+        Ok(())
+    }
+}
+
+/// The state writes of a block, with `None` for a deletion, and the events it emits
+type BlockEffects = (BTreeMap<String, Option<Vec<u8>>>, Vec<EventRecord>);
+
+/// Applies the transactions of batches as a validator would
+struct BlockExecutor {
+    handler: ChronicleTransactionHandler,
+    // Setting entries by address, as the settings transaction processor would hold them
+    settings: BTreeMap<String, Vec<u8>>,
+}
+
+impl BlockExecutor {
+    /// The effects of applying the transactions of `batches` over `state`, or the id of the
+    /// first that is invalid and why
+    fn execute(
+        &self,
+        state: &BTreeMap<String, Vec<u8>>,
+        batches: &[Batch],
+    ) -> Result<BlockEffects, (String, ApplyError)> {
+        let mut context = BlockContext::new(state, &self.settings);
+
+        for tx in batches.iter().flat_map(|batch| batch.get_transactions()) {
+            let req = TpProcessRequest {
+                payload: tx.get_payload().to_vec(),
+                header: TransactionHeader::parse_from_bytes(tx.get_header())
+                    .ok()
+                    .into(),
+                signature: tx.get_header_signature().to_string(),
+                ..Default::default()
+            };
+
+            self.handler
+                .apply(&req, &mut context)
+                .map_err(|e| (req.signature, e))?;
+        }
+
+        let BlockContext { writes, events, .. } = context;
+        Ok((writes.into_inner(), events.into_inner()))
+    }
+}
+
+struct FileLedgerBehavior {
+    executor: Arc<BlockExecutor>,
+    chain: Arc<Mutex<Chain>>,
+    tx: UnboundedSender<Option<(Message_MessageType, Vec<u8>)>>,
+}
+
+impl FileLedgerBehavior {
+    /// Apply the transactions of a batch and write them as a block, or nothing if the batch is
+    /// not properly signed or any of its transactions is invalid
+    fn commit_batch(
+        &self,
+        chain: &mut Chain,
+        batch: Batch,
+    ) -> Result<ClientBatchSubmitResponse_Status, FileLedgerError> {
+        if let Err(reason) = verify_batch(&batch) {
+            warn!(batch_id = %batch.get_header_signature(), %reason, "Rejecting invalid batch");
+            return Ok(ClientBatchSubmitResponse_Status::INVALID_BATCH);
+        }
+
+        let batches = [batch];
+        let (writes, events) = match self.executor.execute(&chain.state, &batches) {
+            Ok(effects) => effects,
+            Err((transaction_id, e)) => {
+                warn!(%transaction_id, ?e, "Rejecting invalid batch");
+                return Ok(ClientBatchSubmitResponse_Status::INVALID_BATCH);
+            }
+        };
+
+        let block = chain.append(&batches, writes, events)?;
+
+        debug!(block_num = block.body.block_num, block_id = %block.block_id, "Committed block");
+
+        self.tx
+            .send(Some((
+                Message_MessageType::CLIENT_EVENTS,
+                block.event_list()?,
+            )))
+            .ok();
+
+        Ok(ClientBatchSubmitResponse_Status::OK)
+    }
+
+    /// Send subscribers the events of the blocks after the last they know of, or of every
+    /// block if they know of none
+    fn catch_up(
+        &self,
+        chain: &Chain,
+        last_known_block_ids: &[String],
+    ) -> Result<ClientEventsSubscribeResponse_Status, FileLedgerError> {
+        let from = if last_known_block_ids.is_empty() {
+            0
+        } else {
+            match last_known_block_ids
+                .iter()
+                .filter_map(|block_id| chain.position(block_id))
+                .max()
+            {
+                Some(position) => position + 1,
+                None => return Ok(ClientEventsSubscribeResponse_Status::UNKNOWN_BLOCK),
+            }
+        };
+
+        for block_num in from..=chain.height() {
+            if let Some(block) = chain.read(block_num)? {
+                self.tx
+                    .send(Some((
+                        Message_MessageType::CLIENT_EVENTS,
+                        block.event_list()?,
+                    )))
+                    .ok();
+            }
+        }
+
+        Ok(ClientEventsSubscribeResponse_Status::OK)
+    }
+}
+
+impl SimulatedSawtoothBehavior for FileLedgerBehavior {
+    #[instrument(skip(self, request))]
+    fn handle_request(
+        &self,
+        message_type: Message_MessageType,
+        request: Vec<u8>,
+    ) -> Result<(Message_MessageType, Vec<u8>), SawtoothCommunicationError> {
+        let mut chain = self.chain.lock().unwrap();
+
+        match message_type {
+            Message_MessageType::CLIENT_BATCH_SUBMIT_REQUEST => {
+                let mut req = ClientBatchSubmitRequest::parse_from_bytes(&request).unwrap();
+
+                let mut response = ClientBatchSubmitResponse::new();
+                response.set_status(ClientBatchSubmitResponse_Status::OK);
+                for batch in req.take_batches() {
+                    let status = self.commit_batch(&mut chain, batch).unwrap_or_else(|e| {
+                        error!(?e, "Writing block to ledger file");
+                        ClientBatchSubmitResponse_Status::INTERNAL_ERROR
+                    });
+                    if status != ClientBatchSubmitResponse_Status::OK {
+                        response.set_status(status);
+                        break;
+                    }
+                }
+
+                Ok((
+                    Message_MessageType::CLIENT_BATCH_SUBMIT_RESPONSE,
+                    response.write_to_bytes().unwrap(),
+                ))
+            }
+            Message_MessageType::CLIENT_BLOCK_GET_BY_NUM_REQUEST => {
+                let req = ClientBlockGetByNumRequest::parse_from_bytes(&request).unwrap();
+
+                let mut response = ClientBlockGetResponse::new();
+                match chain.read(req.get_block_num()) {
+                    Ok(Some(block)) => {
+                        response.set_block(block.header());
+                        response.set_status(ClientBlockGetResponse_Status::OK);
+                    }
+                    Ok(None) => response.set_status(ClientBlockGetResponse_Status::NO_RESOURCE),
+                    Err(e) => {
+                        error!(?e, "Reading block from ledger file");
+                        response.set_status(ClientBlockGetResponse_Status::INTERNAL_ERROR);
+                    }
+                }
+
+                Ok((
+                    Message_MessageType::CLIENT_BLOCK_GET_RESPONSE,
+                    response.write_to_bytes().unwrap(),
+                ))
+            }
+            // Respond with the head of the chain
+            Message_MessageType::CLIENT_BLOCK_LIST_REQUEST => {
+                let mut response = ClientBlockListResponse::new();
+                match chain.read(chain.height()) {
+                    Ok(Some(block)) => {
+                        response.set_blocks(vec![block.header()].into());
+                        response.set_status(ClientBlockListResponse_Status::OK);
+                    }
+                    Ok(None) => response.set_status(ClientBlockListResponse_Status::NO_RESOURCE),
+                    Err(e) => {
+                        error!(?e, "Reading block from ledger file");
+                        response.set_status(ClientBlockListResponse_Status::INTERNAL_ERROR);
+                    }
+                }
+
+                Ok((
+                    Message_MessageType::CLIENT_BLOCK_LIST_RESPONSE,
+                    response.write_to_bytes().unwrap(),
+                ))
+            }
+            Message_MessageType::CLIENT_EVENTS_SUBSCRIBE_REQUEST => {
+                let req = ClientEventsSubscribeRequest::parse_from_bytes(&request).unwrap();
+
+                let mut response = ClientEventsSubscribeResponse::new();
+                response.set_status(
+                    self.catch_up(&chain, req.get_last_known_block_ids())
+                        .unwrap_or_else(|e| {
+                            error!(?e, "Replaying events from ledger file");
+                            // Subscription responses have no internal error status, so refuse
+                            // the subscription with the one that is neither success nor a
+                            // client error, rather than leave the subscriber missing events
+                            ClientEventsSubscribeResponse_Status::STATUS_UNSET
+                        }),
+                );
+
+                Ok((
+                    Message_MessageType::CLIENT_EVENTS_SUBSCRIBE_RESPONSE,
+                    response.write_to_bytes().unwrap(),
+                ))
+            }
+            Message_MessageType::CLIENT_STATE_GET_REQUEST => {
+                let mut req = ClientStateGetRequest::parse_from_bytes(&request).unwrap();
+
+                let mut response = ClientStateGetResponse::new();
+                match chain.state.get(&req.take_address()) {
+                    Some(value) => {
+                        response.set_value(value.clone());
+                        response.set_status(ClientStateGetResponse_Status::OK);
+                    }
+                    None => response.set_status(ClientStateGetResponse_Status::NO_RESOURCE),
+                }
+
+                Ok((
+                    Message_MessageType::CLIENT_STATE_GET_RESPONSE,
+                    response.write_to_bytes().unwrap(),
+                ))
+            }
+            _ => panic!("Unexpected message type {} received", message_type as i32),
+        }
+    }
+}
+
+/// A ledger kept in a file on local disk, for a single Chronicle to use
+pub struct FileLedger {
+    pub ledger: InMemLedger,
+    chain: Arc<Mutex<Chain>>,
+}
+
+impl FileLedger {
+    /// Open the ledger file at `path`, creating it if it does not exist, and check the
+    /// transactions submitted to it against the embedded OPA `policy` and its `entrypoint`.
+    /// Transactions read the Sawtooth `settings`, given as keys and values, as they would from a
+    /// validator. Fails if its chain of blocks has been broken, or if the batches of a block do
+    /// not write the state and emit the events it records when applied again, so it must be
+    /// opened with the policy and settings it was written with.
+    pub fn open(
+        path: impl AsRef<Path>,
+        policy: &str,
+        entrypoint: &str,
+        settings: &[(&str, &str)],
+    ) -> Result<Self, FileLedgerError> {
+        let handler = ChronicleTransactionHandler::new(policy, entrypoint)?;
+        let settings = settings
+            .iter()
+            .map(|(key, value)| {
+                let setting = Setting {
+                    entries: vec![Setting_Entry {
+                        key: key.to_string(),
+                        value: value.to_string(),
+                        ..Default::default()
+                    }]
+                    .into(),
+                    ..Default::default()
+                };
+
+                (
+                    sawtooth_settings_address(key),
+                    setting.write_to_bytes().unwrap(),
+                )
+            })
+            .collect();
+        let executor = Arc::new(BlockExecutor { handler, settings });
+        let chain = Arc::new(Mutex::new(Chain::open(path.as_ref(), Some(&executor))?));
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let behavior = FileLedgerBehavior {
+            executor,
+            chain: chain.clone(),
+            tx,
+        };
+
+        let connect_addr = serve_simulated_validator(behavior, rx);
+
+        Ok(Self {
+            ledger: InMemLedger::new(
+                ZmqRequestResponseSawtoothChannel::new(
+                    "file_ledger",
+                    &[connect_addr],
+                    HighestBlockValidatorSelector,
+                )?,
+                FAMILY,
+                VERSION,
+            ),
+            chain,
+        })
+    }
+
+    /// The number of the last block written
+    pub fn height(&self) -> u64 {
+        self.chain.lock().unwrap().height()
+    }
+
+    pub fn proofs(&self) -> FileLedgerProofs {
+        FileLedgerProofs {
+            chain: self.chain.clone(),
+        }
+    }
+}
+
+/// Proves the state entries committed in a ledger file's blocks
+#[derive(Clone)]
+pub struct FileLedgerProofs {
+    chain: Arc<Mutex<Chain>>,
+}
+
+impl std::fmt::Debug for FileLedgerProofs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileLedgerProofs")
+            .field("path", &self.chain.lock().unwrap().path)
+            .finish()
+    }
+}
+
+impl BlockWriteProofs for FileLedgerProofs {
+    fn prove_block(&self, block_id: &str) -> Result<Option<Vec<InclusionProof>>, ApiError> {
+        Ok(self.chain.lock().unwrap().prove(block_id)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_stl_client::sawtooth::TransactionPayload;
+    use chronicle_protocol::messages::ChronicleSubmitTransaction;
+    use chronicle_signing::{
+        chronicle_secret_names, ChronicleSecretsOptions, ChronicleSigning, BATCHER_NAMESPACE,
+        CHRONICLE_NAMESPACE,
+    };
+    use common::{
+        identity::AuthId,
+        k256::ecdsa::{signature::Signer, SigningKey},
+        prov::{
+            operations::{AgentExists, ChronicleOperation, CreateNamespace},
+            ChronicleTransaction, NamespaceId, UuidPart,
+        },
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+    use sawtooth_sdk::messages::transaction::Transaction;
+    use std::io::Read;
+
+    /// A batch of one transaction of `payload`, signed as a batcher signs it
+    fn signed_batch(payload: &[u8]) -> Batch {
+        let key = SigningKey::random(StdRng::from_entropy());
+        let public_key = hex::encode(key.verifying_key().to_bytes());
+        let sign = |bytes: &[u8]| hex::encode(Signer::<Signature>::sign(&key, bytes).as_ref());
+
+        let header = TransactionHeader {
+            family_name: FAMILY.to_owned(),
+            family_version: VERSION.to_owned(),
+            signer_public_key: public_key.clone(),
+            batcher_public_key: public_key.clone(),
+            payload_sha512: hex::encode(Sha512::digest(payload)),
+            ..Default::default()
+        }
+        .write_to_bytes()
+        .unwrap();
+        let tx = Transaction {
+            header_signature: sign(&header),
+            header,
+            payload: payload.to_vec(),
+            ..Default::default()
+        };
+
+        let header = BatchHeader {
+            signer_public_key: public_key,
+            transaction_ids: vec![tx.header_signature.clone()].into(),
+            ..Default::default()
+        }
+        .write_to_bytes()
+        .unwrap();
+        Batch {
+            header_signature: sign(&header),
+            header,
+            transactions: vec![tx].into(),
+            ..Default::default()
+        }
+    }
+
+    fn write_batch(chain: &mut Chain, batch: Batch, address: &str, value: &[u8]) -> BlockRecord {
+        let transaction_id = batch.get_transactions()[0]
+            .get_header_signature()
+            .to_owned();
+        chain
+            .append(
+                &[batch],
+                [(address.to_owned(), Some(value.to_vec()))].into(),
+                vec![EventRecord {
+                    event_type: "chronicle/prov-update".to_owned(),
+                    attributes: vec![("transaction_id".to_owned(), transaction_id)],
+                    data: STANDARD.encode(value),
+                }],
+            )
+            .unwrap()
+    }
+
+    fn write(chain: &mut Chain, address: &str, value: &[u8]) -> BlockRecord {
+        write_batch(chain, signed_batch(value), address, value)
+    }
+
+    #[test]
+    fn reopened_chain_keeps_blocks_and_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger");
+
+        let mut chain = Chain::open(&path, None).unwrap();
+        assert_eq!(chain.height(), 0);
+        write(&mut chain, "a", b"1");
+        let last = write(&mut chain, "b", b"2");
+        drop(chain);
+
+        let chain = Chain::open(&path, None).unwrap();
+        assert_eq!(chain.height(), 2);
+        assert_eq!(chain.head_id(), last.block_id);
+        assert_eq!(chain.state.get("a"), Some(&b"1".to_vec()));
+        assert_eq!(chain.read(2).unwrap(), Some(last));
+        assert_eq!(chain.read(3).unwrap(), None);
+    }
+
+    #[test]
+    fn proofs_verify_against_the_block_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut chain = Chain::open(&dir.path().join("ledger"), None).unwrap();
+        let block = write(&mut chain, "a", b"{\"value\":1}");
+
+        let proofs = chain.prove(&block.block_id).unwrap().unwrap();
+        assert_eq!(proofs.len(), 1);
+        chronicle_proof::verify(&proofs[0], &block.block_id, Some(b"{ \"value\": 1 }")).unwrap();
+
+        assert!(chain.prove("unknown").unwrap().is_none());
+    }
+
+    #[test]
+    fn altered_block_is_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger");
+
+        let mut chain = Chain::open(&path, None).unwrap();
+        write(&mut chain, "a", b"1");
+        write(&mut chain, "b", b"2");
+        drop(chain);
+
+        let mut content = String::new();
+        File::open(&path)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        std::fs::write(
+            &path,
+            content.replace(&STANDARD.encode(b"1"), &STANDARD.encode(b"3")),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            Chain::open(&path, None),
+            Err(FileLedgerError::Corrupt { block_num: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn batch_with_altered_payload_is_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger");
+
+        let mut batch = signed_batch(b"1");
+        batch.transactions[0].set_payload(b"2".to_vec());
+        assert!(verify_batch(&batch).is_err());
+
+        // A block whose hash chain is intact, but whose batch was not signed as it stands
+        let mut chain = Chain::open(&path, None).unwrap();
+        write(&mut chain, "a", b"1");
+        write_batch(&mut chain, batch, "b", b"2");
+        drop(chain);
+
+        assert!(matches!(
+            Chain::open(&path, None),
+            Err(FileLedgerError::Corrupt { block_num: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn batch_signed_by_another_key_is_detected() {
+        let mut batch = signed_batch(b"1");
+        let other = signed_batch(b"1");
+        batch.set_header_signature(other.get_header_signature().to_owned());
+
+        assert!(verify_batch(&batch).is_err());
+        assert!(verify_batch(&signed_batch(b"1")).is_ok());
+    }
+
+    #[test]
+    fn chain_cannot_be_opened_twice() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger");
+
+        let chain = Chain::open(&path, None).unwrap();
+        assert!(matches!(
+            Chain::open(&path, None),
+            Err(FileLedgerError::Locked(locked)) if locked == path
+        ));
+        drop(chain);
+
+        assert_eq!(Chain::open(&path, None).unwrap().height(), 0);
+    }
+
+    #[test]
+    fn unknown_policy_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger");
+
+        assert!(matches!(
+            FileLedger::open(&path, "no_such_policy", "no_such_policy.allowed_users", &[]),
+            Err(FileLedgerError::Policy(_))
+        ));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn incomplete_last_block_is_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger");
+
+        let mut chain = Chain::open(&path, None).unwrap();
+        let first = write(&mut chain, "a", b"1");
+        drop(chain);
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"block_id\":").unwrap();
+        drop(file);
+
+        let mut chain = Chain::open(&path, None).unwrap();
+        assert_eq!(chain.head_id(), first.block_id);
+        write(&mut chain, "b", b"2");
+        drop(chain);
+
+        assert_eq!(Chain::open(&path, None).unwrap().height(), 2);
+    }
+
+    #[tokio::test]
+    async fn rewritten_event_is_detected_by_applying_its_block_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger");
+        let executor = BlockExecutor {
+            handler: ChronicleTransactionHandler::new(
+                "allow_transactions",
+                "allow_transactions.allowed_users",
+            )
+            .unwrap(),
+            settings: BTreeMap::new(),
+        };
+
+        let signing = ChronicleSigning::new(
+            chronicle_secret_names(),
+            vec![
+                (
+                    CHRONICLE_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::generate_in_memory(),
+                ),
+                (
+                    BATCHER_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::generate_in_memory(),
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+        let namespace = NamespaceId::from_external_id("testns", uuid::Uuid::new_v4());
+        let payload = ChronicleSubmitTransaction {
+            tx: ChronicleTransaction::new(
+                vec![
+                    ChronicleOperation::CreateNamespace(CreateNamespace::new(
+                        namespace.clone(),
+                        "testns",
+                        *namespace.uuid_part(),
+                    )),
+                    ChronicleOperation::AgentExists(AgentExists {
+                        namespace,
+                        external_id: "testagent".into(),
+                    }),
+                ],
+                AuthId::chronicle().signed_identity(&signing).unwrap(),
+            ),
+            signer: signing,
+            policy_name: None,
+            key_rotation: None,
+            agent_signed: None,
+        }
+        .to_bytes()
+        .await
+        .unwrap();
+
+        let mut chain = Chain::open(&path, Some(&executor)).unwrap();
+        let batches = [signed_batch(&payload)];
+        let (writes, events) = executor.execute(&chain.state, &batches).unwrap();
+        chain.append(&batches, writes, events).unwrap();
+        drop(chain);
+        assert_eq!(Chain::open(&path, Some(&executor)).unwrap().height(), 1);
+
+        // Alter the event the transaction emitted, and rewrite the chain of hashes to match
+        let mut previous_block_id = null_block_id();
+        let mut rewritten = String::new();
+        for line in std::fs::read_to_string(&path).unwrap().lines() {
+            let mut body = serde_json::from_str::<BlockRecord>(line).unwrap().body;
+            if body.block_num == 1 {
+                body.events[0].data = STANDARD.encode(b"altered");
+            }
+            body.previous_block_id = previous_block_id;
+            let block = BlockRecord::new(body).unwrap();
+            previous_block_id = block.block_id.clone();
+            rewritten.push_str(&serde_json::to_string(&block).unwrap());
+            rewritten.push('\n');
+        }
+        std::fs::write(&path, rewritten).unwrap();
+
+        // The chain and the batch's signatures still hold, but the block does not replay
+        drop(Chain::open(&path, None).unwrap());
+        assert!(matches!(
+            Chain::open(&path, Some(&executor)),
+            Err(FileLedgerError::Corrupt { block_num: 1, .. })
+        ));
+    }
+}
@@ -304,6 +304,103 @@ impl SimulatedSawtoothBehavior for WellBehavedBehavior {
     }
 }
 
+/// Answer the validator requests of a ledger client with `behavior` on a thread of its own,
+/// returning the address to connect to. Messages sent on the channel are forwarded to the
+/// client unsolicited, as events are by a validator.
+pub(crate) fn serve_simulated_validator(
+    behavior: impl SimulatedSawtoothBehavior + Send + 'static,
+    rx: tokio::sync::mpsc::UnboundedReceiver<Option<(Message_MessageType, Vec<u8>)>>,
+) -> SocketAddr {
+    let listen_port = portpicker::pick_unused_port().expect("No ports free");
+    let listen_addr = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), listen_port);
+    let connect_addr = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), listen_port);
+
+    thread::spawn(move || {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_io()
+            .enable_time()
+            .build()
+            .unwrap();
+        let mut rx = UnboundedReceiverStream::new(rx);
+        let local = tokio::task::LocalSet::new();
+
+        let task = local.run_until(async move {
+            tokio::task::spawn_local(async move {
+                let (mut router_tx, mut router_rx) = router(&Context::new())
+                    .bind(&format!("tcp://{}", listen_addr))
+                    .unwrap()
+                    .split();
+
+                debug!(listen_addr = ?listen_addr, "Embedded TP listening");
+                let mut last_address = vec![];
+                loop {
+                    select! {
+                        message = router_rx.next().fuse() => {
+                          if message.is_none() {
+                            break;
+                          }
+
+                          let multipart = message.unwrap().unwrap();
+
+                          last_address =  multipart[0].to_vec();
+                          let request: async_stl_client::messages::Message =
+                              async_stl_client::prost::Message::decode(&*multipart[1].to_vec()).map_err(|e| error!(%e)).unwrap();
+
+                          debug!(request = ?request);
+
+                          let response = behavior
+                              .handle_request(
+                                  Message_MessageType::from_i32(request.message_type).unwrap(),
+                                  request.content,
+                              )
+                              .unwrap();
+
+                          let message_wrapper = async_stl_client::messages::Message {
+                            message_type: response.0 as i32,
+                            tx_id: request.tx_id,
+                            content: response.1
+                          };
+
+                          let mut multipart = Multipart::default();
+                          multipart.push_back(last_address.clone().into());
+                          multipart.push_back(tmq::Message::from(prost::Message::encode_to_vec(&message_wrapper)));
+
+                          debug!(response = ?multipart);
+                          router_tx.send(multipart).await.ok();
+                        },
+                        unsolicited_message = rx.next().fuse() => {
+                          if unsolicited_message.is_none() {
+                            break;
+                          }
+                          tracing::trace!(unsolicited_message=?unsolicited_message);
+
+                          let unsolicited_message = unsolicited_message.unwrap().unwrap();
+                          debug!(unsolicited_message = ?unsolicited_message);
+                          let message_wrapper = async_stl_client::messages::Message {
+                            message_type: unsolicited_message.0 as i32,
+                            tx_id: "".to_string(),
+                            content: unsolicited_message.1
+                          };
+                          let mut multipart = Multipart::default();
+                          multipart.push_back(last_address.clone().into());
+
+                          multipart.push_back(tmq::Message::from(prost::Message::encode_to_vec(&message_wrapper)));
+                          router_tx.send(multipart).await.ok();
+                        },
+                        complete => {
+                          info!("close embedded router");
+                        }
+                    }
+                }
+            })
+            .await
+        });
+        rt.block_on(task).ok();
+    });
+
+    connect_addr
+}
+
 pub struct EmbeddedChronicleTp {
     pub ledger: InMemLedger,
     context: Arc<Mutex<SimulatedTransactionContext>>,
@@ -328,93 +425,7 @@ impl EmbeddedChronicleTp {
             context: context.clone(),
         };
 
-        let listen_port = portpicker::pick_unused_port().expect("No ports free");
-        let listen_addr = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), listen_port);
-        let connect_addr = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), listen_port);
-
-        let behavior_clone = behavior;
-        thread::spawn(move || {
-            let rt = runtime::Builder::new_current_thread()
-                .enable_io()
-                .enable_time()
-                .build()
-                .unwrap();
-            let mut rx = UnboundedReceiverStream::new(rx);
-            let local = tokio::task::LocalSet::new();
-
-            let task = local.run_until(async move {
-                tokio::task::spawn_local(async move {
-                    let (mut router_tx, mut router_rx) = router(&Context::new())
-                        .bind(&format!("tcp://{}", listen_addr))
-                        .unwrap()
-                        .split();
-
-                    debug!(listen_addr = ?listen_addr, "Embedded TP listening");
-                    let mut last_address = vec![];
-                    loop {
-                        select! {
-                            message = router_rx.next().fuse() => {
-                              if message.is_none() {
-                                break;
-                              }
-
-                              let multipart = message.unwrap().unwrap();
-
-                              last_address =  multipart[0].to_vec();
-                              let request: async_stl_client::messages::Message =
-                                  async_stl_client::prost::Message::decode(&*multipart[1].to_vec()).map_err(|e| error!(%e)).unwrap();
-
-                              debug!(request = ?request);
-
-                              let response = behavior_clone
-                                  .handle_request(
-                                      Message_MessageType::from_i32(request.message_type).unwrap(),
-                                      request.content,
-                                  )
-                                  .unwrap();
-
-                              let message_wrapper = async_stl_client::messages::Message {
-                                message_type: response.0 as i32,
-                                tx_id: request.tx_id,
-                                content: response.1
-                              };
-
-                              let mut multipart = Multipart::default();
-                              multipart.push_back(last_address.clone().into());
-                              multipart.push_back(tmq::Message::from(prost::Message::encode_to_vec(&message_wrapper)));
-
-                              debug!(response = ?multipart);
-                              router_tx.send(multipart).await.ok();
-                            },
-                            unsolicited_message = rx.next().fuse() => {
-                              if unsolicited_message.is_none() {
-                                break;
-                              }
-                              tracing::trace!(unsolicited_message=?unsolicited_message);
-
-                              let unsolicited_message = unsolicited_message.unwrap().unwrap();
-                              debug!(unsolicited_message = ?unsolicited_message);
-                              let message_wrapper = async_stl_client::messages::Message {
-                                message_type: unsolicited_message.0 as i32,
-                                tx_id: "".to_string(),
-                                content: unsolicited_message.1
-                              };
-                              let mut multipart = Multipart::default();
-                              multipart.push_back(last_address.clone().into());
-
-                              multipart.push_back(tmq::Message::from(prost::Message::encode_to_vec(&message_wrapper)));
-                              router_tx.send(multipart).await.ok();
-                            },
-                            complete => {
-                              info!("close embedded router");
-                            }
-                        }
-                    }
-                })
-                .await
-            });
-            rt.block_on(task).ok();
-        });
+        let connect_addr = serve_simulated_validator(behavior, rx);
 
         Ok(Self {
             ledger: InMemLedger::new(
@@ -1,28 +1,35 @@
 #![cfg_attr(feature = "strict", deny(warnings))]
+#![allow(clippy::result_large_err)]
 pub mod chronicle_graphql;
+pub mod file_ledger;
 pub mod inmem;
 mod persistence;
+pub mod resync;
+pub mod verify;
 
 use async_stl_client::{
     error::SawtoothCommunicationError,
     ledger::{BlockId, BlockingLedgerWriter, FromBlock},
 };
+use chronicle_proof::InclusionProof;
 use chronicle_protocol::{
     async_stl_client::ledger::{LedgerReader, LedgerWriter},
     messages::ChronicleSubmitTransaction,
     protocol::ChronicleOperationEvent,
+    state::ChronicleKeys,
 };
 use chronicle_signing::{ChronicleSigning, SecretError};
 use chrono::{DateTime, Utc};
 
-use diesel::{r2d2::ConnectionManager, PgConnection};
-use diesel_migrations::MigrationHarness;
+use diesel::Connection;
 use futures::{select, FutureExt, StreamExt};
 
 use common::{
     attributes::Attributes,
     commands::*,
-    identity::{AuthId, IdentityError},
+    database::{ConnectionPool, StoreConnection},
+    identity::{AgentSignedOperations, AuthId, IdentityError, SignedIdentity},
+    k256::sha2::{Digest, Sha256},
     ledger::{Commit, SubmissionError, SubmissionStage, SubscriptionError},
     prov::{
         operations::{
@@ -40,9 +47,8 @@ use common::{
 
 use metrics::histogram;
 use metrics_exporter_prometheus::PrometheusBuilder;
+use persistence::Store;
 pub use persistence::StoreError;
-use persistence::{Store, MIGRATIONS};
-use r2d2::Pool;
 use std::{
     convert::Infallible,
     marker::PhantomData,
@@ -58,7 +64,7 @@ use tokio::{
 
 use tracing::{debug, error, info, info_span, instrument, trace, warn, Instrument};
 
-pub use persistence::ConnectionOptions;
+pub use persistence::{ConnectionOptions, StoreDecisionLog};
 use user_error::UFE;
 use uuid::Uuid;
 
@@ -92,7 +98,7 @@ pub enum ApiError {
     ApiShutdownTx(#[from] SendError<ApiSendWithReply>),
 
     #[error("Ledger shut down before send: {0}")]
-    LedgerShutdownTx(#[from] SendError<LedgerSendWithReply>),
+    LedgerShutdownTx(#[from] Box<SendError<LedgerSendWithReply>>),
 
     #[error("Invalid socket address: {0}")]
     AddressParse(#[from] AddrParseError),
@@ -126,6 +132,30 @@ pub enum ApiError {
 
     #[error("Authentication endpoint error: {0}")]
     AuthenticationEndpoint(#[from] chronicle_graphql::AuthorizationError),
+
+    #[error("File ledger: {0}")]
+    FileLedger(#[from] file_ledger::FileLedgerError),
+
+    #[error("Agent signed no operations")]
+    NoAgentSignedOperations,
+
+    #[error("Agent signed operations must all be in one namespace")]
+    AgentSignedNamespaces,
+
+    #[error("Outbox item {0} has not failed, so cannot be retried")]
+    OutboxItemNotFailed(i32),
+
+    #[error("Idempotency key {0} was already used with a different request")]
+    IdempotencyConflict(String),
+
+    #[error("Idempotency key {0} is in use by a request that has not completed")]
+    IdempotencyPending(String),
+
+    #[error("Request serialization: {0}")]
+    RequestSerialization(#[from] serde_json::Error),
+
+    #[error("A shadow schema is rebuilt from empty, so must be replayed from the first block")]
+    ShadowResyncFromBlock,
 }
 
 /// Ugly but we need this until ! is stable, see <https://github.com/rust-lang/rust/issues/64715>
@@ -137,12 +167,107 @@ impl From<Infallible> for ApiError {
 
 impl UFE for ApiError {}
 
+impl ApiError {
+    /// Whether the ledger could not be reached, so a submission may succeed if it is tried
+    /// again. Any other error is a refusal of the transaction, which is permanent.
+    pub fn is_ledger_unreachable(&self) -> bool {
+        let source = match self {
+            ApiError::SawtoothCommunicationError(source) => source,
+            ApiError::Ledger(SubmissionError::Communication { source, .. }) => source.as_ref(),
+            _ => return false,
+        };
+
+        matches!(source, SawtoothCommunicationError::NoConnectedValidators)
+    }
+}
+
 type LedgerSendWithReply = (
     ChronicleSubmitTransaction,
     Sender<Result<ChronicleTransactionId, SubmissionError>>,
 );
 
-type ApiSendWithReply = ((ApiCommand, AuthId), Sender<Result<ApiResponse, ApiError>>);
+type ApiSendWithReply = (
+    (ApiCommand, AuthId, Option<String>),
+    Sender<Result<ApiResponse, ApiError>>,
+);
+
+/// How often to attempt to submit transactions queued in the outbox
+const OUTBOX_DRAIN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The longest wait between attempts to submit a queued transaction
+const OUTBOX_MAX_BACKOFF_SECS: i64 = 300;
+
+/// How long a committed transaction stays in the outbox, to be listed as committed, before it
+/// is pruned
+const OUTBOX_COMMITTED_RETENTION_SECS: i64 = 24 * 60 * 60;
+
+/// Wait twice as long after each failed attempt to submit a queued transaction
+fn outbox_backoff(attempts: i32) -> chrono::Duration {
+    chrono::Duration::seconds(
+        2i64.saturating_pow(attempts.clamp(0, 16) as u32)
+            .min(OUTBOX_MAX_BACKOFF_SECS),
+    )
+}
+
+/// Submit queued transactions with `submit` in the order they were queued, stopping at the first
+/// that cannot be submitted until its backoff has elapsed. A transaction that has exhausted its
+/// attempts, or that was refused rather than failing to reach the ledger, is marked as failed and
+/// the outbox stops behind it, as those queued later may depend on it, until it is retried.
+fn drain_queued(
+    store: &Store,
+    max_attempts: i32,
+    mut submit: impl FnMut(&ChronicleTransaction) -> Result<ChronicleTransactionId, ApiError>,
+) -> Result<(), ApiError> {
+    while let Some(queued) = store.next_queued_transaction()? {
+        if queued.status == OutboxStatus::Failed {
+            debug!(
+                outbox_id = queued.id,
+                "Outbox stopped behind failed transaction"
+            );
+            break;
+        }
+        if queued.next_attempt_time > Utc::now().naive_utc() {
+            break;
+        }
+
+        match submit(&queued.tx) {
+            Ok(tx_id) => {
+                info!(outbox_id = queued.id, %tx_id, "Queued transaction submitted");
+                store.record_queued_transaction_submitted(queued.id, &tx_id)?;
+            }
+            Err(error) => {
+                let attempts = queued.attempts + 1;
+                let next_attempt_time = (error.is_ledger_unreachable() && attempts < max_attempts)
+                    .then(|| Utc::now().naive_utc() + outbox_backoff(attempts));
+
+                store.record_queued_transaction_attempt(
+                    queued.id,
+                    &error.to_string(),
+                    next_attempt_time,
+                )?;
+
+                if next_attempt_time.is_some() {
+                    warn!(
+                        outbox_id = queued.id,
+                        attempts,
+                        ?error,
+                        "Queued transaction not submitted"
+                    );
+                } else {
+                    error!(
+                        outbox_id = queued.id,
+                        attempts,
+                        ?error,
+                        "Queued transaction failed, outbox stopped until it is retried"
+                    );
+                }
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
 
 pub trait UuidGen {
     fn uuid() -> Uuid {
@@ -166,6 +291,13 @@ pub struct Api<
     store: persistence::Store,
     uuid_source: PhantomData<U>,
     policy_name: Option<String>,
+    outbox_max_attempts: Option<u32>,
+}
+
+/// A ledger that can prove which state entries its blocks wrote, which only a ledger file can
+pub trait BlockWriteProofs: std::fmt::Debug + Send + Sync {
+    /// Proofs of each state entry the block committed, or `None` for a block it cannot prove
+    fn prove_block(&self, block_id: &str) -> Result<Option<Vec<InclusionProof>>, ApiError>;
 }
 
 #[derive(Debug, Clone)]
@@ -173,20 +305,44 @@ pub struct Api<
 pub struct ApiDispatch {
     tx: Sender<ApiSendWithReply>,
     pub notify_commit: tokio::sync::broadcast::Sender<SubmissionStage>,
+    /// Proofs of the state blocks wrote, if the ledger is a ledger file
+    pub proofs: Option<Arc<dyn BlockWriteProofs>>,
+    signing: ChronicleSigning,
 }
 
 impl ApiDispatch {
+    pub fn with_proofs(self, proofs: impl BlockWriteProofs + 'static) -> Self {
+        Self {
+            proofs: Some(Arc::new(proofs)),
+            ..self
+        }
+    }
+
     #[instrument]
     pub async fn dispatch(
         &self,
         command: ApiCommand,
         identity: AuthId,
+    ) -> Result<ApiResponse, ApiError> {
+        self.dispatch_with_idempotency_key(command, identity, None)
+            .await
+    }
+
+    /// Dispatch a command that, if previously dispatched by the same identity with the same
+    /// idempotency key, is not submitted again. Instead, the response describes the
+    /// original submission.
+    #[instrument]
+    pub async fn dispatch_with_idempotency_key(
+        &self,
+        command: ApiCommand,
+        identity: AuthId,
+        idempotency_key: Option<String>,
     ) -> Result<ApiResponse, ApiError> {
         let (reply_tx, mut reply_rx) = mpsc::channel(1);
         trace!(?command, "Dispatch command to api");
         self.tx
             .clone()
-            .send(((command, identity), reply_tx))
+            .send(((command, identity, idempotency_key), reply_tx))
             .await?;
 
         let reply = reply_rx.recv().await;
@@ -283,15 +439,18 @@ where
         + 'static
         + LedgerReader<Event = ChronicleOperationEvent, Error = SawtoothCommunicationError>,
 {
+    #[allow(clippy::too_many_arguments)]
     #[instrument(skip(ledger))]
     pub async fn new(
-        pool: Pool<ConnectionManager<PgConnection>>,
+        pool: ConnectionPool,
         ledger: LEDGER,
         uuidgen: U,
         signing: ChronicleSigning,
         namespace_bindings: Vec<NamespaceId>,
         policy_name: Option<String>,
         liveness_check_interval: Option<u64>,
+        outbox_max_attempts: Option<u32>,
+        verify_store_interval: Option<u64>,
     ) -> Result<ApiDispatch, ApiError> {
         let (commit_tx, mut commit_rx) = mpsc::channel::<ApiSendWithReply>(10);
 
@@ -299,14 +458,14 @@ where
         let dispatch = ApiDispatch {
             tx: commit_tx.clone(),
             notify_commit: commit_notify_tx.clone(),
+            proofs: None,
+            signing: signing.clone(),
         };
 
         let store = Store::new(pool.clone())?;
 
-        pool.get()?
-            .build_transaction()
-            .run(|connection| connection.run_pending_migrations(MIGRATIONS).map(|_| ()))
-            .map_err(StoreError::DbMigration)?;
+        let mut connection = pool.get()?;
+        persistence::run_migrations(&mut connection)?;
 
         let system_namespace_uuid = (SYSTEM_ID, Uuid::try_from(SYSTEM_UUID).unwrap());
 
@@ -318,6 +477,11 @@ where
 
         let reuse_reader = ledger.clone();
 
+        if let Some(interval) = verify_store_interval {
+            debug!("Starting store verification task");
+            verify::verify_periodically(pool, ledger.clone(), Duration::from_secs(interval));
+        }
+
         let last_seen_block = store.get_last_block_id();
 
         let start_from_block = if let Ok(Some(start_from_block)) = last_seen_block {
@@ -337,8 +501,28 @@ where
                 store: store.clone(),
                 uuid_source: PhantomData,
                 policy_name,
+                outbox_max_attempts,
             };
 
+            // Submitting queued transactions can wait on the ledger, so is kept out of the way of
+            // commands and ledger updates
+            if api.outbox_max_attempts.is_some() {
+                let drain_api = api.clone();
+                tokio::task::spawn(async move {
+                    let mut outbox_drain = tokio::time::interval(OUTBOX_DRAIN_INTERVAL);
+                    loop {
+                        outbox_drain.tick().await;
+                        drain_api
+                            .drain_outbox()
+                            .await
+                            .map_err(|e| {
+                                error!(?e, "Api drain outbox");
+                            })
+                            .ok();
+                    }
+                });
+            }
+
             loop {
                 let state_updates = reuse_reader.clone();
 
@@ -365,7 +549,13 @@ where
                                   }
                                   // Ledger contradicted or error, so nothing to
                                   // apply, but forward notification
-                                  Some((ChronicleOperationEvent(Err(e), id),tx,_block_id,_position, _span)) => {
+                                  Some((ChronicleOperationEvent(Err(e), id, _),tx,block_id,_position, _span)) => {
+                                    api.contradicted(ChronicleTransactionId::from(tx.as_str()), &block_id, &id, &e)
+                                        .await
+                                        .map_err(|e| {
+                                            error!(?e, "Api record contradiction");
+                                        })
+                                        .ok();
                                     commit_notify_tx.send(SubmissionStage::not_committed(
                                       ChronicleTransactionId::from(tx.as_str()),e.clone(), id
                                     )).ok();
@@ -373,12 +563,12 @@ where
                                   // Successfully committed to ledger, so apply
                                   // to db and broadcast notification to
                                   // subscription subscribers
-                                  Some((ChronicleOperationEvent(Ok(ref commit), id,),tx,block_id,_position,_span )) => {
+                                  Some((ChronicleOperationEvent(Ok(ref commit), id, keys),tx,block_id,_position,_span )) => {
 
                                         debug!(committed = ?tx);
                                         debug!(delta = %serde_json::to_string_pretty(&commit.to_json().compact().await.unwrap()).unwrap());
 
-                                        api.sync( commit.clone().into(), &block_id,ChronicleTransactionId::from(tx.as_str()))
+                                        api.sync( commit.clone().into(), &block_id,ChronicleTransactionId::from(tx.as_str()), &id, keys)
                                             .instrument(info_span!("Incoming confirmation", offset = ?block_id, tx_id = %tx))
                                             .await
                                             .map_err(|e| {
@@ -391,10 +581,10 @@ where
                                 }
                             },
                             cmd = commit_rx.recv().fuse() => {
-                                if let Some((command, reply)) = cmd {
+                                if let Some(((command, identity, idempotency_key), reply)) = cmd {
 
                                 let result = api
-                                    .dispatch(command)
+                                    .dispatch_idempotently((command, identity), idempotency_key)
                                     .await;
 
                                 reply
@@ -405,7 +595,7 @@ where
                                     })
                                     .ok();
                                 }
-                        }
+                        },
                         complete => break
                     }
                 }
@@ -506,16 +696,32 @@ where
     fn submit_blocking(
         &mut self,
         tx: &ChronicleTransaction,
+    ) -> Result<ChronicleTransactionId, ApiError> {
+        self.submit_blocking_as(tx, None)
+    }
+
+    /// As [`submit_blocking`](Self::submit_blocking), sending the operations in the form an agent
+    /// signed them if they are `agent_signed`
+    fn submit_blocking_as(
+        &mut self,
+        tx: &ChronicleTransaction,
+        agent_signed: Option<AgentSignedOperations>,
     ) -> Result<ChronicleTransactionId, ApiError> {
         let res = self.ledger_writer.submit(&ChronicleSubmitTransaction {
             tx: tx.clone(),
             signer: self.signing.clone(),
             policy_name: self.policy_name.clone(),
+            key_rotation: None,
+            agent_signed,
         });
 
         match res {
             Ok(tx_id) => {
                 let tx_id = ChronicleTransactionId::from(tx_id.as_str());
+                self.store
+                    .record_transaction_submitted(&tx_id, &tx.identity)
+                    .map_err(|e| error!(?e, "Record submitted transaction"))
+                    .ok();
                 self.submit_tx.send(SubmissionStage::submitted(&tx_id)).ok();
                 Ok(tx_id)
             }
@@ -525,6 +731,14 @@ where
                     &ChronicleTransactionId::from(tx_id.as_str()),
                     e,
                 );
+                self.store
+                    .record_transaction_failed(
+                        submission_error.tx_id(),
+                        &tx.identity,
+                        &submission_error.to_string(),
+                    )
+                    .map_err(|e| error!(?e, "Record failed transaction"))
+                    .ok();
                 self.submit_tx
                     .send(SubmissionStage::submitted_error(&submission_error))
                     .ok();
@@ -535,17 +749,70 @@ where
     }
 
     /// Generate and submit the signed identity to send to the Transaction Processor along with the transactions to be applied
+    ///
+    /// If the outbox is enabled, a transaction that cannot be submitted because the ledger is
+    /// unreachable is queued and its effect applied to the store in advance of the ledger
     fn submit(
         &mut self,
+        connection: &mut StoreConnection,
         id: impl Into<ChronicleIri>,
         identity: AuthId,
         to_apply: Vec<ChronicleOperation>,
     ) -> Result<ApiResponse, ApiError> {
         let identity = identity.signed_identity(&self.signing)?;
         let model = ProvModel::from_tx(&to_apply)?;
-        let tx_id = self.submit_blocking(&ChronicleTransaction::new(to_apply, identity))?;
+        let tx = ChronicleTransaction::new(to_apply, identity);
+
+        if self.outbox_max_attempts.is_none() {
+            let tx_id = self.submit_blocking(&tx)?;
+            return Ok(ApiResponse::submission(id, model, tx_id));
+        }
+
+        // Transactions reach the ledger in the order they were made, so queue behind any
+        // transaction that is yet to be submitted
+        if !self.store.outbox_has_unsubmitted(connection)? {
+            match self.submit_blocking(&tx) {
+                Ok(tx_id) => return Ok(ApiResponse::submission(id, model, tx_id)),
+                Err(error) if error.is_ledger_unreachable() => {
+                    warn!(?error, "Ledger unreachable, queueing transaction")
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        let subject = id.into();
+        let outbox_id = self.store.queue_transaction(connection, &subject, &tx)?;
+        self.store.apply_model(connection, &model)?;
 
-        Ok(ApiResponse::submission(id, model, tx_id))
+        Ok(ApiResponse::queued(subject, model, outbox_id))
+    }
+
+    /// Submit queued transactions in the order they were queued, see [`drain_queued`], then
+    /// prune those committed long enough ago
+    #[instrument(skip(self))]
+    async fn drain_outbox(&self) -> Result<(), ApiError> {
+        let max_attempts = match self.outbox_max_attempts {
+            Some(max_attempts) => max_attempts as i32,
+            None => return Ok(()),
+        };
+
+        let mut api = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let store = api.store.clone();
+            drain_queued(&store, max_attempts, |tx| api.submit_blocking(tx))?;
+
+            let mut connection = store.connection()?;
+            let pruned = store.prune_committed_outbox(
+                &mut connection,
+                Utc::now().naive_utc() - chrono::Duration::seconds(OUTBOX_COMMITTED_RETENTION_SECS),
+            )?;
+            if pruned > 0 {
+                debug!(pruned, "Pruned committed transactions from the outbox");
+            }
+
+            Ok(())
+        })
+        .await?
     }
 
     /// Checks if ChronicleOperations resulting from Chronicle API calls will result in any changes in state
@@ -556,7 +823,7 @@ where
     #[instrument(skip(self, connection))]
     fn check_for_effects(
         &mut self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         to_apply: &Vec<ChronicleOperation>,
     ) -> Result<Option<Vec<ChronicleOperation>>, ApiError> {
         let mut model = ProvModel::default();
@@ -839,16 +1106,16 @@ where
 
     fn apply_effects_and_submit(
         &mut self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         id: impl Into<ChronicleIri>,
         identity: AuthId,
         to_apply: Vec<ChronicleOperation>,
         applying_new_namespace: bool,
     ) -> Result<ApiResponse, ApiError> {
         if applying_new_namespace {
-            self.submit(id, identity, to_apply)
+            self.submit(connection, id, identity, to_apply)
         } else if let Some(to_apply) = self.check_for_effects(connection, &to_apply)? {
-            self.submit(id, identity, to_apply)
+            self.submit(connection, id, identity, to_apply)
         } else {
             info!("API call will not result in any data changes");
             let model = ProvModel::from_tx(&to_apply)?;
@@ -866,7 +1133,7 @@ where
     #[instrument(skip(self, connection))]
     fn ensure_namespace(
         &mut self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         external_id: &ExternalId,
     ) -> Result<(NamespaceId, Vec<ChronicleOperation>), ApiError> {
         let ns = self.store.namespace_by_external_id(connection, external_id);
@@ -904,7 +1171,7 @@ where
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
 
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
                 let applying_new_namespace = !to_apply.is_empty();
@@ -943,7 +1210,7 @@ where
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
 
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
                 let applying_new_namespace = !to_apply.is_empty();
@@ -987,7 +1254,7 @@ where
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
 
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
                 let applying_new_namespace = !to_apply.is_empty();
@@ -1031,7 +1298,7 @@ where
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
 
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
                 let applying_new_namespace = !to_apply.is_empty();
@@ -1080,7 +1347,7 @@ where
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
 
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
                 let applying_new_namespace = !to_apply.is_empty();
@@ -1128,7 +1395,7 @@ where
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
 
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
                 let applying_new_namespace = !to_apply.is_empty();
@@ -1171,10 +1438,10 @@ where
         let external_id = external_id.to_owned();
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 let (namespace, to_apply) = api.ensure_namespace(connection, &external_id)?;
 
-                api.submit(namespace, identity, to_apply)
+                api.submit(connection, namespace, identity, to_apply)
             })
         })
         .await?
@@ -1216,6 +1483,86 @@ where
         Ok(ApiResponse::depth_charge_submission(tx_id))
     }
 
+    /// Dispatch a command unless it has been dispatched before with `idempotency_key`, recording
+    /// the subject and any transaction id of a submission made with a key. The key is bound to a
+    /// hash of the command, so reusing it for a different command is a conflict. It is reserved
+    /// before the command is dispatched, so a retry after Chronicle stops part way through is
+    /// refused rather than submitted twice.
+    #[instrument(skip(self))]
+    async fn dispatch_idempotently(
+        &mut self,
+        command: (ApiCommand, AuthId),
+        idempotency_key: Option<String>,
+    ) -> Result<ApiResponse, ApiError> {
+        let idempotency_key = match idempotency_key {
+            Some(idempotency_key) => idempotency_key,
+            None => return self.dispatch(command).await,
+        };
+
+        let identity = command.1.to_string();
+        let request_hash = hex::encode(Sha256::digest(serde_json::to_vec(&command.0)?));
+
+        let prior = {
+            let api = self.clone();
+            let idempotency_key = idempotency_key.clone();
+            let identity = identity.clone();
+            let request_hash = request_hash.clone();
+            tokio::task::spawn_blocking(move || {
+                let mut connection = api.store.connection()?;
+                api.store.reserve_idempotency_key(
+                    &mut connection,
+                    &idempotency_key,
+                    &identity,
+                    &request_hash,
+                )
+            })
+            .await??
+        };
+
+        if let Some((prior_hash, subject, tx_id, stage)) = prior {
+            if prior_hash != request_hash {
+                return Err(ApiError::IdempotencyConflict(idempotency_key));
+            }
+            let subject = subject.ok_or(ApiError::IdempotencyPending(idempotency_key.clone()))?;
+            debug!(%idempotency_key, %subject, "Command previously dispatched");
+            return Ok(ApiResponse::prior_submission(subject, tx_id, stage));
+        }
+
+        let response = self.dispatch(command).await;
+
+        let submitted = match &response {
+            Ok(ApiResponse::Submission { subject, tx_id, .. }) => {
+                Some((subject.clone(), Some(tx_id.clone())))
+            }
+            Ok(ApiResponse::AlreadyRecorded { subject, .. })
+            | Ok(ApiResponse::Queued { subject, .. }) => Some((subject.clone(), None)),
+            _ => None,
+        };
+
+        let api = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = api.store.connection()?;
+            match submitted {
+                Some((subject, tx_id)) => api.store.record_idempotent_submission(
+                    &mut connection,
+                    &idempotency_key,
+                    &identity,
+                    &subject,
+                    tx_id.as_ref(),
+                ),
+                None => {
+                    api.store
+                        .release_idempotency_key(&mut connection, &idempotency_key, &identity)
+                }
+            }
+        })
+        .await?
+        .map_err(|e| error!(?e, "Record idempotent submission"))
+        .ok();
+
+        response
+    }
+
     #[instrument(skip(self))]
     async fn dispatch(&mut self, command: (ApiCommand, AuthId)) -> Result<ApiResponse, ApiError> {
         match command {
@@ -1232,6 +1579,9 @@ where
                 self.submit_import_operations(identity, namespace, operations)
                     .await
             }
+            (ApiCommand::AgentSigned(AgentSignedCommand { signed }), _identity) => {
+                self.submit_agent_signed_operations(signed).await
+            }
             (ApiCommand::NameSpace(NamespaceCommand::Create { external_id }), identity) => {
                 self.create_namespace(&external_id, identity).await
             }
@@ -1385,6 +1735,12 @@ where
                     .await
             }
             (ApiCommand::Query(query), _identity) => self.query(query).await,
+            (ApiCommand::Outbox(OutboxCommand::List { status }), _identity) => {
+                self.outbox(status).await
+            }
+            (ApiCommand::Outbox(OutboxCommand::Retry { id }), _identity) => {
+                self.retry_outbox_item(id).await
+            }
         }
     }
 
@@ -1403,7 +1759,7 @@ where
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
 
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
                 let applying_new_namespace = !to_apply.is_empty();
@@ -1444,7 +1800,7 @@ where
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
 
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
                 let applying_new_namespace = !to_apply.is_empty();
@@ -1484,7 +1840,7 @@ where
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
 
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
                 let applying_new_namespace = !to_apply.is_empty();
@@ -1525,7 +1881,7 @@ where
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
 
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
                 let applying_new_namespace = !to_apply.is_empty();
@@ -1567,6 +1923,34 @@ where
         .await?
     }
 
+    async fn outbox(&self, status: Option<OutboxStatus>) -> Result<ApiResponse, ApiError> {
+        let api = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = api.store.connection()?;
+
+            Ok(ApiResponse::outbox_items(api.store.outbox_items(
+                &mut connection,
+                status,
+                None,
+            )?))
+        })
+        .await?
+    }
+
+    async fn retry_outbox_item(&self, id: i32) -> Result<ApiResponse, ApiError> {
+        let api = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = api.store.connection()?;
+
+            if !api.store.retry_queued_transaction(&mut connection, id)? {
+                return Err(ApiError::OutboxItemNotFailed(id));
+            }
+
+            Ok(ApiResponse::unit())
+        })
+        .await?
+    }
+
     async fn submit_import_operations(
         &self,
         identity: AuthId,
@@ -1579,7 +1963,7 @@ where
         tokio::task::spawn_blocking(move || {
             // Check here to ensure that import operations result in data changes
             let mut connection = api.store.connection()?;
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 if let Some(operations_to_apply) = api.check_for_effects(connection, &operations)? {
                     info!("Submitting import operations to ledger");
                     let tx_id = api.submit_blocking(&ChronicleTransaction::new(
@@ -1597,17 +1981,65 @@ where
         .await?
     }
 
+    /// Submit operations signed by an agent with its registered key, as the agent rather than
+    /// the caller. They are submitted as they were signed, so are not queued in the outbox or
+    /// trimmed of operations with no effect.
+    async fn submit_agent_signed_operations(
+        &self,
+        signed: AgentSignedOperations,
+    ) -> Result<ApiResponse, ApiError> {
+        let mut api = self.clone();
+        let operations = signed.operations().await?;
+        let namespace = operations
+            .first()
+            .map(|op| op.namespace().clone())
+            .ok_or(ApiError::NoAgentSignedOperations)?;
+        if operations.iter().any(|op| op.namespace() != &namespace) {
+            return Err(ApiError::AgentSignedNamespaces);
+        }
+        let model = ProvModel::from_tx(&operations)?;
+
+        tokio::task::spawn_blocking(move || {
+            let mut connection = api.store.connection()?;
+            let public_key = api
+                .store
+                .agent_public_key(&mut connection, &namespace, &signed.agent)?
+                .ok_or_else(|| IdentityError::NoAgentKey(signed.agent.clone()))?;
+
+            // The transaction processor checks this too, but a bad signature is better reported
+            // before submission
+            let identity = signed.verify(&public_key)?.signed_identity(&api.signing)?;
+
+            info!(agent = %signed.agent, "Submitting agent signed operations to ledger");
+            let agent = signed.agent.clone();
+            let tx_id = api.submit_blocking_as(
+                &ChronicleTransaction::new(operations, identity),
+                Some(signed),
+            )?;
+            Ok(ApiResponse::submission(agent, model, tx_id))
+        })
+        .await?
+    }
+
     #[instrument(level = "debug", skip(self), ret(Debug))]
     async fn sync(
         &self,
         prov: Box<ProvModel>,
         block_id: &BlockId,
         tx_id: ChronicleTransactionId,
+        identity: &SignedIdentity,
+        keys: Option<ChronicleKeys>,
     ) -> Result<ApiResponse, ApiError> {
         let api = self.clone();
         let block_id = *block_id;
+        let identity = identity.clone();
         tokio::task::spawn_blocking(move || {
             api.store.apply_prov(&prov)?;
+            api.store
+                .record_transaction_committed(&tx_id, &block_id, &identity, &prov)?;
+            if let Some(keys) = keys {
+                api.store.record_chronicle_keys(&keys, &block_id)?;
+            }
             api.store.set_last_block_id(&block_id, tx_id)?;
 
             Ok(ApiResponse::Unit)
@@ -1615,6 +2047,32 @@ where
         .await?
     }
 
+    /// Record that the ledger rejected a transaction as contradicting its state
+    #[instrument(skip(self, identity))]
+    async fn contradicted(
+        &self,
+        tx_id: ChronicleTransactionId,
+        block_id: &BlockId,
+        identity: &SignedIdentity,
+        contradiction: &Contradiction,
+    ) -> Result<(), ApiError> {
+        let api = self.clone();
+        let block_id = *block_id;
+        let identity = identity.clone();
+        let contradiction = contradiction.clone();
+        tokio::task::spawn_blocking(move || {
+            api.store.record_transaction_contradicted(
+                &tx_id,
+                &block_id,
+                &identity,
+                &contradiction,
+            )?;
+
+            Ok(())
+        })
+        .await?
+    }
+
     /// Creates and submits a (ChronicleTransaction::StartActivity) determining the appropriate agent by external_id, or via [use_agent] context
     #[instrument(skip(self))]
     async fn instant(
@@ -1628,7 +2086,7 @@ where
         let mut api = self.clone();
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
                 let applying_new_namespace = !to_apply.is_empty();
@@ -1687,7 +2145,7 @@ where
         let mut api = self.clone();
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
                 let applying_new_namespace = !to_apply.is_empty();
@@ -1740,7 +2198,7 @@ where
         let mut api = self.clone();
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
                 let applying_new_namespace = !to_apply.is_empty();
@@ -1790,7 +2248,7 @@ where
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
 
-            connection.build_transaction().run(|connection| {
+            connection.transaction(|connection| {
                 api.store
                     .use_agent(connection, id.external_id_part(), &namespace)
             })?;
@@ -1804,8 +2262,13 @@ where
 #[cfg(test)]
 mod test {
 
-    use crate::{inmem::EmbeddedChronicleTp, Api, ApiDispatch, ApiError, UuidGen};
+    use crate::{
+        chronicle_graphql::subscription::withhold_unreadable, drain_queued,
+        inmem::EmbeddedChronicleTp, outbox_backoff, persistence, Api, ApiDispatch, ApiError,
+        ConnectionOptions, Store, UuidGen, OUTBOX_MAX_BACKOFF_SECS,
+    };
 
+    use async_stl_client::{error::SawtoothCommunicationError, ledger::BlockId};
     use chronicle_signing::{
         chronicle_secret_names, ChronicleSecretsOptions, ChronicleSigning, BATCHER_NAMESPACE,
         CHRONICLE_NAMESPACE,
@@ -1815,27 +2278,33 @@ mod test {
         attributes::{Attribute, Attributes},
         commands::{
             ActivityCommand, AgentCommand, ApiCommand, ApiResponse, EntityCommand, ImportCommand,
-            NamespaceCommand,
+            NamespaceCommand, OutboxStatus, TransactionStage,
         },
-        database::TemporaryDatabase,
-        identity::AuthId,
+        database::{ConnectionPool, StoreConnection, TemporaryDatabase},
+        identity::{AuthId, SignedIdentity},
         k256::sha2::{Digest, Sha256},
+        ledger::{SubmissionError, SubmissionStage},
+        opa::{CliPolicyLoader, ExecutorContext},
         prov::{
-            operations::{ChronicleOperation, DerivationType},
+            operations::{AgentExists, ChronicleOperation, DerivationType},
             to_json_ld::ToJson,
-            ActivityId, AgentId, ChronicleTransactionId, DomaintypeId, EntityId, NamespaceId,
-            ProvModel,
+            ActivityId, AgentId, ChronicleIri, ChronicleTransaction, ChronicleTransactionId,
+            Contradiction, DomaintypeId, EntityId, NamespaceId, ProvModel,
         },
     };
+    use diesel::r2d2::{ConnectionManager, Pool};
     use opa_tp_protocol::state::{policy_address, policy_meta_address, PolicyMeta};
     use protobuf::Message;
     use sawtooth_sdk::messages::setting::{Setting, Setting_Entry};
+    use std::time::Duration;
 
     use uuid::Uuid;
 
     struct TestDispatch<'a> {
         api: ApiDispatch,
-        _db: TemporaryDatabase<'a>, // share lifetime
+        pool: ConnectionPool,
+        _db: Option<TemporaryDatabase<'a>>, // share lifetime
+        _sqlite_dir: Option<tempfile::TempDir>,
         _tp: EmbeddedChronicleTp,
     }
 
@@ -1942,6 +2411,34 @@ mod test {
     }
 
     async fn test_api<'a>() -> TestDispatch<'a> {
+        let database = TemporaryDatabase::default();
+        let pool = database.connection_pool().unwrap();
+
+        test_api_on(pool, Some(database), None).await
+    }
+
+    /// An api whose store is an SQLite database in a temporary directory
+    async fn test_api_sqlite<'a>() -> TestDispatch<'a> {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = Pool::builder()
+            .connection_customizer(Box::new(ConnectionOptions {
+                enable_wal: true,
+                enable_foreign_keys: true,
+                busy_timeout: Some(Duration::from_secs(5)),
+            }))
+            .build(ConnectionManager::<StoreConnection>::new(
+                dir.path().join("chronicle.db").to_str().unwrap(),
+            ))
+            .unwrap();
+
+        test_api_on(pool, None, Some(dir)).await
+    }
+
+    async fn test_api_on<'a>(
+        pool: ConnectionPool,
+        database: Option<TemporaryDatabase<'a>>,
+        sqlite_dir: Option<tempfile::TempDir>,
+    ) -> TestDispatch<'a> {
         chronicle_telemetry::telemetry(None, chronicle_telemetry::ConsoleLogging::Pretty);
 
         let secrets = ChronicleSigning::new(
@@ -1960,26 +2457,28 @@ mod test {
         .await
         .unwrap();
         let embed_tp = embed_chronicle_tp();
-        let database = TemporaryDatabase::default();
-        let pool = database.connection_pool().unwrap();
 
         let liveness_check_interval = None;
 
         let dispatch = Api::new(
-            pool,
+            pool.clone(),
             embed_tp.ledger.clone(),
             SameUuid,
             secrets,
             vec![],
             Some("allow_transactions".into()),
             liveness_check_interval,
+            None,
+            None,
         )
         .await
         .unwrap();
 
         TestDispatch {
             api: dispatch,
+            pool,
             _db: database, // share the lifetime
+            _sqlite_dir: sqlite_dir,
             _tp: embed_tp,
         }
     }
@@ -2215,6 +2714,848 @@ mod test {
         "###);
     }
 
+    #[tokio::test]
+    async fn retry_with_idempotency_key() {
+        let api = test_api().await;
+
+        let identity = AuthId::chronicle();
+
+        let command = ApiCommand::Agent(AgentCommand::Create {
+            external_id: "testagent".into(),
+            namespace: "testns".into(),
+            attributes: Attributes {
+                typ: Some(DomaintypeId::from_external_id("test")),
+                attributes: Default::default(),
+            },
+        });
+
+        let mut notifications = api.api.notify_commit.subscribe();
+
+        let tx_id = match api
+            .api
+            .dispatch_with_idempotency_key(
+                command.clone(),
+                identity.clone(),
+                Some("retried".to_owned()),
+            )
+            .await
+            .unwrap()
+        {
+            ApiResponse::Submission { tx_id, .. } => tx_id,
+            res => panic!("unexpected response: {res:?}"),
+        };
+
+        loop {
+            match notifications.recv().await.unwrap() {
+                SubmissionStage::Committed(commit, _) if commit.tx_id == tx_id => break,
+                SubmissionStage::NotCommitted((_, contradiction, _)) => {
+                    panic!("{contradiction:?}")
+                }
+                _ => continue,
+            }
+        }
+
+        match api
+            .api
+            .dispatch_with_idempotency_key(
+                command.clone(),
+                identity.clone(),
+                Some("retried".to_owned()),
+            )
+            .await
+            .unwrap()
+        {
+            ApiResponse::PriorSubmission {
+                subject,
+                tx_id: prior_tx_id,
+                stage,
+            } => {
+                assert_eq!(subject.to_string(), "chronicle:agent:testagent");
+                assert_eq!(prior_tx_id, Some(tx_id));
+                assert_eq!(stage, Some(TransactionStage::Committed));
+            }
+            res => panic!("unexpected response: {res:?}"),
+        }
+
+        let different = ApiCommand::Agent(AgentCommand::Create {
+            external_id: "otheragent".into(),
+            namespace: "testns".into(),
+            attributes: Attributes {
+                typ: Some(DomaintypeId::from_external_id("test")),
+                attributes: Default::default(),
+            },
+        });
+
+        assert!(matches!(
+            api.api
+                .dispatch_with_idempotency_key(different, identity.clone(), Some("retried".to_owned()))
+                .await,
+            Err(ApiError::IdempotencyConflict(key)) if key == "retried"
+        ));
+
+        // A key reserved by a dispatch that never completed, as if Chronicle stopped part way
+        let request_hash = hex::encode(Sha256::digest(serde_json::to_vec(&command).unwrap()));
+        let store = Store::new(api.pool.clone()).unwrap();
+        assert!(store
+            .reserve_idempotency_key(
+                &mut store.connection().unwrap(),
+                "interrupted",
+                &identity.to_string(),
+                &request_hash,
+            )
+            .unwrap()
+            .is_none());
+
+        assert!(matches!(
+            api.api
+                .dispatch_with_idempotency_key(command, identity, Some("interrupted".to_owned()))
+                .await,
+            Err(ApiError::IdempotencyPending(key)) if key == "interrupted"
+        ));
+    }
+
+    fn outbox_store<'a>() -> (TemporaryDatabase<'a>, Store) {
+        let database = TemporaryDatabase::default();
+        let pool = database.connection_pool().unwrap();
+        persistence::run_migrations(&mut pool.get().unwrap()).unwrap();
+        (database, Store::new(pool).unwrap())
+    }
+
+    fn queue_transactions(store: &Store, count: usize) -> Vec<i32> {
+        let mut connection = store.connection().unwrap();
+        (0..count)
+            .map(|i| {
+                store
+                    .queue_transaction(
+                        &mut connection,
+                        &ChronicleIri::from(AgentId::from_external_id(format!("queued{i}"))),
+                        &ChronicleTransaction::new(vec![], SignedIdentity::new_no_identity()),
+                    )
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn outbox_progress(store: &Store) -> Vec<(OutboxStatus, i32, Option<String>)> {
+        store
+            .outbox_items(&mut store.connection().unwrap(), None, None)
+            .unwrap()
+            .into_iter()
+            .map(|item| {
+                (
+                    item.status,
+                    item.attempts,
+                    item.tx_id.map(|tx_id| tx_id.to_string()),
+                )
+            })
+            .collect()
+    }
+
+    fn unreachable_ledger() -> ApiError {
+        ApiError::SawtoothCommunicationError(SawtoothCommunicationError::NoConnectedValidators)
+    }
+
+    #[test]
+    fn outbox_backoff_doubles_up_to_a_limit() {
+        assert_eq!(outbox_backoff(1), chrono::Duration::seconds(2));
+        assert_eq!(outbox_backoff(2), chrono::Duration::seconds(4));
+        assert_eq!(outbox_backoff(8), chrono::Duration::seconds(256));
+        assert_eq!(
+            outbox_backoff(9),
+            chrono::Duration::seconds(OUTBOX_MAX_BACKOFF_SECS)
+        );
+        assert_eq!(
+            outbox_backoff(i32::MAX),
+            chrono::Duration::seconds(OUTBOX_MAX_BACKOFF_SECS)
+        );
+    }
+
+    #[test]
+    fn outbox_drains_in_the_order_queued() {
+        let (_db, store) = outbox_store();
+        queue_transactions(&store, 3);
+
+        let mut submitted = 0;
+        drain_queued(&store, 3, |_tx| {
+            submitted += 1;
+            Ok(ChronicleTransactionId::from(
+                format!("tx{submitted}").as_str(),
+            ))
+        })
+        .unwrap();
+
+        assert_eq!(
+            outbox_progress(&store),
+            vec![
+                (OutboxStatus::Submitted, 1, Some("tx1".to_owned())),
+                (OutboxStatus::Submitted, 1, Some("tx2".to_owned())),
+                (OutboxStatus::Submitted, 1, Some("tx3".to_owned())),
+            ]
+        );
+        assert!(!store
+            .outbox_has_unsubmitted(&mut store.connection().unwrap())
+            .unwrap());
+    }
+
+    #[test]
+    fn outbox_waits_out_the_backoff() {
+        let (_db, store) = outbox_store();
+        queue_transactions(&store, 2);
+
+        let mut attempts = 0;
+        drain_queued(&store, 3, |_tx| {
+            attempts += 1;
+            Err(unreachable_ledger())
+        })
+        .unwrap();
+        assert_eq!(attempts, 1);
+
+        drain_queued(&store, 3, |_tx| {
+            attempts += 1;
+            Ok(ChronicleTransactionId::from("tx"))
+        })
+        .unwrap();
+        assert_eq!(attempts, 1);
+
+        assert_eq!(
+            outbox_progress(&store),
+            vec![
+                (OutboxStatus::Pending, 1, None),
+                (OutboxStatus::Pending, 0, None),
+            ]
+        );
+        let queued = store.next_queued_transaction().unwrap().unwrap();
+        assert!(queued.next_attempt_time > Utc::now().naive_utc() + chrono::Duration::seconds(1));
+    }
+
+    #[test]
+    fn outbox_stops_behind_a_failed_transaction_until_it_is_retried() {
+        let (_db, store) = outbox_store();
+        let queued = queue_transactions(&store, 2);
+
+        drain_queued(&store, 1, |_tx| Err(unreachable_ledger())).unwrap();
+
+        let mut submitted = 0;
+        drain_queued(&store, 1, |_tx| {
+            submitted += 1;
+            Ok(ChronicleTransactionId::from(
+                format!("tx{submitted}").as_str(),
+            ))
+        })
+        .unwrap();
+        assert_eq!(submitted, 0);
+        assert_eq!(
+            outbox_progress(&store),
+            vec![
+                (OutboxStatus::Failed, 1, None),
+                (OutboxStatus::Pending, 0, None),
+            ]
+        );
+
+        let mut connection = store.connection().unwrap();
+        assert!(store.outbox_has_unsubmitted(&mut connection).unwrap());
+        assert!(!store
+            .retry_queued_transaction(&mut connection, queued[1])
+            .unwrap());
+        assert!(store
+            .retry_queued_transaction(&mut connection, queued[0])
+            .unwrap());
+
+        drain_queued(&store, 1, |_tx| {
+            submitted += 1;
+            Ok(ChronicleTransactionId::from(
+                format!("tx{submitted}").as_str(),
+            ))
+        })
+        .unwrap();
+        assert_eq!(
+            outbox_progress(&store),
+            vec![
+                (OutboxStatus::Submitted, 1, Some("tx1".to_owned())),
+                (OutboxStatus::Submitted, 1, Some("tx2".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn outbox_fails_a_transaction_the_ledger_refuses() {
+        let (_db, store) = outbox_store();
+        queue_transactions(&store, 2);
+
+        drain_queued(&store, 3, |_tx| {
+            Err(ApiError::SawtoothCommunicationError(
+                SawtoothCommunicationError::MalformedMessage,
+            ))
+        })
+        .unwrap();
+
+        assert_eq!(
+            outbox_progress(&store),
+            vec![
+                (OutboxStatus::Failed, 1, None),
+                (OutboxStatus::Pending, 0, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn outbox_status_is_pending_until_a_queued_transaction_is_committed() {
+        let (_db, store) = outbox_store();
+        let mut connection = store.connection().unwrap();
+
+        store.namespace_binding("testns", Uuid::new_v4()).unwrap();
+        let (namespace, namespace_id) = store
+            .namespace_by_external_id(&mut connection, &"testns".into())
+            .unwrap();
+        let subject = ChronicleIri::from(AgentId::from_external_id("queued"));
+        let tx = ChronicleTransaction::new(
+            vec![ChronicleOperation::AgentExists(AgentExists {
+                namespace: namespace.clone(),
+                external_id: "queued".into(),
+            })],
+            SignedIdentity::new_no_identity(),
+        );
+
+        let mut outbox_status = || {
+            store
+                .outbox_status(&mut connection, namespace_id, &subject)
+                .unwrap()
+        };
+
+        assert_eq!(outbox_status(), None);
+
+        for _ in 0..2 {
+            store
+                .queue_transaction(&mut store.connection().unwrap(), &subject, &tx)
+                .unwrap();
+        }
+        assert_eq!(outbox_status(), Some(OutboxStatus::Pending));
+
+        let mut submitted = 0;
+        drain_queued(&store, 1, |_tx| {
+            submitted += 1;
+            Ok(ChronicleTransactionId::from(
+                format!("tx{submitted}").as_str(),
+            ))
+        })
+        .unwrap();
+        assert_eq!(outbox_status(), Some(OutboxStatus::Submitted));
+
+        let block_id = BlockId::try_from(hex::encode([1; 32])).unwrap();
+        let identity = SignedIdentity::new_no_identity();
+        store
+            .record_transaction_committed(
+                &ChronicleTransactionId::from("tx1"),
+                &block_id,
+                &identity,
+                &ProvModel::default(),
+            )
+            .unwrap();
+        assert_eq!(outbox_status(), Some(OutboxStatus::Submitted));
+
+        store
+            .record_transaction_contradicted(
+                &ChronicleTransactionId::from("tx2"),
+                &block_id,
+                &identity,
+                &Contradiction::start_date_alteration(
+                    subject.clone(),
+                    namespace,
+                    Utc::now(),
+                    Utc::now(),
+                ),
+            )
+            .unwrap();
+        assert_eq!(outbox_status(), Some(OutboxStatus::Contradicted));
+
+        assert_eq!(
+            outbox_progress(&store),
+            vec![
+                (OutboxStatus::Committed, 1, Some("tx1".to_owned())),
+                (OutboxStatus::Contradicted, 1, Some("tx2".to_owned())),
+            ]
+        );
+        assert!(!store
+            .outbox_has_unsubmitted(&mut store.connection().unwrap())
+            .unwrap());
+    }
+
+    #[test]
+    fn outbox_is_indexed_by_every_subject_paged_and_pruned_once_committed() {
+        let (_db, store) = outbox_store();
+        let mut connection = store.connection().unwrap();
+
+        store.namespace_binding("testns", Uuid::new_v4()).unwrap();
+        let (namespace, namespace_id) = store
+            .namespace_by_external_id(&mut connection, &"testns".into())
+            .unwrap();
+        let agents = |external_ids: &[&str]| {
+            ChronicleTransaction::new(
+                external_ids
+                    .iter()
+                    .map(|external_id| {
+                        ChronicleOperation::AgentExists(AgentExists {
+                            namespace: namespace.clone(),
+                            external_id: (*external_id).into(),
+                        })
+                    })
+                    .collect(),
+                SignedIdentity::new_no_identity(),
+            )
+        };
+        let subject =
+            |external_id: &str| ChronicleIri::from(AgentId::from_external_id(external_id));
+
+        store
+            .queue_transaction(
+                &mut connection,
+                &subject("first"),
+                &agents(&["first", "also"]),
+            )
+            .unwrap();
+        store
+            .queue_transaction(&mut connection, &subject("second"), &agents(&["second"]))
+            .unwrap();
+
+        // The status of an entry is found by any of the subjects of its operations
+        assert_eq!(
+            store
+                .outbox_status(&mut connection, namespace_id, &subject("also"))
+                .unwrap(),
+            Some(OutboxStatus::Pending)
+        );
+
+        let page = store
+            .outbox_items(&mut connection, None, Some((1, 1)))
+            .unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].subject, subject("second"));
+        assert_eq!(store.outbox_len(&mut connection, None).unwrap(), 2);
+
+        let mut submitted = 0;
+        drain_queued(&store, 1, |_tx| {
+            submitted += 1;
+            Ok(ChronicleTransactionId::from(
+                format!("tx{submitted}").as_str(),
+            ))
+        })
+        .unwrap();
+        store
+            .record_transaction_committed(
+                &ChronicleTransactionId::from("tx1"),
+                &BlockId::try_from(hex::encode([1; 32])).unwrap(),
+                &SignedIdentity::new_no_identity(),
+                &ProvModel::default(),
+            )
+            .unwrap();
+        assert_eq!(
+            store
+                .outbox_len(&mut connection, Some(OutboxStatus::Committed))
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            store
+                .outbox_len(&mut connection, Some(OutboxStatus::Submitted))
+                .unwrap(),
+            1
+        );
+
+        // Committed entries are kept until they have been committed for long enough
+        let now = Utc::now().naive_utc();
+        assert_eq!(
+            store
+                .prune_committed_outbox(&mut connection, now - chrono::Duration::hours(1))
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            store
+                .prune_committed_outbox(&mut connection, now + chrono::Duration::hours(1))
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            outbox_progress(&store),
+            vec![(OutboxStatus::Submitted, 1, Some("tx2".to_owned()))]
+        );
+        assert_eq!(
+            store
+                .outbox_status(&mut connection, namespace_id, &subject("also"))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn withheld_contradiction_is_not_described_by_the_error() {
+        let (_db, store) = outbox_store();
+        let mut connection = store.connection().unwrap();
+
+        let loader = CliPolicyLoader::from_embedded_policy(
+            "allow_transactions",
+            "allow_transactions.allow_except_hidden_data",
+        )
+        .unwrap();
+        let opa = ExecutorContext::from_loader(&loader).unwrap();
+
+        let namespace = NamespaceId::from_external_id("testns", Uuid::new_v4());
+        let block_id = BlockId::try_from(hex::encode([1; 32])).unwrap();
+        for external_id in ["hiddenactivity", "testactivity"] {
+            store
+                .record_transaction_contradicted(
+                    &ChronicleTransactionId::from(external_id),
+                    &block_id,
+                    &SignedIdentity::new_no_identity(),
+                    &Contradiction::start_date_alteration(
+                        ActivityId::from_external_id(external_id).into(),
+                        namespace.clone(),
+                        Utc.with_ymd_and_hms(2014, 7, 8, 9, 10, 11).unwrap(),
+                        Utc.with_ymd_and_hms(2018, 7, 8, 9, 10, 11).unwrap(),
+                    ),
+                )
+                .unwrap();
+        }
+
+        let mut read = |external_id: &str| {
+            store
+                .transaction_receipt(&mut connection, &ChronicleTransactionId::from(external_id))
+                .unwrap()
+                .unwrap()
+        };
+
+        let mut hidden = read("hiddenactivity");
+        assert!(withhold_unreadable(&opa, &AuthId::chronicle(), &mut hidden).await);
+        assert!(hidden.contradiction.is_none());
+        assert!(!hidden.error.as_deref().unwrap().contains("hiddenactivity"));
+
+        let mut visible = read("testactivity");
+        let error = visible.error.clone();
+        assert!(!withhold_unreadable(&opa, &AuthId::chronicle(), &mut visible).await);
+        assert!(visible.contradiction.is_some());
+        assert_eq!(visible.error, error);
+    }
+
+    #[test]
+    fn contributing_transactions_are_found_by_subject() {
+        use diesel::{sql_query, RunQueryDsl};
+
+        let (_db, store) = outbox_store();
+        let mut connection = store.connection().unwrap();
+
+        let namespace = NamespaceId::from_external_id("testns", Uuid::new_v4());
+        let agent = |external_id: &str| {
+            ProvModel::from_tx(&[ChronicleOperation::AgentExists(AgentExists {
+                namespace: namespace.clone(),
+                external_id: external_id.into(),
+            })])
+            .unwrap()
+        };
+
+        let block_id = BlockId::try_from(hex::encode([1; 32])).unwrap();
+        for (tx_id, delta) in [
+            ("tx1", agent("a")),
+            ("tx2", agent("b")),
+            ("tx3", agent("a")),
+        ] {
+            store
+                .record_transaction_committed(
+                    &ChronicleTransactionId::from(tx_id),
+                    &block_id,
+                    &SignedIdentity::new_no_identity(),
+                    &delta,
+                )
+                .unwrap();
+        }
+
+        // A receipt that cannot be decoded is skipped rather than failing the export
+        sql_query("update txreceipt set delta = 'not a delta' where tx_id = 'tx3'")
+            .execute(&mut connection)
+            .unwrap();
+
+        let contributing = |connection: &mut StoreConnection| {
+            store
+                .contributing_transactions(connection, &agent("a"))
+                .unwrap()
+                .into_iter()
+                .map(|receipt| receipt.tx_id.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(contributing(&mut connection), vec!["tx1"]);
+
+        // Transactions recorded before they were indexed are indexed when the store is migrated
+        sql_query("delete from txsubject")
+            .execute(&mut connection)
+            .unwrap();
+        assert!(contributing(&mut connection).is_empty());
+        persistence::run_migrations(&mut connection).unwrap();
+        assert_eq!(contributing(&mut connection), vec!["tx1"]);
+    }
+
+    #[test]
+    fn only_an_unreachable_ledger_is_retried() {
+        let tx_id = ChronicleTransactionId::from("tx");
+
+        assert!(unreachable_ledger().is_ledger_unreachable());
+        assert!(ApiError::Ledger(SubmissionError::communication(
+            &tx_id,
+            SawtoothCommunicationError::NoConnectedValidators
+        ))
+        .is_ledger_unreachable());
+        assert!(!ApiError::Ledger(SubmissionError::communication(
+            &tx_id,
+            SawtoothCommunicationError::MalformedMessage
+        ))
+        .is_ledger_unreachable());
+        assert!(!ApiError::NoAgentSignedOperations.is_ledger_unreachable());
+    }
+
+    #[tokio::test]
+    async fn verify_store_against_ledger() {
+        use crate::verify::{verify_store, Category, Difference};
+        use diesel::{sql_query, RunQueryDsl};
+
+        let mut api = test_api().await;
+
+        api.dispatch(
+            ApiCommand::Agent(AgentCommand::Create {
+                external_id: "testagent".into(),
+                namespace: "testns".into(),
+                attributes: Attributes {
+                    typ: Some(DomaintypeId::from_external_id("test")),
+                    attributes: [(
+                        "test".to_owned(),
+                        Attribute {
+                            typ: "test".to_owned(),
+                            value: serde_json::Value::String("test".to_owned()),
+                        },
+                    )]
+                    .into_iter()
+                    .collect(),
+                },
+            }),
+            AuthId::chronicle(),
+        )
+        .await
+        .unwrap();
+
+        let pool = api.pool.clone();
+
+        let report = verify_store(pool.clone(), &api._tp.ledger).await.unwrap();
+        assert!(report.consistent, "{report:?}");
+
+        sql_query("delete from agent_attribute")
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+
+        let report = verify_store(pool, &api._tp.ledger).await.unwrap();
+        let discrepancies = report
+            .namespaces
+            .iter()
+            .flat_map(|namespace| namespace.discrepancies.iter())
+            .collect::<Vec<_>>();
+
+        assert_eq!(discrepancies.len(), 1);
+        assert_eq!(discrepancies[0].category, Category::Attribute);
+        assert_eq!(discrepancies[0].difference, Difference::Missing);
+        assert_eq!(discrepancies[0].subject, "chronicle:agent:testagent");
+        assert_eq!(discrepancies[0].name, "test");
+    }
+
+    #[tokio::test]
+    async fn sqlite_store_is_consistent_with_ledger() {
+        use crate::verify::verify_store;
+
+        let mut api = test_api_sqlite().await;
+
+        let identity = AuthId::chronicle();
+
+        let create_agent = ApiCommand::Agent(AgentCommand::Create {
+            external_id: "testagent".into(),
+            namespace: "testns".into(),
+            attributes: Attributes {
+                typ: Some(DomaintypeId::from_external_id("test")),
+                attributes: [(
+                    "test".to_owned(),
+                    Attribute {
+                        typ: "test".to_owned(),
+                        value: serde_json::Value::String("test".to_owned()),
+                    },
+                )]
+                .into_iter()
+                .collect(),
+            },
+        });
+
+        let (_, tx_id) = api
+            .dispatch(create_agent.clone(), identity.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_ne!(tx_id, ChronicleTransactionId::from("null"));
+
+        // Recording the same agent again changes nothing
+        let (_, tx_id) = api
+            .dispatch(create_agent, identity.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tx_id, ChronicleTransactionId::from("null"));
+
+        for command in [
+            ApiCommand::Activity(ActivityCommand::Start {
+                id: ActivityId::from_external_id("testactivity"),
+                namespace: "testns".into(),
+                time: Some(Utc.with_ymd_and_hms(2014, 7, 8, 9, 10, 11).unwrap()),
+                agent: Some(AgentId::from_external_id("testagent")),
+            }),
+            ApiCommand::Activity(ActivityCommand::Use {
+                id: EntityId::from_external_id("testusedentity"),
+                namespace: "testns".into(),
+                activity: ActivityId::from_external_id("testactivity"),
+            }),
+            ApiCommand::Activity(ActivityCommand::Generate {
+                id: EntityId::from_external_id("testgeneratedentity"),
+                namespace: "testns".into(),
+                activity: ActivityId::from_external_id("testactivity"),
+            }),
+            ApiCommand::Entity(EntityCommand::Derive {
+                id: EntityId::from_external_id("testgeneratedentity"),
+                namespace: "testns".into(),
+                activity: Some(ActivityId::from_external_id("testactivity")),
+                used_entity: EntityId::from_external_id("testusedentity"),
+                derivation: DerivationType::Revision,
+            }),
+        ] {
+            api.dispatch(command, identity.clone()).await.unwrap();
+        }
+
+        let report = verify_store(api.pool.clone(), &api._tp.ledger)
+            .await
+            .unwrap();
+        assert!(report.consistent, "{report:?}");
+    }
+
+    #[tokio::test]
+    async fn verify_store_replays_the_ledger_and_skips_resources_in_the_outbox() {
+        use crate::{
+            file_ledger::FileLedger,
+            verify::{verify_store, Category, Difference},
+        };
+        use diesel::{sql_query, RunQueryDsl};
+
+        chronicle_telemetry::telemetry(None, chronicle_telemetry::ConsoleLogging::Pretty);
+
+        let database = TemporaryDatabase::default();
+        let pool = database.connection_pool().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let ledger = FileLedger::open(
+            dir.path().join("ledger"),
+            "allow_transactions",
+            "allow_transactions.allowed_users",
+            &[],
+        )
+        .unwrap();
+
+        let secrets = ChronicleSigning::new(
+            chronicle_secret_names(),
+            vec![
+                (
+                    CHRONICLE_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::generate_in_memory(),
+                ),
+                (
+                    BATCHER_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::generate_in_memory(),
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let api = Api::new(
+            pool.clone(),
+            ledger.ledger.clone(),
+            SameUuid,
+            secrets,
+            vec![],
+            Some("allow_transactions".into()),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let mut notifications = api.notify_commit.subscribe();
+        api.dispatch(
+            ApiCommand::Agent(AgentCommand::Create {
+                external_id: "lost".into(),
+                namespace: "testns".into(),
+                attributes: Attributes {
+                    typ: None,
+                    attributes: Default::default(),
+                },
+            }),
+            AuthId::chronicle(),
+        )
+        .await
+        .unwrap();
+        while !matches!(
+            notifications.recv().await.unwrap(),
+            SubmissionStage::Committed(..)
+        ) {}
+
+        // Lose the agent from the store, leaving nothing there that refers to it
+        sql_query("delete from agent where external_id = 'lost'")
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+
+        // Record an agent in the store whose transaction is yet to leave the outbox
+        let store = Store::new(pool.clone()).unwrap();
+        let mut connection = store.connection().unwrap();
+        let (namespace, _) = store
+            .namespace_by_external_id(&mut connection, &"testns".into())
+            .unwrap();
+        let queued = ChronicleTransaction::new(
+            vec![ChronicleOperation::AgentExists(AgentExists {
+                namespace,
+                external_id: "queued".into(),
+            })],
+            SignedIdentity::new_no_identity(),
+        );
+        store
+            .queue_transaction(
+                &mut connection,
+                &ChronicleIri::from(AgentId::from_external_id("queued")),
+                &queued,
+            )
+            .unwrap();
+        store
+            .apply_prov(&ProvModel::from_tx(&queued.tx).unwrap())
+            .unwrap();
+
+        let report = verify_store(pool, &ledger.ledger).await.unwrap();
+        let discrepancies = report
+            .namespaces
+            .iter()
+            .flat_map(|namespace| namespace.discrepancies.iter())
+            .collect::<Vec<_>>();
+
+        assert_eq!(discrepancies.len(), 1, "{report:?}");
+        assert_eq!(discrepancies[0].category, Category::Node);
+        assert_eq!(discrepancies[0].difference, Difference::Missing);
+        assert_eq!(discrepancies[0].subject, "chronicle:agent:lost");
+        assert_eq!(
+            report
+                .namespaces
+                .iter()
+                .map(|namespace| namespace.pending_resources)
+                .sum::<usize>(),
+            1
+        );
+    }
+
     #[tokio::test]
     async fn create_system_activity() {
         let mut api = test_api().await;
@@ -1,26 +1,37 @@
-use std::{collections::BTreeMap, str::FromStr, time::Duration};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    str::FromStr,
+    time::Duration,
+};
 
 use async_stl_client::ledger::{BlockId, BlockIdError};
-use chrono::DateTime;
+use chronicle_protocol::state::ChronicleKeys;
+use chrono::{DateTime, NaiveDateTime};
 
 use chrono::Utc;
 use common::{
     attributes::Attribute,
+    commands::{OutboxItem, OutboxStatus, TransactionStage},
+    database::{StoreBackend, StoreConnection},
+    decision_log::{DecisionLogEntry, DecisionLogError, DecisionLogSink},
+    identity::SignedIdentity,
     prov::{
         operations::DerivationType, Activity, ActivityId, Agent, AgentId, Association, Attribution,
-        ChronicleTransactionId, ChronicleTransactionIdError, Delegation, Derivation, DomaintypeId,
-        Entity, EntityId, ExternalId, ExternalIdPart, Generation, Identity, IdentityId, Namespace,
-        NamespaceId, ProvModel, PublicKeyPart, Role, Usage,
+        ChronicleIri, ChronicleTransaction, ChronicleTransactionId, ChronicleTransactionIdError,
+        Contradiction, Delegation, Derivation, DomaintypeId, Entity, EntityId, ExternalId,
+        ExternalIdPart, Generation, Identity, IdentityId, Namespace, NamespaceId, ParseIriError,
+        ProvModel, PublicKeyPart, Role, Usage,
     },
 };
 use derivative::*;
 
 use diesel::{
+    connection::SimpleConnection,
     prelude::*,
-    r2d2::{ConnectionManager, Pool, PooledConnection},
-    PgConnection,
+    query_dsl::methods::ExecuteDsl,
+    r2d2::{ConnectionManager, CustomizeConnection, Pool, PooledConnection},
 };
-use diesel_migrations::{embed_migrations, EmbeddedMigrations};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use thiserror::Error;
 use tracing::{debug, instrument, warn};
 use uuid::Uuid;
@@ -28,6 +39,121 @@ use uuid::Uuid;
 mod query;
 pub(crate) mod schema;
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+pub const SQLITE_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations_sqlite");
+
+/// Run the migrations for the backend `connection` is to
+pub fn run_migrations(connection: &mut StoreConnection) -> Result<(), StoreError> {
+    match connection {
+        StoreConnection::Postgres(connection) => connection
+            .build_transaction()
+            .run(|connection| connection.run_pending_migrations(MIGRATIONS).map(|_| ())),
+        StoreConnection::Sqlite(connection) => connection
+            .run_pending_migrations(SQLITE_MIGRATIONS)
+            .map(|_| ()),
+    }
+    .map_err(StoreError::DbMigration)?;
+
+    index_committed_transactions(connection)
+}
+
+/// Insert or replace a transaction receipt
+fn write_transaction_receipt(
+    connection: &mut StoreConnection,
+    receipt: &query::TxReceipt,
+) -> Result<(), StoreError> {
+    use schema::txreceipt::dsl;
+
+    execute_upsert(
+        connection,
+        diesel::insert_into(dsl::txreceipt)
+            .values(receipt)
+            .on_conflict(dsl::tx_id)
+            .do_update()
+            .set(receipt),
+    )?;
+
+    Ok(())
+}
+
+/// The IRIs of the agents, activities and entities in `model`
+fn subjects(model: &ProvModel) -> BTreeSet<String> {
+    model
+        .agents
+        .keys()
+        .map(|(_, id)| id.to_string())
+        .chain(model.activities.keys().map(|(_, id)| id.to_string()))
+        .chain(model.entities.keys().map(|(_, id)| id.to_string()))
+        .collect()
+}
+
+/// Index a committed transaction by the subjects of its effects, so the transactions
+/// contributing to a subject can be found without reading every delta
+fn index_transaction_subjects(
+    connection: &mut StoreConnection,
+    tx_id: &str,
+    delta: &ProvModel,
+) -> Result<(), StoreError> {
+    for subject in subjects(delta) {
+        execute_upsert(
+            connection,
+            diesel::insert_into(schema::txsubject::table)
+                .values(query::NewTxSubject {
+                    tx_id,
+                    subject: &subject,
+                })
+                .on_conflict_do_nothing(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Index the transactions committed before their subjects were indexed as they were recorded.
+/// A delta that cannot be decoded is logged and left unindexed.
+fn index_committed_transactions(connection: &mut StoreConnection) -> Result<(), StoreError> {
+    use schema::{txreceipt::dsl, txsubject};
+
+    let indexed = txsubject::table
+        .select(txsubject::tx_id)
+        .first::<String>(connection)
+        .optional()?
+        .is_some();
+    if indexed {
+        return Ok(());
+    }
+
+    connection.transaction(|connection| {
+        let committed = dsl::txreceipt
+            .filter(dsl::stage.eq(TransactionStage::Committed.as_str()))
+            .filter(dsl::delta.is_not_null())
+            .select((dsl::tx_id, dsl::delta.assume_not_null()))
+            .load::<(String, String)>(connection)?;
+
+        for (tx_id, delta) in committed {
+            match serde_json::from_str::<ProvModel>(&delta) {
+                Ok(delta) => index_transaction_subjects(connection, &tx_id, &delta)?,
+                Err(e) => warn!(%tx_id, %e, "Cannot index an undecodable transaction delta"),
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// The most subjects to look up transactions for in one query
+const SUBJECTS_PER_QUERY: usize = 500;
+
+/// Execute an insert with an `on conflict` clause. The multi-backend connection cannot express
+/// these, so they run on the backend's own connection.
+fn execute_upsert<Q>(connection: &mut StoreConnection, query: Q) -> QueryResult<usize>
+where
+    Q: ExecuteDsl<PgConnection> + ExecuteDsl<SqliteConnection>,
+{
+    match connection {
+        StoreConnection::Postgres(connection) => ExecuteDsl::execute(query, connection),
+        StoreConnection::Sqlite(connection) => ExecuteDsl::execute(query, connection),
+    }
+}
 
 #[derive(Error, Debug)]
 pub enum StoreError {
@@ -63,6 +189,18 @@ pub enum StoreError {
     #[error("Invalid transaction ID: {0}")]
     TransactionId(#[from] ChronicleTransactionIdError),
 
+    #[error("Invalid IRI: {0}")]
+    Iri(#[from] ParseIriError),
+
+    #[error("Invalid transaction stage: {0}")]
+    TransactionStage(String),
+
+    #[error("Invalid outbox status: {0}")]
+    OutboxStatus(String),
+
+    #[error("Queued transaction is self contradictory: {0}")]
+    Contradiction(#[from] Contradiction),
+
     #[error("Could not locate record in store")]
     RecordNotFound,
 
@@ -70,6 +208,152 @@ pub enum StoreError {
     Uuid(#[from] uuid::Error),
 }
 
+/// The progress of a transaction as recorded from its submission and the ledger event stream
+#[derive(Debug, Clone)]
+pub struct TransactionReceipt {
+    pub tx_id: ChronicleTransactionId,
+    pub stage: TransactionStage,
+    pub block_id: Option<BlockId>,
+    pub identity: Option<SignedIdentity>,
+    pub delta: Option<ProvModel>,
+    pub contradiction: Option<Contradiction>,
+    pub error: Option<String>,
+    pub updated_time: NaiveDateTime,
+    /// The version of the Chronicle key registered when the transaction was committed
+    pub key_version: Option<i32>,
+}
+
+impl TryFrom<query::TxReceipt> for TransactionReceipt {
+    type Error = StoreError;
+
+    fn try_from(receipt: query::TxReceipt) -> Result<Self, Self::Error> {
+        Ok(TransactionReceipt {
+            tx_id: ChronicleTransactionId::from(receipt.tx_id.as_str()),
+            stage: TransactionStage::from_str(&receipt.stage)
+                .map_err(StoreError::TransactionStage)?,
+            block_id: receipt.block_id.map(BlockId::try_from).transpose()?,
+            identity: receipt
+                .identity
+                .map(|identity| serde_json::from_str(&identity))
+                .transpose()?,
+            delta: receipt
+                .delta
+                .map(|delta| serde_json::from_str(&delta))
+                .transpose()?,
+            contradiction: receipt
+                .contradiction
+                .map(|contradiction| serde_json::from_str(&contradiction))
+                .transpose()?,
+            error: receipt.error,
+            updated_time: receipt.updated_time,
+            key_version: receipt.key_version,
+        })
+    }
+}
+
+/// A transaction in the outbox that has yet to reach the ledger
+#[derive(Debug, Clone)]
+pub struct QueuedTransaction {
+    pub id: i32,
+    pub tx: ChronicleTransaction,
+    pub status: OutboxStatus,
+    pub attempts: i32,
+    pub next_attempt_time: NaiveDateTime,
+}
+
+/// The status of an outbox entry, which once submitted is settled by the stage of its
+/// transaction's receipt
+fn outbox_status(status: &str, stage: Option<&str>) -> Result<OutboxStatus, StoreError> {
+    let status = OutboxStatus::from_str(status).map_err(StoreError::OutboxStatus)?;
+
+    Ok(match (status, stage.map(TransactionStage::from_str)) {
+        (OutboxStatus::Submitted, Some(Ok(TransactionStage::Committed))) => OutboxStatus::Committed,
+        (OutboxStatus::Submitted, Some(Ok(TransactionStage::Contradicted))) => {
+            OutboxStatus::Contradicted
+        }
+        (status, _) => status,
+    })
+}
+
+/// An outbox entry, with the stage of its transaction's receipt if it has one
+fn outbox_item(entry: query::OutboxEntry, stage: Option<String>) -> Result<OutboxItem, StoreError> {
+    let tx: ChronicleTransaction = serde_json::from_str(&entry.transaction)?;
+
+    Ok(OutboxItem {
+        id: entry.id,
+        subject: ChronicleIri::from_str(&entry.subject)?,
+        status: outbox_status(&entry.status, stage.as_deref())?,
+        attempts: entry.attempts,
+        last_error: entry.last_error,
+        tx_id: entry
+            .tx_id
+            .map(|tx_id| ChronicleTransactionId::from(tx_id.as_str())),
+        prov: Box::new(ProvModel::from_tx(&tx.tx)?),
+        queued: DateTime::from_naive_utc_and_offset(entry.queued_time, Utc),
+        next_attempt: DateTime::from_naive_utc_and_offset(entry.next_attempt_time, Utc),
+    })
+}
+
+type OutboxWithReceipts = diesel::dsl::LeftJoinOn<
+    schema::outbox::table,
+    schema::txreceipt::table,
+    diesel::dsl::Eq<schema::outbox::tx_id, diesel::dsl::Nullable<schema::txreceipt::tx_id>>,
+>;
+
+/// The outbox joined to the receipts of the transactions submitted from it, optionally only the
+/// entries with `status`, see [`outbox_status`]
+fn outbox_with_receipts(
+    status: Option<OutboxStatus>,
+) -> diesel::dsl::IntoBoxed<'static, OutboxWithReceipts, StoreBackend> {
+    use schema::{outbox::dsl, txreceipt};
+
+    let query = dsl::outbox
+        .left_join(txreceipt::table.on(dsl::tx_id.eq(txreceipt::tx_id.nullable())))
+        .into_boxed();
+    let submitted = dsl::status.eq(OutboxStatus::Submitted.as_str());
+    let stage = txreceipt::stage.nullable();
+
+    match status {
+        None => query,
+        Some(OutboxStatus::Submitted) => {
+            query.filter(submitted.and(stage.is_null().or(stage.ne_all([
+                TransactionStage::Committed.as_str(),
+                TransactionStage::Contradicted.as_str(),
+            ]))))
+        }
+        Some(OutboxStatus::Committed) => {
+            query.filter(submitted.and(stage.eq(TransactionStage::Committed.as_str())))
+        }
+        Some(OutboxStatus::Contradicted) => {
+            query.filter(submitted.and(stage.eq(TransactionStage::Contradicted.as_str())))
+        }
+        Some(status) => query.filter(dsl::status.eq(status.as_str())),
+    }
+}
+
+/// The namespaces and IRIs of the agents, activities and entities in `model`, see [`subjects`]
+fn namespaced_subjects(model: &ProvModel) -> BTreeSet<(String, String)> {
+    let namespace = |namespace: &NamespaceId| namespace.external_id_part().to_string();
+
+    model
+        .agents
+        .keys()
+        .map(|(ns, id)| (namespace(ns), id.to_string()))
+        .chain(
+            model
+                .activities
+                .keys()
+                .map(|(ns, id)| (namespace(ns), id.to_string())),
+        )
+        .chain(
+            model
+                .entities
+                .keys()
+                .map(|(ns, id)| (namespace(ns), id.to_string())),
+        )
+        .collect()
+}
+
 #[derive(Debug)]
 pub struct ConnectionOptions {
     pub enable_wal: bool,
@@ -77,6 +361,56 @@ pub struct ConnectionOptions {
     pub busy_timeout: Option<Duration>,
 }
 
+/// Applied to each SQLite connection as the pool opens it
+impl CustomizeConnection<StoreConnection, diesel::r2d2::Error> for ConnectionOptions {
+    fn on_acquire(&self, connection: &mut StoreConnection) -> Result<(), diesel::r2d2::Error> {
+        let connection = match connection {
+            StoreConnection::Sqlite(connection) => connection,
+            _ => return Ok(()),
+        };
+
+        if self.enable_wal {
+            connection
+                .batch_execute("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")
+                .map_err(diesel::r2d2::Error::QueryError)?;
+        }
+        if self.enable_foreign_keys {
+            connection
+                .batch_execute("PRAGMA foreign_keys = ON;")
+                .map_err(diesel::r2d2::Error::QueryError)?;
+        }
+        if let Some(timeout) = self.busy_timeout {
+            connection
+                .batch_execute(&format!("PRAGMA busy_timeout = {};", timeout.as_millis()))
+                .map_err(diesel::r2d2::Error::QueryError)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Logs OPA decisions to the `opa_decision` table of Chronicle's store
+pub struct StoreDecisionLog {
+    store: Store,
+}
+
+impl StoreDecisionLog {
+    pub fn new(pool: Pool<ConnectionManager<StoreConnection>>) -> Result<Self, StoreError> {
+        Ok(Self {
+            store: Store::new(pool)?,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl DecisionLogSink for StoreDecisionLog {
+    async fn write(&self, decisions: &[DecisionLogEntry]) -> Result<(), DecisionLogError> {
+        self.store
+            .record_decisions(decisions)
+            .map_err(|e| DecisionLogError::Store(Box::new(e)))
+    }
+}
+
 #[instrument]
 fn sleeper(attempts: i32) -> bool {
     warn!(attempts, "SQLITE_BUSY, retrying");
@@ -84,11 +418,23 @@ fn sleeper(attempts: i32) -> bool {
     true
 }
 
+/// The request hash, subject, transaction and stage recorded for an idempotency key. The subject
+/// is `None` while the key is reserved for a command that is being dispatched.
+pub(crate) type IdempotentSubmission = (
+    String,
+    Option<ChronicleIri>,
+    Option<ChronicleTransactionId>,
+    Option<TransactionStage>,
+);
+
+/// Chronicle's store of provenance and of its own records. PostgreSQL and SQLite share this one
+/// implementation through the multi-backend [`StoreConnection`]; only migrations and upserts are
+/// specific to a backend.
 #[derive(Derivative)]
 #[derivative(Debug, Clone)]
 pub struct Store {
     #[derivative(Debug = "ignore")]
-    pool: Pool<ConnectionManager<PgConnection>>,
+    pool: Pool<ConnectionManager<StoreConnection>>,
 }
 
 impl Store {
@@ -101,13 +447,15 @@ impl Store {
         use schema::namespace::dsl;
 
         let uuid = uuid.to_string();
-        self.connection()?.build_transaction().run(|conn| {
-            diesel::insert_into(dsl::namespace)
-                .values((dsl::external_id.eq(external_id), dsl::uuid.eq(&uuid)))
-                .on_conflict(dsl::external_id)
-                .do_update()
-                .set(dsl::uuid.eq(&uuid))
-                .execute(conn)
+        self.connection()?.transaction(|conn| {
+            execute_upsert(
+                conn,
+                diesel::insert_into(dsl::namespace)
+                    .values((dsl::external_id.eq(external_id), dsl::uuid.eq(&uuid)))
+                    .on_conflict(dsl::external_id)
+                    .do_update()
+                    .set(dsl::uuid.eq(&uuid)),
+            )
         })?;
 
         Ok(())
@@ -116,7 +464,7 @@ impl Store {
     /// Fetch the activity record for the IRI
     fn activity_by_activity_external_id_and_namespace(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         external_id: &ExternalId,
         namespaceid: &NamespaceId,
     ) -> Result<query::Activity, StoreError> {
@@ -136,7 +484,7 @@ impl Store {
     /// Fetch the entity record for the IRI
     fn entity_by_entity_external_id_and_namespace(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         external_id: &ExternalId,
         namespace_id: &NamespaceId,
     ) -> Result<query::Entity, StoreError> {
@@ -156,7 +504,7 @@ impl Store {
     /// Fetch the agent record for the IRI
     pub(crate) fn agent_by_agent_external_id_and_namespace(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         external_id: &ExternalId,
         namespaceid: &NamespaceId,
     ) -> Result<query::Agent, StoreError> {
@@ -173,11 +521,34 @@ impl Store {
             .first::<query::Agent>(connection)?)
     }
 
+    /// The public key registered for an agent, if the agent exists and has one
+    pub(crate) fn agent_public_key(
+        &self,
+        connection: &mut StoreConnection,
+        namespaceid: &NamespaceId,
+        agent: &AgentId,
+    ) -> Result<Option<String>, StoreError> {
+        let (_namespaceid, nsid) =
+            self.namespace_by_external_id(connection, namespaceid.external_id_part())?;
+        use schema::{agent::dsl, identity};
+
+        Ok(schema::agent::table
+            .inner_join(identity::table)
+            .filter(
+                dsl::external_id
+                    .eq(agent.external_id_part())
+                    .and(dsl::namespace_id.eq(nsid)),
+            )
+            .select(identity::public_key)
+            .first::<String>(connection)
+            .optional()?)
+    }
+
     /// Apply an activity to persistent storage, name + namespace are a key, so we update times + domaintype on conflict
     #[instrument(level = "trace", skip(self, connection), ret(Debug))]
     fn apply_activity(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         Activity {
             ref external_id,
             namespaceid,
@@ -215,22 +586,24 @@ impl Store {
             .map(|x| x.naive_utc())
             .or_else(|| existing.as_ref().and_then(|x| x.ended));
 
-        diesel::insert_into(schema::activity::table)
-            .values((
-                dsl::external_id.eq(external_id),
-                dsl::namespace_id.eq(nsid),
-                dsl::started.eq(started.map(|t| t.naive_utc())),
-                dsl::ended.eq(ended.map(|t| t.naive_utc())),
-                dsl::domaintype.eq(domaintypeid.as_ref().map(|x| x.external_id_part())),
-            ))
-            .on_conflict((dsl::external_id, dsl::namespace_id))
-            .do_update()
-            .set((
-                dsl::domaintype.eq(resolved_domain_type),
-                dsl::started.eq(resolved_started),
-                dsl::ended.eq(resolved_ended),
-            ))
-            .execute(connection)?;
+        execute_upsert(
+            connection,
+            diesel::insert_into(schema::activity::table)
+                .values((
+                    dsl::external_id.eq(external_id),
+                    dsl::namespace_id.eq(nsid),
+                    dsl::started.eq(started.map(|t| t.naive_utc())),
+                    dsl::ended.eq(ended.map(|t| t.naive_utc())),
+                    dsl::domaintype.eq(domaintypeid.as_ref().map(|x| x.external_id_part())),
+                ))
+                .on_conflict((dsl::external_id, dsl::namespace_id))
+                .do_update()
+                .set((
+                    dsl::domaintype.eq(resolved_domain_type),
+                    dsl::started.eq(resolved_started),
+                    dsl::ended.eq(resolved_ended),
+                )),
+        )?;
 
         let query::Activity { id, .. } = self.activity_by_activity_external_id_and_namespace(
             connection,
@@ -238,21 +611,18 @@ impl Store {
             namespaceid,
         )?;
 
-        diesel::insert_into(schema::activity_attribute::table)
-            .values(
-                attributes
-                    .iter()
-                    .map(
-                        |(_, Attribute { typ, value, .. })| query::ActivityAttribute {
-                            activity_id: id,
-                            typename: typ.to_owned(),
-                            value: value.to_string(),
-                        },
-                    )
-                    .collect::<Vec<_>>(),
-            )
-            .on_conflict_do_nothing()
-            .execute(connection)?;
+        for (_, Attribute { typ, value, .. }) in attributes.iter() {
+            execute_upsert(
+                connection,
+                diesel::insert_into(schema::activity_attribute::table)
+                    .values(&query::ActivityAttribute {
+                        activity_id: id,
+                        typename: typ.to_owned(),
+                        value: value.to_string(),
+                    })
+                    .on_conflict_do_nothing(),
+            )?;
+        }
 
         Ok(())
     }
@@ -262,7 +632,7 @@ impl Store {
     #[instrument(level = "trace", skip(self, connection), ret(Debug))]
     fn apply_agent(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         Agent {
             ref external_id,
             namespaceid,
@@ -290,34 +660,35 @@ impl Store {
                     .and_then(|x| x.domaintype.as_ref().map(ExternalId::from))
             });
 
-        diesel::insert_into(schema::agent::table)
-            .values((
-                dsl::external_id.eq(external_id),
-                dsl::namespace_id.eq(nsid),
-                dsl::current.eq(0),
-                dsl::domaintype.eq(domaintypeid.as_ref().map(|x| x.external_id_part())),
-            ))
-            .on_conflict((dsl::namespace_id, dsl::external_id))
-            .do_update()
-            .set(dsl::domaintype.eq(resolved_domain_type))
-            .execute(connection)?;
+        execute_upsert(
+            connection,
+            diesel::insert_into(schema::agent::table)
+                .values((
+                    dsl::external_id.eq(external_id),
+                    dsl::namespace_id.eq(nsid),
+                    dsl::current.eq(0),
+                    dsl::domaintype.eq(domaintypeid.as_ref().map(|x| x.external_id_part())),
+                ))
+                .on_conflict((dsl::namespace_id, dsl::external_id))
+                .do_update()
+                .set(dsl::domaintype.eq(resolved_domain_type)),
+        )?;
 
         let query::Agent { id, .. } =
             self.agent_by_agent_external_id_and_namespace(connection, external_id, namespaceid)?;
 
-        diesel::insert_into(schema::agent_attribute::table)
-            .values(
-                attributes
-                    .iter()
-                    .map(|(_, Attribute { typ, value, .. })| query::AgentAttribute {
+        for (_, Attribute { typ, value, .. }) in attributes.iter() {
+            execute_upsert(
+                connection,
+                diesel::insert_into(schema::agent_attribute::table)
+                    .values(&query::AgentAttribute {
                         agent_id: id,
                         typename: typ.to_owned(),
                         value: value.to_string(),
                     })
-                    .collect::<Vec<_>>(),
-            )
-            .on_conflict_do_nothing()
-            .execute(connection)?;
+                    .on_conflict_do_nothing(),
+            )?;
+        }
 
         Ok(())
     }
@@ -325,7 +696,7 @@ impl Store {
     #[instrument(level = "trace", skip(self, connection), ret(Debug))]
     fn apply_entity(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         Entity {
             namespaceid,
             id,
@@ -353,33 +724,34 @@ impl Store {
                     .and_then(|x| x.domaintype.as_ref().map(ExternalId::from))
             });
 
-        diesel::insert_into(schema::entity::table)
-            .values((
-                dsl::external_id.eq(&external_id),
-                dsl::namespace_id.eq(nsid),
-                dsl::domaintype.eq(domaintypeid.as_ref().map(|x| x.external_id_part())),
-            ))
-            .on_conflict((dsl::namespace_id, dsl::external_id))
-            .do_update()
-            .set(dsl::domaintype.eq(resolved_domain_type))
-            .execute(connection)?;
+        execute_upsert(
+            connection,
+            diesel::insert_into(schema::entity::table)
+                .values((
+                    dsl::external_id.eq(&external_id),
+                    dsl::namespace_id.eq(nsid),
+                    dsl::domaintype.eq(domaintypeid.as_ref().map(|x| x.external_id_part())),
+                ))
+                .on_conflict((dsl::namespace_id, dsl::external_id))
+                .do_update()
+                .set(dsl::domaintype.eq(resolved_domain_type)),
+        )?;
 
         let query::Entity { id, .. } =
             self.entity_by_entity_external_id_and_namespace(connection, external_id, namespaceid)?;
 
-        diesel::insert_into(schema::entity_attribute::table)
-            .values(
-                attributes
-                    .iter()
-                    .map(|(_, Attribute { typ, value, .. })| query::EntityAttribute {
+        for (_, Attribute { typ, value, .. }) in attributes.iter() {
+            execute_upsert(
+                connection,
+                diesel::insert_into(schema::entity_attribute::table)
+                    .values(&query::EntityAttribute {
                         entity_id: id,
                         typename: typ.to_owned(),
                         value: value.to_string(),
                     })
-                    .collect::<Vec<_>>(),
-            )
-            .on_conflict_do_nothing()
-            .execute(connection)?;
+                    .on_conflict_do_nothing(),
+            )?;
+        }
 
         Ok(())
     }
@@ -387,7 +759,7 @@ impl Store {
     #[instrument(level = "trace", skip(self, connection), ret(Debug))]
     fn apply_has_identity(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         model: &ProvModel,
         namespaceid: &NamespaceId,
         agent: &AgentId,
@@ -413,7 +785,7 @@ impl Store {
     #[instrument(level = "trace", skip(self, connection), ret(Debug))]
     fn apply_had_identity(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         model: &ProvModel,
         namespaceid: &NamespaceId,
         agent: &AgentId,
@@ -427,10 +799,12 @@ impl Store {
         )?;
         use schema::hadidentity::dsl;
 
-        diesel::insert_into(schema::hadidentity::table)
-            .values((dsl::agent_id.eq(agent.id), dsl::identity_id.eq(identity.id)))
-            .on_conflict_do_nothing()
-            .execute(connection)?;
+        execute_upsert(
+            connection,
+            diesel::insert_into(schema::hadidentity::table)
+                .values((dsl::agent_id.eq(agent.id), dsl::identity_id.eq(identity.id)))
+                .on_conflict_do_nothing(),
+        )?;
 
         Ok(())
     }
@@ -438,7 +812,7 @@ impl Store {
     #[instrument(level = "trace", skip(self, connection), ret(Debug))]
     fn apply_identity(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         Identity {
             id,
             namespaceid,
@@ -452,91 +826,12 @@ impl Store {
         let (_, nsid) =
             self.namespace_by_external_id(connection, namespaceid.external_id_part())?;
 
-        diesel::insert_into(schema::identity::table)
-            .values((dsl::namespace_id.eq(nsid), dsl::public_key.eq(public_key)))
-            .on_conflict_do_nothing()
-            .execute(connection)?;
-
-        Ok(())
-    }
-
-    fn apply_model(
-        &self,
-        connection: &mut PgConnection,
-        model: &ProvModel,
-    ) -> Result<(), StoreError> {
-        for (_, ns) in model.namespaces.iter() {
-            self.apply_namespace(connection, ns)?
-        }
-        for (_, agent) in model.agents.iter() {
-            self.apply_agent(connection, agent, &model.namespaces)?
-        }
-        for (_, activity) in model.activities.iter() {
-            self.apply_activity(connection, activity, &model.namespaces)?
-        }
-        for (_, entity) in model.entities.iter() {
-            self.apply_entity(connection, entity, &model.namespaces)?
-        }
-        for (_, identity) in model.identities.iter() {
-            self.apply_identity(connection, identity, &model.namespaces)?
-        }
-
-        for ((namespaceid, agent_id), (_, identity_id)) in model.has_identity.iter() {
-            self.apply_has_identity(connection, model, namespaceid, agent_id, identity_id)?;
-        }
-
-        for ((namespaceid, agent_id), identity_id) in model.had_identity.iter() {
-            for (_, identity_id) in identity_id {
-                self.apply_had_identity(connection, model, namespaceid, agent_id, identity_id)?;
-            }
-        }
-
-        for ((namespaceid, _), association) in model.association.iter() {
-            for association in association.iter() {
-                self.apply_was_associated_with(connection, namespaceid, association)?;
-            }
-        }
-
-        for ((namespaceid, _), usage) in model.usage.iter() {
-            for usage in usage.iter() {
-                self.apply_used(connection, namespaceid, usage)?;
-            }
-        }
-
-        for ((namespaceid, activity_id), was_informed_by) in model.was_informed_by.iter() {
-            for (_, informing_activity_id) in was_informed_by.iter() {
-                self.apply_was_informed_by(
-                    connection,
-                    namespaceid,
-                    activity_id,
-                    informing_activity_id,
-                )?;
-            }
-        }
-
-        for ((namespaceid, _), generation) in model.generation.iter() {
-            for generation in generation.iter() {
-                self.apply_was_generated_by(connection, namespaceid, generation)?;
-            }
-        }
-
-        for ((namespaceid, _), derivation) in model.derivation.iter() {
-            for derivation in derivation.iter() {
-                self.apply_derivation(connection, namespaceid, derivation)?;
-            }
-        }
-
-        for ((namespaceid, _), delegation) in model.delegation.iter() {
-            for delegation in delegation.iter() {
-                self.apply_delegation(connection, namespaceid, delegation)?;
-            }
-        }
-
-        for ((namespace_id, _), attribution) in model.attribution.iter() {
-            for attribution in attribution.iter() {
-                self.apply_was_attributed_to(connection, namespace_id, attribution)?;
-            }
-        }
+        execute_upsert(
+            connection,
+            diesel::insert_into(schema::identity::table)
+                .values((dsl::namespace_id.eq(nsid), dsl::public_key.eq(public_key)))
+                .on_conflict_do_nothing(),
+        )?;
 
         Ok(())
     }
@@ -544,7 +839,7 @@ impl Store {
     #[instrument(level = "trace", skip(self, connection), ret(Debug))]
     fn apply_namespace(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         Namespace {
             ref external_id,
             ref uuid,
@@ -552,21 +847,15 @@ impl Store {
         }: &Namespace,
     ) -> Result<(), StoreError> {
         use schema::namespace::dsl;
-        diesel::insert_into(schema::namespace::table)
-            .values((
-                dsl::external_id.eq(external_id),
-                dsl::uuid.eq(uuid.to_string()),
-            ))
-            .on_conflict_do_nothing()
-            .execute(connection)?;
-
-        Ok(())
-    }
-
-    pub(crate) fn apply_prov(&self, prov: &ProvModel) -> Result<(), StoreError> {
-        self.connection()?
-            .build_transaction()
-            .run(|connection| self.apply_model(connection, prov))?;
+        execute_upsert(
+            connection,
+            diesel::insert_into(schema::namespace::table)
+                .values((
+                    dsl::external_id.eq(external_id),
+                    dsl::uuid.eq(uuid.to_string()),
+                ))
+                .on_conflict_do_nothing(),
+        )?;
 
         Ok(())
     }
@@ -574,7 +863,7 @@ impl Store {
     #[instrument(skip(connection))]
     fn apply_used(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         namespace: &NamespaceId,
         usage: &Usage,
     ) -> Result<(), StoreError> {
@@ -591,13 +880,15 @@ impl Store {
         )?;
 
         use schema::usage::dsl as link;
-        diesel::insert_into(schema::usage::table)
-            .values((
-                &link::activity_id.eq(storedactivity.id),
-                &link::entity_id.eq(storedentity.id),
-            ))
-            .on_conflict_do_nothing()
-            .execute(connection)?;
+        execute_upsert(
+            connection,
+            diesel::insert_into(schema::usage::table)
+                .values((
+                    &link::activity_id.eq(storedactivity.id),
+                    &link::entity_id.eq(storedentity.id),
+                ))
+                .on_conflict_do_nothing(),
+        )?;
 
         Ok(())
     }
@@ -605,7 +896,7 @@ impl Store {
     #[instrument(skip(connection))]
     fn apply_was_informed_by(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         namespace: &NamespaceId,
         activity_id: &ActivityId,
         informing_activity_id: &ActivityId,
@@ -623,13 +914,15 @@ impl Store {
         )?;
 
         use schema::wasinformedby::dsl as link;
-        diesel::insert_into(schema::wasinformedby::table)
-            .values((
-                &link::activity_id.eq(storedactivity.id),
-                &link::informing_activity_id.eq(storedinformingactivity.id),
-            ))
-            .on_conflict_do_nothing()
-            .execute(connection)?;
+        execute_upsert(
+            connection,
+            diesel::insert_into(schema::wasinformedby::table)
+                .values((
+                    &link::activity_id.eq(storedactivity.id),
+                    &link::informing_activity_id.eq(storedinformingactivity.id),
+                ))
+                .on_conflict_do_nothing(),
+        )?;
 
         Ok(())
     }
@@ -637,7 +930,7 @@ impl Store {
     #[instrument(skip(self, connection))]
     fn apply_was_associated_with(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         namespaceid: &common::prov::NamespaceId,
         association: &Association,
     ) -> Result<(), StoreError> {
@@ -655,14 +948,16 @@ impl Store {
 
         use schema::association::dsl as asoc;
         let no_role = common::prov::Role("".to_string());
-        diesel::insert_into(schema::association::table)
-            .values((
-                &asoc::activity_id.eq(storedactivity.id),
-                &asoc::agent_id.eq(storedagent.id),
-                &asoc::role.eq(association.role.as_ref().unwrap_or(&no_role)),
-            ))
-            .on_conflict_do_nothing()
-            .execute(connection)?;
+        execute_upsert(
+            connection,
+            diesel::insert_into(schema::association::table)
+                .values((
+                    &asoc::activity_id.eq(storedactivity.id),
+                    &asoc::agent_id.eq(storedagent.id),
+                    &asoc::role.eq(association.role.as_ref().unwrap_or(&no_role)),
+                ))
+                .on_conflict_do_nothing(),
+        )?;
 
         Ok(())
     }
@@ -670,7 +965,7 @@ impl Store {
     #[instrument(skip(self, connection, namespace))]
     fn apply_delegation(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         namespace: &common::prov::NamespaceId,
         delegation: &Delegation,
     ) -> Result<(), StoreError> {
@@ -703,15 +998,17 @@ impl Store {
 
         use schema::delegation::dsl as link;
         let no_role = common::prov::Role("".to_string());
-        diesel::insert_into(schema::delegation::table)
-            .values((
-                &link::responsible_id.eq(responsible.id),
-                &link::delegate_id.eq(delegate.id),
-                &link::activity_id.eq(activity.unwrap_or(-1)),
-                &link::role.eq(delegation.role.as_ref().unwrap_or(&no_role)),
-            ))
-            .on_conflict_do_nothing()
-            .execute(connection)?;
+        execute_upsert(
+            connection,
+            diesel::insert_into(schema::delegation::table)
+                .values((
+                    &link::responsible_id.eq(responsible.id),
+                    &link::delegate_id.eq(delegate.id),
+                    &link::activity_id.eq(activity.unwrap_or(-1)),
+                    &link::role.eq(delegation.role.as_ref().unwrap_or(&no_role)),
+                ))
+                .on_conflict_do_nothing(),
+        )?;
 
         Ok(())
     }
@@ -719,7 +1016,7 @@ impl Store {
     #[instrument(skip(self, connection, namespace))]
     fn apply_derivation(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         namespace: &common::prov::NamespaceId,
         derivation: &Derivation,
     ) -> Result<(), StoreError> {
@@ -748,15 +1045,17 @@ impl Store {
             .transpose()?;
 
         use schema::derivation::dsl as link;
-        diesel::insert_into(schema::derivation::table)
-            .values((
-                &link::used_entity_id.eq(stored_used.id),
-                &link::generated_entity_id.eq(stored_generated.id),
-                &link::typ.eq(derivation.typ),
-                &link::activity_id.eq(stored_activity.map_or(-1, |activity| activity.id)),
-            ))
-            .on_conflict_do_nothing()
-            .execute(connection)?;
+        execute_upsert(
+            connection,
+            diesel::insert_into(schema::derivation::table)
+                .values((
+                    &link::used_entity_id.eq(stored_used.id),
+                    &link::generated_entity_id.eq(stored_generated.id),
+                    &link::typ.eq(derivation.typ),
+                    &link::activity_id.eq(stored_activity.map_or(-1, |activity| activity.id)),
+                ))
+                .on_conflict_do_nothing(),
+        )?;
 
         Ok(())
     }
@@ -764,7 +1063,7 @@ impl Store {
     #[instrument(skip(connection))]
     fn apply_was_generated_by(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         namespace: &common::prov::NamespaceId,
         generation: &Generation,
     ) -> Result<(), StoreError> {
@@ -781,13 +1080,15 @@ impl Store {
         )?;
 
         use schema::generation::dsl as link;
-        diesel::insert_into(schema::generation::table)
-            .values((
-                &link::activity_id.eq(storedactivity.id),
-                &link::generated_entity_id.eq(storedentity.id),
-            ))
-            .on_conflict_do_nothing()
-            .execute(connection)?;
+        execute_upsert(
+            connection,
+            diesel::insert_into(schema::generation::table)
+                .values((
+                    &link::activity_id.eq(storedactivity.id),
+                    &link::generated_entity_id.eq(storedentity.id),
+                ))
+                .on_conflict_do_nothing(),
+        )?;
 
         Ok(())
     }
@@ -795,7 +1096,7 @@ impl Store {
     #[instrument(skip(self, connection))]
     fn apply_was_attributed_to(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         namespace_id: &common::prov::NamespaceId,
         attribution: &Attribution,
     ) -> Result<(), StoreError> {
@@ -813,28 +1114,30 @@ impl Store {
 
         use schema::attribution::dsl as attr;
         let no_role = common::prov::Role("".to_string());
-        diesel::insert_into(schema::attribution::table)
-            .values((
-                &attr::entity_id.eq(stored_entity.id),
-                &attr::agent_id.eq(stored_agent.id),
-                &attr::role.eq(attribution.role.as_ref().unwrap_or(&no_role)),
-            ))
-            .on_conflict_do_nothing()
-            .execute(connection)?;
+        execute_upsert(
+            connection,
+            diesel::insert_into(schema::attribution::table)
+                .values((
+                    &attr::entity_id.eq(stored_entity.id),
+                    &attr::agent_id.eq(stored_agent.id),
+                    &attr::role.eq(attribution.role.as_ref().unwrap_or(&no_role)),
+                ))
+                .on_conflict_do_nothing(),
+        )?;
 
         Ok(())
     }
 
     pub(crate) fn connection(
         &self,
-    ) -> Result<PooledConnection<ConnectionManager<PgConnection>>, StoreError> {
+    ) -> Result<PooledConnection<ConnectionManager<StoreConnection>>, StoreError> {
         Ok(self.pool.get()?)
     }
 
     #[instrument(skip(connection))]
     pub(crate) fn get_current_agent(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
     ) -> Result<query::Agent, StoreError> {
         use schema::agent::dsl;
         Ok(schema::agent::table
@@ -842,29 +1145,32 @@ impl Store {
             .first::<query::Agent>(connection)?)
     }
 
-    /// Get the last fully synchronized offset
-    #[instrument]
-    pub(crate) fn get_last_block_id(&self) -> Result<Option<BlockId>, StoreError> {
-        use schema::ledgersync::dsl;
-        self.connection()?.build_transaction().run(|connection| {
-            let block_id_and_tx = schema::ledgersync::table
-                .order_by(dsl::sync_time)
-                .select((dsl::bc_offset, dsl::tx_id))
-                .first::<(Option<String>, String)>(connection)
-                .map_err(StoreError::from)?;
+    /// All namespaces known to the store
+    #[instrument(skip(connection))]
+    pub(crate) fn namespaces(
+        &self,
+        connection: &mut StoreConnection,
+    ) -> Result<Vec<NamespaceId>, StoreError> {
+        use self::schema::namespace::dsl;
 
-            if let Some(block_id) = block_id_and_tx.0 {
-                Ok(Some(BlockId::try_from(block_id)?))
-            } else {
-                Ok(None)
-            }
-        })
+        dsl::namespace
+            .order(dsl::external_id)
+            .select((dsl::external_id, dsl::uuid))
+            .load::<query::Namespace>(connection)?
+            .into_iter()
+            .map(|ns| {
+                Ok(NamespaceId::from_external_id(
+                    ns.external_id,
+                    Uuid::from_str(&ns.uuid)?,
+                ))
+            })
+            .collect()
     }
 
     #[instrument(skip(connection))]
     pub(crate) fn namespace_by_external_id(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         namespace: &ExternalId,
     ) -> Result<(NamespaceId, i32), StoreError> {
         use self::schema::namespace::dsl;
@@ -885,7 +1191,7 @@ impl Store {
     #[instrument(skip(connection))]
     pub(crate) fn identity_by(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
         namespaceid: &NamespaceId,
         identity: &IdentityId,
     ) -> Result<query::Identity, StoreError> {
@@ -904,7 +1210,7 @@ impl Store {
     }
 
     #[instrument]
-    pub(crate) fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Result<Self, StoreError> {
+    pub(crate) fn new(pool: Pool<ConnectionManager<StoreConnection>>) -> Result<Self, StoreError> {
         Ok(Store { pool })
     }
 
@@ -913,7 +1219,7 @@ impl Store {
         agent: query::Agent,
         namespaceid: &NamespaceId,
         model: &mut ProvModel,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
     ) -> Result<(), StoreError> {
         debug!(?agent, "Map agent to prov");
 
@@ -992,7 +1298,7 @@ impl Store {
         activity: query::Activity,
         namespaceid: &NamespaceId,
         model: &mut ProvModel,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
     ) -> Result<(), StoreError> {
         debug!(?activity, "Map activity to prov");
 
@@ -1095,7 +1401,7 @@ impl Store {
         entity: query::Entity,
         namespace_id: &NamespaceId,
         model: &mut ProvModel,
-        connection: &mut PgConnection,
+        connection: &mut StoreConnection,
     ) -> Result<(), StoreError> {
         debug!(?entity, "Map entity to prov");
 
@@ -1195,239 +1501,770 @@ impl Store {
         Ok(())
     }
 
+    /// The request hash, subject, transaction and stage of a command previously dispatched by
+    /// `identity` with `idempotency_key`, if any
     #[instrument(skip(connection))]
-    pub(crate) fn prov_model_for_namespace(
+    pub(crate) fn idempotent_submission(
         &self,
-        connection: &mut PgConnection,
-        namespace: &NamespaceId,
-    ) -> Result<ProvModel, StoreError> {
-        let mut model = ProvModel::default();
-        let (namespaceid, nsid) =
-            self.namespace_by_external_id(connection, namespace.external_id_part())?;
+        connection: &mut StoreConnection,
+        idempotency_key: &str,
+        identity: &str,
+    ) -> Result<Option<IdempotentSubmission>, StoreError> {
+        use schema::idempotency::dsl;
 
-        let agents = schema::agent::table
-            .filter(schema::agent::namespace_id.eq(&nsid))
-            .load::<query::Agent>(connection)?;
+        let prior = dsl::idempotency
+            .filter(
+                dsl::idempotency_key
+                    .eq(idempotency_key)
+                    .and(dsl::identity.eq(identity)),
+            )
+            .select((dsl::request_hash, dsl::subject, dsl::tx_id))
+            .first::<(String, Option<String>, Option<String>)>(connection)
+            .optional()?;
 
-        for agent in agents {
-            self.prov_model_for_agent(agent, &namespaceid, &mut model, connection)?;
-        }
+        let (request_hash, subject, tx_id) = match prior {
+            Some(prior) => prior,
+            None => return Ok(None),
+        };
 
-        let activities = schema::activity::table
-            .filter(schema::activity::namespace_id.eq(nsid))
-            .load::<query::Activity>(connection)?;
+        let tx_id = tx_id.map(|tx_id| ChronicleTransactionId::from(tx_id.as_str()));
+        let stage = match &tx_id {
+            Some(tx_id) => self
+                .transaction_receipt(connection, tx_id)?
+                .map(|receipt| receipt.stage),
+            None => None,
+        };
 
-        for activity in activities {
-            self.prov_model_for_activity(activity, &namespaceid, &mut model, connection)?;
-        }
+        Ok(Some((
+            request_hash,
+            subject
+                .map(|subject| ChronicleIri::from_str(&subject))
+                .transpose()?,
+            tx_id,
+            stage,
+        )))
+    }
 
-        let entities = schema::entity::table
-            .filter(schema::entity::namespace_id.eq(nsid))
-            .load::<query::Entity>(connection)?;
+    /// Reserve `idempotency_key` for a command with `request_hash` before it is dispatched, so
+    /// that a retry cannot dispatch it again however dispatching it ends. Returns what was
+    /// recorded for the key instead if it is already in use.
+    #[instrument(skip(connection))]
+    pub(crate) fn reserve_idempotency_key(
+        &self,
+        connection: &mut StoreConnection,
+        idempotency_key: &str,
+        identity: &str,
+        request_hash: &str,
+    ) -> Result<Option<IdempotentSubmission>, StoreError> {
+        use schema::idempotency::dsl;
+
+        connection.transaction(|connection| {
+            if let Some(prior) =
+                self.idempotent_submission(connection, idempotency_key, identity)?
+            {
+                return Ok(Some(prior));
+            }
 
-        for entity in entities {
-            self.prov_model_for_entity(entity, &namespaceid, &mut model, connection)?;
-        }
+            let reserved = execute_upsert(
+                connection,
+                diesel::insert_into(dsl::idempotency)
+                    .values((
+                        dsl::idempotency_key.eq(idempotency_key),
+                        dsl::identity.eq(identity),
+                        dsl::request_hash.eq(request_hash),
+                        dsl::created_time.eq(Utc::now().naive_utc()),
+                    ))
+                    .on_conflict_do_nothing(),
+            )?;
+
+            // Reserved concurrently by another request with the key
+            if reserved == 0 {
+                return self.idempotent_submission(connection, idempotency_key, identity);
+            }
 
-        Ok(model)
+            Ok(None)
+        })
     }
 
-    /// Set the last fully synchronized offset
-    #[instrument]
-    pub(crate) fn set_last_block_id(
+    /// Record the outcome of dispatching a command with a reserved idempotency key, `tx_id` is
+    /// `None` if the command was not submitted, as it made no data changes or was queued
+    #[instrument(skip(connection))]
+    pub(crate) fn record_idempotent_submission(
         &self,
-        block_id: &BlockId,
-        tx_id: ChronicleTransactionId,
+        connection: &mut StoreConnection,
+        idempotency_key: &str,
+        identity: &str,
+        subject: &ChronicleIri,
+        tx_id: Option<&ChronicleTransactionId>,
     ) -> Result<(), StoreError> {
-        use schema::ledgersync as dsl;
+        use schema::idempotency::dsl;
 
-        Ok(self.connection()?.build_transaction().run(|connection| {
-            diesel::insert_into(dsl::table)
-                .values((
-                    dsl::bc_offset.eq(block_id.to_string()),
-                    dsl::tx_id.eq(&*tx_id.to_string()),
-                    (dsl::sync_time.eq(Utc::now().naive_utc())),
-                ))
-                .on_conflict(dsl::tx_id)
-                .do_update()
-                .set(dsl::sync_time.eq(Utc::now().naive_utc()))
-                .execute(connection)
-                .map(|_| ())
-        })?)
+        diesel::update(dsl::idempotency)
+            .filter(
+                dsl::idempotency_key
+                    .eq(idempotency_key)
+                    .and(dsl::identity.eq(identity)),
+            )
+            .set((
+                dsl::subject.eq(subject.to_string()),
+                dsl::tx_id.eq(tx_id.map(|tx_id| tx_id.to_string())),
+            ))
+            .execute(connection)?;
+
+        Ok(())
     }
 
+    /// Release an idempotency key reserved for a command that was not submitted, so that it can
+    /// be retried
     #[instrument(skip(connection))]
-    pub(crate) fn use_agent(
+    pub(crate) fn release_idempotency_key(
         &self,
-        connection: &mut PgConnection,
-        external_id: &ExternalId,
-        namespace: &ExternalId,
+        connection: &mut StoreConnection,
+        idempotency_key: &str,
+        identity: &str,
     ) -> Result<(), StoreError> {
-        let (_, nsid) = self.namespace_by_external_id(connection, namespace)?;
-        use schema::agent::dsl;
+        use schema::idempotency::dsl;
 
-        diesel::update(schema::agent::table.filter(dsl::current.ne(0)))
-            .set(dsl::current.eq(0))
+        diesel::delete(dsl::idempotency)
+            .filter(
+                dsl::idempotency_key
+                    .eq(idempotency_key)
+                    .and(dsl::identity.eq(identity))
+                    .and(dsl::subject.is_null()),
+            )
             .execute(connection)?;
 
-        diesel::update(
-            schema::agent::table.filter(
-                dsl::external_id
-                    .eq(external_id)
-                    .and(dsl::namespace_id.eq(nsid)),
-            ),
-        )
-        .set(dsl::current.eq(1))
-        .execute(connection)?;
-
         Ok(())
     }
 
-    #[instrument(level = "debug", skip(connection))]
-    pub fn prov_model_for_agent_id(
+    /// The recorded progress of a transaction, if it has been submitted by this Chronicle or
+    /// seen on the ledger
+    #[instrument(skip(connection))]
+    pub(crate) fn transaction_receipt(
         &self,
-        connection: &mut PgConnection,
-        id: &AgentId,
-        ns: &ExternalId,
-    ) -> Result<ProvModel, StoreError> {
-        let agent = schema::agent::table
-            .inner_join(schema::namespace::dsl::namespace)
-            .filter(schema::agent::external_id.eq(id.external_id_part()))
-            .filter(schema::namespace::external_id.eq(ns))
-            .select(query::Agent::as_select())
-            .first(connection)?;
-
-        let namespace = self.namespace_by_external_id(connection, ns)?.0;
-
-        let mut model = ProvModel::default();
-        self.prov_model_for_agent(agent, &namespace, &mut model, connection)?;
-        Ok(model)
+        connection: &mut StoreConnection,
+        tx_id: &ChronicleTransactionId,
+    ) -> Result<Option<TransactionReceipt>, StoreError> {
+        use schema::txreceipt::dsl;
+
+        dsl::txreceipt
+            .filter(dsl::tx_id.eq(tx_id.to_string()))
+            .select(query::TxReceipt::as_select())
+            .first::<query::TxReceipt>(connection)
+            .optional()?
+            .map(TransactionReceipt::try_from)
+            .transpose()
     }
 
-    #[instrument(level = "debug", skip(connection))]
-    pub fn apply_prov_model_for_agent_id(
+    /// The committed transactions whose effects include any of the agents, activities or
+    /// entities in `model`, oldest first
+    #[instrument(skip(connection, model))]
+    pub(crate) fn contributing_transactions(
         &self,
-        connection: &mut PgConnection,
-        mut model: ProvModel,
-        id: &AgentId,
-        ns: &ExternalId,
-    ) -> Result<ProvModel, StoreError> {
-        if let Some(agent) = schema::agent::table
-            .inner_join(schema::namespace::dsl::namespace)
-            .filter(schema::agent::external_id.eq(id.external_id_part()))
-            .filter(schema::namespace::external_id.eq(ns))
-            .select(query::Agent::as_select())
-            .first(connection)
-            .optional()?
-        {
-            let namespace = self.namespace_by_external_id(connection, ns)?.0;
-            self.prov_model_for_agent(agent, &namespace, &mut model, connection)?;
+        connection: &mut StoreConnection,
+        model: &ProvModel,
+    ) -> Result<Vec<TransactionReceipt>, StoreError> {
+        use schema::{txreceipt::dsl, txsubject};
+
+        let contributes = |delta: &ProvModel| {
+            delta.agents.keys().any(|id| model.agents.contains_key(id))
+                || delta
+                    .activities
+                    .keys()
+                    .any(|id| model.activities.contains_key(id))
+                || delta
+                    .entities
+                    .keys()
+                    .any(|id| model.entities.contains_key(id))
+        };
+
+        // Bound the parameters of each query, as SQLite limits how many a statement may have
+        let subjects = subjects(model).into_iter().collect::<Vec<_>>();
+        let mut receipts = BTreeMap::new();
+        for subjects in subjects.chunks(SUBJECTS_PER_QUERY) {
+            for receipt in dsl::txreceipt
+                .filter(dsl::stage.eq(TransactionStage::Committed.as_str()))
+                .filter(
+                    dsl::tx_id.eq_any(
+                        txsubject::table
+                            .filter(txsubject::subject.eq_any(subjects))
+                            .select(txsubject::tx_id),
+                    ),
+                )
+                .select(query::TxReceipt::as_select())
+                .load::<query::TxReceipt>(connection)?
+            {
+                receipts.insert(receipt.tx_id.clone(), receipt);
+            }
         }
-        Ok(model)
+
+        let mut receipts = receipts
+            .into_values()
+            .filter_map(|receipt| {
+                let tx_id = receipt.tx_id.clone();
+                TransactionReceipt::try_from(receipt)
+                    .map_err(|e| warn!(%tx_id, %e, "Skipping an undecodable transaction receipt"))
+                    .ok()
+            })
+            .filter(|receipt| receipt.delta.as_ref().is_some_and(contributes))
+            .collect::<Vec<_>>();
+        receipts.sort_by_key(|receipt| receipt.updated_time);
+
+        Ok(receipts)
     }
 
-    #[instrument(level = "debug", skip(connection))]
-    pub fn prov_model_for_activity_id(
+    /// Record a transaction as submitted, unless the ledger has already reported its outcome
+    #[instrument(skip(identity))]
+    pub(crate) fn record_transaction_submitted(
         &self,
-        connection: &mut PgConnection,
-        id: &ActivityId,
-        ns: &ExternalId,
-    ) -> Result<ProvModel, StoreError> {
-        let activity = schema::activity::table
-            .inner_join(schema::namespace::dsl::namespace)
-            .filter(schema::activity::external_id.eq(id.external_id_part()))
-            .filter(schema::namespace::external_id.eq(ns))
-            .select(query::Activity::as_select())
-            .first(connection)?;
+        tx_id: &ChronicleTransactionId,
+        identity: &SignedIdentity,
+    ) -> Result<(), StoreError> {
+        let receipt = query::TxReceipt {
+            identity: Some(serde_json::to_string(identity)?),
+            ..query::TxReceipt::new(tx_id, TransactionStage::Submitted)
+        };
 
-        let namespace = self.namespace_by_external_id(connection, ns)?.0;
+        self.connection()?.transaction(|connection| {
+            execute_upsert(
+                connection,
+                diesel::insert_into(schema::txreceipt::table)
+                    .values(&receipt)
+                    .on_conflict_do_nothing(),
+            )
+            .map(|_| ())
+        })?;
 
-        let mut model = ProvModel::default();
-        self.prov_model_for_activity(activity, &namespace, &mut model, connection)?;
-        Ok(model)
+        Ok(())
     }
 
-    #[instrument(level = "debug", skip(connection))]
-    pub fn apply_prov_model_for_activity_id(
+    /// Record that a transaction could not be submitted to the ledger
+    #[instrument(skip(identity))]
+    pub(crate) fn record_transaction_failed(
         &self,
-        connection: &mut PgConnection,
-        mut model: ProvModel,
-        id: &ActivityId,
-        ns: &ExternalId,
-    ) -> Result<ProvModel, StoreError> {
-        if let Some(activity) = schema::activity::table
-            .inner_join(schema::namespace::dsl::namespace)
-            .filter(schema::activity::external_id.eq(id.external_id_part()))
-            .filter(schema::namespace::external_id.eq(ns))
-            .select(query::Activity::as_select())
-            .first(connection)
-            .optional()?
-        {
-            let namespace = self.namespace_by_external_id(connection, ns)?.0;
-            self.prov_model_for_activity(activity, &namespace, &mut model, connection)?;
-        }
-        Ok(model)
+        tx_id: &ChronicleTransactionId,
+        identity: &SignedIdentity,
+        error: &str,
+    ) -> Result<(), StoreError> {
+        self.upsert_transaction_receipt(query::TxReceipt {
+            identity: Some(serde_json::to_string(identity)?),
+            error: Some(error.to_owned()),
+            ..query::TxReceipt::new(tx_id, TransactionStage::Failed)
+        })
     }
 
-    #[instrument(level = "debug", skip(connection))]
-    pub fn prov_model_for_entity_id(
+    /// Record a transaction committed to the ledger along with its effects
+    #[instrument(skip(identity, delta))]
+    pub(crate) fn record_transaction_committed(
         &self,
-        connection: &mut PgConnection,
-        id: &EntityId,
-        ns: &ExternalId,
-    ) -> Result<ProvModel, StoreError> {
-        let entity = schema::entity::table
-            .inner_join(schema::namespace::dsl::namespace)
-            .filter(schema::entity::external_id.eq(id.external_id_part()))
-            .filter(schema::namespace::external_id.eq(ns))
-            .select(query::Entity::as_select())
-            .first(connection)?;
+        tx_id: &ChronicleTransactionId,
+        block_id: &BlockId,
+        identity: &SignedIdentity,
+        delta: &ProvModel,
+    ) -> Result<(), StoreError> {
+        // The ledger is read in order, so the latest key seen is the one current at this block
+        let key_version = {
+            let mut connection = self.connection()?;
+            self.current_key_version(&mut connection)?
+        };
 
-        let namespace = self.namespace_by_external_id(connection, ns)?.0;
+        let receipt = query::TxReceipt {
+            block_id: Some(block_id.to_string()),
+            identity: Some(serde_json::to_string(identity)?),
+            delta: Some(serde_json::to_string(delta)?),
+            key_version,
+            ..query::TxReceipt::new(tx_id, TransactionStage::Committed)
+        };
 
-        let mut model = ProvModel::default();
-        self.prov_model_for_entity(entity, &namespace, &mut model, connection)?;
-        Ok(model)
+        self.connection()?.transaction(|connection| {
+            write_transaction_receipt(connection, &receipt)?;
+            index_transaction_subjects(connection, &receipt.tx_id, delta)
+        })
     }
 
-    #[instrument(level = "debug", skip(connection))]
-    pub fn apply_prov_model_for_entity_id(
+    fn current_key_version(
         &self,
-        connection: &mut PgConnection,
-        mut model: ProvModel,
-        id: &EntityId,
-        ns: &ExternalId,
-    ) -> Result<ProvModel, StoreError> {
-        if let Some(entity) = schema::entity::table
-            .inner_join(schema::namespace::dsl::namespace)
-            .filter(schema::entity::external_id.eq(id.external_id_part()))
-            .filter(schema::namespace::external_id.eq(ns))
-            .select(query::Entity::as_select())
-            .first(connection)
-            .optional()?
-        {
-            let namespace = self.namespace_by_external_id(connection, ns)?.0;
-            self.prov_model_for_entity(entity, &namespace, &mut model, connection)?;
-        }
-        Ok(model)
+        connection: &mut StoreConnection,
+    ) -> Result<Option<i32>, StoreError> {
+        use schema::chronicle_key::dsl;
+
+        Ok(dsl::chronicle_key
+            .select(diesel::dsl::max(dsl::version))
+            .first::<Option<i32>>(connection)?)
     }
 
-    pub(crate) fn prov_model_for_usage(
+    /// Record the Chronicle keys registered on the ledger as of `block_id`
+    #[instrument(skip(keys))]
+    pub(crate) fn record_chronicle_keys(
         &self,
-        connection: &mut PgConnection,
-        mut model: ProvModel,
-        id: &EntityId,
-        activity_id: &ActivityId,
-        ns: &ExternalId,
-    ) -> Result<ProvModel, StoreError> {
-        if let Some(entity) = schema::entity::table
-            .inner_join(schema::namespace::dsl::namespace)
-            .filter(schema::entity::external_id.eq(id.external_id_part()))
-            .filter(schema::namespace::external_id.eq(ns))
-            .select(query::Entity::as_select())
-            .first(connection)
-            .optional()?
-        {
+        keys: &ChronicleKeys,
+        block_id: &BlockId,
+    ) -> Result<(), StoreError> {
+        let block_id = block_id.to_string();
+
+        self.connection()?.transaction(|connection| {
+            for registration in keys.expired.iter().chain(std::iter::once(&keys.current)) {
+                execute_upsert(
+                    connection,
+                    diesel::insert_into(schema::chronicle_key::table)
+                        .values(query::NewChronicleKey {
+                            version: registration.version as i32,
+                            public_key: &registration.key,
+                            block_id: &block_id,
+                        })
+                        .on_conflict_do_nothing(),
+                )?;
+            }
+            Ok::<_, StoreError>(())
+        })
+    }
+
+    /// The PEM encoded Chronicle key registered as `version`
+    #[instrument(skip(connection))]
+    pub(crate) fn chronicle_key(
+        &self,
+        connection: &mut StoreConnection,
+        version: i32,
+    ) -> Result<Option<String>, StoreError> {
+        use schema::chronicle_key::dsl;
+
+        Ok(dsl::chronicle_key
+            .filter(dsl::version.eq(version))
+            .select(dsl::public_key)
+            .first::<String>(connection)
+            .optional()?)
+    }
+
+    /// Record a transaction rejected by the ledger as contradicting recorded provenance
+    #[instrument(skip(identity))]
+    pub(crate) fn record_transaction_contradicted(
+        &self,
+        tx_id: &ChronicleTransactionId,
+        block_id: &BlockId,
+        identity: &SignedIdentity,
+        contradiction: &Contradiction,
+    ) -> Result<(), StoreError> {
+        self.upsert_transaction_receipt(query::TxReceipt {
+            block_id: Some(block_id.to_string()),
+            identity: Some(serde_json::to_string(identity)?),
+            contradiction: Some(serde_json::to_string(contradiction)?),
+            error: Some(contradiction.to_string()),
+            ..query::TxReceipt::new(tx_id, TransactionStage::Contradicted)
+        })
+    }
+
+    fn upsert_transaction_receipt(&self, receipt: query::TxReceipt) -> Result<(), StoreError> {
+        self.connection()?
+            .transaction(|connection| write_transaction_receipt(connection, &receipt))
+    }
+
+    /// Queue a transaction to be submitted once the ledger can be reached, returning its id in
+    /// the outbox
+    #[instrument(skip(connection, tx))]
+    pub(crate) fn queue_transaction(
+        &self,
+        connection: &mut StoreConnection,
+        subject: &ChronicleIri,
+        tx: &ChronicleTransaction,
+    ) -> Result<i32, StoreError> {
+        use schema::outbox::dsl;
+
+        let now = Utc::now().naive_utc();
+
+        let id = diesel::insert_into(dsl::outbox)
+            .values((
+                dsl::subject.eq(subject.to_string()),
+                dsl::transaction.eq(serde_json::to_string(tx)?),
+                dsl::status.eq(OutboxStatus::Pending.as_str()),
+                dsl::queued_time.eq(now),
+                dsl::next_attempt_time.eq(now),
+            ))
+            .returning(dsl::id)
+            .get_result(connection)?;
+
+        // Index the entry by everything its operations affect, for `outbox_status`
+        for (namespace, subject) in namespaced_subjects(&ProvModel::from_tx(&tx.tx)?) {
+            execute_upsert(
+                connection,
+                diesel::insert_into(schema::outboxsubject::table)
+                    .values(query::NewOutboxSubject {
+                        outbox_id: id,
+                        namespace: &namespace,
+                        subject: &subject,
+                    })
+                    .on_conflict_do_nothing(),
+            )?;
+        }
+
+        Ok(id)
+    }
+
+    /// Whether any queued transaction, pending or failed, has yet to be submitted, in which case
+    /// a new transaction must be queued behind it
+    #[instrument(skip(connection))]
+    pub(crate) fn outbox_has_unsubmitted(
+        &self,
+        connection: &mut StoreConnection,
+    ) -> Result<bool, StoreError> {
+        use schema::outbox::dsl;
+
+        Ok(diesel::select(diesel::dsl::exists(
+            dsl::outbox.filter(dsl::status.ne(OutboxStatus::Submitted.as_str())),
+        ))
+        .get_result(connection)?)
+    }
+
+    /// The longest queued transaction that has yet to be submitted, which may have failed
+    #[instrument]
+    pub(crate) fn next_queued_transaction(&self) -> Result<Option<QueuedTransaction>, StoreError> {
+        use schema::outbox::dsl;
+
+        dsl::outbox
+            .filter(dsl::status.ne(OutboxStatus::Submitted.as_str()))
+            .order(dsl::id.asc())
+            .select((
+                dsl::id,
+                dsl::transaction,
+                dsl::status,
+                dsl::attempts,
+                dsl::next_attempt_time,
+            ))
+            .first::<(i32, String, String, i32, NaiveDateTime)>(&mut self.connection()?)
+            .optional()?
+            .map(|(id, tx, status, attempts, next_attempt_time)| {
+                Ok(QueuedTransaction {
+                    id,
+                    tx: serde_json::from_str(&tx)?,
+                    status: OutboxStatus::from_str(&status).map_err(StoreError::OutboxStatus)?,
+                    attempts,
+                    next_attempt_time,
+                })
+            })
+            .transpose()
+    }
+
+    /// Return a failed transaction to the outbox with a fresh set of attempts, returning `false`
+    /// if there is no failed transaction with `id`
+    #[instrument(skip(connection))]
+    pub(crate) fn retry_queued_transaction(
+        &self,
+        connection: &mut StoreConnection,
+        id: i32,
+    ) -> Result<bool, StoreError> {
+        use schema::outbox::dsl;
+
+        let updated = diesel::update(
+            dsl::outbox.filter(
+                dsl::id
+                    .eq(id)
+                    .and(dsl::status.eq(OutboxStatus::Failed.as_str())),
+            ),
+        )
+        .set((
+            dsl::status.eq(OutboxStatus::Pending.as_str()),
+            dsl::attempts.eq(0),
+            dsl::next_attempt_time.eq(Utc::now().naive_utc()),
+        ))
+        .execute(connection)?;
+
+        Ok(updated == 1)
+    }
+
+    /// Record the submission of a queued transaction
+    #[instrument]
+    pub(crate) fn record_queued_transaction_submitted(
+        &self,
+        id: i32,
+        tx_id: &ChronicleTransactionId,
+    ) -> Result<(), StoreError> {
+        use schema::outbox::dsl;
+
+        diesel::update(dsl::outbox.find(id))
+            .set((
+                dsl::status.eq(OutboxStatus::Submitted.as_str()),
+                dsl::attempts.eq(dsl::attempts + 1),
+                dsl::tx_id.eq(tx_id.to_string()),
+            ))
+            .execute(&mut self.connection()?)?;
+
+        Ok(())
+    }
+
+    /// Record a failed attempt to submit a queued transaction, to be retried at
+    /// `next_attempt_time` or, if `None`, abandoned
+    #[instrument]
+    pub(crate) fn record_queued_transaction_attempt(
+        &self,
+        id: i32,
+        error: &str,
+        next_attempt_time: Option<NaiveDateTime>,
+    ) -> Result<(), StoreError> {
+        use schema::outbox::dsl;
+
+        let status = match next_attempt_time {
+            Some(_) => OutboxStatus::Pending,
+            None => OutboxStatus::Failed,
+        };
+
+        diesel::update(dsl::outbox.find(id))
+            .set((
+                dsl::status.eq(status.as_str()),
+                dsl::attempts.eq(dsl::attempts + 1),
+                dsl::last_error.eq(error),
+                dsl::next_attempt_time
+                    .eq(next_attempt_time.unwrap_or_else(|| Utc::now().naive_utc())),
+            ))
+            .execute(&mut self.connection()?)?;
+
+        Ok(())
+    }
+
+    /// The contents of the outbox in the order queued, optionally only those with `status`, and
+    /// optionally only the page of them at an offset and of a length
+    #[instrument(skip(connection))]
+    pub(crate) fn outbox_items(
+        &self,
+        connection: &mut StoreConnection,
+        status: Option<OutboxStatus>,
+        page: Option<(i64, i64)>,
+    ) -> Result<Vec<OutboxItem>, StoreError> {
+        use schema::{outbox::dsl, txreceipt};
+
+        let mut query = outbox_with_receipts(status)
+            .order(dsl::id.asc())
+            .select((query::OutboxEntry::as_select(), txreceipt::stage.nullable()));
+        if let Some((offset, limit)) = page {
+            query = query.offset(offset).limit(limit);
+        }
+
+        query
+            .load::<(query::OutboxEntry, Option<String>)>(connection)?
+            .into_iter()
+            .map(|(entry, stage)| outbox_item(entry, stage))
+            .collect()
+    }
+
+    /// The number of transactions in the outbox, optionally only those with `status`
+    #[instrument(skip(connection))]
+    pub(crate) fn outbox_len(
+        &self,
+        connection: &mut StoreConnection,
+        status: Option<OutboxStatus>,
+    ) -> Result<i64, StoreError> {
+        Ok(outbox_with_receipts(status)
+            .count()
+            .get_result(connection)?)
+    }
+
+    /// Remove transactions from the outbox that were committed before `committed_before`,
+    /// returning how many were removed. Those that were not committed are kept, as they may yet
+    /// need attention.
+    #[instrument(skip(connection))]
+    pub(crate) fn prune_committed_outbox(
+        &self,
+        connection: &mut StoreConnection,
+        committed_before: NaiveDateTime,
+    ) -> Result<usize, StoreError> {
+        use schema::{outbox::dsl, outboxsubject, txreceipt};
+
+        connection.transaction(|connection| {
+            let committed = outbox_with_receipts(Some(OutboxStatus::Committed))
+                .filter(txreceipt::updated_time.nullable().lt(committed_before))
+                .select(dsl::id)
+                .load::<i32>(connection)?;
+
+            diesel::delete(
+                outboxsubject::table.filter(outboxsubject::outbox_id.eq_any(&committed)),
+            )
+            .execute(connection)?;
+
+            Ok(
+                diesel::delete(dsl::outbox.filter(dsl::id.eq_any(&committed)))
+                    .execute(connection)?,
+            )
+        })
+    }
+
+    /// Whether transactions queued in the outbox with operations on `subject` in the namespace
+    /// with `namespace_id` have yet to be committed. This is the status of the first of them
+    /// that has not been, unless any of them has been contradicted, in which case their effects
+    /// on `subject` are not those of the ledger.
+    #[instrument(skip(connection))]
+    pub(crate) fn outbox_status(
+        &self,
+        connection: &mut StoreConnection,
+        namespace_id: i32,
+        subject: &ChronicleIri,
+    ) -> Result<Option<OutboxStatus>, StoreError> {
+        use schema::{namespace, outbox::dsl, outboxsubject, txreceipt};
+
+        let namespace = namespace::table
+            .find(namespace_id)
+            .select(namespace::external_id)
+            .first::<String>(connection)?;
+
+        let mut uncommitted = vec![];
+        for (status, stage) in dsl::outbox
+            .inner_join(outboxsubject::table.on(outboxsubject::outbox_id.eq(dsl::id)))
+            .left_join(txreceipt::table.on(dsl::tx_id.eq(txreceipt::tx_id.nullable())))
+            .filter(outboxsubject::namespace.eq(&namespace))
+            .filter(outboxsubject::subject.eq(subject.to_string()))
+            .order(dsl::id.asc())
+            .select((dsl::status, txreceipt::stage.nullable()))
+            .load::<(String, Option<String>)>(connection)?
+        {
+            let status = outbox_status(&status, stage.as_deref())?;
+            if status != OutboxStatus::Committed {
+                uncommitted.push(status);
+            }
+        }
+
+        if uncommitted.contains(&OutboxStatus::Contradicted) {
+            return Ok(Some(OutboxStatus::Contradicted));
+        }
+
+        Ok(uncommitted.first().copied())
+    }
+
+    /// The namespaces and IRIs of the subjects of transactions in the outbox that are yet to be
+    /// committed or contradicted, whose effects on the store the ledger may not yet have
+    #[instrument(skip(connection))]
+    pub(crate) fn outbox_pending_subjects(
+        &self,
+        connection: &mut StoreConnection,
+    ) -> Result<BTreeSet<(String, String)>, StoreError> {
+        use schema::{outbox::dsl, outboxsubject};
+
+        let mut pending = vec![];
+        for status in [
+            OutboxStatus::Pending,
+            OutboxStatus::Failed,
+            OutboxStatus::Submitted,
+        ] {
+            pending.extend(
+                outbox_with_receipts(Some(status))
+                    .select(dsl::id)
+                    .load::<i32>(connection)?,
+            );
+        }
+
+        Ok(outboxsubject::table
+            .filter(outboxsubject::outbox_id.eq_any(&pending))
+            .select((outboxsubject::namespace, outboxsubject::subject))
+            .load::<(String, String)>(connection)?
+            .into_iter()
+            .collect())
+    }
+
+    #[instrument(skip(connection))]
+    pub(crate) fn use_agent(
+        &self,
+        connection: &mut StoreConnection,
+        external_id: &ExternalId,
+        namespace: &ExternalId,
+    ) -> Result<(), StoreError> {
+        let (_, nsid) = self.namespace_by_external_id(connection, namespace)?;
+        use schema::agent::dsl;
+
+        diesel::update(schema::agent::table.filter(dsl::current.ne(0)))
+            .set(dsl::current.eq(0))
+            .execute(connection)?;
+
+        diesel::update(
+            schema::agent::table.filter(
+                dsl::external_id
+                    .eq(external_id)
+                    .and(dsl::namespace_id.eq(nsid)),
+            ),
+        )
+        .set(dsl::current.eq(1))
+        .execute(connection)?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip(connection))]
+    pub fn apply_prov_model_for_agent_id(
+        &self,
+        connection: &mut StoreConnection,
+        mut model: ProvModel,
+        id: &AgentId,
+        ns: &ExternalId,
+    ) -> Result<ProvModel, StoreError> {
+        if let Some(agent) = schema::agent::table
+            .inner_join(schema::namespace::dsl::namespace)
+            .filter(schema::agent::external_id.eq(id.external_id_part()))
+            .filter(schema::namespace::external_id.eq(ns))
+            .select(query::Agent::as_select())
+            .first(connection)
+            .optional()?
+        {
+            let namespace = self.namespace_by_external_id(connection, ns)?.0;
+            self.prov_model_for_agent(agent, &namespace, &mut model, connection)?;
+        }
+        Ok(model)
+    }
+
+    #[instrument(level = "debug", skip(connection))]
+    pub fn apply_prov_model_for_activity_id(
+        &self,
+        connection: &mut StoreConnection,
+        mut model: ProvModel,
+        id: &ActivityId,
+        ns: &ExternalId,
+    ) -> Result<ProvModel, StoreError> {
+        if let Some(activity) = schema::activity::table
+            .inner_join(schema::namespace::dsl::namespace)
+            .filter(schema::activity::external_id.eq(id.external_id_part()))
+            .filter(schema::namespace::external_id.eq(ns))
+            .select(query::Activity::as_select())
+            .first(connection)
+            .optional()?
+        {
+            let namespace = self.namespace_by_external_id(connection, ns)?.0;
+            self.prov_model_for_activity(activity, &namespace, &mut model, connection)?;
+        }
+        Ok(model)
+    }
+
+    #[instrument(level = "debug", skip(connection))]
+    pub fn apply_prov_model_for_entity_id(
+        &self,
+        connection: &mut StoreConnection,
+        mut model: ProvModel,
+        id: &EntityId,
+        ns: &ExternalId,
+    ) -> Result<ProvModel, StoreError> {
+        if let Some(entity) = schema::entity::table
+            .inner_join(schema::namespace::dsl::namespace)
+            .filter(schema::entity::external_id.eq(id.external_id_part()))
+            .filter(schema::namespace::external_id.eq(ns))
+            .select(query::Entity::as_select())
+            .first(connection)
+            .optional()?
+        {
+            let namespace = self.namespace_by_external_id(connection, ns)?.0;
+            self.prov_model_for_entity(entity, &namespace, &mut model, connection)?;
+        }
+        Ok(model)
+    }
+
+    pub(crate) fn prov_model_for_usage(
+        &self,
+        connection: &mut StoreConnection,
+        mut model: ProvModel,
+        id: &EntityId,
+        activity_id: &ActivityId,
+        ns: &ExternalId,
+    ) -> Result<ProvModel, StoreError> {
+        if let Some(entity) = schema::entity::table
+            .inner_join(schema::namespace::dsl::namespace)
+            .filter(schema::entity::external_id.eq(id.external_id_part()))
+            .filter(schema::namespace::external_id.eq(ns))
+            .select(query::Entity::as_select())
+            .first(connection)
+            .optional()?
+        {
             if let Some(activity) = schema::activity::table
                 .inner_join(schema::namespace::dsl::namespace)
                 .filter(schema::activity::external_id.eq(id.external_id_part()))
@@ -1456,4 +2293,271 @@ impl Store {
         }
         Ok(model)
     }
+
+    #[instrument(skip(self, decisions), fields(decisions = decisions.len()))]
+    pub(crate) fn record_decisions(
+        &self,
+        decisions: &[DecisionLogEntry],
+    ) -> Result<(), StoreError> {
+        use schema::opa_decision::dsl;
+
+        self.connection()?.transaction(|connection| {
+            for decision in decisions {
+                let (policy, revision) = decision.policy().unwrap_or_default();
+                execute_upsert(
+                    connection,
+                    diesel::insert_into(dsl::opa_decision)
+                        .values((
+                            dsl::decision_id.eq(&decision.decision_id),
+                            dsl::decision_time.eq(decision.timestamp.naive_utc()),
+                            dsl::identity.eq(&decision.requested_by),
+                            dsl::policy.eq(policy),
+                            dsl::revision.eq(revision),
+                            dsl::path.eq(&decision.path),
+                            dsl::decision
+                                .eq(decision.decision().map(|decision| decision.to_string())),
+                            dsl::error.eq(decision.error.as_deref()),
+                            dsl::input.eq(serde_json::to_string(&decision.input)?),
+                            dsl::eval_ns.eq(decision.eval_ns() as i64),
+                        ))
+                        // A retried batch may have been written before
+                        .on_conflict_do_nothing(),
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    pub(crate) fn apply_model(
+        &self,
+        connection: &mut StoreConnection,
+        model: &ProvModel,
+    ) -> Result<(), StoreError> {
+        for (_, ns) in model.namespaces.iter() {
+            self.apply_namespace(connection, ns)?
+        }
+        for (_, agent) in model.agents.iter() {
+            self.apply_agent(connection, agent, &model.namespaces)?
+        }
+        for (_, activity) in model.activities.iter() {
+            self.apply_activity(connection, activity, &model.namespaces)?
+        }
+        for (_, entity) in model.entities.iter() {
+            self.apply_entity(connection, entity, &model.namespaces)?
+        }
+        for (_, identity) in model.identities.iter() {
+            self.apply_identity(connection, identity, &model.namespaces)?
+        }
+
+        for ((namespaceid, agent_id), (_, identity_id)) in model.has_identity.iter() {
+            self.apply_has_identity(connection, model, namespaceid, agent_id, identity_id)?;
+        }
+
+        for ((namespaceid, agent_id), identity_id) in model.had_identity.iter() {
+            for (_, identity_id) in identity_id {
+                self.apply_had_identity(connection, model, namespaceid, agent_id, identity_id)?;
+            }
+        }
+
+        for ((namespaceid, _), association) in model.association.iter() {
+            for association in association.iter() {
+                self.apply_was_associated_with(connection, namespaceid, association)?;
+            }
+        }
+
+        for ((namespaceid, _), usage) in model.usage.iter() {
+            for usage in usage.iter() {
+                self.apply_used(connection, namespaceid, usage)?;
+            }
+        }
+
+        for ((namespaceid, activity_id), was_informed_by) in model.was_informed_by.iter() {
+            for (_, informing_activity_id) in was_informed_by.iter() {
+                self.apply_was_informed_by(
+                    connection,
+                    namespaceid,
+                    activity_id,
+                    informing_activity_id,
+                )?;
+            }
+        }
+
+        for ((namespaceid, _), generation) in model.generation.iter() {
+            for generation in generation.iter() {
+                self.apply_was_generated_by(connection, namespaceid, generation)?;
+            }
+        }
+
+        for ((namespaceid, _), derivation) in model.derivation.iter() {
+            for derivation in derivation.iter() {
+                self.apply_derivation(connection, namespaceid, derivation)?;
+            }
+        }
+
+        for ((namespaceid, _), delegation) in model.delegation.iter() {
+            for delegation in delegation.iter() {
+                self.apply_delegation(connection, namespaceid, delegation)?;
+            }
+        }
+
+        for ((namespace_id, _), attribution) in model.attribution.iter() {
+            for attribution in attribution.iter() {
+                self.apply_was_attributed_to(connection, namespace_id, attribution)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn apply_prov(&self, prov: &ProvModel) -> Result<(), StoreError> {
+        self.connection()?
+            .transaction(|connection| self.apply_model(connection, prov))?;
+
+        Ok(())
+    }
+
+    /// Get the last fully synchronized offset
+    #[instrument]
+    pub(crate) fn get_last_block_id(&self) -> Result<Option<BlockId>, StoreError> {
+        use schema::ledgersync::dsl;
+        self.connection()?.transaction(|connection| {
+            let block_id_and_tx = schema::ledgersync::table
+                .order_by(dsl::sync_time)
+                .select((dsl::bc_offset, dsl::tx_id))
+                .first::<(Option<String>, String)>(connection)
+                .map_err(StoreError::from)?;
+
+            if let Some(block_id) = block_id_and_tx.0 {
+                Ok(Some(BlockId::try_from(block_id)?))
+            } else {
+                Ok(None)
+            }
+        })
+    }
+
+    #[instrument(skip(connection))]
+    pub(crate) fn prov_model_for_namespace(
+        &self,
+        connection: &mut StoreConnection,
+        namespace: &NamespaceId,
+    ) -> Result<ProvModel, StoreError> {
+        let mut model = ProvModel::default();
+        let (namespaceid, nsid) =
+            self.namespace_by_external_id(connection, namespace.external_id_part())?;
+
+        let agents = schema::agent::table
+            .filter(schema::agent::namespace_id.eq(&nsid))
+            .load::<query::Agent>(connection)?;
+
+        for agent in agents {
+            self.prov_model_for_agent(agent, &namespaceid, &mut model, connection)?;
+        }
+
+        let activities = schema::activity::table
+            .filter(schema::activity::namespace_id.eq(nsid))
+            .load::<query::Activity>(connection)?;
+
+        for activity in activities {
+            self.prov_model_for_activity(activity, &namespaceid, &mut model, connection)?;
+        }
+
+        let entities = schema::entity::table
+            .filter(schema::entity::namespace_id.eq(nsid))
+            .load::<query::Entity>(connection)?;
+
+        for entity in entities {
+            self.prov_model_for_entity(entity, &namespaceid, &mut model, connection)?;
+        }
+
+        Ok(model)
+    }
+
+    /// Set the last fully synchronized offset
+    #[instrument]
+    pub(crate) fn set_last_block_id(
+        &self,
+        block_id: &BlockId,
+        tx_id: ChronicleTransactionId,
+    ) -> Result<(), StoreError> {
+        use schema::ledgersync as dsl;
+
+        Ok(self.connection()?.transaction(|connection| {
+            execute_upsert(
+                connection,
+                diesel::insert_into(dsl::table)
+                    .values((
+                        dsl::bc_offset.eq(block_id.to_string()),
+                        dsl::tx_id.eq(&*tx_id.to_string()),
+                        (dsl::sync_time.eq(Utc::now().naive_utc())),
+                    ))
+                    .on_conflict(dsl::tx_id)
+                    .do_update()
+                    .set(dsl::sync_time.eq(Utc::now().naive_utc())),
+            )
+            .map(|_| ())
+        })?)
+    }
+
+    #[instrument(level = "debug", skip(connection))]
+    pub fn prov_model_for_agent_id(
+        &self,
+        connection: &mut StoreConnection,
+        id: &AgentId,
+        ns: &ExternalId,
+    ) -> Result<ProvModel, StoreError> {
+        let agent = schema::agent::table
+            .inner_join(schema::namespace::dsl::namespace)
+            .filter(schema::agent::external_id.eq(id.external_id_part()))
+            .filter(schema::namespace::external_id.eq(ns))
+            .select(query::Agent::as_select())
+            .first(connection)?;
+
+        let namespace = self.namespace_by_external_id(connection, ns)?.0;
+
+        let mut model = ProvModel::default();
+        self.prov_model_for_agent(agent, &namespace, &mut model, connection)?;
+        Ok(model)
+    }
+
+    #[instrument(level = "debug", skip(connection))]
+    pub fn prov_model_for_activity_id(
+        &self,
+        connection: &mut StoreConnection,
+        id: &ActivityId,
+        ns: &ExternalId,
+    ) -> Result<ProvModel, StoreError> {
+        let activity = schema::activity::table
+            .inner_join(schema::namespace::dsl::namespace)
+            .filter(schema::activity::external_id.eq(id.external_id_part()))
+            .filter(schema::namespace::external_id.eq(ns))
+            .select(query::Activity::as_select())
+            .first(connection)?;
+
+        let namespace = self.namespace_by_external_id(connection, ns)?.0;
+
+        let mut model = ProvModel::default();
+        self.prov_model_for_activity(activity, &namespace, &mut model, connection)?;
+        Ok(model)
+    }
+
+    #[instrument(level = "debug", skip(connection))]
+    pub fn prov_model_for_entity_id(
+        &self,
+        connection: &mut StoreConnection,
+        id: &EntityId,
+        ns: &ExternalId,
+    ) -> Result<ProvModel, StoreError> {
+        let entity = schema::entity::table
+            .inner_join(schema::namespace::dsl::namespace)
+            .filter(schema::entity::external_id.eq(id.external_id_part()))
+            .filter(schema::namespace::external_id.eq(ns))
+            .select(query::Entity::as_select())
+            .first(connection)?;
+
+        let namespace = self.namespace_by_external_id(connection, ns)?.0;
+
+        let mut model = ProvModel::default();
+        self.prov_model_for_entity(entity, &namespace, &mut model, connection)?;
+        Ok(model)
+    }
 }
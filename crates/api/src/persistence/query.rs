@@ -1,5 +1,6 @@
 use super::schema::*;
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
+use common::{commands::TransactionStage, prov::ChronicleTransactionId};
 use diesel::prelude::*;
 
 #[derive(Queryable)]
@@ -8,6 +9,7 @@ pub struct Namespace {
     pub uuid: String,
 }
 
+#[allow(dead_code)]
 #[derive(Queryable)]
 pub struct LedgerSync {
     pub bc_offset: String,
@@ -62,6 +64,7 @@ pub struct NewActivity<'a> {
     pub domaintype: Option<&'a str>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Queryable, Selectable)]
 #[diesel(table_name = agent)]
 pub struct Agent {
@@ -73,6 +76,7 @@ pub struct Agent {
     pub identity_id: Option<i32>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Queryable)]
 pub struct Identity {
     pub id: i32,
@@ -80,6 +84,7 @@ pub struct Identity {
     pub public_key: String,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Queryable, Selectable)]
 #[diesel(table_name = activity)]
 pub struct Activity {
@@ -91,6 +96,7 @@ pub struct Activity {
     pub ended: Option<NaiveDateTime>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Queryable, Selectable)]
 #[diesel(table_name = entity)]
 pub struct Entity {
@@ -108,3 +114,70 @@ pub struct NewAgent<'a> {
     pub current: i32,
     pub domaintype: Option<&'a str>,
 }
+
+#[derive(Insertable, Queryable, Selectable, AsChangeset)]
+#[diesel(table_name = txreceipt, treat_none_as_null = true)]
+pub struct TxReceipt {
+    pub tx_id: String,
+    pub stage: String,
+    pub block_id: Option<String>,
+    pub identity: Option<String>,
+    pub delta: Option<String>,
+    pub contradiction: Option<String>,
+    pub error: Option<String>,
+    pub updated_time: NaiveDateTime,
+    pub key_version: Option<i32>,
+}
+
+impl TxReceipt {
+    pub fn new(tx_id: &ChronicleTransactionId, stage: TransactionStage) -> Self {
+        Self {
+            tx_id: tx_id.to_string(),
+            stage: stage.as_str().to_owned(),
+            block_id: None,
+            identity: None,
+            delta: None,
+            contradiction: None,
+            error: None,
+            updated_time: Utc::now().naive_utc(),
+            key_version: None,
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = txsubject)]
+pub struct NewTxSubject<'a> {
+    pub tx_id: &'a str,
+    pub subject: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = outboxsubject)]
+pub struct NewOutboxSubject<'a> {
+    pub outbox_id: i32,
+    pub namespace: &'a str,
+    pub subject: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = chronicle_key)]
+pub struct NewChronicleKey<'a> {
+    pub version: i32,
+    pub public_key: &'a str,
+    pub block_id: &'a str,
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = outbox)]
+pub struct OutboxEntry {
+    pub id: i32,
+    pub subject: String,
+    pub transaction: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub tx_id: Option<String>,
+    pub queued_time: NaiveDateTime,
+    pub next_attempt_time: NaiveDateTime,
+}
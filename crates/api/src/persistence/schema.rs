@@ -54,6 +54,14 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    chronicle_key (version) {
+        version -> Int4,
+        public_key -> Text,
+        block_id -> Text,
+    }
+}
+
 diesel::table! {
     delegation (responsible_id, delegate_id, activity_id, role) {
         delegate_id -> Int4,
@@ -111,6 +119,17 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    idempotency (idempotency_key, identity) {
+        idempotency_key -> Text,
+        identity -> Text,
+        request_hash -> Text,
+        subject -> Nullable<Text>,
+        tx_id -> Nullable<Text>,
+        created_time -> Timestamp,
+    }
+}
+
 diesel::table! {
     ledgersync (tx_id) {
         tx_id -> Text,
@@ -127,6 +146,64 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    opa_decision (decision_id) {
+        decision_id -> Text,
+        decision_time -> Timestamp,
+        identity -> Text,
+        policy -> Text,
+        revision -> Text,
+        path -> Text,
+        decision -> Nullable<Text>,
+        error -> Nullable<Text>,
+        input -> Text,
+        eval_ns -> Int8,
+    }
+}
+
+diesel::table! {
+    outbox (id) {
+        id -> Int4,
+        subject -> Text,
+        transaction -> Text,
+        status -> Text,
+        attempts -> Int4,
+        last_error -> Nullable<Text>,
+        tx_id -> Nullable<Text>,
+        queued_time -> Timestamp,
+        next_attempt_time -> Timestamp,
+    }
+}
+
+diesel::table! {
+    outboxsubject (outbox_id, namespace, subject) {
+        outbox_id -> Int4,
+        namespace -> Text,
+        subject -> Text,
+    }
+}
+
+diesel::table! {
+    txreceipt (tx_id) {
+        tx_id -> Text,
+        stage -> Text,
+        block_id -> Nullable<Text>,
+        identity -> Nullable<Text>,
+        delta -> Nullable<Text>,
+        contradiction -> Nullable<Text>,
+        error -> Nullable<Text>,
+        updated_time -> Timestamp,
+        key_version -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    txsubject (tx_id, subject) {
+        tx_id -> Text,
+        subject -> Text,
+    }
+}
+
 diesel::table! {
     usage (activity_id, entity_id) {
         activity_id -> Int4,
@@ -169,6 +246,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     agent_attribute,
     association,
     attribution,
+    chronicle_key,
     delegation,
     derivation,
     entity,
@@ -176,8 +254,14 @@ diesel::allow_tables_to_appear_in_same_query!(
     generation,
     hadidentity,
     identity,
+    idempotency,
     ledgersync,
     namespace,
+    opa_decision,
+    outbox,
+    outboxsubject,
+    txreceipt,
+    txsubject,
     usage,
     wasinformedby,
 );
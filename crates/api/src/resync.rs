@@ -0,0 +1,514 @@
+//! Rebuilding the store by replaying Chronicle's events from the ledger
+
+use std::time::{Duration, Instant};
+
+use async_stl_client::{
+    error::SawtoothCommunicationError,
+    ledger::{BlockId, FromBlock},
+};
+use chronicle_protocol::{
+    async_stl_client::ledger::LedgerReader, protocol::ChronicleOperationEvent,
+};
+use common::{
+    commands::OutboxStatus,
+    database::ConnectionPool,
+    prov::{ChronicleTransactionId, ExternalIdPart, NamespaceId, UuidPart, SYSTEM_ID, SYSTEM_UUID},
+};
+use diesel::{dsl::sql, sql_query, sql_types::Text, Connection, RunQueryDsl};
+use futures::StreamExt;
+use tracing::{debug, info, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    persistence::{self, Store},
+    ApiError, StoreError,
+};
+
+/// The schema in which a store is rebuilt before it replaces the live store
+pub const SHADOW_SCHEMA: &str = "chronicle_resync";
+
+/// The schema in which the replaced store is kept, until the next resync
+pub const REPLACED_SCHEMA: &str = "chronicle_replaced";
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Tables holding provenance from the ledger, in an order they can be emptied in without
+/// violating foreign keys. Chronicle's own records of what it has submitted are not derived from
+/// the ledger, so are kept.
+const LEDGER_TABLES: &[&str] = &[
+    "hadidentity",
+    "wasinformedby",
+    "usage",
+    "generation",
+    "derivation",
+    "delegation",
+    "attribution",
+    "association",
+    "agent_attribute",
+    "activity_attribute",
+    "entity_attribute",
+    "agent",
+    "entity",
+    "activity",
+    "identity",
+    "namespace",
+    "chronicle_key",
+    "ledgersync",
+];
+
+#[derive(Debug, Clone)]
+pub struct ResyncOptions {
+    /// The block to replay from. Replaying from any block but the first keeps the provenance
+    /// already in the live store, as that of earlier blocks would otherwise be lost, and
+    /// cannot be combined with a shadow schema.
+    pub from_block: FromBlock,
+    /// How long the ledger must be quiet before the store is taken to have caught up
+    pub idle_timeout: Duration,
+    /// A pool whose connections have `SHADOW_SCHEMA` as their search path, to rebuild the store
+    /// in while the live store continues to serve the api. If `None`, the live store is emptied
+    /// and rebuilt in place. Shadow schemas need a PostgreSQL store.
+    pub shadow: Option<ConnectionPool>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ResyncProgress {
+    pub blocks: u64,
+    pub transactions: u64,
+    pub contradictions: u64,
+    pub last_block: Option<BlockId>,
+    pub elapsed: Duration,
+}
+
+impl ResyncProgress {
+    pub fn transactions_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.transactions as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Rebuild the store from `options.from_block`, reporting progress periodically and once
+/// complete. The live store is emptied first only when replaying from the first block, otherwise
+/// the blocks are replayed onto it. When rebuilding in a shadow schema, it is swapped for the live schema once caught
+/// up, after which blocks committed during the swap are replayed into the live schema.
+///
+/// An api serving the live schema keeps applying the blocks it receives after the swap, so
+/// those blocks are applied by both, concurrently. Both apply a block as upserts of the same
+/// rows, so which applies it first, or whether both do, makes no difference to the outcome.
+#[instrument(skip(live, reader, namespace_bindings, progress))]
+pub async fn resync<R>(
+    live: ConnectionPool,
+    reader: R,
+    namespace_bindings: Vec<NamespaceId>,
+    options: ResyncOptions,
+    mut progress: impl FnMut(&ResyncProgress),
+) -> Result<ResyncProgress, ApiError>
+where
+    R: LedgerReader<Event = ChronicleOperationEvent, Error = SawtoothCommunicationError>,
+{
+    let replay_all = matches!(options.from_block, FromBlock::First);
+    if options.shadow.is_some() && !replay_all {
+        return Err(ApiError::ShadowResyncFromBlock);
+    }
+
+    let live_store = Store::new(live.clone())?;
+
+    let mut store = match &options.shadow {
+        Some(shadow) => {
+            let mut connection = live.get()?;
+            sql_query(format!("drop schema if exists {SHADOW_SCHEMA} cascade"))
+                .execute(&mut connection)?;
+            sql_query(format!("create schema {SHADOW_SCHEMA}")).execute(&mut connection)?;
+            info!("Rebuilding store in schema {SHADOW_SCHEMA}");
+            Store::new(shadow.clone())?
+        }
+        None if replay_all => {
+            warn!("Rebuilding the live store in place, any api using it should be stopped");
+            live_store.clone()
+        }
+        None => {
+            warn!("Replaying onto the live store, any api using it should be stopped");
+            live_store.clone()
+        }
+    };
+
+    prepare(
+        &store,
+        options.shadow.is_none() && replay_all,
+        &namespace_bindings,
+    )?;
+
+    let mut events = reader
+        .state_updates("chronicle/prov-update", options.from_block, None)
+        .await?;
+
+    let started = Instant::now();
+    let mut last_report = started;
+    let mut state = ResyncProgress::default();
+    let mut swap_pending = options.shadow.is_some();
+
+    loop {
+        let event = match tokio::time::timeout(options.idle_timeout, events.next()).await {
+            Ok(Some(event)) => Some(event),
+            Ok(None) => {
+                debug!("Ledger reader stream ended");
+                None
+            }
+            Err(_) => None,
+        };
+
+        let (ChronicleOperationEvent(result, identity, keys), tx, block_id, _position, _span) =
+            match event {
+                Some(event) => event,
+                None if swap_pending => {
+                    swap(&live_store)?;
+                    info!("Swapped rebuilt store in, replaying blocks committed meanwhile");
+                    store = live_store.clone();
+                    swap_pending = false;
+                    continue;
+                }
+                None => break,
+            };
+
+        if state.last_block != Some(block_id) {
+            state.blocks += 1;
+            state.last_block = Some(block_id);
+        }
+        state.transactions += 1;
+        if result.is_err() {
+            state.contradictions += 1;
+        }
+
+        let tx_id = ChronicleTransactionId::from(tx.to_string().as_str());
+        let replay_store = store.clone();
+        tokio::task::spawn_blocking(move || match result {
+            Ok(delta) => {
+                replay_store.apply_prov(&delta)?;
+                replay_store.record_transaction_committed(&tx_id, &block_id, &identity, &delta)?;
+                if let Some(keys) = keys {
+                    replay_store.record_chronicle_keys(&keys, &block_id)?;
+                }
+                replay_store.set_last_block_id(&block_id, tx_id)
+            }
+            Err(contradiction) => replay_store.record_transaction_contradicted(
+                &tx_id,
+                &block_id,
+                &identity,
+                &contradiction,
+            ),
+        })
+        .await??;
+
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            state.elapsed = started.elapsed();
+            progress(&state);
+            last_report = Instant::now();
+        }
+    }
+
+    reapply_queued(&live_store)?;
+
+    state.elapsed = started.elapsed();
+    progress(&state);
+
+    Ok(state)
+}
+
+/// Migrate and bind namespaces in the store to be rebuilt, emptying it first if asked to
+fn prepare(store: &Store, empty: bool, namespace_bindings: &[NamespaceId]) -> Result<(), ApiError> {
+    let mut connection = store.connection()?;
+
+    persistence::run_migrations(&mut connection)?;
+
+    // The rows with id -1 stand in for `None` in relations, and are created by the migrations
+    if empty {
+        connection.transaction(|connection| {
+            for table in LEDGER_TABLES {
+                let keep = match *table {
+                    "activity" | "namespace" => " where id <> -1",
+                    _ => "",
+                };
+                sql_query(format!("delete from {table}{keep}")).execute(connection)?;
+            }
+            Ok::<_, diesel::result::Error>(())
+        })?;
+    }
+
+    store.namespace_binding(SYSTEM_ID, Uuid::try_from(SYSTEM_UUID).unwrap())?;
+    for ns in namespace_bindings {
+        store.namespace_binding(ns.external_id_part().as_str(), ns.uuid_part().to_owned())?
+    }
+
+    Ok(())
+}
+
+/// Carry Chronicle's own records over from the live schema to the rebuilt one, then exchange
+/// them. Both happen in one transaction, so the api sees either the old store or the new.
+fn swap(live: &Store) -> Result<(), ApiError> {
+    let mut connection = live.connection()?;
+
+    connection.transaction(|connection| {
+        let live_schema =
+            diesel::select(sql::<Text>("current_schema()")).get_result::<String>(connection)?;
+
+        for statement in [
+            format!(
+                "lock table {live_schema}.idempotency, {live_schema}.outbox, \
+                 {live_schema}.outboxsubject, {live_schema}.txreceipt, \
+                 {live_schema}.txsubject, {live_schema}.opa_decision in exclusive mode"
+            ),
+            format!("drop schema if exists {REPLACED_SCHEMA} cascade"),
+            format!(
+                "insert into {SHADOW_SCHEMA}.idempotency select * from {live_schema}.idempotency \
+                 on conflict do nothing"
+            ),
+            format!(
+                "insert into {SHADOW_SCHEMA}.txreceipt select * from {live_schema}.txreceipt \
+                 on conflict do nothing"
+            ),
+            format!(
+                "insert into {SHADOW_SCHEMA}.txsubject select * from {live_schema}.txsubject \
+                 on conflict do nothing"
+            ),
+            format!(
+                "insert into {SHADOW_SCHEMA}.opa_decision select * from \
+                 {live_schema}.opa_decision on conflict do nothing"
+            ),
+            format!("insert into {SHADOW_SCHEMA}.outbox select * from {live_schema}.outbox"),
+            format!(
+                "insert into {SHADOW_SCHEMA}.outboxsubject select * from \
+                 {live_schema}.outboxsubject"
+            ),
+            format!(
+                "select setval(pg_get_serial_sequence('{SHADOW_SCHEMA}.outbox', 'id'), \
+                 coalesce(max(id), 0) + 1, false) from {SHADOW_SCHEMA}.outbox"
+            ),
+            format!("alter schema {live_schema} rename to {REPLACED_SCHEMA}"),
+            format!("alter schema {SHADOW_SCHEMA} rename to {live_schema}"),
+        ] {
+            sql_query(statement).execute(connection)?;
+        }
+
+        Ok::<_, diesel::result::Error>(())
+    })?;
+
+    Ok(())
+}
+
+/// The effects of transactions still queued in the outbox, pending or failed, are not on the
+/// ledger, so are applied again once the store has been rebuilt. Those of a contradicted
+/// transaction are not, so are rolled back.
+fn reapply_queued(store: &Store) -> Result<(), ApiError> {
+    let mut connection = store.connection()?;
+
+    connection.transaction(|connection| {
+        let mut queued = store.outbox_items(connection, Some(OutboxStatus::Failed), None)?;
+        queued.extend(store.outbox_items(connection, Some(OutboxStatus::Pending), None)?);
+        queued.sort_by_key(|item| item.id);
+
+        for item in queued {
+            store.apply_model(connection, &item.prov)?;
+        }
+        Ok::<_, StoreError>(())
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{file_ledger::FileLedger, Api, ApiDispatch, UuidGen};
+    use chronicle_signing::{
+        chronicle_secret_names, ChronicleSecretsOptions, ChronicleSigning, BATCHER_NAMESPACE,
+        CHRONICLE_NAMESPACE,
+    };
+    use common::{
+        attributes::Attributes,
+        commands::{AgentCommand, ApiCommand, ApiResponse},
+        database::{StoreConnection, TemporaryDatabase},
+        identity::AuthId,
+        ledger::SubmissionStage,
+    };
+    use diesel::sql_types::{BigInt, Bool};
+
+    #[derive(Debug, Clone)]
+    struct RandomUuid;
+
+    impl UuidGen for RandomUuid {}
+
+    async fn create_agent(api: &ApiDispatch, external_id: &str, idempotency_key: Option<&str>) {
+        let mut notifications = api.notify_commit.subscribe();
+
+        let tx_id = match api
+            .dispatch_with_idempotency_key(
+                ApiCommand::Agent(AgentCommand::Create {
+                    external_id: external_id.into(),
+                    namespace: "testns".into(),
+                    attributes: Attributes {
+                        typ: None,
+                        attributes: Default::default(),
+                    },
+                }),
+                AuthId::chronicle(),
+                idempotency_key.map(ToOwned::to_owned),
+            )
+            .await
+            .unwrap()
+        {
+            ApiResponse::Submission { tx_id, .. } => tx_id,
+            res => panic!("unexpected response: {res:?}"),
+        };
+
+        loop {
+            match notifications.recv().await.unwrap() {
+                SubmissionStage::Committed(commit, _) if commit.tx_id == tx_id => break,
+                SubmissionStage::NotCommitted((_, contradiction, _)) => {
+                    panic!("{contradiction:?}")
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    fn agents(connection: &mut StoreConnection, schema: &str, external_id: &str) -> i64 {
+        diesel::select(sql::<BigInt>(&format!(
+            "(select count(*) from {schema}.agent where external_id = '{external_id}')"
+        )))
+        .get_result(connection)
+        .unwrap()
+    }
+
+    fn swapped(connection: &mut StoreConnection) -> bool {
+        diesel::select(sql::<Bool>(&format!(
+            "exists (select 1 from pg_namespace where nspname = '{REPLACED_SCHEMA}')"
+        )))
+        .get_result(connection)
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn shadow_resync_rebuilds_swaps_and_replays_blocks_committed_meanwhile() {
+        chronicle_telemetry::telemetry(None, chronicle_telemetry::ConsoleLogging::Pretty);
+
+        let database = TemporaryDatabase::default();
+        let live = database.connection_pool().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let ledger = FileLedger::open(
+            dir.path().join("ledger"),
+            "allow_transactions",
+            "allow_transactions.allowed_users",
+            &[],
+        )
+        .unwrap();
+
+        let signing = ChronicleSigning::new(
+            chronicle_secret_names(),
+            vec![
+                (
+                    CHRONICLE_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::generate_in_memory(),
+                ),
+                (
+                    BATCHER_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::generate_in_memory(),
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let api = Api::new(
+            live.clone(),
+            ledger.ledger.clone(),
+            RandomUuid,
+            signing,
+            vec![],
+            Some("allow_transactions".into()),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        create_agent(&api, "before", Some("before")).await;
+        let mut connection = live.get().unwrap();
+        sql_query(
+            "insert into opa_decision (decision_id, decision_time, identity, policy, revision, \
+             path, input, eval_ns) values ('before', now(), 'chronicle', 'allow_transactions', \
+             'abc123', 'allow_transactions/allowed_users', '{}', 0)",
+        )
+        .execute(&mut connection)
+        .unwrap();
+
+        // Lose the agent from the live store, though not from the ledger
+        sql_query("delete from agent where external_id = 'before'")
+            .execute(&mut connection)
+            .unwrap();
+
+        let options = ResyncOptions {
+            from_block: FromBlock::First,
+            idle_timeout: Duration::from_secs(3),
+            shadow: Some(database.connection_pool_in_schema(SHADOW_SCHEMA).unwrap()),
+        };
+
+        // A shadow schema replayed from a later block would lose the provenance before it
+        let from_block = ResyncOptions {
+            from_block: FromBlock::BlockId(BlockId::try_from(hex::encode([1; 32])).unwrap()),
+            ..options.clone()
+        };
+        assert!(matches!(
+            resync(
+                live.clone(),
+                ledger.ledger.clone(),
+                vec![],
+                from_block,
+                |_| {}
+            )
+            .await,
+            Err(ApiError::ShadowResyncFromBlock)
+        ));
+
+        let (progress, ()) = tokio::join!(
+            resync(live.clone(), ledger.ledger.clone(), vec![], options, |_| {}),
+            async {
+                // The running api applies this to the schema being replaced, so only the
+                // resync can record it in the rebuilt one
+                create_agent(&api, "during", None).await;
+
+                while !swapped(&mut live.get().unwrap()) {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+
+                create_agent(&api, "after", None).await;
+            }
+        );
+        let progress = progress.unwrap();
+
+        assert_eq!(progress.transactions, 3);
+        assert_eq!(agents(&mut connection, "public", "before"), 1);
+        assert_eq!(agents(&mut connection, "public", "during"), 1);
+        assert_eq!(agents(&mut connection, "public", "after"), 1);
+        assert_eq!(agents(&mut connection, REPLACED_SCHEMA, "before"), 0);
+        assert_eq!(agents(&mut connection, REPLACED_SCHEMA, "during"), 1);
+
+        // Chronicle's own records are carried over
+        let store = Store::new(live).unwrap();
+        assert!(store
+            .idempotent_submission(&mut connection, "before", &AuthId::chronicle().to_string())
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            diesel::select(sql::<BigInt>(
+                "(select count(*) from opa_decision where decision_id = 'before')"
+            ))
+            .get_result::<i64>(&mut connection)
+            .unwrap(),
+            1
+        );
+    }
+}
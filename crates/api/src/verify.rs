@@ -0,0 +1,624 @@
+//! Checking the store against the provenance committed to the ledger
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+
+use async_stl_client::error::SawtoothCommunicationError;
+use chronicle_protocol::{
+    address::SawtoothAddress,
+    async_stl_client::ledger::{BlockId, FromBlock, LedgerReader, Position},
+    protocol::ChronicleOperationEvent,
+};
+use chrono::{DateTime, Utc};
+use common::{
+    database::{ConnectionPool, StoreConnection},
+    ledger::LedgerAddress,
+    prov::{ChronicleIri, ExternalIdPart, NamespaceId, ProvModel},
+};
+use diesel::{sql_query, Connection, RunQueryDsl};
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use tracing::{debug, error, info, instrument, warn};
+
+use crate::{persistence::Store, ApiError, StoreError};
+
+const LEDGER_READ_CONCURRENCY: usize = 16;
+/// How long to wait for more of the ledger's provenance before taking it to have all been replayed
+const LEDGER_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Node,
+    Relation,
+    Attribute,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difference {
+    /// On the ledger, but not in the store
+    Missing,
+    /// In the store, but not on the ledger
+    Extra,
+    /// In both, with different values
+    Differs,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Discrepancy {
+    pub category: Category,
+    pub difference: Difference,
+    /// The node, or the subject of the relation
+    pub subject: String,
+    /// The kind of node or relation, or the name of the field or attribute that differs
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ledger: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NamespaceReport {
+    pub namespace: String,
+    /// The number of resources read from ledger state
+    pub ledger_resources: usize,
+    /// The number of resources with transactions in the outbox yet to reach the ledger, whose
+    /// extra or differing provenance in the store is not reported
+    pub pending_resources: usize,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerificationReport {
+    pub checked: DateTime<Utc>,
+    pub consistent: bool,
+    pub namespaces: Vec<NamespaceReport>,
+}
+
+impl VerificationReport {
+    pub fn discrepancies(&self) -> usize {
+        self.namespaces
+            .iter()
+            .map(|namespace| namespace.discrepancies.len())
+            .sum()
+    }
+}
+
+/// The resources recorded by the provenance committed to the ledger, by namespace, and the last
+/// block replayed to find them
+#[derive(Debug, Default)]
+struct LedgerResources {
+    resources: BTreeMap<NamespaceId, BTreeSet<ChronicleIri>>,
+    last_block: Option<BlockId>,
+}
+
+impl LedgerResources {
+    /// Replay the provenance committed to the ledger after the last block replayed, up to the
+    /// head of the chain when called, or until no more arrives for [`LEDGER_IDLE_TIMEOUT`]
+    async fn catch_up<R>(&mut self, reader: &R) -> Result<(), ApiError>
+    where
+        R: LedgerReader<Event = ChronicleOperationEvent, Error = SawtoothCommunicationError>,
+    {
+        let (Position(head), _) = reader.block_height().await?;
+        let from_block = match self.last_block {
+            Some(block_id) => FromBlock::BlockId(block_id),
+            None => FromBlock::First,
+        };
+
+        let mut events = reader
+            .state_updates("chronicle/prov-update", from_block, None)
+            .await?;
+
+        let mut replayed = 0;
+        let mut block = None;
+        while let Ok(Some((ChronicleOperationEvent(result, ..), _tx, block_id, position, _span))) =
+            tokio::time::timeout(LEDGER_IDLE_TIMEOUT, events.next()).await
+        {
+            // Blocks committed since the check began are left for the next
+            if position.0 > head {
+                break;
+            }
+
+            if let Ok(delta) = result {
+                for (namespace, resources) in resources(&delta) {
+                    self.resources
+                        .entry(namespace)
+                        .or_default()
+                        .extend(resources);
+                }
+            }
+            block = Some(block_id);
+            replayed += 1;
+        }
+
+        debug!(replayed, "Replayed ledger provenance");
+        if block.is_some() {
+            self.last_block = block;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compare the provenance of each namespace in the store with the ledger state at the addresses
+/// of its resources. The resources on the ledger are found by replaying the provenance committed
+/// to it, so that those the store lacks are reported along with any they refer to. Resources
+/// with transactions in the outbox yet to reach the ledger are expected to be ahead of it in the
+/// store, so only what the store is missing is reported for them.
+#[instrument(skip(pool, reader))]
+pub async fn verify_store<R>(
+    pool: ConnectionPool,
+    reader: &R,
+) -> Result<VerificationReport, ApiError>
+where
+    R: LedgerReader<Event = ChronicleOperationEvent, Error = SawtoothCommunicationError>,
+{
+    verify(pool, reader, &mut LedgerResources::default()).await
+}
+
+/// Verify the store, replaying the ledger on from the blocks replayed by previous checks
+async fn verify<R>(
+    pool: ConnectionPool,
+    reader: &R,
+    ledger_resources: &mut LedgerResources,
+) -> Result<VerificationReport, ApiError>
+where
+    R: LedgerReader<Event = ChronicleOperationEvent, Error = SawtoothCommunicationError>,
+{
+    let checked = Utc::now();
+    let store = Store::new(pool)?;
+
+    let (stored, pending) = tokio::task::spawn_blocking(move || {
+        let mut connection = store.connection()?;
+        connection.transaction(|connection| {
+            // Read every namespace from the same snapshot
+            if let StoreConnection::Postgres(_) = &**connection {
+                sql_query("set transaction isolation level repeatable read, read only")
+                    .execute(connection)?;
+            }
+
+            let stored = store
+                .namespaces(connection)?
+                .into_iter()
+                .map(|namespace| {
+                    let model = store.prov_model_for_namespace(connection, &namespace)?;
+                    Ok((namespace, model))
+                })
+                .collect::<Result<BTreeMap<_, _>, StoreError>>()?;
+
+            Ok::<_, StoreError>((stored, store.outbox_pending_subjects(connection)?))
+        })
+    })
+    .await??;
+
+    ledger_resources.catch_up(reader).await?;
+
+    let namespaces_checked = stored
+        .keys()
+        .chain(ledger_resources.resources.keys())
+        .cloned()
+        .collect::<BTreeSet<_>>();
+
+    let mut namespaces = vec![];
+    for namespace in namespaces_checked {
+        let stored = stored.get(&namespace).cloned().unwrap_or_default();
+
+        let mut pending_read = resources(&stored).remove(&namespace).unwrap_or_default();
+        pending_read.extend(
+            ledger_resources
+                .resources
+                .get(&namespace)
+                .into_iter()
+                .flatten()
+                .cloned(),
+        );
+        let (ledger, ledger_resources) = ledger_model(reader, &namespace, pending_read).await?;
+
+        let in_outbox = |subject: &str| {
+            pending.contains(&(namespace.external_id_part().to_string(), subject.to_owned()))
+        };
+        let (discrepancies, pending_discrepancies): (Vec<_>, Vec<_>) = compare(&stored, &ledger)
+            .into_iter()
+            .partition(|discrepancy| {
+                discrepancy.difference == Difference::Missing || !in_outbox(&discrepancy.subject)
+            });
+
+        namespaces.push(NamespaceReport {
+            namespace: namespace.to_string(),
+            ledger_resources,
+            pending_resources: pending_discrepancies
+                .iter()
+                .map(|discrepancy| &discrepancy.subject)
+                .collect::<BTreeSet<_>>()
+                .len(),
+            discrepancies,
+        });
+    }
+
+    Ok(VerificationReport {
+        checked,
+        consistent: namespaces
+            .iter()
+            .all(|namespace| namespace.discrepancies.is_empty()),
+        namespaces,
+    })
+}
+
+/// Verify the store every `interval`, logging any discrepancies found. The first check is made
+/// after one interval, giving the store time to catch up with the ledger. Each check replays
+/// only the blocks committed since the last.
+pub fn verify_periodically<R>(pool: ConnectionPool, reader: R, interval: Duration)
+where
+    R: LedgerReader<Event = ChronicleOperationEvent, Error = SawtoothCommunicationError>
+        + Send
+        + Sync
+        + 'static,
+{
+    tokio::task::spawn(async move {
+        let mut checks = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        checks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut ledger_resources = LedgerResources::default();
+
+        loop {
+            checks.tick().await;
+
+            match verify(pool.clone(), &reader, &mut ledger_resources).await {
+                Ok(report) if report.consistent => info!("Store is consistent with the ledger"),
+                Ok(report) => warn!(
+                    discrepancies = report.discrepancies(),
+                    report = %serde_json::to_string(&report).unwrap_or_default(),
+                    "Store differs from the ledger"
+                ),
+                Err(e) => error!(?e, "Store verification"),
+            }
+        }
+    });
+}
+
+/// Read `resources` from ledger state, and any resources they refer to in turn, into a model.
+/// Returns the model and the number of resources found.
+async fn ledger_model<R>(
+    reader: &R,
+    namespace: &NamespaceId,
+    mut pending: BTreeSet<ChronicleIri>,
+) -> Result<(ProvModel, usize), ApiError>
+where
+    R: LedgerReader<Event = ChronicleOperationEvent, Error = SawtoothCommunicationError>,
+{
+    let mut model = ProvModel::default();
+    let mut read = BTreeSet::new();
+    let mut found = 0;
+
+    while !pending.is_empty() {
+        read.extend(pending.iter().cloned());
+
+        let entries = stream::iter(pending.into_iter().map(|resource| {
+            let address = SawtoothAddress::from(&LedgerAddress::in_namespace(namespace, resource));
+            async move {
+                match reader.get_state_entry(&address.to_string()).await {
+                    Ok(entry) => Ok(Some(entry)),
+                    Err(SawtoothCommunicationError::ResourceNotFound) => Ok(None),
+                    Err(e) => Err(e),
+                }
+            }
+        }))
+        .buffer_unordered(LEDGER_READ_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+        for entry in entries {
+            if let Some(entry) = entry? {
+                model.apply_json_ld_bytes(&entry).await?;
+                found += 1;
+            }
+        }
+
+        pending = resources(&model)
+            .remove(namespace)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|resource| !read.contains(resource))
+            .collect();
+    }
+
+    Ok((model, found))
+}
+
+/// The addressable resources in `model` by namespace, including those only referred to by
+/// relations
+fn resources(model: &ProvModel) -> BTreeMap<NamespaceId, BTreeSet<ChronicleIri>> {
+    fn of<'a>(
+        resources: &'a mut BTreeMap<NamespaceId, BTreeSet<ChronicleIri>>,
+        namespace: &NamespaceId,
+    ) -> &'a mut BTreeSet<ChronicleIri> {
+        resources.entry(namespace.clone()).or_default()
+    }
+
+    let mut all = BTreeMap::new();
+
+    for (namespace, id) in model.agents.keys() {
+        of(&mut all, namespace).insert(id.clone().into());
+    }
+    for (namespace, id) in model.activities.keys() {
+        of(&mut all, namespace).insert(id.clone().into());
+    }
+    for (namespace, id) in model.entities.keys() {
+        of(&mut all, namespace).insert(id.clone().into());
+    }
+
+    for ((namespace, _), associations) in &model.association {
+        let resources = of(&mut all, namespace);
+        for association in associations {
+            resources.insert(association.id.clone().into());
+            resources.insert(association.agent_id.clone().into());
+            resources.insert(association.activity_id.clone().into());
+        }
+    }
+    for ((namespace, _), attributions) in &model.attribution {
+        let resources = of(&mut all, namespace);
+        for attribution in attributions {
+            resources.insert(attribution.id.clone().into());
+            resources.insert(attribution.agent_id.clone().into());
+            resources.insert(attribution.entity_id.clone().into());
+        }
+    }
+    for ((namespace, _), delegations) in &model.delegation {
+        let resources = of(&mut all, namespace);
+        for delegation in delegations {
+            resources.insert(delegation.id.clone().into());
+            resources.insert(delegation.delegate_id.clone().into());
+            resources.insert(delegation.responsible_id.clone().into());
+            resources.extend(delegation.activity_id.clone().map(Into::into));
+        }
+    }
+    for ((namespace, _), derivations) in &model.derivation {
+        let resources = of(&mut all, namespace);
+        for derivation in derivations {
+            resources.insert(derivation.generated_id.clone().into());
+            resources.insert(derivation.used_id.clone().into());
+            resources.extend(derivation.activity_id.clone().map(Into::into));
+        }
+    }
+    for ((namespace, _), generations) in &model.generation {
+        let resources = of(&mut all, namespace);
+        for generation in generations {
+            resources.insert(generation.activity_id.clone().into());
+            resources.insert(generation.generated_id.clone().into());
+        }
+    }
+    for ((namespace, _), usages) in &model.usage {
+        let resources = of(&mut all, namespace);
+        for usage in usages {
+            resources.insert(usage.activity_id.clone().into());
+            resources.insert(usage.entity_id.clone().into());
+        }
+    }
+    for (namespace, informing) in model.was_informed_by.values().flatten() {
+        of(&mut all, namespace).insert(informing.clone().into());
+    }
+
+    all
+}
+
+/// Differences between the stored and ledger models, for the provenance the store projects.
+/// Identities are not projected into namespace models by the store, so are not compared.
+fn compare(stored: &ProvModel, ledger: &ProvModel) -> Vec<Discrepancy> {
+    let mut discrepancies = vec![];
+    let out = &mut discrepancies;
+
+    compare_nodes(out, "agent", &stored.agents, &ledger.agents, |(_, id)| {
+        id.clone().into()
+    });
+    compare_nodes(
+        out,
+        "activity",
+        &stored.activities,
+        &ledger.activities,
+        |(_, id)| id.clone().into(),
+    );
+    compare_nodes(
+        out,
+        "entity",
+        &stored.entities,
+        &ledger.entities,
+        |(_, id)| id.clone().into(),
+    );
+
+    compare_relations(
+        out,
+        "association",
+        &stored.association,
+        &ledger.association,
+        |(_, id)| id.clone().into(),
+    );
+    compare_relations(
+        out,
+        "attribution",
+        &stored.attribution,
+        &ledger.attribution,
+        |(_, id)| id.clone().into(),
+    );
+    compare_relations(
+        out,
+        "delegation",
+        &stored.delegation,
+        &ledger.delegation,
+        |(_, id)| id.clone().into(),
+    );
+    compare_relations(
+        out,
+        "derivation",
+        &stored.derivation,
+        &ledger.derivation,
+        |(_, id)| id.clone().into(),
+    );
+    compare_relations(
+        out,
+        "generation",
+        &stored.generation,
+        &ledger.generation,
+        |(_, id)| id.clone().into(),
+    );
+    compare_relations(out, "usage", &stored.usage, &ledger.usage, |(_, id)| {
+        id.clone().into()
+    });
+    compare_relations(
+        out,
+        "was_informed_by",
+        &stored.was_informed_by,
+        &ledger.was_informed_by,
+        |(_, id)| id.clone().into(),
+    );
+
+    discrepancies
+}
+
+fn json(value: &impl Serialize) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+fn discrepancy(
+    category: Category,
+    difference: Difference,
+    subject: &ChronicleIri,
+    name: &str,
+    store: Option<Value>,
+    ledger: Option<Value>,
+) -> Discrepancy {
+    Discrepancy {
+        category,
+        difference,
+        subject: subject.to_string(),
+        name: name.to_owned(),
+        store,
+        ledger,
+    }
+}
+
+fn compare_nodes<K: Ord, V: Serialize + PartialEq>(
+    out: &mut Vec<Discrepancy>,
+    name: &str,
+    stored: &BTreeMap<K, V>,
+    ledger: &BTreeMap<K, V>,
+    subject: impl Fn(&K) -> ChronicleIri,
+) {
+    for key in stored.keys().chain(ledger.keys()).collect::<BTreeSet<_>>() {
+        match (stored.get(key), ledger.get(key)) {
+            (Some(stored), None) => out.push(discrepancy(
+                Category::Node,
+                Difference::Extra,
+                &subject(key),
+                name,
+                Some(json(stored)),
+                None,
+            )),
+            (None, Some(ledger)) => out.push(discrepancy(
+                Category::Node,
+                Difference::Missing,
+                &subject(key),
+                name,
+                None,
+                Some(json(ledger)),
+            )),
+            (Some(stored), Some(ledger)) if stored != ledger => {
+                compare_fields(out, &subject(key), json(stored), json(ledger))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Report each differing field of a node, and each differing attribute individually
+fn compare_fields(
+    out: &mut Vec<Discrepancy>,
+    subject: &ChronicleIri,
+    stored: Value,
+    ledger: Value,
+) {
+    let (stored, ledger) = match (stored, ledger) {
+        (Value::Object(stored), Value::Object(ledger)) => (stored, ledger),
+        _ => return,
+    };
+
+    for field in stored.keys().chain(ledger.keys()).collect::<BTreeSet<_>>() {
+        let (stored, ledger) = (stored.get(field), ledger.get(field));
+        if stored == ledger {
+            continue;
+        }
+
+        match (field.as_str(), stored, ledger) {
+            ("attributes", Some(Value::Object(stored)), Some(Value::Object(ledger))) => {
+                for attribute in stored.keys().chain(ledger.keys()).collect::<BTreeSet<_>>() {
+                    let values = (stored.get(attribute), ledger.get(attribute));
+                    let difference = match values {
+                        (Some(_), None) => Difference::Extra,
+                        (None, Some(_)) => Difference::Missing,
+                        (stored_value, ledger_value) if stored_value != ledger_value => {
+                            Difference::Differs
+                        }
+                        _ => continue,
+                    };
+                    out.push(discrepancy(
+                        Category::Attribute,
+                        difference,
+                        subject,
+                        attribute,
+                        values.0.cloned(),
+                        values.1.cloned(),
+                    ));
+                }
+            }
+            _ => out.push(discrepancy(
+                Category::Node,
+                Difference::Differs,
+                subject,
+                field,
+                stored.cloned(),
+                ledger.cloned(),
+            )),
+        }
+    }
+}
+
+fn compare_relations<K: Ord, T: Ord + Serialize>(
+    out: &mut Vec<Discrepancy>,
+    name: &str,
+    stored: &BTreeMap<K, BTreeSet<T>>,
+    ledger: &BTreeMap<K, BTreeSet<T>>,
+    subject: impl Fn(&K) -> ChronicleIri,
+) {
+    let none = BTreeSet::new();
+
+    for key in stored.keys().chain(ledger.keys()).collect::<BTreeSet<_>>() {
+        let (stored, ledger) = (
+            stored.get(key).unwrap_or(&none),
+            ledger.get(key).unwrap_or(&none),
+        );
+
+        for relation in ledger.difference(stored) {
+            out.push(discrepancy(
+                Category::Relation,
+                Difference::Missing,
+                &subject(key),
+                name,
+                None,
+                Some(json(relation)),
+            ));
+        }
+        for relation in stored.difference(ledger) {
+            out.push(discrepancy(
+                Category::Relation,
+                Difference::Extra,
+                &subject(key),
+                name,
+                Some(json(relation)),
+                None,
+            ));
+        }
+    }
+}
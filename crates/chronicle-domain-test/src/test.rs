@@ -205,6 +205,8 @@ mod test {
             vec![],
             None,
             liveness_check_interval,
+            None,
+            None,
         )
         .await
         .unwrap();
@@ -573,7 +575,7 @@ mod test {
 
         insta::assert_json_snapshot!(schema
           .execute(Request::new(
-            &format!(
+            format!(
             r#"
           mutation {{
             defineContractorAgent(
@@ -757,7 +759,7 @@ mod test {
 
         insta::assert_json_snapshot!(schema
           .execute(Request::new(
-            &format!(
+            format!(
               r#"
             mutation {{
               defineContractorAgent(
@@ -929,7 +931,7 @@ mod test {
 
         insta::assert_json_snapshot!(schema
           .execute(Request::new(
-            &format!(
+            format!(
               r#"
             mutation {{
               defineContractorAgent(
@@ -1108,7 +1110,7 @@ mod test {
 
         insta::assert_json_snapshot!(schema
           .execute(Request::new(
-            &format!(
+            format!(
             r#"
           mutation {{
             defineContractorAgent(
@@ -1271,7 +1273,7 @@ mod test {
 
         insta::assert_json_snapshot!(schema
           .execute(Request::new(
-            &format!(
+            format!(
             r#"
           query multipleQueries {{
             activityById(id: {{externalId: "{external_id_input}" }}) {{
@@ -2384,7 +2386,7 @@ mod test {
         // create an activity that used an entity and was associated with an agent
         insta::assert_json_snapshot!(schema
           .execute(Request::new(
-            &format!(
+            format!(
             r#"
               mutation certifiedActivity {{
                 defineContractorAgent(externalId: "{test_agent}", attributes: {{ locationAttribute: "SomeLocation"}}) {{
@@ -2450,7 +2452,7 @@ mod test {
         // attribute the entity to the agent
         insta::assert_json_snapshot!(schema
               .execute(Request::new(
-                &format!(
+                format!(
                 r#"
                   mutation attribution {{
                     wasAttributedTo( role: CERTIFIER, responsible: {{ id: "chronicle:agent:{test_agent}" }}, entity: {{id: "chronicle:entity:{test_entity}" }}) {{
@@ -2474,7 +2476,7 @@ mod test {
         // query WasAttributedTo relationship
         insta::assert_toml_snapshot!(schema
                   .execute(Request::new(
-                    &format!(
+                    format!(
                 r#"
                   query queryWasAttributedTo {{
                     entityById(id: {{ id: "chronicle:entity:{test_entity}" }}) {{
@@ -2561,7 +2563,7 @@ mod test {
 
         insta::assert_toml_snapshot!(schema
                 .execute(Request::new(
-                  &format!(
+                  format!(
                     r#"
                 query queryAgentAttribution {{
                   agentById(id: {{externalId: "{test_agent}" }}) {{
@@ -2649,7 +2651,7 @@ mod test {
         // create another agent and attribute the entity to that other agent as well
         insta::assert_json_snapshot!(schema
               .execute(Request::new(
-                &format!(
+                format!(
                 r#"
                   mutation anotherAgent {{
                     defineContractorAgent(externalId: "Certifier2", attributes: {{ locationAttribute: "AnotherLocation"}}) {{
@@ -2679,7 +2681,7 @@ mod test {
         // query WasAttributedTo relationship
         insta::assert_toml_snapshot!(schema
               .execute(Request::new(
-                &format!(
+                format!(
                   r#"
                 query queryWasAttributedToSecondAgent {{
                   entityById(id: {{id: "chronicle:entity:{test_entity}" }}) {{
@@ -3137,7 +3139,7 @@ mod test {
             if (i % 2) == 0 {
                 let res = schema
                     .execute(Request::new(
-                        &format!(
+                        format!(
                             r#"
                     mutation {{
                       defineItemCertifiedActivity(externalId:"{activity_name}", attributes: {{ certIdAttribute: "testcertid" }}) {{
@@ -3152,7 +3154,7 @@ mod test {
                 assert_eq!(res.errors, vec![]);
             } else {
                 let res = schema
-                    .execute(Request::new(&format!(
+                    .execute(Request::new(format!(
                         r#"
                     mutation {{
                       defineItemCodifiedActivity(externalId:"{activity_name}") {{
@@ -4903,6 +4905,288 @@ mod test {
         "###);
     }
 
+    #[tokio::test]
+    async fn query_committed_transaction() {
+        use chronicle::async_graphql::futures_util::StreamExt;
+
+        let (schema, _database) = test_schema().await;
+
+        let mut stream = schema.execute_stream(Request::new(
+            r#"
+          subscription {
+            commitNotifications {
+              txId
+            }
+          }
+          "#
+            .to_string(),
+        ));
+
+        assert!(schema
+            .execute(Request::new(
+                r#"
+            mutation {
+              defineContractorAgent(
+                externalId: "testagent"
+                attributes: { locationAttribute: "location" }
+              ) {
+                context
+              }
+            }
+            "#,
+            ))
+            .await
+            .is_ok());
+
+        let res = stream.next().await.unwrap().data.into_json().unwrap();
+        let tx_id = res["commitNotifications"]["txId"].as_str().unwrap();
+
+        let res = schema
+            .execute(Request::new(format!(
+                r#"
+            query {{
+              transaction(txId: "{tx_id}") {{
+                stage
+                contradiction
+                error
+              }}
+            }}
+            "#
+            )))
+            .await;
+
+        insta::assert_json_snapshot!(res, @r###"
+        {
+          "data": {
+            "transaction": {
+              "stage": "COMMITTED",
+              "contradiction": null,
+              "error": null
+            }
+          }
+        }
+        "###);
+
+        insta::assert_json_snapshot!(schema
+            .execute(Request::new(
+                r#"
+            query {
+              transaction(txId: "unknown") {
+                stage
+              }
+            }
+            "#,
+            ))
+            .await, @r###"
+        {
+          "data": {
+            "transaction": null
+          }
+        }
+        "###);
+    }
+
+    #[tokio::test]
+    async fn subscribe_filtered_commit_notification() {
+        use chronicle::async_graphql::futures_util::StreamExt;
+
+        let (schema, _database) = test_schema().await;
+
+        let mut stream = schema.execute_stream(Request::new(
+            r#"
+          subscription {
+            commitNotifications(
+              subjects: ["chronicle:agent:testagent2"]
+              operationKinds: [AGENT_EXISTS]
+            ) {
+              stage
+            }
+          }
+          "#
+            .to_string(),
+        ));
+
+        for external_id in ["testagent1", "testagent2"] {
+            assert!(schema
+                .execute(Request::new(format!(
+                    r#"
+                mutation {{
+                  defineContractorAgent(
+                    externalId: "{external_id}"
+                    attributes: {{ locationAttribute: "location" }}
+                  ) {{
+                    context
+                  }}
+                }}
+                "#
+                )))
+                .await
+                .is_ok());
+        }
+
+        let res = stream.next().await.unwrap();
+
+        insta::assert_json_snapshot!(res, @r###"
+        {
+          "data": {
+            "commitNotifications": {
+              "stage": "COMMIT"
+            }
+          }
+        }
+        "###);
+
+        let res = schema
+            .execute_stream(Request::new(
+                r#"
+              subscription {
+                commitNotifications(subjects: ["not-an-iri"]) {
+                  stage
+                }
+              }
+              "#,
+            ))
+            .next()
+            .await
+            .unwrap();
+
+        assert!(!res.errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn subscribe_withholds_commit_notification_denied_read_data() {
+        use chronicle::async_graphql::futures_util::StreamExt;
+
+        let loader = CliPolicyLoader::from_embedded_policy(
+            "allow_transactions",
+            "allow_transactions.allow_except_hidden_data",
+        )
+        .unwrap();
+        let opa_executor = ExecutorContext::from_loader(&loader).unwrap();
+        let (schema, _database) = test_schema_with_opa(opa_executor).await;
+
+        let mut stream = schema.execute_stream(Request::new(
+            r#"
+          subscription {
+            commitNotifications(stage: COMMIT) {
+              stage
+              delta
+            }
+          }
+          "#
+            .to_string(),
+        ));
+
+        for external_id in ["hiddenagent", "testagent"] {
+            assert!(schema
+                .execute(Request::new(format!(
+                    r#"
+                mutation {{
+                  defineContractorAgent(
+                    externalId: "{external_id}"
+                    attributes: {{ locationAttribute: "location" }}
+                  ) {{
+                    context
+                  }}
+                }}
+                "#
+                )))
+                .await
+                .is_ok());
+        }
+
+        // The policy denies ReadData of the first agent, so the first notification is the second's
+        let res = stream.next().await.unwrap();
+        assert!(res.errors.is_empty());
+
+        let notification = res.data.into_json().unwrap().to_string();
+        assert!(notification.contains("chronicle:agent:testagent"));
+        assert!(!notification.contains("hiddenagent"));
+    }
+
+    #[tokio::test]
+    async fn query_transaction_withholds_delta_denied_read_data() {
+        use chronicle::async_graphql::futures_util::StreamExt;
+
+        let loader = CliPolicyLoader::from_embedded_policy(
+            "allow_transactions",
+            "allow_transactions.allow_except_hidden_data",
+        )
+        .unwrap();
+        let opa_executor = ExecutorContext::from_loader(&loader).unwrap();
+        let (schema, _database) = test_schema_with_opa(opa_executor).await;
+
+        let mut stream = schema.execute_stream(Request::new(
+            r#"
+          subscription {
+            commitNotifications(stage: COMMIT) {
+              txId
+            }
+          }
+          "#
+            .to_string(),
+        ));
+
+        let mut tx_ids = vec![];
+        for external_id in ["hiddenagent", "testagent"] {
+            let res = schema
+                .execute(Request::new(format!(
+                    r#"
+                mutation {{
+                  defineContractorAgent(
+                    externalId: "{external_id}"
+                    attributes: {{ locationAttribute: "location" }}
+                  ) {{
+                    txId
+                  }}
+                }}
+                "#
+                )))
+                .await
+                .data
+                .into_json()
+                .unwrap();
+            tx_ids.push(
+                res["defineContractorAgent"]["txId"]
+                    .as_str()
+                    .unwrap()
+                    .to_owned(),
+            );
+        }
+
+        // Only the second agent's commit is notified, and the first is committed before it
+        stream.next().await.unwrap();
+
+        let transaction = |tx_id: &str| {
+            Request::new(format!(
+                r#"
+            query {{
+              transaction(txId: "{tx_id}") {{
+                stage
+                delta
+                error
+              }}
+            }}
+            "#
+            ))
+        };
+
+        insta::assert_json_snapshot!(schema.execute(transaction(&tx_ids[0])).await, @r###"
+        {
+          "data": {
+            "transaction": {
+              "stage": "COMMITTED",
+              "delta": null,
+              "error": null
+            }
+          }
+        }
+        "###);
+
+        let res = schema.execute(transaction(&tx_ids[1])).await;
+        assert!(!res.data.into_json().unwrap()["transaction"]["delta"].is_null());
+    }
+
     async fn subscription_response(
         schema: &Schema<Query, Mutation, Subscription>,
         subscription: &str,
@@ -0,0 +1,438 @@
+//! Merkle inclusion proofs for the state entries a block of a Chronicle ledger file writes.
+//! Sawtooth blocks commit to the root of the validator's Merkle-Radix tree of all state instead,
+//! which these proofs cannot be checked against.
+//!
+//! The state entries written by a block are hashed into a binary Merkle tree, ordered as the
+//! block commits them, whose root is the block's write root. A block's id is the hash of its
+//! header, which holds the write root and a hash of the rest of the block, so the id commits to
+//! every entry the block wrote. A proof holds one entry, the sibling hashes on the path from it
+//! to the write root, and the header, so anyone who trusts a block id can check the entry was
+//! written in that block without trusting the Chronicle that produced the proof.
+//!
+//! The write root is a commitment to what one block wrote, not to the ledger's whole state, so
+//! a proof shows that a block wrote an entry and not that the entry is still current.
+//!
+//! Leaves and interior nodes are hashed with distinct prefixes, and a node without a sibling is
+//! carried up to the next level unchanged.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+pub type Hash = [u8; 32];
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+#[derive(Error, Debug)]
+pub enum ProofError {
+    #[error("Proof is for block {proof}, not block {expected}")]
+    WrongBlock { proof: String, expected: String },
+
+    #[error("Proof's block header hashes to {computed}, not to its block id {block_id}")]
+    HeaderMismatch { block_id: String, computed: String },
+
+    #[error("Merkle path leads to {computed}, not to write root {write_root}")]
+    PathMismatch {
+        computed: String,
+        write_root: String,
+    },
+
+    #[error("Record differs from the state entry the proof is for")]
+    RecordMismatch,
+
+    #[error("Invalid hex: {0}")]
+    Hex(#[from] hex::FromHexError),
+
+    #[error("Invalid hash, expected 32 bytes: {0}")]
+    HashLength(String),
+}
+
+/// The header of a block, whose hash is the block's id
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockHeader {
+    #[serde(alias = "block_id")]
+    pub block_id: String,
+    #[serde(alias = "block_num")]
+    pub block_num: u64,
+    #[serde(alias = "previous_block_id")]
+    pub previous_block_id: String,
+    /// The root of the Merkle tree over the state entries the block wrote
+    #[serde(alias = "write_root")]
+    pub write_root: String,
+    /// The hash of everything else in the block
+    #[serde(alias = "body_hash")]
+    pub body_hash: String,
+}
+
+impl BlockHeader {
+    pub fn new(
+        block_num: u64,
+        previous_block_id: String,
+        write_root: String,
+        body_hash: String,
+    ) -> Self {
+        Self {
+            block_id: block_id(block_num, &previous_block_id, &write_root, &body_hash),
+            block_num,
+            previous_block_id,
+            write_root,
+            body_hash,
+        }
+    }
+
+    /// The id the header's fields hash to, which is its `block_id` unless it has been altered
+    pub fn computed_id(&self) -> String {
+        block_id(
+            self.block_num,
+            &self.previous_block_id,
+            &self.write_root,
+            &self.body_hash,
+        )
+    }
+}
+
+fn block_id(block_num: u64, previous_block_id: &str, write_root: &str, body_hash: &str) -> String {
+    let mut hasher = Sha256::new().chain_update(block_num.to_be_bytes());
+    for field in [previous_block_id, write_root, body_hash] {
+        hasher.update((field.len() as u64).to_be_bytes());
+        hasher.update(field.as_bytes());
+    }
+
+    hex::encode(hasher.finalize())
+}
+
+/// Which side of the path a sibling hash is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofStep {
+    pub sibling: String,
+    pub side: Side,
+}
+
+/// Proof that a block wrote a state entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InclusionProof {
+    #[serde(flatten)]
+    pub header: BlockHeader,
+    /// The ledger state address of the record
+    pub address: String,
+    /// The record's state entry, hex encoded
+    pub value: String,
+    /// Sibling hashes from the leaf up to the write root
+    pub path: Vec<ProofStep>,
+}
+
+pub fn leaf_hash(address: &str, value: &[u8]) -> Hash {
+    Sha256::new()
+        .chain_update([LEAF_PREFIX])
+        .chain_update((address.len() as u64).to_be_bytes())
+        .chain_update(address.as_bytes())
+        .chain_update(value)
+        .finalize()
+        .into()
+}
+
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    Sha256::new()
+        .chain_update([NODE_PREFIX])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .into()
+}
+
+fn next_level(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node_hash(left, right),
+            [single] => *single,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// The root of the tree over `leaves`, or the hash of nothing if there are none
+pub fn merkle_root(leaves: &[Hash]) -> Hash {
+    if leaves.is_empty() {
+        return Sha256::digest([]).into();
+    }
+
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+
+    level[0]
+}
+
+/// The sibling hashes from the leaf at `index` up to the root
+pub fn merkle_path(leaves: &[Hash], mut index: usize) -> Vec<ProofStep> {
+    let mut path = vec![];
+    let mut level = leaves.to_vec();
+
+    while level.len() > 1 {
+        let sibling = index ^ 1;
+        if let Some(hash) = level.get(sibling) {
+            path.push(ProofStep {
+                sibling: hex::encode(hash),
+                side: if sibling < index {
+                    Side::Left
+                } else {
+                    Side::Right
+                },
+            });
+        }
+
+        level = next_level(&level);
+        index /= 2;
+    }
+
+    path
+}
+
+fn leaves<'a>(entries: impl IntoIterator<Item = (&'a str, &'a [u8])>) -> Vec<Hash> {
+    entries
+        .into_iter()
+        .map(|(address, value)| leaf_hash(address, value))
+        .collect()
+}
+
+/// The write root of a block writing `entries`, hex encoded
+pub fn write_root<'a>(entries: impl IntoIterator<Item = (&'a str, &'a [u8])>) -> String {
+    hex::encode(merkle_root(&leaves(entries)))
+}
+
+/// Proofs of each of the state entries the block with `header` writes, in the order it writes
+/// them. The header's write root must be that of `entries`.
+pub fn prove_entries<'a>(
+    header: &BlockHeader,
+    entries: impl IntoIterator<Item = (&'a str, &'a [u8])> + Clone,
+) -> Vec<InclusionProof> {
+    let leaves = leaves(entries.clone());
+
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, (address, value))| InclusionProof {
+            header: header.clone(),
+            address: address.to_owned(),
+            value: hex::encode(value),
+            path: merkle_path(&leaves, index),
+        })
+        .collect()
+}
+
+fn decode_hash(hash: &str) -> Result<Hash, ProofError> {
+    hex::decode(hash)?
+        .try_into()
+        .map_err(|_| ProofError::HashLength(hash.to_owned()))
+}
+
+/// Records are the same if their bytes are, or if both are the same JSON, as an export may
+/// lay it out differently
+fn same_record(record: &[u8], value: &[u8]) -> bool {
+    record == value
+        || matches!(
+            (
+                serde_json::from_slice::<serde_json::Value>(record),
+                serde_json::from_slice::<serde_json::Value>(value),
+            ),
+            (Ok(record), Ok(value)) if record == value
+        )
+}
+
+/// Check that `proof` shows the block with the trusted id `block_id` wrote its state entry, and
+/// if a `record` is given, that it is that entry
+pub fn verify(
+    proof: &InclusionProof,
+    block_id: &str,
+    record: Option<&[u8]>,
+) -> Result<(), ProofError> {
+    if proof.header.block_id != block_id {
+        return Err(ProofError::WrongBlock {
+            proof: proof.header.block_id.clone(),
+            expected: block_id.to_owned(),
+        });
+    }
+
+    let computed = proof.header.computed_id();
+    if computed != block_id {
+        return Err(ProofError::HeaderMismatch {
+            block_id: block_id.to_owned(),
+            computed,
+        });
+    }
+
+    let value = hex::decode(&proof.value)?;
+    if let Some(record) = record {
+        if !same_record(record, &value) {
+            return Err(ProofError::RecordMismatch);
+        }
+    }
+
+    let mut hash = leaf_hash(&proof.address, &value);
+    for step in &proof.path {
+        let sibling = decode_hash(&step.sibling)?;
+        hash = match step.side {
+            Side::Left => node_hash(&sibling, &hash),
+            Side::Right => node_hash(&hash, &sibling),
+        };
+    }
+
+    let computed = hex::encode(hash);
+    if computed != proof.header.write_root {
+        return Err(ProofError::PathMismatch {
+            computed,
+            write_root: proof.header.write_root.clone(),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entries(count: usize) -> Vec<(String, Vec<u8>)> {
+        (0..count)
+            .map(|i| {
+                (
+                    format!("{i:070x}"),
+                    format!("{{\"value\":{i}}}").into_bytes(),
+                )
+            })
+            .collect()
+    }
+
+    fn proofs(entries: &[(String, Vec<u8>)]) -> (BlockHeader, Vec<InclusionProof>) {
+        let entries = entries
+            .iter()
+            .map(|(address, value)| (address.as_str(), value.as_slice()));
+        let header = BlockHeader::new(
+            1,
+            "genesis".to_owned(),
+            write_root(entries.clone()),
+            hex::encode(Sha256::digest(b"body")),
+        );
+
+        let proofs = prove_entries(&header, entries);
+        (header, proofs)
+    }
+
+    #[test]
+    fn every_entry_proves_inclusion() {
+        for count in 1..=9 {
+            let entries = entries(count);
+            let (header, proofs) = proofs(&entries);
+
+            assert_eq!(proofs.len(), count);
+            for (proof, (_, value)) in proofs.iter().zip(&entries) {
+                verify(proof, &header.block_id, Some(value)).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn reformatted_record_matches() {
+        let entries = entries(3);
+        let (header, proofs) = proofs(&entries);
+
+        verify(&proofs[1], &header.block_id, Some(b"{ \"value\": 1 }")).unwrap();
+    }
+
+    #[test]
+    fn altered_proofs_are_rejected() {
+        let entries = entries(5);
+        let (header, proofs) = proofs(&entries);
+
+        let mut proof = proofs[2].clone();
+        proof.value = hex::encode(b"{\"value\":7}");
+        assert!(matches!(
+            verify(&proof, &header.block_id, None),
+            Err(ProofError::PathMismatch { .. })
+        ));
+
+        let mut proof = proofs[2].clone();
+        proof.address = entries[3].0.clone();
+        assert!(matches!(
+            verify(&proof, &header.block_id, None),
+            Err(ProofError::PathMismatch { .. })
+        ));
+
+        let mut proof = proofs[2].clone();
+        proof.path[0].side = Side::Left;
+        assert!(verify(&proof, &header.block_id, None).is_err());
+
+        assert!(matches!(
+            verify(&proofs[2], &header.block_id, Some(b"{\"value\":3}")),
+            Err(ProofError::RecordMismatch)
+        ));
+
+        assert!(matches!(
+            verify(&proofs[2], "other", None),
+            Err(ProofError::WrongBlock { .. })
+        ));
+    }
+
+    #[test]
+    fn proofs_against_another_write_root_are_rejected() {
+        let entries = entries(4);
+        let (header, proofs) = proofs(&entries);
+
+        // A proof of an entry the block never wrote, against a write root of its own
+        let forged = [("forged", b"{}".as_slice())];
+        let mut proof = prove_entries(
+            &BlockHeader {
+                write_root: write_root(forged),
+                ..header.clone()
+            },
+            forged,
+        )
+        .remove(0);
+        assert!(matches!(
+            verify(&proof, &header.block_id, None),
+            Err(ProofError::HeaderMismatch { .. })
+        ));
+
+        proof = proofs[0].clone();
+        proof.header.block_num = 2;
+        assert!(matches!(
+            verify(&proof, &header.block_id, None),
+            Err(ProofError::HeaderMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn headers_read_snake_and_camel_case() {
+        let entries = entries(1);
+        let (header, proofs) = proofs(&entries);
+
+        let json = serde_json::to_value(&proofs[0]).unwrap();
+        assert_eq!(json["blockId"], header.block_id);
+        assert_eq!(json["writeRoot"], header.write_root);
+
+        let snake_case = serde_json::json!({
+            "block_id": header.block_id,
+            "block_num": header.block_num,
+            "previous_block_id": header.previous_block_id,
+            "write_root": header.write_root,
+            "body_hash": header.body_hash,
+        });
+        assert_eq!(
+            serde_json::from_value::<BlockHeader>(snake_case).unwrap(),
+            header
+        );
+    }
+}
@@ -1,3 +1,4 @@
+#![allow(clippy::result_large_err)]
 use async_stl_client::{
     ledger::SawtoothLedger,
     zmq_client::{RetryingRequestResponseChannel, ZmqRequestResponseSawtoothChannel},
@@ -8,6 +9,7 @@ pub mod address;
 pub mod messages;
 pub mod protocol;
 pub mod settings;
+pub mod state;
 
 pub use async_stl_client;
 use protocol::ChronicleOperationEvent;
@@ -1,9 +1,12 @@
 use std::sync::Arc;
 
 use chronicle_signing::{BatcherKnownKeyNamesSigner, ChronicleSigning, SecretError};
-use common::prov::{to_json_ld::ToJson, ChronicleTransaction};
+use common::{
+    identity::{AgentSignedOperations, SignedIdentity},
+    ledger::LedgerAddress,
+    prov::{to_json_ld::ToJson, AgentId, ChronicleTransaction, ExternalIdPart},
+};
 use k256::ecdsa::VerifyingKey;
-use opa_tp_protocol::state::{policy_address, policy_meta_address};
 use serde_json::json;
 
 use crate::{
@@ -11,6 +14,9 @@ use crate::{
     protocol::ProtocolError,
     sawtooth::submission::{BodyVariant, IdentityVariant},
     settings::sawtooth_settings_address,
+    state::{
+        agent_nonce_address, chronicle_keys_address, SignedKeyRotation, BOOTSTRAP_KEY_SETTING,
+    },
     PROTOCOL_VERSION, SUBMISSION_BODY_VERSION,
 };
 
@@ -26,6 +32,10 @@ pub struct ChronicleSubmitTransaction {
     pub tx: ChronicleTransaction,
     pub signer: ChronicleSigning,
     pub policy_name: Option<String>,
+    /// Rotate the Chronicle key rather than apply `tx`, which is then empty
+    pub key_rotation: Option<SignedKeyRotation>,
+    /// The operations of `tx` as signed by an agent, which are sent in place of `tx`'s own
+    pub agent_signed: Option<AgentSignedOperations>,
 }
 
 #[async_trait::async_trait]
@@ -53,7 +63,19 @@ impl TransactionPayload for ChronicleSubmitTransaction {
         let identity_json = serde_json::to_string(&self.tx.identity)?;
         tracing::debug!(ops_json = %ops_json, identity_json = %identity_json);
 
-        submission.body_variant = Some(BodyVariant::Body(BodyMessageV1 { payload: ops_json }));
+        submission.body_variant = Some(match (&self.key_rotation, &self.agent_signed) {
+            (Some(rotation), _) => BodyVariant::KeyRotation(KeyRotationMessageV1 {
+                payload: rotation.payload.clone(),
+                previous_signature: rotation.previous_signature.clone(),
+                new_signature: rotation.new_signature.clone(),
+            }),
+            (None, Some(signed)) => BodyVariant::AgentSigned(AgentSignedBodyV1 {
+                payload: signed.payload.clone(),
+                agent: signed.agent.external_id_part().to_string(),
+                signature: signed.signature.clone(),
+            }),
+            (None, None) => BodyVariant::Body(BodyMessageV1 { payload: ops_json }),
+        });
         submission.identity_variant = Some(IdentityVariant::Identity(IdentityMessageV1 {
             payload: identity_json,
         }));
@@ -71,6 +93,32 @@ impl ChronicleSubmitTransaction {
             tx,
             signer,
             policy_name,
+            key_rotation: None,
+            agent_signed: None,
+        }
+    }
+
+    /// Submit operations signed by an agent, which `tx` holds along with the identity of the
+    /// agent, signed by Chronicle
+    pub fn agent_signed(
+        tx: ChronicleTransaction,
+        signed: AgentSignedOperations,
+        signer: ChronicleSigning,
+        policy_name: Option<String>,
+    ) -> Self {
+        Self {
+            agent_signed: Some(signed),
+            ..Self::new(tx, signer, policy_name)
+        }
+    }
+
+    pub fn rotate_key(rotation: SignedKeyRotation, signer: ChronicleSigning) -> Self {
+        Self {
+            tx: ChronicleTransaction::new(vec![], SignedIdentity::new_no_identity()),
+            signer,
+            policy_name: None,
+            key_rotation: Some(rotation),
+            agent_signed: None,
         }
     }
 }
@@ -88,11 +136,30 @@ impl LedgerTransaction for ChronicleSubmitTransaction {
     }
 
     fn addresses(&self) -> Vec<String> {
+        // The agent's registered key is read from its ledger entry
+        let signing_agent = self.agent_signed.as_ref().and_then(|signed| {
+            self.tx
+                .tx
+                .first()
+                .map(|op| LedgerAddress::in_namespace(op.namespace(), signed.agent.clone()))
+        });
+
+        // As is the agent's use of its nonce
+        let nonce = self.agent_signed.as_ref().and_then(|signed| {
+            signed.signed_payload().ok().map(|payload| {
+                agent_nonce_address(&payload.namespace, &signed.agent, &payload.nonce)
+            })
+        });
+
         self.tx
             .tx
             .iter()
             .flat_map(|op| op.dependencies())
+            .chain(signing_agent)
             .map(|dep| SawtoothAddress::from(&dep).to_string())
+            .chain(nonce)
+            // Identities are checked against the registered Chronicle keys
+            .chain(std::iter::once(chronicle_keys_address()))
             .collect::<std::collections::HashSet<_>>()
             .into_iter()
             .collect()
@@ -102,9 +169,8 @@ impl LedgerTransaction for ChronicleSubmitTransaction {
         &self,
         message_builder: &MessageBuilder,
     ) -> Result<(async_stl_client::messages::Transaction, TransactionId), Self::Error> {
-        //Ensure we append any opa policy binary address and meta address to the
-        //list of addresses, along with the settings address
-        let mut addresses: Vec<_> = self
+        //Ensure we append the settings addresses to the list of addresses
+        let addresses: Vec<_> = self
             .addresses()
             .into_iter()
             .chain(vec![
@@ -113,18 +179,20 @@ impl LedgerTransaction for ChronicleSubmitTransaction {
             ])
             .collect();
 
+        // With a policy set, operations may be checked against the policy of their namespace
+        // rather than this one, so the transaction can read any of the OPA transaction
+        // processor's state
+        let mut inputs = addresses.clone();
         if self.policy_name.is_some() {
-            addresses = addresses
-                .into_iter()
-                .chain(vec![
-                    policy_address(self.policy_name.as_ref().unwrap()),
-                    policy_meta_address(self.policy_name.as_ref().unwrap()),
-                ])
-                .collect();
+            inputs.push(opa_tp_protocol::address::PREFIX.to_string());
+        }
+        // The first rotation is checked against the bootstrap key set on chain
+        if self.key_rotation.is_some() {
+            inputs.push(sawtooth_settings_address(BOOTSTRAP_KEY_SETTING));
         }
         message_builder
             .make_sawtooth_transaction(
-                addresses.clone(),
+                inputs,
                 addresses,
                 vec![],
                 self,
@@ -138,3 +206,23 @@ impl LedgerTransaction for ChronicleSubmitTransaction {
             .await
     }
 }
+
+impl From<crate::protocol::messages::AgentSignedBodyV1> for AgentSignedOperations {
+    fn from(message: crate::protocol::messages::AgentSignedBodyV1) -> Self {
+        Self {
+            agent: AgentId::from_external_id(message.agent),
+            payload: message.payload,
+            signature: message.signature,
+        }
+    }
+}
+
+impl From<crate::protocol::messages::KeyRotationMessageV1> for SignedKeyRotation {
+    fn from(message: crate::protocol::messages::KeyRotationMessageV1) -> Self {
+        Self {
+            payload: message.payload,
+            previous_signature: message.previous_signature,
+            new_signature: message.new_signature,
+        }
+    }
+}
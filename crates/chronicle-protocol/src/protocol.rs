@@ -16,10 +16,18 @@ use tracing::span;
 
 use thiserror::Error;
 
+use crate::state::ChronicleKeys;
+
 use self::messages::event::OptionContradiction;
 
+/// The outcome of a transaction. A rotation of the Chronicle key commits no provenance, and
+/// carries the keys registered as a result.
 #[derive(Debug)]
-pub struct ChronicleOperationEvent(pub Result<ProvModel, Contradiction>, pub SignedIdentity);
+pub struct ChronicleOperationEvent(
+    pub Result<ProvModel, Contradiction>,
+    pub SignedIdentity,
+    pub Option<ChronicleKeys>,
+);
 
 impl From<ChronicleOperationEvent> for Result<ProvModel, Contradiction> {
     fn from(val: ChronicleOperationEvent) -> Self {
@@ -45,7 +53,18 @@ impl LedgerEvent for ChronicleOperationEvent {
                 span::Id::from_u64(event.span_id)
             }
         };
+        let keys = if event.keys.is_empty() {
+            None
+        } else {
+            Some(
+                serde_json::from_str::<ChronicleKeys>(&event.keys).map_err(|e| {
+                    SawtoothCommunicationError::LedgerEventParse { source: e.into() }
+                })?,
+            )
+        };
+
         let model = match (event.delta, event.option_contradiction) {
+            _ if keys.is_some() => Ok(ProvModel::default()),
             (_, Some(OptionContradiction::Contradiction(contradiction))) => Err(
                 serde_json::from_str::<Contradiction>(&contradiction).map_err(|e| {
                     SawtoothCommunicationError::LedgerEventParse { source: e.into() }
@@ -70,7 +89,7 @@ impl LedgerEvent for ChronicleOperationEvent {
                 })?
             }
         };
-        Ok((Self(model, identity), Span::Span(span_id.into_u64())))
+        Ok((Self(model, identity, keys), Span::Span(span_id.into_u64())))
     }
 }
 
@@ -143,6 +162,18 @@ pub fn chronicle_contradicted(
     })
 }
 
+pub fn chronicle_keys_rotated(
+    span: u64,
+    keys: &ChronicleKeys,
+) -> Result<messages::Event, ProtocolError> {
+    Ok(messages::Event {
+        version: PROTOCOL_VERSION.to_owned(),
+        span_id: span,
+        keys: serde_json::to_string(keys)?,
+        ..Default::default()
+    })
+}
+
 impl messages::Event {
     pub async fn get_contradiction(&self) -> Result<Option<Contradiction>, ProtocolError> {
         Ok(self
@@ -161,8 +192,7 @@ impl messages::Event {
 }
 
 pub fn serialize_submission(submission: &messages::Submission) -> Vec<u8> {
-    let mut buf = Vec::new();
-    buf.reserve(submission.encoded_len());
+    let mut buf = Vec::with_capacity(submission.encoded_len());
     submission.encode(&mut buf).unwrap();
     buf
 }
@@ -0,0 +1,465 @@
+use chronicle_signing::{
+    ChronicleKnownKeyNamesSigner, ChronicleSigning, SecretError, TaggedVerifyingKey,
+    CHRONICLE_NAMESPACE, CHRONICLE_PK,
+};
+use common::prov::AgentId;
+use k256::sha2::{Digest, Sha256};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::address::PREFIX;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRegistration {
+    // PEM encoded public key
+    pub key: String,
+    pub version: u64,
+}
+
+impl KeyRegistration {
+    pub fn verifying_key(&self) -> Result<TaggedVerifyingKey, KeyRotationError> {
+        TaggedVerifyingKey::from_public_key_pem(&self.key).map_err(|_| KeyRotationError::InvalidKey)
+    }
+}
+
+/// The Chronicle keys registered on the ledger. Identities must be signed with the current key,
+/// and those signed before a rotation can be checked against the expired keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChronicleKeys {
+    pub current: KeyRegistration,
+    // Oldest first
+    pub expired: Vec<KeyRegistration>,
+}
+
+impl ChronicleKeys {
+    /// The registration of `key`, whether current or expired
+    pub fn registration(&self, key: &TaggedVerifyingKey) -> Option<&KeyRegistration> {
+        std::iter::once(&self.current)
+            .chain(self.expired.iter().rev())
+            .find(|registration| registration.verifying_key().ok().as_ref() == Some(key))
+    }
+}
+
+/// The Sawtooth setting holding the key that may make the first rotation, as the settings
+/// transaction processor's authorized keys propose and vote on it. The key is written as
+/// [`TaggedVerifyingKey`] reads it, the hex of its SEC1 encoding for a secp256k1 key.
+pub const BOOTSTRAP_KEY_SETTING: &str = "chronicle.keys.bootstrap";
+
+/// The Transit version of the Chronicle key registered as `version`. A Chronicle key held by
+/// Vault Transit is registered from its first Transit version as the bootstrap key, version 0,
+/// and each rotation adds a version to both.
+pub fn transit_key_version(version: u64) -> u64 {
+    version + 1
+}
+
+pub fn chronicle_keys_address() -> String {
+    format!(
+        "{}{}",
+        &*PREFIX,
+        hex::encode(Sha256::digest("chronicle:keys".as_bytes()))
+    )
+}
+
+/// Where the use of `nonce` by `agent` to sign operations in `namespace` is recorded
+pub fn agent_nonce_address(namespace: &str, agent: &AgentId, nonce: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in ["chronicle:nonce", namespace, &agent.to_string(), nonce] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+
+    format!("{}{}", &*PREFIX, hex::encode(hasher.finalize()))
+}
+
+#[derive(Error, Debug)]
+pub enum KeyRotationError {
+    #[error("Signer : {0}")]
+    Signing(#[from] SecretError),
+
+    #[error("Malformed JSON: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+
+    #[error("Invalid public key")]
+    InvalidKey,
+
+    #[error("Rotation is from a key that is not the current Chronicle key")]
+    NotCurrentKey,
+
+    #[error("No bootstrap key is set with {BOOTSTRAP_KEY_SETTING} to make the first rotation")]
+    NoBootstrapKey,
+
+    #[error("First rotation is from a key that is not the bootstrap key")]
+    NotBootstrapKey,
+
+    #[error("Rotation is to version {got}, expected {expected}")]
+    Version { expected: u64, got: u64 },
+
+    #[error("Rotation is not signed by both keys")]
+    BadSignature,
+
+    #[error(
+        "Chronicle key version {version} is Transit version {transit}, but Transit holds version \
+         {latest}"
+    )]
+    TransitVersion {
+        version: u64,
+        transit: u64,
+        latest: u64,
+    },
+}
+
+/// Replaces the current Chronicle key with a new one, or registers the first two keys
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotateKey {
+    pub previous_key: String,
+    pub new_key: String,
+    /// The version the new key will have, so that a rotation cannot be replayed
+    pub version: u64,
+}
+
+/// A rotation signed by the previous key, which authorizes it, and the new key, which proves
+/// it is held by whoever rotates to it. Both sign the JSON `payload` as it is sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedKeyRotation {
+    pub payload: String,
+    pub previous_signature: Vec<u8>,
+    pub new_signature: Vec<u8>,
+}
+
+fn public_key_pem(key: &TaggedVerifyingKey) -> Result<String, KeyRotationError> {
+    key.to_public_key_pem()
+        .map_err(|_| KeyRotationError::InvalidKey)
+}
+
+impl SignedKeyRotation {
+    /// Rotate from the Chronicle key of `previous` to that of `new`, after the `registered`
+    /// keys if there are any
+    pub async fn new<S: ChronicleKnownKeyNamesSigner>(
+        previous: &S,
+        new: &S,
+        registered: Option<&ChronicleKeys>,
+    ) -> Result<Self, KeyRotationError> {
+        let rotation = RotateKey {
+            previous_key: public_key_pem(&previous.chronicle_verifying().await?)?,
+            new_key: public_key_pem(&new.chronicle_verifying().await?)?,
+            version: registered.map_or(1, |keys| keys.current.version + 1),
+        };
+        let payload = serde_json::to_string(&rotation)?;
+
+        Ok(Self {
+            previous_signature: previous.chronicle_sign(payload.as_bytes()).await?,
+            new_signature: new.chronicle_sign(payload.as_bytes()).await?,
+            payload,
+        })
+    }
+
+    /// Rotate the Chronicle key `signing` holds in Vault Transit to its next Transit version,
+    /// after the `registered` keys if there are any. Transit is only rotated if its latest
+    /// version is already registered, so a rotation that was never committed is registered
+    /// rather than rotated again.
+    pub async fn in_transit(
+        signing: &ChronicleSigning,
+        registered: Option<&ChronicleKeys>,
+    ) -> Result<Self, KeyRotationError> {
+        let version = registered.map_or(1, |keys| keys.current.version + 1);
+        let transit = transit_key_version(version);
+
+        let mut latest = signing
+            .transit_version(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+            .await?;
+        if latest < transit {
+            latest = signing
+                .rotate_transit_key(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+                .await?;
+        }
+        if latest != transit {
+            return Err(KeyRotationError::TransitVersion {
+                version,
+                transit,
+                latest,
+            });
+        }
+
+        Self::new(
+            &signing.at_transit_version(CHRONICLE_NAMESPACE, transit - 1)?,
+            &signing.at_transit_version(CHRONICLE_NAMESPACE, transit)?,
+            registered,
+        )
+        .await
+    }
+
+    pub fn rotation(&self) -> Result<RotateKey, KeyRotationError> {
+        Ok(serde_json::from_str(&self.payload)?)
+    }
+
+    /// Check the rotation against the `registered` keys, returning the keys that result from it.
+    /// With nothing yet registered, the rotation must be from the `bootstrap` key, which becomes
+    /// version 0.
+    pub fn apply(
+        &self,
+        registered: Option<ChronicleKeys>,
+        bootstrap: Option<&TaggedVerifyingKey>,
+    ) -> Result<ChronicleKeys, KeyRotationError> {
+        let rotation = self.rotation()?;
+        let previous_key = TaggedVerifyingKey::from_public_key_pem(&rotation.previous_key)
+            .map_err(|_| KeyRotationError::InvalidKey)?;
+        let new_key = TaggedVerifyingKey::from_public_key_pem(&rotation.new_key)
+            .map_err(|_| KeyRotationError::InvalidKey)?;
+
+        for (key, signature) in [
+            (&previous_key, &self.previous_signature),
+            (&new_key, &self.new_signature),
+        ] {
+            key.verify(self.payload.as_bytes(), signature)
+                .map_err(|_| KeyRotationError::BadSignature)?;
+        }
+
+        let mut keys = match registered {
+            Some(keys) => keys,
+            None => match bootstrap {
+                Some(bootstrap) if *bootstrap == previous_key => ChronicleKeys {
+                    current: KeyRegistration {
+                        key: rotation.previous_key.clone(),
+                        version: 0,
+                    },
+                    expired: vec![],
+                },
+                Some(_) => return Err(KeyRotationError::NotBootstrapKey),
+                None => return Err(KeyRotationError::NoBootstrapKey),
+            },
+        };
+
+        if keys.current.verifying_key()? != previous_key {
+            return Err(KeyRotationError::NotCurrentKey);
+        }
+        if rotation.version != keys.current.version + 1 {
+            return Err(KeyRotationError::Version {
+                expected: keys.current.version + 1,
+                got: rotation.version,
+            });
+        }
+
+        let previous = std::mem::replace(
+            &mut keys.current,
+            KeyRegistration {
+                key: rotation.new_key.clone(),
+                version: rotation.version,
+            },
+        );
+        keys.expired.push(previous);
+
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chronicle_signing::{chronicle_secret_names, ChronicleSecretsOptions};
+    use testcontainers::{clients::Cli, core::WaitFor, images::generic::GenericImage, Container};
+    use url::Url;
+    use vaultrs::{
+        api::transit::{requests::CreateKeyRequest, KeyType},
+        client::{VaultClient, VaultClientSettingsBuilder},
+    };
+
+    async fn embedded_signing() -> ChronicleSigning {
+        ChronicleSigning::new(
+            chronicle_secret_names(),
+            vec![(
+                CHRONICLE_NAMESPACE.to_string(),
+                ChronicleSecretsOptions::Embedded,
+            )],
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn rotations_are_chained() {
+        let (first, second, third) = (
+            embedded_signing().await,
+            embedded_signing().await,
+            embedded_signing().await,
+        );
+
+        let first_key = first.chronicle_verifying().await.unwrap();
+        let rotation = SignedKeyRotation::new(&first, &second, None).await.unwrap();
+        let keys = rotation.apply(None, Some(&first_key)).unwrap();
+
+        assert_eq!(keys.current.version, 1);
+        assert_eq!(keys.expired.len(), 1);
+        assert_eq!(keys.registration(&first_key).unwrap().version, 0);
+
+        // Replaying the rotation now fails, as does rotating from an expired key
+        assert!(matches!(
+            rotation.apply(Some(keys.clone()), Some(&first_key)),
+            Err(KeyRotationError::NotCurrentKey)
+        ));
+        let from_expired = SignedKeyRotation::new(&first, &third, Some(&keys))
+            .await
+            .unwrap();
+        assert!(matches!(
+            from_expired.apply(Some(keys.clone()), Some(&first_key)),
+            Err(KeyRotationError::NotCurrentKey)
+        ));
+
+        let rotation = SignedKeyRotation::new(&second, &third, Some(&keys))
+            .await
+            .unwrap();
+        let keys = rotation.apply(Some(keys), Some(&first_key)).unwrap();
+
+        assert_eq!(keys.current.version, 2);
+        let second_key = second.chronicle_verifying().await.unwrap();
+        assert_eq!(keys.registration(&second_key).unwrap().version, 1);
+    }
+
+    #[tokio::test]
+    async fn rotation_must_be_signed_by_both_keys() {
+        let (first, second) = (embedded_signing().await, embedded_signing().await);
+
+        let first_key = first.chronicle_verifying().await.unwrap();
+        let mut rotation = SignedKeyRotation::new(&first, &second, None).await.unwrap();
+        rotation.new_signature = rotation.previous_signature.clone();
+
+        assert!(matches!(
+            rotation.apply(None, Some(&first_key)),
+            Err(KeyRotationError::BadSignature)
+        ));
+    }
+
+    #[tokio::test]
+    async fn first_rotation_must_be_from_the_bootstrap_key() {
+        let (bootstrap, stranger, new) = (
+            embedded_signing().await,
+            embedded_signing().await,
+            embedded_signing().await,
+        );
+        let bootstrap_key = bootstrap.chronicle_verifying().await.unwrap();
+
+        let rotation = SignedKeyRotation::new(&stranger, &new, None).await.unwrap();
+        assert!(matches!(
+            rotation.apply(None, Some(&bootstrap_key)),
+            Err(KeyRotationError::NotBootstrapKey)
+        ));
+        assert!(matches!(
+            rotation.apply(None, None),
+            Err(KeyRotationError::NoBootstrapKey)
+        ));
+
+        let rotation = SignedKeyRotation::new(&bootstrap, &new, None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            rotation.apply(None, None),
+            Err(KeyRotationError::NoBootstrapKey)
+        ));
+        assert_eq!(
+            rotation
+                .apply(None, Some(&bootstrap_key))
+                .unwrap()
+                .current
+                .version,
+            1
+        );
+    }
+
+    /// Start a Vault dev server with an Ed25519 'chronicle-pk' key in its Transit engine, and
+    /// sign with it
+    async fn transit_signing(docker: &Cli) -> (Container<'_, GenericImage>, ChronicleSigning) {
+        let container = docker.run(
+            GenericImage::new("hashicorp/vault", "1.13")
+                .with_env_var("VAULT_DEV_ROOT_TOKEN_ID", "root")
+                .with_env_var("VAULT_DEV_LISTEN_ADDRESS", "0.0.0.0:8200")
+                .with_env_var("SKIP_SETCAP", "true")
+                .with_exposed_port(8200)
+                .with_wait_for(WaitFor::message_on_stdout("Vault server started!")),
+        );
+        let url = Url::parse(&format!(
+            "http://127.0.0.1:{}",
+            container.get_host_port_ipv4(8200)
+        ))
+        .unwrap();
+
+        let client = VaultClient::new(
+            VaultClientSettingsBuilder::default()
+                .address(url.as_str())
+                .token("root")
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+        vaultrs::sys::mount::enable(&client, "transit", "transit", None)
+            .await
+            .unwrap();
+        vaultrs::transit::key::create(
+            &client,
+            "transit",
+            CHRONICLE_PK,
+            Some(CreateKeyRequest::builder().key_type(KeyType::Ed25519)),
+        )
+        .await
+        .unwrap();
+
+        let signing = ChronicleSigning::new(
+            vec![(CHRONICLE_NAMESPACE.to_string(), CHRONICLE_PK.to_string())],
+            vec![(
+                CHRONICLE_NAMESPACE.to_string(),
+                ChronicleSecretsOptions::signed_by_vault_transit(&url, "root", "transit"),
+            )],
+        )
+        .await
+        .unwrap();
+
+        (container, signing)
+    }
+
+    #[tokio::test]
+    async fn transit_versions_are_registered_as_chronicle_key_versions() {
+        let docker = Cli::default();
+        let (_container, signing) = transit_signing(&docker).await;
+
+        let bootstrap_key = signing.chronicle_verifying().await.unwrap();
+        let rotation = SignedKeyRotation::in_transit(&signing, None).await.unwrap();
+        assert_eq!(
+            signing
+                .transit_version(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+                .await
+                .unwrap(),
+            2
+        );
+
+        // A rotation that was never committed is made again from the same Transit version
+        let retried = SignedKeyRotation::in_transit(&signing, None).await.unwrap();
+        assert_eq!(
+            signing
+                .transit_version(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(retried.rotation().unwrap(), rotation.rotation().unwrap());
+
+        let keys = rotation.apply(None, Some(&bootstrap_key)).unwrap();
+        assert_eq!(keys.current.version, 1);
+        assert_eq!(
+            keys.current.verifying_key().unwrap(),
+            signing.chronicle_verifying().await.unwrap()
+        );
+        assert_eq!(keys.registration(&bootstrap_key).unwrap().version, 0);
+
+        let rotation = SignedKeyRotation::in_transit(&signing, Some(&keys))
+            .await
+            .unwrap();
+        let keys = rotation.apply(Some(keys), Some(&bootstrap_key)).unwrap();
+        assert_eq!(keys.current.version, 2);
+        assert_eq!(
+            signing
+                .transit_version(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+                .await
+                .unwrap(),
+            transit_key_version(keys.current.version)
+        );
+    }
+}
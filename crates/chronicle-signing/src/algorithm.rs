@@ -0,0 +1,318 @@
+use std::{fmt, str::FromStr};
+
+use ed25519_dalek::pkcs8::{
+    spki::der::pem::LineEnding as Ed25519LineEnding, DecodePublicKey as DecodeEd25519PublicKey,
+    EncodePublicKey as EncodeEd25519PublicKey,
+};
+use k256::pkcs8::{DecodePublicKey, EncodePublicKey, LineEnding};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::SecretError;
+
+/// The signature algorithms Chronicle can verify
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyAlgorithm {
+    /// ECDSA over secp256k1, which Chronicle's own keys use
+    #[default]
+    Secp256k1,
+    Ed25519,
+    /// ECDSA over NIST P-256
+    P256,
+}
+
+impl KeyAlgorithm {
+    pub fn is_secp256k1(&self) -> bool {
+        *self == Self::Secp256k1
+    }
+}
+
+impl fmt::Display for KeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Secp256k1 => write!(f, "secp256k1"),
+            Self::Ed25519 => write!(f, "ed25519"),
+            Self::P256 => write!(f, "p256"),
+        }
+    }
+}
+
+impl FromStr for KeyAlgorithm {
+    type Err = SecretError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "secp256k1" => Ok(Self::Secp256k1),
+            "ed25519" => Ok(Self::Ed25519),
+            "p256" => Ok(Self::P256),
+            other => Err(SecretError::UnknownAlgorithm(other.to_owned())),
+        }
+    }
+}
+
+/// A public key along with the algorithm its signatures are verified with.
+///
+/// As text, keys are the hex of their SEC1 compressed point, or raw bytes for Ed25519, prefixed
+/// with their algorithm as in `ed25519:<hex>`. Secp256k1 keys are written without a prefix, so
+/// keys registered before algorithms were tagged read as they always did. Serialized, keys are
+/// the hex of their DER encoded SubjectPublicKeyInfo, which names the algorithm and is how
+/// secp256k1 verifying keys have always been serialized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaggedVerifyingKey {
+    Secp256k1(k256::ecdsa::VerifyingKey),
+    Ed25519(ed25519_dalek::VerifyingKey),
+    P256(p256::ecdsa::VerifyingKey),
+}
+
+impl From<k256::ecdsa::VerifyingKey> for TaggedVerifyingKey {
+    fn from(key: k256::ecdsa::VerifyingKey) -> Self {
+        Self::Secp256k1(key)
+    }
+}
+
+impl From<ed25519_dalek::VerifyingKey> for TaggedVerifyingKey {
+    fn from(key: ed25519_dalek::VerifyingKey) -> Self {
+        Self::Ed25519(key)
+    }
+}
+
+impl From<p256::ecdsa::VerifyingKey> for TaggedVerifyingKey {
+    fn from(key: p256::ecdsa::VerifyingKey) -> Self {
+        Self::P256(key)
+    }
+}
+
+impl TaggedVerifyingKey {
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        match self {
+            Self::Secp256k1(_) => KeyAlgorithm::Secp256k1,
+            Self::Ed25519(_) => KeyAlgorithm::Ed25519,
+            Self::P256(_) => KeyAlgorithm::P256,
+        }
+    }
+
+    pub fn from_bytes(algorithm: KeyAlgorithm, bytes: &[u8]) -> Result<Self, SecretError> {
+        match algorithm {
+            KeyAlgorithm::Secp256k1 => k256::ecdsa::VerifyingKey::from_sec1_bytes(bytes)
+                .map(Self::Secp256k1)
+                .map_err(|_| SecretError::InvalidPublicKey),
+            KeyAlgorithm::Ed25519 => <&[u8; 32]>::try_from(bytes)
+                .ok()
+                .and_then(|bytes| ed25519_dalek::VerifyingKey::from_bytes(bytes).ok())
+                .map(Self::Ed25519)
+                .ok_or(SecretError::InvalidPublicKey),
+            KeyAlgorithm::P256 => p256::ecdsa::VerifyingKey::from_sec1_bytes(bytes)
+                .map(Self::P256)
+                .map_err(|_| SecretError::InvalidPublicKey),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Secp256k1(key) => key.to_bytes().to_vec(),
+            Self::Ed25519(key) => key.to_bytes().to_vec(),
+            Self::P256(key) => key.to_encoded_point(true).as_bytes().to_vec(),
+        }
+    }
+
+    /// Read a PEM encoded SubjectPublicKeyInfo of any supported algorithm
+    pub fn from_public_key_pem(pem: &str) -> Result<Self, SecretError> {
+        k256::ecdsa::VerifyingKey::from_public_key_pem(pem)
+            .map(Self::Secp256k1)
+            .or_else(|_| p256::ecdsa::VerifyingKey::from_public_key_pem(pem).map(Self::P256))
+            .or_else(|_| {
+                ed25519_dalek::VerifyingKey::from_public_key_pem(pem)
+                    .map(Self::Ed25519)
+                    .map_err(|_| SecretError::InvalidPublicKey)
+            })
+    }
+
+    pub fn from_public_key_der(der: &[u8]) -> Result<Self, SecretError> {
+        k256::ecdsa::VerifyingKey::from_public_key_der(der)
+            .map(Self::Secp256k1)
+            .or_else(|_| p256::ecdsa::VerifyingKey::from_public_key_der(der).map(Self::P256))
+            .or_else(|_| {
+                ed25519_dalek::VerifyingKey::from_public_key_der(der)
+                    .map(Self::Ed25519)
+                    .map_err(|_| SecretError::InvalidPublicKey)
+            })
+    }
+
+    pub fn to_public_key_der(&self) -> Result<Vec<u8>, SecretError> {
+        match self {
+            Self::Secp256k1(key) => k256::PublicKey::from(key)
+                .to_public_key_der()
+                .map(|der| der.as_bytes().to_vec())
+                .map_err(|_| SecretError::InvalidPublicKey),
+            Self::Ed25519(key) => key
+                .to_public_key_der()
+                .map(|der| der.as_bytes().to_vec())
+                .map_err(|_| SecretError::InvalidPublicKey),
+            Self::P256(key) => key
+                .to_public_key_der()
+                .map(|der| der.as_bytes().to_vec())
+                .map_err(|_| SecretError::InvalidPublicKey),
+        }
+    }
+
+    /// PEM encoded SubjectPublicKeyInfo, with the CRLF line endings Chronicle keys have always
+    /// been registered with
+    pub fn to_public_key_pem(&self) -> Result<String, SecretError> {
+        match self {
+            Self::Secp256k1(key) => k256::PublicKey::from(key)
+                .to_public_key_pem(LineEnding::CRLF)
+                .map_err(|_| SecretError::InvalidPublicKey),
+            Self::Ed25519(key) => key
+                .to_public_key_pem(Ed25519LineEnding::CRLF)
+                .map_err(|_| SecretError::InvalidPublicKey),
+            Self::P256(key) => key
+                .to_public_key_pem(LineEnding::CRLF)
+                .map_err(|_| SecretError::InvalidPublicKey),
+        }
+    }
+
+    /// Check `signature` is that of `data`. ECDSA signatures are the 64 bytes of r and s, and
+    /// are over the SHA-256 digest of `data`.
+    pub fn verify(&self, data: &[u8], signature: &[u8]) -> Result<(), SecretError> {
+        match self {
+            Self::Secp256k1(key) => {
+                use k256::ecdsa::signature::Verifier;
+
+                let signature = k256::ecdsa::Signature::try_from(signature)
+                    .map_err(|_| SecretError::BadSignature)?;
+                key.verify(data, &signature)
+                    .map_err(|_| SecretError::BadSignature)
+            }
+            Self::Ed25519(key) => {
+                let signature = ed25519_dalek::Signature::from_slice(signature)
+                    .map_err(|_| SecretError::BadSignature)?;
+                key.verify_strict(data, &signature)
+                    .map_err(|_| SecretError::BadSignature)
+            }
+            Self::P256(key) => {
+                use p256::ecdsa::signature::Verifier;
+
+                let signature = p256::ecdsa::Signature::try_from(signature)
+                    .map_err(|_| SecretError::BadSignature)?;
+                key.verify(data, &signature)
+                    .map_err(|_| SecretError::BadSignature)
+            }
+        }
+    }
+}
+
+impl fmt::Display for TaggedVerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.algorithm() {
+            KeyAlgorithm::Secp256k1 => write!(f, "{}", hex::encode(self.to_bytes())),
+            algorithm => write!(f, "{algorithm}:{}", hex::encode(self.to_bytes())),
+        }
+    }
+}
+
+impl FromStr for TaggedVerifyingKey {
+    type Err = SecretError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, key) = match s.split_once(':') {
+            Some((algorithm, key)) => (algorithm.parse()?, key),
+            None => (KeyAlgorithm::Secp256k1, s),
+        };
+
+        Self::from_bytes(
+            algorithm,
+            &hex::decode(key).map_err(|_| SecretError::InvalidPublicKey)?,
+        )
+    }
+}
+
+impl Serialize for TaggedVerifyingKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let der = self.to_public_key_der().map_err(ser::Error::custom)?;
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&hex::encode_upper(der))
+        } else {
+            serializer.serialize_bytes(&der)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TaggedVerifyingKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let der = if deserializer.is_human_readable() {
+            hex::decode(String::deserialize(deserializer)?).map_err(de::Error::custom)?
+        } else {
+            Vec::<u8>::deserialize(deserializer)?
+        };
+
+        Self::from_public_key_der(&der).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn signatures_verify_with_their_algorithm() {
+        use ed25519_dalek::Signer as _;
+        use k256::ecdsa::signature::Signer as _;
+
+        let data = b"hello world";
+
+        let k256_key = k256::ecdsa::SigningKey::random(StdRng::from_entropy());
+        let k256_signature: k256::ecdsa::Signature = k256_key.sign(data);
+        let ed25519_key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let ed25519_signature = ed25519_key.sign(data);
+        let p256_key = p256::ecdsa::SigningKey::random(StdRng::from_entropy());
+        let p256_signature: p256::ecdsa::Signature = p256_key.sign(data);
+
+        for (key, signature) in [
+            (
+                TaggedVerifyingKey::from(k256_key.verifying_key()),
+                k256_signature.as_ref().to_vec(),
+            ),
+            (
+                TaggedVerifyingKey::from(ed25519_key.verifying_key()),
+                ed25519_signature.to_bytes().to_vec(),
+            ),
+            (
+                TaggedVerifyingKey::from(p256_key.verifying_key()),
+                p256_signature.as_ref().to_vec(),
+            ),
+        ] {
+            assert!(key.verify(data, &signature).is_ok(), "{}", key.algorithm());
+            assert!(matches!(
+                key.verify(b"boom", &signature),
+                Err(SecretError::BadSignature)
+            ));
+
+            let text = key.to_string();
+            assert_eq!(text.parse::<TaggedVerifyingKey>().unwrap(), key);
+            let json = serde_json::to_string(&key).unwrap();
+            assert_eq!(
+                serde_json::from_str::<TaggedVerifyingKey>(&json).unwrap(),
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn secp256k1_keys_read_as_before() {
+        let key = k256::ecdsa::SigningKey::random(StdRng::from_entropy()).verifying_key();
+        let tagged = TaggedVerifyingKey::from(key);
+
+        assert_eq!(tagged.to_string(), hex::encode(key.to_bytes()));
+        assert_eq!(
+            format!("secp256k1:{}", hex::encode(key.to_bytes()))
+                .parse::<TaggedVerifyingKey>()
+                .unwrap(),
+            tagged
+        );
+        assert_eq!(
+            serde_json::to_string(&tagged).unwrap(),
+            serde_json::to_string(&key).unwrap()
+        );
+    }
+}
@@ -10,14 +10,24 @@ use secret_vault::{
     SecretNamespace, SecretVaultBuilder, SecretVaultRef, SecretVaultView,
 };
 use std::{
+    collections::HashMap,
     path::{Path, PathBuf},
     sync::Arc,
 };
 use thiserror::Error;
 use tracing::instrument;
 use url::Url;
+mod algorithm;
 mod embedded_secret_manager_source;
+mod pkcs11_token;
 mod vault_secret_manager_source;
+mod vault_transit;
+
+pub use algorithm::{KeyAlgorithm, TaggedVerifyingKey};
+pub use pkcs11_token::Pkcs11Options;
+use pkcs11_token::{open_token, Pkcs11Token};
+use vault_transit::VaultTransit;
+pub use vault_transit::VaultTransitOptions;
 
 pub static CHRONICLE_NAMESPACE: &str = "chronicle";
 pub static BATCHER_NAMESPACE: &str = "batcher";
@@ -36,12 +46,46 @@ pub enum SecretError {
     NoPublicKeyFound,
     #[error("No private key found")]
     NoPrivateKeyFound,
+    #[error("Unknown key algorithm {0}")]
+    UnknownAlgorithm(String),
+    #[error("Signature does not verify")]
+    BadSignature,
 
     #[error("Vault {source}")]
     SecretVault {
         #[from]
         source: SecretVaultError,
     },
+
+    #[error("PKCS#11: {0}")]
+    Pkcs11(#[from] cryptoki::error::Error),
+
+    #[error("PKCS#11 call did not complete: {0}")]
+    Pkcs11Task(#[from] tokio::task::JoinError),
+
+    #[error("No PKCS#11 token labelled {0}")]
+    TokenNotFound(String),
+
+    #[error("Key {0} is held by a PKCS#11 token or Vault Transit and cannot be exported")]
+    KeyNotExportable(String),
+
+    #[error("Key {0} is {1}, where secp256k1 is needed")]
+    NotSecp256k1(String, KeyAlgorithm),
+
+    #[error("Vault Transit: {0}")]
+    Transit(#[from] vaultrs::error::ClientError),
+
+    #[error("Keys in {0} are not held by Vault Transit")]
+    NotTransit(String),
+
+    #[error("Transit key {0} is of type {1}, which Chronicle cannot sign with")]
+    UnsupportedTransitKey(String, String),
+
+    #[error("Transit key {0} has no version {1}")]
+    NoKeyVersion(String, u64),
+
+    #[error("Malformed Transit signature {0}")]
+    MalformedTransitSignature(String),
 }
 
 pub enum ChronicleSecretsOptions {
@@ -53,6 +97,10 @@ pub enum ChronicleSecretsOptions {
     Test,
     //Filesystem based keys
     Filesystem(PathBuf),
+    // Keys held by a PKCS#11 token, which signs with them
+    Pkcs11(Pkcs11Options),
+    // P-256 or Ed25519 keys held by Vault's Transit engine, which signs with them
+    Transit(VaultTransitOptions),
 }
 
 impl ChronicleSecretsOptions {
@@ -85,11 +133,33 @@ impl ChronicleSecretsOptions {
     pub fn test_keys() -> ChronicleSecretsOptions {
         ChronicleSecretsOptions::Test
     }
+
+    // Sign with keys on a PKCS#11 token, labelled with their secret names
+    pub fn held_by_pkcs11(module: &Path, token_label: &str, pin: &str) -> ChronicleSecretsOptions {
+        ChronicleSecretsOptions::Pkcs11(Pkcs11Options::new(module, token_label, pin))
+    }
+
+    // Sign with keys in the Transit engine at `mount_path`, named with their secret names
+    pub fn signed_by_vault_transit(
+        vault_url: &Url,
+        token: &str,
+        mount_path: &str,
+    ) -> ChronicleSecretsOptions {
+        ChronicleSecretsOptions::Transit(VaultTransitOptions::new(
+            vault_url.clone(),
+            token,
+            mount_path,
+        ))
+    }
 }
 
 #[derive(Clone)]
 pub struct ChronicleSigning {
     vault: Arc<tokio::sync::Mutex<Box<dyn SecretVaultView + Send + Sync>>>,
+    // Tokens holding the keys of their namespace
+    tokens: Arc<HashMap<String, Pkcs11Token>>,
+    // Transit engines holding the keys of their namespace
+    transit: Arc<HashMap<String, VaultTransit>>,
 }
 
 impl std::fmt::Debug for ChronicleSigning {
@@ -106,8 +176,26 @@ impl ChronicleSigning {
         options: Vec<(String, ChronicleSecretsOptions)>,
     ) -> Result<Self, SecretError> {
         let mut multi_source = MultipleSecretsSources::new();
+        let mut tokens = HashMap::new();
+        let mut transit = HashMap::new();
+        for (namespace, options) in &options {
+            match options {
+                ChronicleSecretsOptions::Pkcs11(options) => {
+                    tokens.insert(namespace.clone(), open_token(options)?);
+                }
+                ChronicleSecretsOptions::Transit(options) => {
+                    transit.insert(namespace.clone(), VaultTransit::new(options)?);
+                }
+                _ => {}
+            }
+        }
+
+        // Keys on a token or in Transit are not secrets the vault can provide
         let required_secret_refs: Vec<_> = required_secret_names
             .into_iter()
+            .filter(|(namespace, _)| {
+                !tokens.contains_key(namespace) && !transit.contains_key(namespace)
+            })
             .map(|(namespace, name)| {
                 SecretVaultRef::new(SecretName::new(name))
                     .with_namespace(SecretNamespace::new(namespace))
@@ -142,6 +230,7 @@ impl ChronicleSigning {
                     multi_source =
                         multi_source.add_source(&SecretNamespace::new(namespace), source);
                 }
+                (_, ChronicleSecretsOptions::Pkcs11(_) | ChronicleSecretsOptions::Transit(_)) => {}
             }
         }
 
@@ -152,8 +241,52 @@ impl ChronicleSigning {
         vault.refresh().await?;
         Ok(Self {
             vault: Arc::new(tokio::sync::Mutex::new(Box::new(vault.viewer()))),
+            tokens: Arc::new(tokens),
+            transit: Arc::new(transit),
         })
     }
+
+    fn transit(&self, secret_namespace: &str) -> Result<&VaultTransit, SecretError> {
+        self.transit
+            .get(secret_namespace)
+            .ok_or_else(|| SecretError::NotTransit(secret_namespace.to_owned()))
+    }
+
+    /// The Transit version of `secret_name` this signs with, the latest unless pinned with
+    /// [`at_transit_version`](Self::at_transit_version)
+    pub async fn transit_version(
+        &self,
+        secret_namespace: &str,
+        secret_name: &str,
+    ) -> Result<u64, SecretError> {
+        self.transit(secret_namespace)?.version(secret_name).await
+    }
+
+    /// A signer that uses `version` of the Transit keys in `secret_namespace`, rather than their
+    /// latest versions
+    pub fn at_transit_version(
+        &self,
+        secret_namespace: &str,
+        version: u64,
+    ) -> Result<Self, SecretError> {
+        let mut transit = (*self.transit).clone();
+        let pinned = self.transit(secret_namespace)?.at_version(version);
+        transit.insert(secret_namespace.to_owned(), pinned);
+
+        Ok(Self {
+            transit: Arc::new(transit),
+            ..self.clone()
+        })
+    }
+
+    /// Add a version to `secret_name` in Transit, which signs from then on, and return it
+    pub async fn rotate_transit_key(
+        &self,
+        secret_namespace: &str,
+        secret_name: &str,
+    ) -> Result<u64, SecretError> {
+        self.transit(secret_namespace)?.rotate(secret_name).await
+    }
 }
 
 #[async_trait::async_trait]
@@ -184,6 +317,46 @@ pub trait WithSecret {
         secret_namespace: &str,
         secret_name: &str,
     ) -> Result<VerifyingKey, SecretError>;
+
+    /// Sign data with a key, by default with the signing key loaded into this process
+    async fn sign_with_key(
+        &self,
+        secret_namespace: &str,
+        secret_name: &str,
+        data: &[u8],
+    ) -> Result<Signature, SecretError> {
+        self.with_signing_key(secret_namespace, secret_name, |signing_key| {
+            let s: Signature = signing_key.sign(data);
+            s
+        })
+        .await
+    }
+
+    /// The verifying key of a key of any algorithm, by default the secp256k1 key
+    async fn tagged_verifying_key(
+        &self,
+        secret_namespace: &str,
+        secret_name: &str,
+    ) -> Result<TaggedVerifyingKey, SecretError> {
+        Ok(self
+            .verifying_key(secret_namespace, secret_name)
+            .await?
+            .into())
+    }
+
+    /// Sign data with a key of any algorithm, as [`TaggedVerifyingKey::verify`] checks it, by
+    /// default with the secp256k1 key
+    async fn sign_tagged(
+        &self,
+        secret_namespace: &str,
+        secret_name: &str,
+        data: &[u8],
+    ) -> Result<Vec<u8>, SecretError> {
+        Ok(self
+            .sign_with_key(secret_namespace, secret_name, data)
+            .await?
+            .to_vec())
+    }
 }
 
 #[async_trait::async_trait]
@@ -199,6 +372,11 @@ impl WithSecret for ChronicleSigning {
         F: Send,
         T: Send,
     {
+        if self.tokens.contains_key(secret_namespace) || self.transit.contains_key(secret_namespace)
+        {
+            return Err(SecretError::KeyNotExportable(secret_name.to_owned()));
+        }
+
         let secret_ref = SecretVaultRef::new(SecretName::new(secret_name.to_owned()))
             .with_namespace(secret_namespace.into());
         let secret = self
@@ -232,6 +410,12 @@ impl WithSecret for ChronicleSigning {
         F: Send,
         T: Send,
     {
+        if self.tokens.contains_key(secret_namespace) || self.transit.contains_key(secret_namespace)
+        {
+            let verifying_key = self.verifying_key(secret_namespace, secret_name).await?;
+            return Ok(f(verifying_key));
+        }
+
         let secret_ref = SecretVaultRef::new(SecretName::new(secret_name.to_owned()))
             .with_namespace(secret_namespace.into());
         let secret = self
@@ -258,6 +442,19 @@ impl WithSecret for ChronicleSigning {
         secret_namespace: &str,
         secret_name: &str,
     ) -> Result<VerifyingKey, SecretError> {
+        if let Some(token) = self.tokens.get(secret_namespace) {
+            return token.verifying_key(secret_name).await;
+        }
+        if let Some(transit) = self.transit.get(secret_namespace) {
+            return match transit.verifying_key(secret_name).await? {
+                TaggedVerifyingKey::Secp256k1(key) => Ok(key),
+                key => Err(SecretError::NotSecp256k1(
+                    secret_name.to_owned(),
+                    key.algorithm(),
+                )),
+            };
+        }
+
         let secret_ref = SecretVaultRef::new(SecretName::new(secret_name.to_owned()))
             .with_namespace(secret_namespace.into());
         let secret = self
@@ -278,6 +475,56 @@ impl WithSecret for ChronicleSigning {
 
         Ok(key?)
     }
+
+    async fn sign_with_key(
+        &self,
+        secret_namespace: &str,
+        secret_name: &str,
+        data: &[u8],
+    ) -> Result<Signature, SecretError> {
+        if let Some(token) = self.tokens.get(secret_namespace) {
+            return token.sign(secret_name, data).await;
+        }
+        if self.transit.contains_key(secret_namespace) {
+            // Transit holds no secp256k1 keys
+            self.verifying_key(secret_namespace, secret_name).await?;
+        }
+
+        self.with_signing_key(secret_namespace, secret_name, |signing_key| {
+            let s: Signature = signing_key.sign(data);
+            s
+        })
+        .await
+    }
+
+    async fn tagged_verifying_key(
+        &self,
+        secret_namespace: &str,
+        secret_name: &str,
+    ) -> Result<TaggedVerifyingKey, SecretError> {
+        match self.transit.get(secret_namespace) {
+            Some(transit) => transit.verifying_key(secret_name).await,
+            None => Ok(self
+                .verifying_key(secret_namespace, secret_name)
+                .await?
+                .into()),
+        }
+    }
+
+    async fn sign_tagged(
+        &self,
+        secret_namespace: &str,
+        secret_name: &str,
+        data: &[u8],
+    ) -> Result<Vec<u8>, SecretError> {
+        match self.transit.get(secret_namespace) {
+            Some(transit) => transit.sign(secret_name, data).await,
+            None => Ok(self
+                .sign_with_key(secret_namespace, secret_name, data)
+                .await?
+                .to_vec()),
+        }
+    }
 }
 
 /// Trait for signing with a key known by chronicle
@@ -310,11 +557,8 @@ impl<T: WithSecret + Send + Sync> ChronicleSigner for T {
         secret_name: &str,
         data: &[u8],
     ) -> Result<Signature, SecretError> {
-        self.with_signing_key(secret_namespace, secret_name, |signing_key| {
-            let s: Signature = signing_key.sign(data);
-            s
-        })
-        .await
+        self.sign_with_key(secret_namespace, secret_name, data)
+            .await
     }
 
     /// Verify a signature with the chronicle key
@@ -349,7 +593,7 @@ pub trait BatcherKnownKeyNamesSigner {
     async fn batcher_verifying(&self) -> Result<VerifyingKey, SecretError>;
 }
 
-/// Trait for signing with a key known by chronicle
+/// Trait for signing with a key known by chronicle, which may be of any algorithm
 #[async_trait::async_trait]
 pub trait ChronicleKnownKeyNamesSigner {
     /// Sign data with the chronicle key and return a signature
@@ -359,7 +603,7 @@ pub trait ChronicleKnownKeyNamesSigner {
     async fn chronicle_verify(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecretError>;
 
     /// Get the verifying key for the chronicle key
-    async fn chronicle_verifying(&self) -> Result<VerifyingKey, SecretError>;
+    async fn chronicle_verifying(&self) -> Result<TaggedVerifyingKey, SecretError>;
 }
 
 /// Trait for signing with a key known by OPA
@@ -404,21 +648,23 @@ impl<T: ChronicleSigner + WithSecret + Send + Sync> BatcherKnownKeyNamesSigner f
 impl<T: ChronicleSigner + WithSecret + Send + Sync> ChronicleKnownKeyNamesSigner for T {
     #[instrument(skip(self,data), level = "trace", name = "chronicle_sign", fields(namespace = CHRONICLE_NAMESPACE, pk = CHRONICLE_PK))]
     async fn chronicle_sign(&self, data: &[u8]) -> Result<Vec<u8>, SecretError> {
-        Ok(self
-            .sign(CHRONICLE_NAMESPACE, CHRONICLE_PK, data)
-            .await?
-            .to_vec())
+        self.sign_tagged(CHRONICLE_NAMESPACE, CHRONICLE_PK, data)
+            .await
     }
 
     #[instrument(skip(self,data,signature), level = "trace", name = "chronicle_verify", fields(namespace = CHRONICLE_NAMESPACE, pk = CHRONICLE_PK))]
     async fn chronicle_verify(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecretError> {
-        self.verify(CHRONICLE_NAMESPACE, CHRONICLE_PK, data, signature)
-            .await
+        Ok(self
+            .chronicle_verifying()
+            .await?
+            .verify(data, signature)
+            .is_ok())
     }
 
     #[instrument(skip(self), level = "trace", name = "chronicle_verifying", fields(namespace = CHRONICLE_NAMESPACE, pk = CHRONICLE_PK))]
-    async fn chronicle_verifying(&self) -> Result<VerifyingKey, SecretError> {
-        self.verifying_key(CHRONICLE_NAMESPACE, CHRONICLE_PK).await
+    async fn chronicle_verifying(&self) -> Result<TaggedVerifyingKey, SecretError> {
+        self.tagged_verifying_key(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+            .await
     }
 }
 
@@ -462,6 +708,11 @@ pub fn opa_secret_names() -> Vec<(String, String)> {
 mod tests {
     use super::*;
     use k256::schnorr::signature::Signature;
+    use testcontainers::{clients::Cli, core::WaitFor, images::generic::GenericImage, Container};
+    use vaultrs::{
+        api::transit::{requests::CreateKeyRequest, KeyType},
+        client::{VaultClient, VaultClientSettingsBuilder},
+    };
 
     #[tokio::test]
     async fn embedded_keys() {
@@ -590,4 +841,288 @@ mod tests {
             .await
             .unwrap());
     }
+
+    /// Create a SoftHSM token holding a secp256k1 key pair labelled with each of `labels`, in a
+    /// token directory under `dir`
+    fn softhsm_token(module: &Path, dir: &Path, token_label: &str, pin: &str, labels: &[&str]) {
+        use cryptoki::{
+            context::{CInitializeArgs, Pkcs11},
+            mechanism::Mechanism,
+            object::Attribute,
+            session::UserType,
+            types::AuthPin,
+        };
+
+        let tokens = dir.join("tokens");
+        std::fs::create_dir(&tokens).unwrap();
+        let conf = dir.join("softhsm2.conf");
+        std::fs::write(
+            &conf,
+            format!("directories.tokendir = {}\n", tokens.display()),
+        )
+        .unwrap();
+        std::env::set_var("SOFTHSM2_CONF", &conf);
+
+        let pkcs11 = Pkcs11::new(module).unwrap();
+        pkcs11.initialize(CInitializeArgs::OsThreads).unwrap();
+
+        let so_pin = AuthPin::new("5678".into());
+        let slot = pkcs11.get_all_slots().unwrap()[0];
+        pkcs11.init_token(slot, &so_pin, token_label).unwrap();
+
+        // SoftHSM moves an initialized token to a new slot
+        let slot = pkcs11
+            .get_slots_with_token()
+            .unwrap()
+            .into_iter()
+            .find(|slot| pkcs11.get_token_info(*slot).unwrap().label() == token_label)
+            .unwrap();
+
+        let session = pkcs11.open_rw_session(slot).unwrap();
+        session.login(UserType::So, Some(&so_pin)).unwrap();
+        session.init_pin(&AuthPin::new(pin.into())).unwrap();
+        session.logout().unwrap();
+        session
+            .login(UserType::User, Some(&AuthPin::new(pin.into())))
+            .unwrap();
+
+        // DER encoding of the secp256k1 curve OID, 1.3.132.0.10
+        let secp256k1 = vec![0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x0a];
+        for label in labels {
+            session
+                .generate_key_pair(
+                    &Mechanism::EccKeyPairGen,
+                    &[
+                        Attribute::Token(true),
+                        Attribute::Verify(true),
+                        Attribute::EcParams(secp256k1.clone()),
+                        Attribute::Label(label.as_bytes().to_vec()),
+                    ],
+                    &[
+                        Attribute::Token(true),
+                        Attribute::Private(true),
+                        Attribute::Sensitive(true),
+                        Attribute::Sign(true),
+                        Attribute::Label(label.as_bytes().to_vec()),
+                    ],
+                )
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn pkcs11_keys() {
+        let module = std::env::var("SOFTHSM2_MODULE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("/usr/lib/softhsm/libsofthsm2.so"));
+        let dir = tempfile::tempdir().unwrap();
+        softhsm_token(
+            &module,
+            dir.path(),
+            "chronicle",
+            "1234",
+            &[CHRONICLE_PK, BATCHER_PK],
+        );
+
+        // Both namespaces share the token, and so the one session logged in to it
+        let token = || ChronicleSecretsOptions::held_by_pkcs11(&module, "chronicle", "1234");
+        let secrets = ChronicleSigning::new(
+            chronicle_secret_names(),
+            vec![
+                (CHRONICLE_NAMESPACE.to_string(), token()),
+                (BATCHER_NAMESPACE.to_string(), token()),
+            ],
+        )
+        .await
+        .unwrap();
+
+        // As does a signer created later in the same process, such as to rotate a key
+        let rotated = ChronicleSigning::new(
+            vec![(CHRONICLE_NAMESPACE.to_string(), CHRONICLE_PK.to_string())],
+            vec![(CHRONICLE_NAMESPACE.to_string(), token())],
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            secrets
+                .with_signing_key(CHRONICLE_NAMESPACE, CHRONICLE_PK, |_| ())
+                .await,
+            Err(SecretError::KeyNotExportable(_))
+        ));
+
+        let sig = secrets
+            .sign(CHRONICLE_NAMESPACE, CHRONICLE_PK, "hello world".as_bytes())
+            .await
+            .unwrap();
+        let verifying_key = secrets
+            .verifying_key(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+            .await
+            .unwrap();
+
+        assert!(verifying_key.verify("hello world".as_bytes(), &sig).is_ok());
+        assert!(verifying_key.verify("boom".as_bytes(), &sig).is_err());
+
+        assert!(secrets
+            .verify(
+                CHRONICLE_NAMESPACE,
+                CHRONICLE_PK,
+                "hello world".as_bytes(),
+                sig.as_bytes()
+            )
+            .await
+            .unwrap());
+
+        let batcher_sig = secrets
+            .batcher_sign("hello world".as_bytes())
+            .await
+            .unwrap();
+        assert!(secrets
+            .batcher_verify("hello world".as_bytes(), &batcher_sig)
+            .await
+            .unwrap());
+        assert!(!secrets
+            .batcher_verify("hello world".as_bytes(), sig.as_bytes())
+            .await
+            .unwrap());
+
+        let rotated_sig = rotated
+            .sign(CHRONICLE_NAMESPACE, CHRONICLE_PK, "hello world".as_bytes())
+            .await
+            .unwrap();
+        assert!(verifying_key
+            .verify("hello world".as_bytes(), &rotated_sig)
+            .is_ok());
+    }
+
+    /// Start a Vault dev server, with a Transit engine at each of `mounts` holding a
+    /// 'chronicle-pk' key of the paired type
+    async fn vault_transit<'a>(
+        docker: &'a Cli,
+        mounts: &[(&str, KeyType)],
+    ) -> (Container<'a, GenericImage>, Url) {
+        let container = docker.run(
+            GenericImage::new("hashicorp/vault", "1.13")
+                .with_env_var("VAULT_DEV_ROOT_TOKEN_ID", "root")
+                .with_env_var("VAULT_DEV_LISTEN_ADDRESS", "0.0.0.0:8200")
+                .with_env_var("SKIP_SETCAP", "true")
+                .with_exposed_port(8200)
+                .with_wait_for(WaitFor::message_on_stdout("Vault server started!")),
+        );
+        let url = Url::parse(&format!(
+            "http://127.0.0.1:{}",
+            container.get_host_port_ipv4(8200)
+        ))
+        .unwrap();
+
+        let client = VaultClient::new(
+            VaultClientSettingsBuilder::default()
+                .address(url.as_str())
+                .token("root")
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+        for (mount, key_type) in mounts {
+            vaultrs::sys::mount::enable(&client, mount, "transit", None)
+                .await
+                .unwrap();
+            vaultrs::transit::key::create(
+                &client,
+                mount,
+                CHRONICLE_PK,
+                Some(CreateKeyRequest::builder().key_type(*key_type)),
+            )
+            .await
+            .unwrap();
+        }
+
+        (container, url)
+    }
+
+    #[tokio::test]
+    async fn vault_transit_keys() {
+        let docker = Cli::default();
+        let (_container, url) = vault_transit(
+            &docker,
+            &[
+                ("transit", KeyType::EcdsaP256),
+                ("transit-ed25519", KeyType::Ed25519),
+            ],
+        )
+        .await;
+
+        for (mount, algorithm) in [
+            ("transit", KeyAlgorithm::P256),
+            ("transit-ed25519", KeyAlgorithm::Ed25519),
+        ] {
+            let secrets = ChronicleSigning::new(
+                vec![(CHRONICLE_NAMESPACE.to_string(), CHRONICLE_PK.to_string())],
+                vec![(
+                    CHRONICLE_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::signed_by_vault_transit(&url, "root", mount),
+                )],
+            )
+            .await
+            .unwrap();
+
+            let verifying_key = secrets.chronicle_verifying().await.unwrap();
+            assert_eq!(verifying_key.algorithm(), algorithm);
+
+            let sig = secrets
+                .chronicle_sign("hello world".as_bytes())
+                .await
+                .unwrap();
+            assert!(verifying_key.verify("hello world".as_bytes(), &sig).is_ok());
+            assert!(secrets
+                .chronicle_verify("hello world".as_bytes(), &sig)
+                .await
+                .unwrap());
+            assert!(!secrets
+                .chronicle_verify("boom".as_bytes(), &sig)
+                .await
+                .unwrap());
+
+            // Transit keys never leave Vault, and are not secp256k1
+            assert!(matches!(
+                secrets
+                    .with_signing_key(CHRONICLE_NAMESPACE, CHRONICLE_PK, |_| ())
+                    .await,
+                Err(SecretError::KeyNotExportable(_))
+            ));
+            assert!(matches!(
+                secrets
+                    .verifying_key(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+                    .await,
+                Err(SecretError::NotSecp256k1(_, _))
+            ));
+
+            // A rotation signs with the new version, while the old one can still be asked for
+            assert_eq!(
+                secrets
+                    .transit_version(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+                    .await
+                    .unwrap(),
+                1
+            );
+            assert_eq!(
+                secrets
+                    .rotate_transit_key(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+                    .await
+                    .unwrap(),
+                2
+            );
+            let rotated = secrets.chronicle_verifying().await.unwrap();
+            assert_ne!(rotated, verifying_key);
+
+            let previous = secrets.at_transit_version(CHRONICLE_NAMESPACE, 1).unwrap();
+            assert_eq!(previous.chronicle_verifying().await.unwrap(), verifying_key);
+            let sig = previous
+                .chronicle_sign("hello world".as_bytes())
+                .await
+                .unwrap();
+            assert!(verifying_key.verify("hello world".as_bytes(), &sig).is_ok());
+            assert!(rotated.verify("hello world".as_bytes(), &sig).is_err());
+        }
+    }
 }
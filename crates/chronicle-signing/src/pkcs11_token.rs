@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use cryptoki::{
+    context::{CInitializeArgs, Pkcs11},
+    error::{Error, RvError},
+    mechanism::Mechanism,
+    object::{Attribute, AttributeType, ObjectClass, ObjectHandle},
+    session::{Session, UserType},
+    types::AuthPin,
+};
+use k256::{
+    ecdsa::{Signature, VerifyingKey},
+    sha2::{Digest, Sha256},
+};
+use lazy_static::lazy_static;
+use tracing::debug;
+
+use crate::SecretError;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Pkcs11Options {
+    /// Path to the PKCS#11 module, such as libsofthsm2.so
+    pub module: PathBuf,
+    pub token_label: String,
+    pub pin: String,
+}
+
+impl Pkcs11Options {
+    pub fn new(module: &Path, token_label: &str, pin: &str) -> Self {
+        Self {
+            module: module.to_owned(),
+            token_label: token_label.to_owned(),
+            pin: pin.to_owned(),
+        }
+    }
+}
+
+lazy_static! {
+    /// A module may only be initialized once per process, and a user logged in to a token once
+    /// per application, so modules and logged in tokens are shared by every namespace and every
+    /// [`crate::ChronicleSigning`] that uses them
+    static ref MODULES: Mutex<Pkcs11Modules> = Mutex::new(Pkcs11Modules::default());
+}
+
+#[derive(Default)]
+struct Pkcs11Modules {
+    modules: HashMap<PathBuf, Pkcs11>,
+    tokens: HashMap<(PathBuf, String), Pkcs11Token>,
+}
+
+/// Open a logged in session on the token `options` names, or share the one already open
+pub(crate) fn open_token(options: &Pkcs11Options) -> Result<Pkcs11Token, SecretError> {
+    MODULES.lock().unwrap().open(options)
+}
+
+impl Pkcs11Modules {
+    fn open(&mut self, options: &Pkcs11Options) -> Result<Pkcs11Token, SecretError> {
+        let key = (options.module.clone(), options.token_label.clone());
+        if let Some(token) = self.tokens.get(&key) {
+            return Ok(token.clone());
+        }
+
+        if !self.modules.contains_key(&options.module) {
+            let pkcs11 = Pkcs11::new(&options.module)?;
+            pkcs11.initialize(CInitializeArgs::OsThreads)?;
+            self.modules.insert(options.module.clone(), pkcs11);
+        }
+        let pkcs11 = &self.modules[&options.module];
+
+        let mut slot = None;
+        for candidate in pkcs11.get_slots_with_token()? {
+            if pkcs11.get_token_info(candidate)?.label() == options.token_label {
+                slot = Some(candidate);
+                break;
+            }
+        }
+        let slot = slot.ok_or_else(|| SecretError::TokenNotFound(options.token_label.clone()))?;
+
+        let session = pkcs11.open_ro_session(slot)?;
+        // A login applies to every session the application has on the token
+        match session.login(UserType::User, Some(&AuthPin::new(options.pin.clone()))) {
+            Ok(()) | Err(Error::Pkcs11(RvError::UserAlreadyLoggedIn)) => {}
+            Err(e) => return Err(e.into()),
+        }
+        debug!(token = options.token_label, "Logged in to PKCS#11 token");
+
+        let token = Pkcs11Token {
+            _module: pkcs11.clone(),
+            session: Arc::new(Mutex::new(session)),
+        };
+        self.tokens.insert(key, token.clone());
+
+        Ok(token)
+    }
+}
+
+/// A logged in session on a token holding secp256k1 keys labelled with their secret names.
+/// Keys are used where they are, and never leave the token.
+#[derive(Clone)]
+pub(crate) struct Pkcs11Token {
+    _module: Pkcs11,
+    session: Arc<Mutex<Session>>,
+}
+
+impl Pkcs11Token {
+    fn find_key(
+        session: &Session,
+        class: ObjectClass,
+        label: &str,
+    ) -> Result<Option<ObjectHandle>, SecretError> {
+        Ok(session
+            .find_objects(&[
+                Attribute::Class(class),
+                Attribute::Label(label.as_bytes().to_vec()),
+            ])?
+            .into_iter()
+            .next())
+    }
+
+    /// Run `f` with the session on a blocking thread, as a call to a token may wait on its
+    /// hardware
+    async fn with_session<T, F>(&self, f: F) -> Result<T, SecretError>
+    where
+        F: FnOnce(&Session) -> Result<T, SecretError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let session = self.session.clone();
+        tokio::task::spawn_blocking(move || f(&session.lock().unwrap())).await?
+    }
+
+    /// Sign the SHA-256 digest of `data` on the token, as a signing key in process would
+    pub(crate) async fn sign(&self, label: &str, data: &[u8]) -> Result<Signature, SecretError> {
+        let (label, digest) = (label.to_owned(), Sha256::digest(data));
+
+        let signature = self
+            .with_session(move |session| {
+                let key = Self::find_key(session, ObjectClass::PRIVATE_KEY, &label)?
+                    .ok_or(SecretError::NoPrivateKeyFound)?;
+                Ok(session.sign(&Mechanism::Ecdsa, key, &digest)?)
+            })
+            .await?;
+        let signature = Signature::try_from(signature.as_slice())
+            .map_err(|_| SecretError::InvalidPrivateKey)?;
+
+        // Tokens need not produce the low-s form that verification requires
+        Ok(signature.normalize_s().unwrap_or(signature))
+    }
+
+    pub(crate) async fn verifying_key(&self, label: &str) -> Result<VerifyingKey, SecretError> {
+        let label = label.to_owned();
+
+        let point = self
+            .with_session(move |session| {
+                let key = Self::find_key(session, ObjectClass::PUBLIC_KEY, &label)?
+                    .ok_or(SecretError::NoPublicKeyFound)?;
+                match session
+                    .get_attributes(key, &[AttributeType::EcPoint])?
+                    .into_iter()
+                    .next()
+                {
+                    Some(Attribute::EcPoint(point)) => Ok(point),
+                    _ => Err(SecretError::NoPublicKeyFound),
+                }
+            })
+            .await?;
+
+        VerifyingKey::from_sec1_bytes(unwrap_ec_point(&point))
+            .map_err(|_| SecretError::InvalidPublicKey)
+    }
+}
+
+/// CKA_EC_POINT is a DER OCTET STRING holding the point, though some tokens give the bare point
+fn unwrap_ec_point(point: &[u8]) -> &[u8] {
+    match point {
+        [0x04, len, rest @ ..] if *len as usize == rest.len() && matches!(len, 33 | 65) => rest,
+        point => point,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ec_point_is_unwrapped_from_octet_string() {
+        let point = [[0x04].as_slice(), &[0xab; 64]].concat();
+        let wrapped = [[0x04, 65].as_slice(), &point].concat();
+
+        assert_eq!(unwrap_ec_point(&wrapped), point.as_slice());
+        assert_eq!(unwrap_ec_point(&point), point.as_slice());
+
+        let compressed = [[0x02].as_slice(), &[0x3f; 32]].concat();
+        assert_eq!(unwrap_ec_point(&compressed), compressed.as_slice());
+    }
+}
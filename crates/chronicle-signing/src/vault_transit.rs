@@ -0,0 +1,216 @@
+use std::{collections::HashMap, sync::Arc};
+
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use rustify::{
+    endpoint::Endpoint,
+    enums::{RequestMethod, RequestType, ResponseType},
+};
+use serde::Deserialize;
+use tracing::debug;
+use url::Url;
+use vaultrs::{
+    api::{
+        self,
+        transit::{requests::SignDataRequest, HashAlgorithm, MarshalingAlgorithm},
+    },
+    client::{VaultClient, VaultClientSettingsBuilder},
+    transit,
+};
+
+use crate::{KeyAlgorithm, SecretError, TaggedVerifyingKey};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct VaultTransitOptions {
+    pub vault_url: Url,
+    pub token: String,
+    /// Where the Transit secrets engine is mounted, such as `transit`
+    pub mount_path: String,
+}
+
+impl VaultTransitOptions {
+    pub fn new(vault_url: Url, token: &str, mount_path: &str) -> Self {
+        Self {
+            vault_url,
+            token: token.to_owned(),
+            mount_path: mount_path.to_owned(),
+        }
+    }
+}
+
+/// A Transit key as read from `<mount>/keys/<name>`, with the public key of each version
+#[derive(Debug, Deserialize)]
+struct TransitKey {
+    #[serde(rename = "type")]
+    key_type: String,
+    latest_version: u64,
+    keys: HashMap<String, TransitKeyVersion>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TransitKeyVersion {
+    public_key: String,
+}
+
+/// Read a Transit key. The request vaultrs provides expects the creation time that symmetric
+/// keys list for each version, where signing keys list their public keys.
+#[derive(Debug)]
+struct ReadTransitKeyRequest {
+    mount: String,
+    name: String,
+}
+
+impl Endpoint for ReadTransitKeyRequest {
+    type Response = TransitKey;
+    const REQUEST_BODY_TYPE: RequestType = RequestType::JSON;
+    const RESPONSE_BODY_TYPE: ResponseType = ResponseType::JSON;
+
+    fn path(&self) -> String {
+        format!("{}/keys/{}", self.mount, self.name)
+    }
+
+    fn method(&self) -> RequestMethod {
+        RequestMethod::GET
+    }
+}
+
+impl TransitKey {
+    fn algorithm(&self, name: &str) -> Result<KeyAlgorithm, SecretError> {
+        match &*self.key_type {
+            "ecdsa-p256" => Ok(KeyAlgorithm::P256),
+            "ed25519" => Ok(KeyAlgorithm::Ed25519),
+            other => Err(SecretError::UnsupportedTransitKey(
+                name.to_owned(),
+                other.to_owned(),
+            )),
+        }
+    }
+
+    /// The public key of `version`, which Transit gives as PEM for ECDSA keys and as base64 for
+    /// Ed25519 keys
+    fn verifying_key(&self, name: &str, version: u64) -> Result<TaggedVerifyingKey, SecretError> {
+        let public_key = &self
+            .keys
+            .get(&version.to_string())
+            .ok_or_else(|| SecretError::NoKeyVersion(name.to_owned(), version))?
+            .public_key;
+
+        match self.algorithm(name)? {
+            KeyAlgorithm::Ed25519 => TaggedVerifyingKey::from_bytes(
+                KeyAlgorithm::Ed25519,
+                &STANDARD
+                    .decode(public_key)
+                    .map_err(|_| SecretError::InvalidPublicKey)?,
+            ),
+            _ => TaggedVerifyingKey::from_public_key_pem(public_key),
+        }
+    }
+}
+
+/// A Transit secrets engine holding P-256 or Ed25519 keys named with their secret names. Keys
+/// are used where they are, and never leave Vault.
+///
+/// Transit numbers the versions of a key from 1, and signs with the latest unless told
+/// otherwise. Signatures take the form [`TaggedVerifyingKey::verify`] checks.
+#[derive(Clone)]
+pub(crate) struct VaultTransit {
+    client: Arc<VaultClient>,
+    mount_path: String,
+    // The version of each key to use, rather than the latest
+    version: Option<u64>,
+}
+
+impl VaultTransit {
+    pub(crate) fn new(options: &VaultTransitOptions) -> Result<Self, SecretError> {
+        let settings = VaultClientSettingsBuilder::default()
+            .address(options.vault_url.as_str())
+            .token(&options.token)
+            .build()
+            .unwrap();
+
+        Ok(Self {
+            client: Arc::new(VaultClient::new(settings)?),
+            mount_path: options.mount_path.clone(),
+            version: None,
+        })
+    }
+
+    /// Use `version` of each key rather than the latest
+    pub(crate) fn at_version(&self, version: u64) -> Self {
+        Self {
+            version: Some(version),
+            ..self.clone()
+        }
+    }
+
+    async fn read(&self, name: &str) -> Result<TransitKey, SecretError> {
+        let request = ReadTransitKeyRequest {
+            mount: self.mount_path.clone(),
+            name: name.to_owned(),
+        };
+
+        Ok(api::exec_with_result(&*self.client, request).await?)
+    }
+
+    /// The version of `name` in use
+    pub(crate) async fn version(&self, name: &str) -> Result<u64, SecretError> {
+        match self.version {
+            Some(version) => Ok(version),
+            None => Ok(self.read(name).await?.latest_version),
+        }
+    }
+
+    pub(crate) async fn verifying_key(
+        &self,
+        name: &str,
+    ) -> Result<TaggedVerifyingKey, SecretError> {
+        let key = self.read(name).await?;
+
+        key.verifying_key(name, self.version.unwrap_or(key.latest_version))
+    }
+
+    /// Sign `data` with `name`. ECDSA signatures are over its SHA-256 digest, and are asked for
+    /// as JWS marshals them, the 64 bytes of r and s.
+    pub(crate) async fn sign(&self, name: &str, data: &[u8]) -> Result<Vec<u8>, SecretError> {
+        let key = self.read(name).await?;
+        let version = self.version.unwrap_or(key.latest_version);
+
+        let mut request = SignDataRequest::builder();
+        request.key_version(version);
+        let decode = match key.algorithm(name)? {
+            KeyAlgorithm::P256 => {
+                request
+                    .hash_algorithm(HashAlgorithm::Sha2_256)
+                    .marshaling_algorithm(MarshalingAlgorithm::Jws);
+                URL_SAFE_NO_PAD
+            }
+            _ => STANDARD,
+        };
+
+        let signature = transit::data::sign(
+            &*self.client,
+            &self.mount_path,
+            name,
+            &STANDARD.encode(data),
+            Some(&mut request),
+        )
+        .await?
+        .signature;
+
+        signature
+            .strip_prefix(&format!("vault:v{version}:"))
+            .and_then(|signature| decode.decode(signature).ok())
+            .ok_or(SecretError::MalformedTransitSignature(signature))
+    }
+
+    /// Add a version to `name`, which is used from then on, and return it
+    pub(crate) async fn rotate(&self, name: &str) -> Result<u64, SecretError> {
+        transit::key::rotate(&*self.client, &self.mount_path, name).await?;
+        let version = self.read(name).await?.latest_version;
+        debug!(name, version, "Rotated Vault Transit key");
+
+        Ok(version)
+    }
+}
@@ -1,7 +1,9 @@
 use std::{collections::BTreeMap, convert::Infallible};
 
-use api::ApiError;
-use chronicle_protocol::async_stl_client::error::SawtoothCommunicationError;
+use api::{file_ledger::FileLedgerError, ApiError};
+use chronicle_protocol::{
+    async_stl_client::error::SawtoothCommunicationError, state::KeyRotationError,
+};
 use chronicle_signing::SecretError;
 use clap::{
     builder::{PossibleValuesParser, StringValueParser},
@@ -9,7 +11,9 @@ use clap::{
 };
 use common::{
     attributes::{Attribute, Attributes},
-    commands::{ActivityCommand, AgentCommand, ApiCommand, EntityCommand},
+    commands::{
+        ActivityCommand, AgentCommand, ApiCommand, EntityCommand, OutboxCommand, OutboxStatus,
+    },
     import::FromUrlError,
     opa::{OpaExecutorError, PolicyLoaderError},
     prov::{
@@ -101,6 +105,18 @@ pub enum CliError {
 
     #[error("UTF-8 error: {0}")]
     Utf8Error(#[from] std::str::Utf8Error),
+
+    #[error("File ledger error: {0}")]
+    FileLedger(#[from] FileLedgerError),
+
+    #[error("Invalid public key: {path}")]
+    InvalidPublicKey { path: String },
+
+    #[error("Key rotation: {0}")]
+    KeyRotation(#[from] KeyRotationError),
+
+    #[error("Invalid signature: {0}")]
+    Signature(#[from] hex::FromHexError),
 }
 
 impl CliError {
@@ -119,7 +135,7 @@ impl From<Infallible> for CliError {
 impl UFE for CliError {}
 
 pub(crate) trait SubCommand {
-    fn as_cmd(&self) -> Command;
+    fn as_cmd(&self) -> Command<'_>;
     fn matches(&self, matches: &ArgMatches) -> Result<Option<ApiCommand>, CliError>;
 }
 
@@ -138,7 +154,7 @@ impl AttributeCliModel {
         }
     }
 
-    pub fn as_arg(&self) -> Arg {
+    pub fn as_arg(&self) -> Arg<'_> {
         Arg::new(&*self.attribute_name)
             .long(&self.attribute_name)
             .help(&*self.attribute_help)
@@ -326,7 +342,7 @@ fn attributes_from(
 }
 
 impl SubCommand for AgentCliModel {
-    fn as_cmd(&self) -> Command {
+    fn as_cmd(&self) -> Command<'_> {
         let cmd = Command::new(&*self.external_id).about(&*self.about);
 
         let mut define = Command::new("define")
@@ -420,7 +436,7 @@ impl ActivityCliModel {
 }
 
 impl SubCommand for ActivityCliModel {
-    fn as_cmd(&self) -> Command {
+    fn as_cmd(&self) -> Command<'_> {
         let cmd = Command::new(&*self.external_id).about(&*self.about);
 
         let mut define =
@@ -677,7 +693,7 @@ impl EntityCliModel {
 }
 
 impl SubCommand for EntityCliModel {
-    fn as_cmd(&self) -> Command {
+    fn as_cmd(&self) -> Command<'_> {
         let cmd = Command::new(&self.external_id).about(&*self.about);
 
         let mut define =
@@ -813,7 +829,7 @@ impl From<ChronicleDomainDef> for CliModel {
 }
 
 impl SubCommand for CliModel {
-    fn as_cmd(&self) -> Command {
+    fn as_cmd(&self) -> Command<'_> {
         let mut app = Command::new("chronicle")
             .version(LONG_VERSION)
             .author("Paravela Limited")
@@ -871,12 +887,39 @@ impl SubCommand for CliModel {
                     .help("Name of the database")
                     .default_value("chronicle"),
             )
+            .arg(
+                Arg::new("sqlite")
+                    .long("sqlite")
+                    .takes_value(true)
+                    .value_name("path")
+                    .value_hint(ValueHint::FilePath)
+                    .env("CHRONICLE_SQLITE")
+                    .help("Use an SQLite database at this path, created if need be, rather than PostgreSQL"),
+            )
             .arg(
                 Arg::new("opa-bundle-address")
                 .long("opa-bundle-address")
                 .takes_value(true)
                 .help("URL or path for loading OPA policy bundle")
             )
+            .arg(
+                Arg::new("opa-bundle-key")
+                    .long("opa-bundle-key")
+                    .takes_value(true)
+                    .value_name("path")
+                    .value_hint(ValueHint::FilePath)
+                    .env("OPA_BUNDLE_KEY")
+                    .help("Only load OPA policy bundles signed with the private key of this PEM encoded public key")
+            )
+            .arg(
+                Arg::new("opa-bundle-key-id")
+                    .long("opa-bundle-key-id")
+                    .takes_value(true)
+                    .value_name("id")
+                    .env("OPA_BUNDLE_KEY_ID")
+                    .requires("opa-bundle-key")
+                    .help("The key id signed OPA policy bundles must name, if any, 'default' if not set")
+            )
             .arg(
                 Arg::with_name("opa-policy-name")
                     .long("opa-policy-name")
@@ -889,6 +932,63 @@ impl SubCommand for CliModel {
                     .help("Entrypoint to the named OPA policy")
                     .takes_value(true)
             )
+            .arg(
+                Arg::new("opa-capture")
+                    .long("opa-capture")
+                    .takes_value(true)
+                    .value_name("directory")
+                    .value_hint(ValueHint::DirPath)
+                    .help("Record the inputs evaluated by the OPA policy, with its decisions, in this directory as fixtures for opactl test-policy")
+            )
+            .arg(
+                Arg::new("opa-explain-denials")
+                    .long("opa-explain-denials")
+                    .takes_value(false)
+                    .env("OPA_EXPLAIN_DENIALS")
+                    .help("Include the reasons the OPA policy gives for denying a request in the denyReasons extension of GraphQL errors")
+            )
+            .arg(
+                Arg::new("opa-mask")
+                    .long("opa-mask")
+                    .takes_value(false)
+                    .env("OPA_MASK")
+                    .help("Null the attributes and relations named by the mask rule of the OPA policy in what readers are served")
+            )
+            .arg(
+                Arg::new("opa-decision-log")
+                    .long("opa-decision-log")
+                    .takes_value(true)
+                    .value_name("sink")
+                    .env("OPA_DECISION_LOG")
+                    .help("Log each OPA policy decision to an http(s) URL accepting OPA decision logs, to the opa_decision table of the database with 'database', or otherwise to this file as JSON lines")
+            )
+            .arg(
+                Arg::new("opa-decision-log-sample")
+                    .long("opa-decision-log-sample")
+                    .takes_value(true)
+                    .value_name("fraction")
+                    .default_value("1")
+                    .requires("opa-decision-log")
+                    .help("The fraction of allowed decisions to log, between 0 and 1; denials are always logged")
+            )
+            .arg(
+                Arg::new("opa-decision-log-queue")
+                    .long("opa-decision-log-queue")
+                    .takes_value(true)
+                    .value_name("decisions")
+                    .default_value("10000")
+                    .requires("opa-decision-log")
+                    .help("The most decisions waiting to be logged; more are dropped until the sink catches up")
+            )
+            .arg(
+                Arg::new("opa-decision-log-redact")
+                    .long("opa-decision-log-redact")
+                    .takes_value(true)
+                    .multiple_occurrences(true)
+                    .value_name("pointer")
+                    .requires("opa-decision-log")
+                    .help("Leave the value at this JSON pointer out of logged inputs, such as /identity/claims")
+            )
             .group(
                 ArgGroup::with_name("opa-bundle-address-args")
                     .args(&["opa-bundle-address"])
@@ -905,6 +1005,54 @@ impl SubCommand for CliModel {
                     ),
             )
             .subcommand(Command::new("export-schema").about("Print SDL and exit"))
+            .subcommand(
+                Command::new("verify-file-ledger-proof")
+                    .about("Check a proof that a block of a ledger file wrote a record and exit")
+                    .arg(
+                        Arg::new("proof")
+                            .long("proof")
+                            .takes_value(true)
+                            .required(true)
+                            .value_hint(ValueHint::FilePath)
+                            .help(
+                                "A JSON proof, array of proofs or response from \
+                                 /tx/{id}/file-ledger-proof",
+                            ),
+                    )
+                    .arg(
+                        Arg::new("block-id")
+                            .long("block-id")
+                            .takes_value(true)
+                            .required(true)
+                            .help("The id of the block the proof is for, from a source you trust"),
+                    )
+                    .arg(
+                        Arg::new("record")
+                            .long("record")
+                            .takes_value(true)
+                            .value_hint(ValueHint::FilePath)
+                            .help("A record, as exported, that a proof must be of"),
+                    ),
+            )
+            .subcommand(
+                Command::new("verify-credential")
+                    .about("Check the signatures of an exported provenance credential and exit")
+                    .arg(
+                        Arg::new("credential")
+                            .long("credential")
+                            .takes_value(true)
+                            .required(true)
+                            .value_hint(ValueHint::FilePath)
+                            .help("A credential from /credential/{ns}/{iri}"),
+                    )
+                    .arg(
+                        Arg::new("public-key")
+                            .long("public-key")
+                            .takes_value(true)
+                            .value_hint(ValueHint::FilePath)
+                            .help("A PEM public key the credential must be issued with"),
+                    ),
+            )
             .subcommand(
                 Command::new("serve-api")
                     .alias("serve-graphql")
@@ -937,6 +1085,20 @@ impl SubCommand for CliModel {
                             .takes_value(true)
                             .value_name("interval")
                             .default_missing_value("1800"),
+                    ).arg(
+                        Arg::new("outbox")
+                            .long("outbox")
+                            .help("Queue transactions while the ledger is unreachable and specify the number of attempts to submit each")
+                            .takes_value(true)
+                            .value_name("max-attempts")
+                            .default_missing_value("100"),
+                    ).arg(
+                        Arg::new("verify-store")
+                            .long("verify-store")
+                            .help("Periodically compare the database with the ledger and specify the interval in seconds")
+                            .takes_value(true)
+                            .value_name("interval")
+                            .default_missing_value("3600"),
                     ).arg(
                         Arg::new("jwks-address")
                             .long("jwks-address")
@@ -983,6 +1145,32 @@ impl SubCommand for CliModel {
                     ),
             )
             .subcommand(Command::new("verify-keystore").about("Initialize and verify keystore, then exit"))
+            .subcommand(
+                Command::new("outbox")
+                    .about("List transactions queued while the ledger was unreachable")
+                    .arg(
+                        Arg::new("status")
+                            .long("status")
+                            .takes_value(true)
+                            .value_parser([
+                                "pending",
+                                "submitted",
+                                "failed",
+                                "committed",
+                                "contradicted",
+                            ])
+                            .help("Only list transactions with this status"),
+                    )
+                    .arg(
+                        Arg::new("retry")
+                            .long("retry")
+                            .takes_value(true)
+                            .value_name("id")
+                            .value_parser(clap::value_parser!(i32))
+                            .conflicts_with("status")
+                            .help("Return a failed transaction to the outbox, so that it and those queued behind it are submitted"),
+                    ),
+            )
             .subcommand(
                 Command::new("import")
                     .about("Import and apply Chronicle operations, then exit")
@@ -1006,6 +1194,22 @@ impl SubCommand for CliModel {
                             .value_parser(StringValueParser::new())
                             .help("A path or url to data import file"),
                     )
+                    .arg(
+                        Arg::new("agent")
+                            .long("agent")
+                            .takes_value(true)
+                            .value_name("external-id")
+                            .requires("signature")
+                            .help("Submit the operations as signed by this agent, with its registered key"),
+                    )
+                    .arg(
+                        Arg::new("signature")
+                            .long("signature")
+                            .takes_value(true)
+                            .value_name("hex")
+                            .requires("agent")
+                            .help("The agent's signature of the import data, hex encoded"),
+                    )
             );
 
         for agent in self.agents.iter() {
@@ -1048,6 +1252,19 @@ impl SubCommand for CliModel {
                     .conflicts_with("batcher-key-from-vault"),
             );
 
+            app = app.arg(
+                Arg::new("batcher-key-from-pkcs11")
+                    .long("batcher-key-from-pkcs11")
+                    .takes_value(false)
+                    .help("Sign batches on a PKCS#11 token, with the key labelled 'batcher-pk'")
+                    .requires_all(&["pkcs11-module", "pkcs11-token-label", "pkcs11-pin"])
+                    .conflicts_with_all(&[
+                        "batcher-key-from-path",
+                        "batcher-key-from-vault",
+                        "batcher-key-generated",
+                    ]),
+            );
+
             app = app.arg(
                 Arg::new("chronicle-key-from-path")
                     .long("chronicle-key-from-path")
@@ -1076,6 +1293,58 @@ impl SubCommand for CliModel {
                     .conflicts_with("chronicle-key-from-vault"),
             );
 
+            app = app.arg(
+                Arg::new("chronicle-key-from-pkcs11")
+                    .long("chronicle-key-from-pkcs11")
+                    .takes_value(false)
+                    .help("Sign identities and query results on a PKCS#11 token, with the key labelled 'chronicle-pk'")
+                    .requires_all(&["pkcs11-module", "pkcs11-token-label", "pkcs11-pin"])
+                    .conflicts_with_all(&[
+                        "chronicle-key-from-path",
+                        "chronicle-key-from-vault",
+                        "chronicle-key-generated",
+                    ]),
+            );
+
+            app = app.arg(
+                Arg::new("chronicle-key-from-vault-transit")
+                    .long("chronicle-key-from-vault-transit")
+                    .takes_value(false)
+                    .help("Sign identities and query results in Hashicorp Vault's Transit engine, with the P-256 or Ed25519 key 'chronicle-pk'")
+                    .requires_all(&["vault-address", "vault-token"])
+                    .conflicts_with_all(&[
+                        "chronicle-key-from-path",
+                        "chronicle-key-from-vault",
+                        "chronicle-key-generated",
+                        "chronicle-key-from-pkcs11",
+                    ]),
+            );
+
+            app = app.arg(
+                Arg::new("pkcs11-module")
+                    .long("pkcs11-module")
+                    .takes_value(true)
+                    .value_hint(ValueHint::FilePath)
+                    .help("Path to the PKCS#11 module, such as libsofthsm2.so")
+                    .env("PKCS11_MODULE"),
+            );
+
+            app = app.arg(
+                Arg::new("pkcs11-token-label")
+                    .long("pkcs11-token-label")
+                    .takes_value(true)
+                    .help("Label of the PKCS#11 token holding the keys")
+                    .env("PKCS11_TOKEN_LABEL"),
+            );
+
+            app = app.arg(
+                Arg::new("pkcs11-pin")
+                    .long("pkcs11-pin")
+                    .takes_value(true)
+                    .help("User PIN for the PKCS#11 token")
+                    .env("PKCS11_PIN"),
+            );
+
             app = app.arg(
                 Arg::new("vault-address")
                     .long("vault-address")
@@ -1102,6 +1371,59 @@ impl SubCommand for CliModel {
                     .env("VAULT_MOUNT_PATH"),
             );
 
+            app = app.arg(
+                Arg::new("vault-transit-mount-path")
+                    .long("vault-transit-mount-path")
+                    .takes_value(true)
+                    .default_value("transit")
+                    .help("Mount path of the vault Transit secrets engine")
+                    .env("VAULT_TRANSIT_MOUNT_PATH"),
+            );
+
+            app = app.subcommand(
+                Command::new("resync")
+                    .about("Rebuild the Chronicle database from the ledger, then exit")
+                    .arg(
+                        Arg::new("from-block")
+                            .long("from-block")
+                            .takes_value(true)
+                            .value_name("block-id")
+                            .conflicts_with("shadow")
+                            .help("Replay the ledger from this block onto the existing database, rather than rebuilding it from the first"),
+                    )
+                    .arg(
+                        Arg::new("shadow")
+                            .long("shadow")
+                            .takes_value(false)
+                            .help("Rebuild in a shadow schema and swap it in once caught up, so a running API stays available"),
+                    )
+                    .arg(
+                        Arg::new("idle-timeout")
+                            .long("idle-timeout")
+                            .takes_value(true)
+                            .value_name("seconds")
+                            .default_value("10")
+                            .help("Consider the database caught up once no blocks have arrived for this long"),
+                    ),
+            );
+
+            app = app.subcommand(
+                Command::new("verify-store")
+                    .about("Compare the Chronicle database with the ledger and report any discrepancies as JSON, then exit"),
+            );
+
+            app = app.subcommand(
+                Command::new("rotate-key")
+                    .about("Replace the Chronicle key registered on the ledger with a new one, then exit")
+                    .arg(
+                        Arg::new("new-key-path")
+                            .long("new-key-path")
+                            .takes_value(true)
+                            .value_hint(ValueHint::DirPath)
+                            .help("Path to a directory containing the new Chronicle key, unless it is held by Vault Transit"),
+                    ),
+            );
+
             app.arg(
                 // default is provided by cargo.toml
                 Arg::new("sawtooth")
@@ -1111,6 +1433,24 @@ impl SubCommand for CliModel {
                     .help("Sets sawtooth validator address")
                     .takes_value(true),
             )
+            .arg(
+                Arg::new("file-ledger")
+                    .long("file-ledger")
+                    .value_name("path")
+                    .value_hint(ValueHint::FilePath)
+                    .env("CHRONICLE_FILE_LEDGER")
+                    .help("Keep the ledger in a file at this path, created if need be, rather than using a sawtooth validator")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("file-ledger-bootstrap-key")
+                    .long("file-ledger-bootstrap-key")
+                    .value_name("hex")
+                    .env("CHRONICLE_FILE_LEDGER_BOOTSTRAP_KEY")
+                    .requires("file-ledger")
+                    .help("The hex encoded public key that may make the first Chronicle key rotation on the ledger file, prefixed with 'p256:' or 'ed25519:' unless it is secp256k1")
+                    .takes_value(true),
+            )
             .arg(
                 Arg::new("embedded-opa-policy")
                     .long("embedded-opa-policy")
@@ -1128,6 +1468,23 @@ impl SubCommand for CliModel {
 
     /// Iterate our possible subcommands via model and short circuit with the first one that matches
     fn matches(&self, matches: &ArgMatches) -> Result<Option<ApiCommand>, CliError> {
+        if let Some(matches) = matches.subcommand_matches("outbox") {
+            if let Some(id) = matches.get_one::<i32>("retry") {
+                return Ok(Some(ApiCommand::Outbox(OutboxCommand::Retry { id: *id })));
+            }
+            return Ok(Some(ApiCommand::Outbox(OutboxCommand::List {
+                status: matches
+                    .get_one::<String>("status")
+                    .map(|status| match status.as_str() {
+                        "pending" => OutboxStatus::Pending,
+                        "submitted" => OutboxStatus::Submitted,
+                        "failed" => OutboxStatus::Failed,
+                        "committed" => OutboxStatus::Committed,
+                        "contradicted" => OutboxStatus::Contradicted,
+                        _ => unreachable!(), // Guaranteed by PossibleValuesParser
+                    }),
+            })));
+        }
         for (agent, matches) in self.agents.iter().filter_map(|agent| {
             matches
                 .subcommand_matches(&agent.external_id)
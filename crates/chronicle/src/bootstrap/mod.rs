@@ -1,29 +1,40 @@
 mod cli;
 mod opa;
 
+#[cfg(not(feature = "inmem"))]
+use api::file_ledger::FileLedger;
 #[cfg(feature = "inmem")]
 use api::inmem::EmbeddedChronicleTp;
 use api::{
     chronicle_graphql::{ChronicleApiServer, ChronicleGraphQl, JwksUri, SecurityConf, UserInfoUri},
-    Api, ApiDispatch, ApiError, StoreError, UuidGen,
+    Api, ApiDispatch, ApiError, ConnectionOptions, StoreDecisionLog, StoreError, UuidGen,
 };
 use async_graphql::{async_trait, ObjectType};
+use chronicle_proof::{InclusionProof, ProofError};
 #[cfg(not(feature = "inmem"))]
 use chronicle_protocol::{
     address::{FAMILY, VERSION},
+    async_stl_client::{
+        error::SawtoothCommunicationError,
+        ledger::{LedgerReader, LedgerWriter, TransactionId},
+    },
+    messages::ChronicleSubmitTransaction,
+    state::{chronicle_keys_address, ChronicleKeys, SignedKeyRotation, BOOTSTRAP_KEY_SETTING},
     ChronicleLedger,
 };
 use chronicle_signing::{
-    chronicle_secret_names, ChronicleSecretsOptions, ChronicleSigning, BATCHER_NAMESPACE,
-    CHRONICLE_NAMESPACE,
+    chronicle_secret_names, ChronicleSecretsOptions, ChronicleSigning, TaggedVerifyingKey,
+    BATCHER_NAMESPACE, CHRONICLE_NAMESPACE,
 };
 use clap::{ArgMatches, Command};
 use clap_complete::{generate, Generator, Shell};
 pub use cli::*;
 use common::{
-    commands::ApiResponse,
-    database::{get_connection_with_retry, DatabaseConnector},
-    identity::AuthId,
+    commands::{AgentSignedCommand, ApiCommand, ApiResponse},
+    credential::ProvenanceCredential,
+    database::{get_connection_with_retry, ConnectionPool, DatabaseConnector, StoreConnection},
+    decision_log::{DecisionLogOptions, DecisionLogger, FileDecisionLog, HttpDecisionLog},
+    identity::{AgentSignedOperations, AuthId},
     import::{load_bytes_from_stdin, load_bytes_from_url},
     k256::{
         pkcs8::{EncodePrivateKey, LineEnding},
@@ -31,10 +42,11 @@ use common::{
     },
     ledger::SubmissionStage,
     opa::ExecutorContext,
-    prov::{operations::ChronicleOperation, to_json_ld::ToJson, NamespaceId},
+    prov::{operations::ChronicleOperation, to_json_ld::ToJson, AgentId, NamespaceId},
 };
 use rand::rngs::StdRng;
 use rand_core::SeedableRng;
+use serde::Deserialize;
 use std::io::IsTerminal;
 use tracing::{debug, error, info, instrument, warn};
 use user_error::UFE;
@@ -52,8 +64,9 @@ use std::{
     fs::File,
     io::{self, Write},
     net::{SocketAddr, ToSocketAddrs},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
+    time::Duration,
 };
 
 use crate::codegen::ChronicleDomainDef;
@@ -69,8 +82,7 @@ fn sawtooth_address(options: &ArgMatches) -> Result<Vec<SocketAddr>, CliError> {
             arg: "sawtooth".to_owned(),
         })
         .and_then(|s| Url::parse(&s).map_err(CliError::from))
-        .map(|u| u.socket_addrs(|| Some(4004)))
-        .map_err(CliError::from)??)
+        .map(|u| u.socket_addrs(|| Some(4004)))??)
 }
 
 #[allow(dead_code)]
@@ -92,6 +104,37 @@ fn ledger(options: &ArgMatches) -> Result<ChronicleLedger, CliError> {
     ))
 }
 
+/// The ledger file given by `--file-ledger`, which is used rather than a validator
+#[cfg(not(feature = "inmem"))]
+fn file_ledger(options: &ArgMatches) -> Result<Option<FileLedger>, CliError> {
+    let (policy_name, entrypoint) = file_ledger_policy(options);
+    // The ledger file has no settings transaction processor to hold the bootstrap key
+    let settings = options
+        .value_of("file-ledger-bootstrap-key")
+        .map(|key| (BOOTSTRAP_KEY_SETTING, key))
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    Ok(options
+        .value_of("file-ledger")
+        .map(|path| FileLedger::open(path, policy_name, entrypoint, &settings))
+        .transpose()?)
+}
+
+/// The embedded OPA policy and entrypoint a ledger file checks its transactions against, from
+/// `--opa-policy-name` and `--opa-policy-entrypoint` or the default policy if they are not set
+#[cfg(not(feature = "inmem"))]
+fn file_ledger_policy(options: &ArgMatches) -> (&str, &str) {
+    (
+        options
+            .value_of("opa-policy-name")
+            .unwrap_or("allow_transactions"),
+        options
+            .value_of("opa-policy-entrypoint")
+            .unwrap_or("allow_transactions.allowed_users"),
+    )
+}
+
 #[allow(dead_code)]
 fn in_mem_ledger(
     _options: &ArgMatches,
@@ -110,20 +153,18 @@ struct UniqueUuid;
 
 impl UuidGen for UniqueUuid {}
 
-type ConnectionPool = Pool<ConnectionManager<PgConnection>>;
-
 struct RemoteDatabaseConnector {
     db_uri: String,
 }
 
 #[async_trait::async_trait]
 impl DatabaseConnector<(), StoreError> for RemoteDatabaseConnector {
-    async fn try_connect(&self) -> Result<((), Pool<ConnectionManager<PgConnection>>), StoreError> {
+    async fn try_connect(&self) -> Result<((), ConnectionPool), StoreError> {
         use diesel::Connection;
         PgConnection::establish(&self.db_uri)?;
         Ok((
             (),
-            Pool::builder().build(ConnectionManager::<PgConnection>::new(&self.db_uri))?,
+            Pool::builder().build(ConnectionManager::<StoreConnection>::new(&self.db_uri))?,
         ))
     }
 
@@ -144,6 +185,25 @@ async fn pool_remote(db_uri: impl ToString) -> Result<ConnectionPool, ApiError>
     Ok(pool)
 }
 
+/// An SQLite database at `path`, which needs no database server
+fn pool_sqlite(path: &str) -> Result<ConnectionPool, ApiError> {
+    Ok(Pool::builder()
+        .connection_customizer(Box::new(ConnectionOptions {
+            enable_wal: true,
+            enable_foreign_keys: true,
+            busy_timeout: Some(Duration::from_secs(5)),
+        }))
+        .build(ConnectionManager::<StoreConnection>::new(path))?)
+}
+
+/// The SQLite database given by `--sqlite`, otherwise PostgreSQL
+async fn pool(matches: &ArgMatches) -> Result<ConnectionPool, ApiError> {
+    match matches.value_of("sqlite") {
+        Some(path) => pool_sqlite(path),
+        None => pool_remote(&construct_db_uri(matches)).await,
+    }
+}
+
 pub async fn api_server<Query, Mutation>(
     api: &ApiDispatch,
     pool: &ConnectionPool,
@@ -191,8 +251,8 @@ fn namespace_bindings(options: &ArgMatches) -> Vec<NamespaceId> {
 
 fn vault_secrets_options(options: &ArgMatches) -> Result<ChronicleSecretsOptions, CliError> {
     let vault_url = options
-        .value_of("vault-url")
-        .ok_or_else(|| CliError::missing_argument("vault-url"))?;
+        .value_of("vault-address")
+        .ok_or_else(|| CliError::missing_argument("vault-address"))?;
     let token = options
         .value_of("vault-token")
         .ok_or_else(|| CliError::missing_argument("vault-token"))?;
@@ -206,16 +266,52 @@ fn vault_secrets_options(options: &ArgMatches) -> Result<ChronicleSecretsOptions
     ))
 }
 
+fn vault_transit_options(options: &ArgMatches) -> Result<ChronicleSecretsOptions, CliError> {
+    let vault_url = options
+        .value_of("vault-address")
+        .ok_or_else(|| CliError::missing_argument("vault-address"))?;
+    let token = options
+        .value_of("vault-token")
+        .ok_or_else(|| CliError::missing_argument("vault-token"))?;
+    let mount_path = options
+        .value_of("vault-transit-mount-path")
+        .ok_or_else(|| CliError::missing_argument("vault-transit-mount-path"))?;
+    Ok(ChronicleSecretsOptions::signed_by_vault_transit(
+        &Url::parse(vault_url)?,
+        token,
+        mount_path,
+    ))
+}
+
+fn pkcs11_secrets_options(options: &ArgMatches) -> Result<ChronicleSecretsOptions, CliError> {
+    let module = options
+        .value_of("pkcs11-module")
+        .ok_or_else(|| CliError::missing_argument("pkcs11-module"))?;
+    let token_label = options
+        .value_of("pkcs11-token-label")
+        .ok_or_else(|| CliError::missing_argument("pkcs11-token-label"))?;
+    let pin = options
+        .value_of("pkcs11-pin")
+        .ok_or_else(|| CliError::missing_argument("pkcs11-pin"))?;
+    Ok(ChronicleSecretsOptions::held_by_pkcs11(
+        Path::new(module),
+        token_label,
+        pin,
+    ))
+}
+
 async fn chronicle_signing(options: &ArgMatches) -> Result<ChronicleSigning, CliError> {
     // Determine batcher configuration
     let batcher_options = match (
         options.get_one::<PathBuf>("batcher-key-from-path"),
         options.get_flag("batcher-key-from-vault"),
         options.get_flag("batcher-key-generated"),
+        options.get_flag("batcher-key-from-pkcs11"),
     ) {
-        (Some(path), _, _) => ChronicleSecretsOptions::stored_at_path(path),
-        (_, true, _) => vault_secrets_options(options)?,
-        (_, _, true) => ChronicleSecretsOptions::generate_in_memory(),
+        (Some(path), _, _, _) => ChronicleSecretsOptions::stored_at_path(path),
+        (_, true, _, _) => vault_secrets_options(options)?,
+        (_, _, true, _) => ChronicleSecretsOptions::generate_in_memory(),
+        (_, _, _, true) => pkcs11_secrets_options(options)?,
         _ => unreachable!("CLI should always set batcher key"),
     };
 
@@ -223,10 +319,14 @@ async fn chronicle_signing(options: &ArgMatches) -> Result<ChronicleSigning, Cli
         options.get_one::<PathBuf>("chronicle-key-from-path"),
         options.get_flag("chronicle-key-from-vault"),
         options.get_flag("chronicle-key-generated"),
+        options.get_flag("chronicle-key-from-pkcs11"),
+        options.get_flag("chronicle-key-from-vault-transit"),
     ) {
-        (Some(path), _, _) => ChronicleSecretsOptions::stored_at_path(path),
-        (_, true, _) => vault_secrets_options(options)?,
-        (_, _, true) => ChronicleSecretsOptions::generate_in_memory(),
+        (Some(path), _, _, _, _) => ChronicleSecretsOptions::stored_at_path(path),
+        (_, true, _, _, _) => vault_secrets_options(options)?,
+        (_, _, true, _, _) => ChronicleSecretsOptions::generate_in_memory(),
+        (_, _, _, true, _) => pkcs11_secrets_options(options)?,
+        (_, _, _, _, true) => vault_transit_options(options)?,
         _ => unreachable!("CLI should always set chronicle key"),
     };
 
@@ -246,19 +346,46 @@ pub async fn api(
     options: &ArgMatches,
     policy_name: Option<String>,
     liveness_check_interval: Option<u64>,
+    outbox_max_attempts: Option<u32>,
+    verify_store_interval: Option<u64>,
 ) -> Result<ApiDispatch, CliError> {
-    let ledger = ledger(options)?;
+    let signing = chronicle_signing(options).await?;
+    let namespace_bindings = namespace_bindings(options);
+
+    let api = match file_ledger(options)? {
+        Some(file_ledger) => {
+            let proofs = file_ledger.proofs();
+            Api::new(
+                pool.clone(),
+                file_ledger.ledger,
+                UniqueUuid,
+                signing,
+                namespace_bindings,
+                policy_name,
+                liveness_check_interval,
+                outbox_max_attempts,
+                verify_store_interval,
+            )
+            .await?
+            .with_proofs(proofs)
+        }
+        None => {
+            Api::new(
+                pool.clone(),
+                ledger(options)?,
+                UniqueUuid,
+                signing,
+                namespace_bindings,
+                policy_name,
+                liveness_check_interval,
+                outbox_max_attempts,
+                verify_store_interval,
+            )
+            .await?
+        }
+    };
 
-    Ok(Api::new(
-        pool.clone(),
-        ledger,
-        UniqueUuid,
-        chronicle_signing(options).await?,
-        namespace_bindings(options),
-        policy_name,
-        liveness_check_interval,
-    )
-    .await?)
+    Ok(api)
 }
 
 #[cfg(feature = "inmem")]
@@ -267,6 +394,8 @@ pub async fn api(
     options: &ArgMatches,
     remote_opa: Option<String>,
     liveness_check_interval: Option<u64>,
+    outbox_max_attempts: Option<u32>,
+    verify_store_interval: Option<u64>,
 ) -> Result<api::ApiDispatch, CliError> {
     let embedded_tp = in_mem_ledger(options)?;
 
@@ -278,6 +407,8 @@ pub async fn api(
         vec![],
         remote_opa,
         liveness_check_interval,
+        outbox_max_attempts,
+        verify_store_interval,
     )
     .await?)
 }
@@ -341,6 +472,31 @@ impl ConfiguredOpa {
         }
     }
 
+    fn map_context(self, f: impl FnOnce(ExecutorContext) -> ExecutorContext) -> Self {
+        match self {
+            ConfiguredOpa::Embedded(context) => ConfiguredOpa::Embedded(f(context)),
+            ConfiguredOpa::Remote(context, settings) => ConfiguredOpa::Remote(f(context), settings),
+            ConfiguredOpa::Url(context) => ConfiguredOpa::Url(f(context)),
+        }
+    }
+
+    /// Record the inputs the policy evaluates as fixtures in `directory`
+    pub fn capturing_to(self, directory: &Path) -> Self {
+        self.map_context(|context| context.capturing_to(directory))
+    }
+
+    pub fn explaining_denials(self) -> Self {
+        self.map_context(ExecutorContext::explaining_denials)
+    }
+
+    pub fn masking(self) -> Self {
+        self.map_context(ExecutorContext::masking)
+    }
+
+    pub fn logging_decisions_to(self, decision_log: DecisionLogger) -> Self {
+        self.map_context(|context| context.logging_decisions_to(decision_log))
+    }
+
     pub fn remote_settings(&self) -> Option<String> {
         match self {
             ConfiguredOpa::Embedded(_) => None,
@@ -378,19 +534,96 @@ async fn configure_opa(options: &ArgMatches) -> Result<ConfiguredOpa, CliError>
             options.value_of("opa-policy-name").unwrap(),
             options.value_of("opa-policy-entrypoint").unwrap(),
         );
-        let opa = self::opa::opa_executor_from_url(url, policy_name, entrypoint).await?;
+        let opa = self::opa::opa_executor_from_url(
+            url,
+            policy_name,
+            entrypoint,
+            self::opa::bundle_verifier(options)?,
+        )
+        .await?;
         tracing::info!("Chronicle operating with OPA policy from URL");
 
         Ok(ConfiguredOpa::Url(opa))
+    } else if options.is_present("file-ledger") {
+        // A ledger file has no settings to hold a policy, so its transaction processor and
+        // the api both use the embedded one it is configured with
+        let (policy_name, entrypoint) = file_ledger_policy(options);
+        let opa = opa_executor_from_embedded_policy(policy_name, entrypoint).await?;
+        tracing::info!(
+            policy_name,
+            entrypoint,
+            "Chronicle operating with a ledger file and an embedded OPA policy"
+        );
+
+        Ok(ConfiguredOpa::Embedded(opa))
     } else {
-        let (opa, settings) =
-            self::opa::opa_executor_from_sawtooth_settings(&sawtooth_address(options)?).await?;
-        tracing::info!(use_on_chain_opa= ?settings, "Chronicle operating in secure mode with on chain OPA policy");
+        let (opa, settings) = self::opa::opa_executor_from_sawtooth_settings(
+            &sawtooth_address(options)?,
+            self::opa::bundle_verifier(options)?,
+        )
+        .await?;
+        tracing::info!(use_on_chain_opa= ?settings, policy_hash = %opa.hash().await, "Chronicle operating in secure mode with on chain OPA policy");
 
         Ok(ConfiguredOpa::Remote(opa, settings))
     }
 }
 
+/// Where to log policy decisions, if `--opa-decision-log` is set
+fn configure_decision_log(
+    matches: &ArgMatches,
+    pool: &ConnectionPool,
+) -> Result<Option<DecisionLogger>, CliError> {
+    let sink = match matches.value_of("opa-decision-log") {
+        Some(sink) => sink,
+        None => return Ok(None),
+    };
+
+    let sample = matches.value_of("opa-decision-log-sample").unwrap_or("1");
+    let sample = sample
+        .parse::<f64>()
+        .ok()
+        .filter(|sample| (0.0..=1.0).contains(sample))
+        .ok_or_else(|| CliError::InvalidArgument {
+            arg: "opa-decision-log-sample".to_owned(),
+            expected: "a fraction between 0 and 1".to_owned(),
+            got: sample.to_owned(),
+        })?;
+    let queue = matches
+        .value_of("opa-decision-log-queue")
+        .unwrap_or("10000");
+    let queue = queue
+        .parse::<usize>()
+        .ok()
+        .filter(|queue| *queue > 0)
+        .ok_or_else(|| CliError::InvalidArgument {
+            arg: "opa-decision-log-queue".to_owned(),
+            expected: "a number of decisions greater than 0".to_owned(),
+            got: queue.to_owned(),
+        })?;
+    let options = DecisionLogOptions {
+        sample,
+        redact: matches
+            .values_of("opa-decision-log-redact")
+            .map(|paths| paths.map(ToOwned::to_owned).collect())
+            .unwrap_or_default(),
+        queue,
+    };
+
+    let logger = if sink == "database" {
+        DecisionLogger::spawn(
+            StoreDecisionLog::new(pool.clone()).map_err(ApiError::from)?,
+            options,
+        )
+    } else if sink.starts_with("http://") || sink.starts_with("https://") {
+        DecisionLogger::spawn(HttpDecisionLog::new(Url::parse(sink)?), options)
+    } else {
+        DecisionLogger::spawn(FileDecisionLog::new(Path::new(sink)), options)
+    };
+    info!(sink, "Logging OPA decisions");
+
+    Ok(Some(logger))
+}
+
 /// If `--liveness-check` is set, we use either the interval in seconds provided or the default of 1800.
 /// Otherwise, we use `None` to disable the depth charge.
 fn configure_depth_charge(matches: &ArgMatches) -> Option<u64> {
@@ -413,6 +646,38 @@ fn configure_depth_charge(matches: &ArgMatches) -> Option<u64> {
     None
 }
 
+/// If `--outbox` is set, we queue transactions that cannot be submitted, retrying each either the
+/// number of times provided or the default of 100. Otherwise, we use `None` to disable the outbox.
+fn configure_outbox(matches: &ArgMatches) -> Option<u32> {
+    if let Some(serve_api_matches) = matches.subcommand_matches("serve-api") {
+        if let Some(max_attempts) = serve_api_matches.value_of("outbox") {
+            let max_attempts = max_attempts.parse::<u32>().unwrap_or_else(|e| {
+                warn!("Failed to parse '--outbox' value: {e}");
+                100
+            });
+            debug!("Outbox enabled, with up to {max_attempts} attempts per transaction");
+            return Some(max_attempts);
+        }
+    }
+    None
+}
+
+/// If `--verify-store` is set, we compare the database with the ledger at either the interval in
+/// seconds provided or the default of 3600. Otherwise, we use `None` to disable verification.
+fn configure_verify_store(matches: &ArgMatches) -> Option<u64> {
+    if let Some(serve_api_matches) = matches.subcommand_matches("serve-api") {
+        if let Some(interval) = serve_api_matches.value_of("verify-store") {
+            let interval = interval.parse::<u64>().unwrap_or_else(|e| {
+                warn!("Failed to parse '--verify-store' value: {e}");
+                3600
+            });
+            debug!("Store verification enabled, every {interval} seconds");
+            return Some(interval);
+        }
+    }
+    None
+}
+
 #[instrument(skip(gql, cli))]
 async fn execute_subcommand<Query, Mutation>(
     gql: ChronicleGraphQl<Query, Mutation>,
@@ -426,17 +691,40 @@ where
 
     let matches = cli.as_cmd().get_matches();
 
-    let pool = pool_remote(&construct_db_uri(&matches)).await?;
+    let pool = pool(&matches).await?;
 
-    let opa = configure_opa(&matches).await?;
+    let mut opa = configure_opa(&matches).await?;
+    if let Some(directory) = matches.value_of("opa-capture") {
+        std::fs::create_dir_all(directory)?;
+        warn!(
+            directory,
+            "Recording OPA inputs, which include user identities and claims"
+        );
+        opa = opa.capturing_to(Path::new(directory));
+    }
+    if matches.is_present("opa-explain-denials") {
+        opa = opa.explaining_denials();
+    }
+    if matches.is_present("opa-mask") {
+        opa = opa.masking();
+    }
+    if let Some(decision_log) = configure_decision_log(&matches, &pool)? {
+        opa = opa.logging_decisions_to(decision_log);
+    }
 
     let liveness_check_interval = configure_depth_charge(&matches);
 
+    let outbox_max_attempts = configure_outbox(&matches);
+
+    let verify_store_interval = configure_verify_store(&matches);
+
     let api = api(
         &pool,
         &matches,
         opa.remote_settings(),
         liveness_check_interval,
+        outbox_max_attempts,
+        verify_store_interval,
     )
     .await?;
     let ret_api = api.clone();
@@ -536,6 +824,35 @@ where
             return Ok((ApiResponse::Unit, ret_api));
         }
 
+        if let Some(agent) = matches.value_of("agent") {
+            let signed = AgentSignedOperations {
+                agent: AgentId::from_external_id(agent),
+                payload: data.to_owned(),
+                signature: hex::decode(matches.value_of("signature").unwrap())?,
+            };
+            // The signature covers every operation, so none can be left out
+            for op in signed.operations().await.map_err(ApiError::from)? {
+                if op.namespace() != &namespace {
+                    return Err(CliError::InvalidArgument {
+                        arg: "namespace-id".to_owned(),
+                        expected: op.namespace().to_string(),
+                        got: namespace.to_string(),
+                    });
+                }
+            }
+
+            info!("Importing data signed by agent {agent} to Chronicle namespace: {namespace}");
+
+            let response = api
+                .dispatch(
+                    ApiCommand::AgentSigned(AgentSignedCommand { signed }),
+                    AuthId::chronicle(),
+                )
+                .await?;
+
+            return Ok((response, ret_api));
+        }
+
         let json_array = serde_json::from_str::<Vec<serde_json::Value>>(data)?;
 
         let mut operations = Vec::new();
@@ -567,6 +884,184 @@ where
     }
 }
 
+/// Rebuild the database from the ledger, in place or in a shadow schema that then replaces it
+#[cfg(not(feature = "inmem"))]
+async fn resync(options: &ArgMatches, matches: &ArgMatches) -> Result<(), CliError> {
+    use api::resync::{ResyncOptions, ResyncProgress, SHADOW_SCHEMA};
+    use async_stl_client::ledger::{BlockId, FromBlock};
+
+    let pool = pool(options).await?;
+
+    let shadow = if !matches.is_present("shadow") {
+        None
+    } else if options.is_present("sqlite") {
+        return Err(CliError::InvalidArgument {
+            arg: "shadow".to_owned(),
+            expected: "a PostgreSQL database".to_owned(),
+            got: "an SQLite database".to_owned(),
+        });
+    } else {
+        let db_uri = construct_db_uri(options);
+        Some(pool_remote(format!("{db_uri}?options=-csearch_path%3D{SHADOW_SCHEMA}")).await?)
+    };
+
+    let from_block = match matches.value_of("from-block") {
+        Some(block_id) => {
+            FromBlock::BlockId(BlockId::try_from(block_id.to_owned()).map_err(|_| {
+                CliError::InvalidArgument {
+                    arg: "from-block".to_owned(),
+                    expected: "a block id".to_owned(),
+                    got: block_id.to_owned(),
+                }
+            })?)
+        }
+        None => FromBlock::First,
+    };
+
+    let idle_timeout = matches.value_of("idle-timeout").unwrap();
+    let idle_timeout = idle_timeout
+        .parse::<u64>()
+        .map_err(|_| CliError::InvalidArgument {
+            arg: "idle-timeout".to_owned(),
+            expected: "a number of seconds".to_owned(),
+            got: idle_timeout.to_owned(),
+        })?;
+
+    let report = |progress: &ResyncProgress| {
+        eprintln!(
+            "{} blocks, {} transactions ({} contradicted), {:.1} transactions/s, last block {}",
+            progress.blocks,
+            progress.transactions,
+            progress.contradictions,
+            progress.transactions_per_second(),
+            progress
+                .last_block
+                .map(|block_id| block_id.to_string())
+                .unwrap_or_else(|| "-".to_owned()),
+        )
+    };
+
+    let resync_options = ResyncOptions {
+        from_block,
+        idle_timeout: std::time::Duration::from_secs(idle_timeout),
+        shadow,
+    };
+
+    let progress = match file_ledger(options)? {
+        Some(file_ledger) => {
+            api::resync::resync(
+                pool,
+                file_ledger.ledger,
+                namespace_bindings(options),
+                resync_options,
+                report,
+            )
+            .await?
+        }
+        None => {
+            api::resync::resync(
+                pool,
+                ledger(options)?,
+                namespace_bindings(options),
+                resync_options,
+                report,
+            )
+            .await?
+        }
+    };
+
+    println!(
+        "Resync complete in {:.1?}: {} transactions from {} blocks",
+        progress.elapsed, progress.transactions, progress.blocks
+    );
+
+    Ok(())
+}
+
+/// Compare the database with the ledger, printing a report of any discrepancies as JSON.
+/// Returns whether the two are consistent.
+#[cfg(not(feature = "inmem"))]
+async fn verify_store(options: &ArgMatches) -> Result<bool, CliError> {
+    let pool = pool(options).await?;
+
+    let report = match file_ledger(options)? {
+        Some(file_ledger) => api::verify::verify_store(pool, &file_ledger.ledger).await?,
+        None => api::verify::verify_store(pool, &ledger(options)?).await?,
+    };
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    Ok(report.consistent)
+}
+
+/// Register the Chronicle key in `--new-key-path` on the ledger in place of the one Chronicle is
+/// configured with, which signs the rotation along with the new key. A key held by Vault Transit
+/// is instead rotated there, and its next version registered.
+#[cfg(not(feature = "inmem"))]
+async fn rotate_key(
+    options: &ArgMatches,
+    rotate_matches: &ArgMatches,
+) -> Result<TransactionId, CliError> {
+    let previous = chronicle_signing(options).await?;
+    let new = match (
+        rotate_matches.value_of("new-key-path"),
+        options.get_flag("chronicle-key-from-vault-transit"),
+    ) {
+        (Some(path), false) => Some(
+            ChronicleSigning::new(
+                chronicle_secret_names(),
+                vec![(
+                    CHRONICLE_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::stored_at_path(Path::new(path)),
+                )],
+            )
+            .await?,
+        ),
+        (None, true) => None,
+        (Some(path), true) => {
+            return Err(CliError::InvalidArgument {
+                arg: "new-key-path".to_owned(),
+                expected: "no new key path, as Vault Transit holds the new key".to_owned(),
+                got: path.to_owned(),
+            })
+        }
+        (None, false) => return Err(CliError::missing_argument("new-key-path")),
+    };
+
+    match file_ledger(options)? {
+        Some(file_ledger) => submit_key_rotation(&file_ledger.ledger, previous, new.as_ref()).await,
+        None => submit_key_rotation(&ledger(options)?, previous, new.as_ref()).await,
+    }
+}
+
+/// Submit a rotation from `previous` to `new`, or to the next Transit version of `previous`
+#[cfg(not(feature = "inmem"))]
+async fn submit_key_rotation<L>(
+    ledger: &L,
+    previous: ChronicleSigning,
+    new: Option<&ChronicleSigning>,
+) -> Result<TransactionId, CliError>
+where
+    L: LedgerReader<Error = SawtoothCommunicationError>
+        + LedgerWriter<Transaction = ChronicleSubmitTransaction, Error = SawtoothCommunicationError>,
+{
+    let registered = match ledger.get_state_entry(&chronicle_keys_address()).await {
+        Ok(entry) => Some(serde_json::from_slice::<ChronicleKeys>(&entry)?),
+        Err(SawtoothCommunicationError::ResourceNotFound) => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    let rotation = match new {
+        Some(new) => SignedKeyRotation::new(&previous, new, registered.as_ref()).await?,
+        None => SignedKeyRotation::in_transit(&previous, registered.as_ref()).await?,
+    };
+
+    Ok(ledger
+        .submit(&ChronicleSubmitTransaction::rotate_key(rotation, previous))
+        .await
+        .map_err(|(_id, e)| e)?)
+}
+
 fn get_namespace(matches: &ArgMatches) -> NamespaceId {
     let namespace_id = matches.value_of("namespace-id").unwrap();
     let namespace_uuid = matches.value_of("namespace-uuid").unwrap();
@@ -701,6 +1196,44 @@ where
                 }
             }
         }
+        (
+            ApiResponse::PriorSubmission {
+                subject,
+                tx_id,
+                stage,
+            },
+            _api,
+        ) => match (tx_id, stage) {
+            (Some(tx_id), Some(stage)) => {
+                println!("Previously submitted as transaction {tx_id} ({stage:?}): {subject}")
+            }
+            _ => println!("Previously found to result in no data changes: {subject}"),
+        },
+        (
+            ApiResponse::Queued {
+                subject,
+                prov: _,
+                outbox_id,
+            },
+            _api,
+        ) => {
+            println!("Ledger unreachable, queued for submission as outbox item {outbox_id}: {subject}")
+        }
+        (ApiResponse::OutboxItems { items }, _api) => {
+            for item in items {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    item.id,
+                    item.status.as_str(),
+                    item.attempts,
+                    item.subject,
+                    item.tx_id
+                        .map(|tx_id| tx_id.to_string())
+                        .unwrap_or_else(|| "-".to_owned()),
+                    item.last_error.unwrap_or_default()
+                );
+            }
+        }
         (ApiResponse::DepthChargeSubmitted { tx_id }, _) => error!(
             "DepthChargeSubmitted is an unexpected API response for transaction: {tx_id}. Depth charge not implemented."
         ),
@@ -708,6 +1241,89 @@ where
     Ok(())
 }
 
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProofFile {
+    Proof(InclusionProof),
+    Proofs(Vec<InclusionProof>),
+    Response { proofs: Vec<InclusionProof> },
+}
+
+/// Check each proof in the `--proof` file against the `--block-id` block. With a `--record`, only
+/// the proofs of that record are checked, and there must be at least one.
+fn verify_file_ledger_proof(matches: &ArgMatches) -> Result<bool, CliError> {
+    let read = |arg: &str| std::fs::read(matches.get_one::<String>(arg).unwrap());
+
+    let proofs = match serde_json::from_slice(&read("proof")?)? {
+        ProofFile::Proof(proof) => vec![proof],
+        ProofFile::Proofs(proofs) | ProofFile::Response { proofs } => proofs,
+    };
+    let block_id = matches.get_one::<String>("block-id").unwrap();
+    let record = matches
+        .get_one::<String>("record")
+        .map(std::fs::read)
+        .transpose()?;
+
+    let mut checked = 0;
+    let mut valid = true;
+    for proof in &proofs {
+        match chronicle_proof::verify(proof, block_id, record.as_deref()) {
+            Ok(()) => println!("{}: included in block {}", proof.address, block_id),
+            Err(ProofError::RecordMismatch) => continue,
+            Err(e) => {
+                println!("{}: {}", proof.address, e);
+                valid = false;
+            }
+        }
+        checked += 1;
+    }
+
+    if checked == 0 {
+        println!("No proof is of the given record");
+        return Ok(false);
+    }
+
+    Ok(valid)
+}
+
+/// Check a `--credential` is signed by its issuer, or by the `--public-key` if one is given,
+/// and that the identities of the transactions it cites are signed
+fn verify_credential(matches: &ArgMatches) -> Result<bool, CliError> {
+    let credential: ProvenanceCredential = serde_json::from_slice(&std::fs::read(
+        matches.get_one::<String>("credential").unwrap(),
+    )?)?;
+    let trusted = matches
+        .get_one::<String>("public-key")
+        .map(|path| -> Result<_, CliError> {
+            TaggedVerifyingKey::from_public_key_pem(&std::fs::read_to_string(path)?).map_err(|_| {
+                CliError::InvalidPublicKey {
+                    path: path.to_owned(),
+                }
+            })
+        })
+        .transpose()?;
+
+    match credential.verify(trusted.as_ref()) {
+        Ok(verified) => {
+            println!(
+                "{} issued by {}",
+                credential.credential_subject.id, credential.issuer
+            );
+            for (tx_id, identity) in verified.identities {
+                println!(
+                    "{tx_id}: submitted by {}",
+                    serde_json::to_string(&identity)?
+                );
+            }
+            Ok(true)
+        }
+        Err(e) => {
+            println!("{}: {}", credential.credential_subject.id, e);
+            Ok(false)
+        }
+    }
+}
+
 fn print_completions<G: Generator>(gen: G, app: &mut Command) {
     generate(gen, app, app.get_name().to_string(), &mut io::stdout());
 }
@@ -735,6 +1351,29 @@ pub async fn bootstrap<Query, Mutation>(
         print!("{}", gql.exportable_schema());
         std::process::exit(0);
     }
+
+    if let Some(matches) = matches.subcommand_matches("verify-credential") {
+        match verify_credential(matches) {
+            Ok(true) => std::process::exit(0),
+            Ok(false) => std::process::exit(2),
+            Err(e) => {
+                e.into_ufe().print();
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("verify-file-ledger-proof") {
+        match verify_file_ledger_proof(matches) {
+            Ok(true) => std::process::exit(0),
+            Ok(false) => std::process::exit(2),
+            Err(e) => {
+                e.into_ufe().print();
+                std::process::exit(1);
+            }
+        }
+    }
+
     chronicle_telemetry::telemetry(
         matches
             .get_one::<String>("instrument")
@@ -770,6 +1409,51 @@ pub async fn bootstrap<Query, Mutation>(
         std::process::exit(0);
     }
 
+    #[cfg(not(feature = "inmem"))]
+    if let Some(resync_matches) = matches.subcommand_matches("resync") {
+        resync(&matches, resync_matches)
+            .await
+            .map_err(|e| {
+                error!(?e, "Resync error");
+                e.into_ufe().print();
+                std::process::exit(1);
+            })
+            .ok();
+
+        std::process::exit(0);
+    }
+
+    #[cfg(not(feature = "inmem"))]
+    if matches.subcommand_matches("verify-store").is_some() {
+        match verify_store(&matches).await {
+            Ok(true) => std::process::exit(0),
+            // Distinguish discrepancies from failing to verify
+            Ok(false) => std::process::exit(2),
+            Err(e) => {
+                error!(?e, "Store verification error");
+                e.into_ufe().print();
+                std::process::exit(1);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "inmem"))]
+    if let Some(rotate_matches) = matches.subcommand_matches("rotate-key") {
+        match rotate_key(&matches, rotate_matches).await {
+            Ok(tx_id) => {
+                println!(
+                    "Key rotation submitted as transaction {tx_id}. Once it is committed, restart Chronicle with the new key."
+                );
+                std::process::exit(0);
+            }
+            Err(e) => {
+                error!(?e, "Key rotation error");
+                e.into_ufe().print();
+                std::process::exit(1);
+            }
+        }
+    }
+
     config_and_exec(gql, domain.into())
         .await
         .map_err(|e| {
@@ -934,6 +1618,8 @@ pub mod test {
             vec![],
             Some("allow_transactions".to_owned()),
             liveness_check_interval,
+            None,
+            None,
         )
         .await
         .unwrap();
@@ -10,12 +10,15 @@ use chronicle_protocol::{
 };
 use clap::ArgMatches;
 use common::opa::{
-    CliPolicyLoader, ExecutorContext, PolicyLoader, SawtoothPolicyLoader, UrlPolicyLoader,
+    CliPolicyLoader, ExecutorContext, PolicyLoader, PolicyLoaderError, SawtoothPolicyLoader,
+    UrlPolicyLoader,
 };
-use tracing::{debug, instrument};
+use opa_tp_protocol::bundle::{BundleVerifier, DEFAULT_KEY_ID};
+use tracing::{debug, info, instrument};
 
 use super::CliError;
 
+#[allow(dead_code)]
 trait SetRuleOptions {
     fn rule_addr(&mut self, options: &ArgMatches) -> Result<(), CliError>;
     fn rule_entrypoint(&mut self, options: &ArgMatches) -> Result<(), CliError>;
@@ -50,6 +53,23 @@ impl SetRuleOptions for CliPolicyLoader {
     }
 }
 
+/// The verifier of policy bundles, if `--opa-bundle-key` is set
+#[cfg(not(feature = "inmem"))]
+pub fn bundle_verifier(options: &ArgMatches) -> Result<Option<BundleVerifier>, CliError> {
+    let path = match options.value_of("opa-bundle-key") {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let key_id = options
+        .value_of("opa-bundle-key-id")
+        .unwrap_or(DEFAULT_KEY_ID);
+
+    let verifier =
+        BundleVerifier::from_pem(key_id, &std::fs::read(path)?).map_err(PolicyLoaderError::from)?;
+    info!(key_id, "Verifying OPA policy bundle signatures");
+    Ok(Some(verifier))
+}
+
 #[instrument()]
 pub async fn opa_executor_from_embedded_policy(
     policy_name: &str,
@@ -62,6 +82,7 @@ pub async fn opa_executor_from_embedded_policy(
 #[instrument()]
 pub async fn opa_executor_from_sawtooth_settings(
     validator_address: &Vec<SocketAddr>,
+    verifier: Option<BundleVerifier>,
 ) -> Result<(ExecutorContext, OpaSettings), CliError> {
     let settings = SettingsReader::new(SawtoothLedger::new(
         ZmqRequestResponseSawtoothChannel::new(
@@ -76,12 +97,29 @@ pub async fn opa_executor_from_sawtooth_settings(
     let opa_settings = read_opa_settings(&settings).await?;
     debug!(on_chain_opa_policy = ?opa_settings);
     let mut loader = SawtoothPolicyLoader::new(
-        validator_address.get(0).unwrap(),
+        validator_address.first().unwrap(),
         &opa_settings.policy_name,
         &opa_settings.entrypoint,
-    )?;
+    )?
+    .verifying_bundles_with(verifier.clone());
     loader.load_policy().await?;
-    Ok((ExecutorContext::from_loader(&loader)?, opa_settings))
+    let context = ExecutorContext::from_loader(&loader)?;
+
+    // Namespaces set to use other policies are read before starting, and again whenever they
+    // are set, with each policy reloaded like Chronicle's own when it is updated
+    let namespace_loader = SawtoothPolicyLoader::new(
+        validator_address.first().unwrap(),
+        &opa_settings.policy_name,
+        &opa_settings.entrypoint,
+    )?
+    .verifying_bundles_with(verifier);
+    namespace_loader
+        .load_namespace_policies(validator_address.first().unwrap(), &context)
+        .await?;
+    namespace_loader.watch_namespace_policies(*validator_address.first().unwrap(), context.clone());
+
+    loader.watch(context.clone());
+    Ok((context, opa_settings))
 }
 
 #[instrument()]
@@ -89,8 +127,10 @@ pub async fn opa_executor_from_url(
     url: &str,
     policy_name: &str,
     entrypoint: &str,
+    verifier: Option<BundleVerifier>,
 ) -> Result<ExecutorContext, CliError> {
-    let mut loader = UrlPolicyLoader::new(url, policy_name, entrypoint);
+    let mut loader =
+        UrlPolicyLoader::new(url, policy_name, entrypoint).verifying_bundles_with(verifier);
     loader.load_policy().await?;
     Ok(ExecutorContext::from_loader(&loader)?)
 }
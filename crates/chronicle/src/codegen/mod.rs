@@ -319,6 +319,7 @@ fn gen_activity_definition(activity: &ActivityDef) -> rust::Tokens {
     let async_result = &rust::import("chronicle::async_graphql", "Result").qualified();
     let context = &rust::import("chronicle::async_graphql", "Context").qualified();
     let domain_type_id = &rust::import("chronicle::common::prov", "DomaintypeId");
+    let outbox_status = &rust::import("chronicle::common::commands", "OutboxStatus").qualified();
     let date_time = &rust::import("chronicle::chrono", "DateTime");
     let utc = &rust::import("chronicle::chrono", "Utc");
     let chronicle_json = &rust::import("chronicle::common::prov", "ChronicleJSON");
@@ -328,6 +329,7 @@ fn gen_activity_definition(activity: &ActivityDef) -> rust::Tokens {
     let generated_doc = include_str!("../../../../domain_docs/generated.md");
     let id_doc = include_str!("../../../../domain_docs/id.md");
     let namespace_doc = include_str!("../../../../domain_docs/namespace.md");
+    let outbox_status_doc = include_str!("../../../../domain_docs/outbox_status.md");
     let start_doc = include_str!("../../../../domain_docs/start.md");
     let type_doc = include_str!("../../../../domain_docs/type.md");
     let used_doc = include_str!("../../../../domain_docs/used.md");
@@ -370,6 +372,11 @@ fn gen_activity_definition(activity: &ActivityDef) -> rust::Tokens {
             self.0.ended.map(|x| #date_time::from_naive_utc_and_offset(x, #utc))
         }
 
+        #[doc = #_(#outbox_status_doc)]
+        async fn outbox_status<'a>(&self, ctx: &#context<'a>) -> #async_result<Option<#outbox_status>> {
+            #activity_impl::outbox_status(self.0.namespace_id, &self.0.external_id, ctx).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+        }
+
         #[doc = #_(#type_doc)]
         #[graphql(name = "type")]
         async fn typ(&self) -> Option<#domain_type_id> {
@@ -380,48 +387,56 @@ fn gen_activity_definition(activity: &ActivityDef) -> rust::Tokens {
         async fn was_associated_with<'a>(
             &self,
             ctx: &#context<'a>,
-        ) -> #async_result<Vec<Association>> {
+        ) -> #async_result<Option<Vec<Association>>> {
             Ok(
                 #activity_impl::was_associated_with(self.0.id, ctx)
                     .await
                     .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .into_iter()
-                    .map(|(r_agent, r_role, d_agent, d_role)| map_association_to_role(r_agent, d_agent, r_role, d_role))
-                    .collect(),
+                    .map(|res| {
+                        res.into_iter()
+                            .map(|(r_agent, r_role, d_agent, d_role)| map_association_to_role(r_agent, d_agent, r_role, d_role))
+                            .collect()
+                    }),
             )
         }
 
         #[doc = #_(#used_doc)]
-        async fn used<'a>(&self, ctx: &#context<'a>) -> #async_result<Vec<#(entity_union_type_name())>> {
+        async fn used<'a>(&self, ctx: &#context<'a>) -> #async_result<Option<Vec<#(entity_union_type_name())>>> {
             Ok(#activity_impl::used(self.0.id, ctx)
                 .await
                 .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                .into_iter()
-                .map(map_entity_to_domain_type)
-                .collect())
+                .map(|res| {
+                    res.into_iter()
+                        .map(map_entity_to_domain_type)
+                        .collect()
+                }))
         }
 
         #[doc = #_(#was_informed_by_doc)]
-        async fn was_informed_by<'a>(&self, ctx: &#context<'a>) -> #async_result<Vec<#(activity_union_type_name())>> {
+        async fn was_informed_by<'a>(&self, ctx: &#context<'a>) -> #async_result<Option<Vec<#(activity_union_type_name())>>> {
             Ok(#activity_impl::was_informed_by(self.0.id, ctx)
                 .await
                 .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                .into_iter()
-                .map(map_activity_to_domain_type)
-                .collect())
+                .map(|res| {
+                    res.into_iter()
+                        .map(map_activity_to_domain_type)
+                        .collect()
+                }))
         }
 
         #[doc = #_(#generated_doc)]
         async fn generated<'a>(
             &self,
             ctx: &#context<'a>,
-        ) -> #async_result<Vec<#(entity_union_type_name())>> {
+        ) -> #async_result<Option<Vec<#(entity_union_type_name())>>> {
             Ok(#activity_impl::generated(self.0.id, ctx)
                 .await
                 .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                .into_iter()
-                .map(map_entity_to_domain_type)
-                .collect())
+                .map(|res| {
+                    res.into_iter()
+                        .map(map_entity_to_domain_type)
+                        .collect()
+                }))
         }
 
         #(for attribute in &activity.attributes =>
@@ -471,6 +486,7 @@ fn gen_entity_definition(entity: &EntityDef) -> rust::Tokens {
     let async_result = &rust::import("chronicle::async_graphql", "Result").qualified();
     let context = &rust::import("chronicle::async_graphql", "Context").qualified();
     let domain_type_id = &rust::import("chronicle::common::prov", "DomaintypeId");
+    let outbox_status = &rust::import("chronicle::common::commands", "OutboxStatus").qualified();
     let chronicle_json = &rust::import("chronicle::common::prov", "ChronicleJSON");
     let async_graphql_error_extensions =
         &rust::import("chronicle::async_graphql", "ErrorExtensions").qualified();
@@ -479,6 +495,7 @@ fn gen_entity_definition(entity: &EntityDef) -> rust::Tokens {
     let had_primary_source_doc = include_str!("../../../../domain_docs/had_primary_source.md");
     let id_doc = include_str!("../../../../domain_docs/id.md");
     let namespace_doc = include_str!("../../../../domain_docs/namespace.md");
+    let outbox_status_doc = include_str!("../../../../domain_docs/outbox_status.md");
     let type_doc = include_str!("../../../../domain_docs/type.md");
     let was_attributed_to_doc = include_str!("../../../../domain_docs/was_attributed_to.md");
     let was_derived_from_doc = include_str!("../../../../domain_docs/was_derived_from.md");
@@ -512,6 +529,11 @@ fn gen_entity_definition(entity: &EntityDef) -> rust::Tokens {
             &self.0.external_id
         }
 
+        #[doc = #_(#outbox_status_doc)]
+        async fn outbox_status<'a>(&self, ctx: &#context<'a>) -> #async_result<Option<#outbox_status>> {
+            #entity_impl::outbox_status(self.0.namespace_id, &self.0.external_id, ctx).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+        }
+
         #[doc = #_(#type_doc)]
         #[graphql(name = "type")]
         async fn typ(&self) -> Option<#domain_type_id> {
@@ -522,14 +544,16 @@ fn gen_entity_definition(entity: &EntityDef) -> rust::Tokens {
         async fn was_attributed_to<'a>(
             &self,
             ctx: &#context<'a>,
-        ) -> #async_result<Vec<Attribution>> {
+        ) -> #async_result<Option<Vec<Attribution>>> {
             Ok(
                 #entity_impl::was_attributed_to(self.0.id, ctx)
                     .await
                     .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .into_iter()
-                    .map(|(agent, role)| map_attribution_to_role(agent, role))
-                    .collect(),
+                    .map(|res| {
+                        res.into_iter()
+                            .map(|(agent, role)| map_attribution_to_role(agent, role))
+                            .collect()
+                    }),
             )
         }
 
@@ -537,58 +561,68 @@ fn gen_entity_definition(entity: &EntityDef) -> rust::Tokens {
         async fn was_generated_by<'a>(
             &self,
             ctx: &#context<'a>,
-        ) -> #async_result<Vec<#(activity_union_type_name())>> {
+        ) -> #async_result<Option<Vec<#(activity_union_type_name())>>> {
             Ok(#entity_impl::was_generated_by(self.0.id, ctx)
                 .await
                 .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                .into_iter()
-                .map(map_activity_to_domain_type)
-                .collect())
+                .map(|res| {
+                    res.into_iter()
+                        .map(map_activity_to_domain_type)
+                        .collect()
+                }))
         }
 
         #[doc = #_(#was_derived_from_doc)]
-        async fn was_derived_from<'a>(&self, ctx: &#context<'a>) -> #async_result<Vec<#(entity_union_type_name())>> {
+        async fn was_derived_from<'a>(&self, ctx: &#context<'a>) -> #async_result<Option<Vec<#(entity_union_type_name())>>> {
             Ok(#entity_impl::was_derived_from(self.0.id, ctx)
                 .await
                 .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                .into_iter()
-                .map(map_entity_to_domain_type)
-                .collect())
+                .map(|res| {
+                    res.into_iter()
+                        .map(map_entity_to_domain_type)
+                        .collect()
+                }))
         }
 
         #[doc = #_(#had_primary_source_doc)]
         async fn had_primary_source<'a>(
             &self,
             ctx: &#context<'a>,
-        ) -> #async_result<Vec<#(entity_union_type_name())>> {
+        ) -> #async_result<Option<Vec<#(entity_union_type_name())>>> {
             Ok(
                 #entity_impl::had_primary_source(self.0.id, ctx)
                     .await
                     .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .into_iter()
-                    .map(map_entity_to_domain_type)
-                    .collect(),
+                    .map(|res| {
+                        res.into_iter()
+                            .map(map_entity_to_domain_type)
+                            .collect()
+                    }),
             )
         }
 
         #[doc = #_(#was_revision_of_doc)]
-        async fn was_revision_of<'a>(&self, ctx: &#context<'a>) -> #async_result<Vec<#(entity_union_type_name())>> {
+        async fn was_revision_of<'a>(&self, ctx: &#context<'a>) -> #async_result<Option<Vec<#(entity_union_type_name())>>> {
             Ok(#entity_impl::was_revision_of(self.0.id, ctx)
                 .await
                 .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                .into_iter()
-                .map(map_entity_to_domain_type)
-                .collect())
+                .map(|res| {
+                    res.into_iter()
+                        .map(map_entity_to_domain_type)
+                        .collect()
+                }))
         }
 
         #[doc = #_(#was_quoted_from_doc)]
-        async fn was_quoted_from<'a>(&self, ctx: &#context<'a>) -> #async_result<Vec<#(entity_union_type_name())>> {
+        async fn was_quoted_from<'a>(&self, ctx: &#context<'a>) -> #async_result<Option<Vec<#(entity_union_type_name())>>> {
             Ok(#entity_impl::was_quoted_from(self.0.id, ctx)
                 .await
                 .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                .into_iter()
-                .map(map_entity_to_domain_type)
-                .collect())
+                .map(|res| {
+                    res.into_iter()
+                        .map(map_entity_to_domain_type)
+                        .collect()
+                }))
         }
 
         #(for attribute in &entity.attributes =>
@@ -639,6 +673,7 @@ fn gen_agent_definition(agent: &AgentDef) -> rust::Tokens {
     let context = &rust::import("chronicle::async_graphql", "Context").qualified();
     let agent_id = &rust::import("chronicle::common::prov", "AgentId");
     let domain_type_id = &rust::import("chronicle::common::prov", "DomaintypeId");
+    let outbox_status = &rust::import("chronicle::common::commands", "OutboxStatus").qualified();
     let chronicle_json = &rust::import("chronicle::common::prov", "ChronicleJSON");
     let async_graphql_error_extensions =
         &rust::import("chronicle::async_graphql", "ErrorExtensions").qualified();
@@ -649,6 +684,7 @@ fn gen_agent_definition(agent: &AgentDef) -> rust::Tokens {
     let id_doc = include_str!("../../../../domain_docs/id.md");
     let identity_doc = include_str!("../../../../domain_docs/identity.md");
     let namespace_doc = include_str!("../../../../domain_docs/namespace.md");
+    let outbox_status_doc = include_str!("../../../../domain_docs/outbox_status.md");
     let type_doc = include_str!("../../../../domain_docs/type.md");
 
     quote! {
@@ -684,26 +720,28 @@ fn gen_agent_definition(agent: &AgentDef) -> rust::Tokens {
         }
 
         #[doc = #_(#acted_on_behalf_of_doc)]
-        async fn acted_on_behalf_of<'a>(&self, ctx: &#context<'a>) -> #async_result<Vec<AgentRef>> {
+        async fn acted_on_behalf_of<'a>(&self, ctx: &#context<'a>) -> #async_result<Option<Vec<AgentRef>>> {
             Ok(#agent_impl::acted_on_behalf_of(self.0.id, ctx)
                 .await
                 .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                .into_iter()
-                .map(|(agent,role)|(Self(agent),role))
-                .map(|(agent,role)| AgentRef {agent : #agent_union_type::from(agent), role: role.into()})
-                .collect())
+                .map(|res| {
+                    res.into_iter()
+                        .map(|(agent,role)|(Self(agent),role))
+                        .map(|(agent,role)| AgentRef {agent : #agent_union_type::from(agent), role: role.into()})
+                        .collect()
+                }))
         }
 
         #[doc = #_(#attribution_doc)]
-        async fn attribution<'a>(&self, ctx: &#context<'a>) -> #async_result<Vec<Attributed>> {
+        async fn attribution<'a>(&self, ctx: &#context<'a>) -> #async_result<Option<Vec<Attributed>>> {
             Ok(#agent_impl::attribution(self.0.id, ctx)
                 .await
                 .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                .into_iter()
-                .map(|(entity, role)| {
-                    map_attributed_to_role(entity, role)
-                })
-                .collect())
+                .map(|res| {
+                    res.into_iter()
+                        .map(|(entity, role)| map_attributed_to_role(entity, role))
+                        .collect()
+                }))
         }
 
         #(for attribute in &agent.attributes =>
@@ -740,6 +778,11 @@ fn gen_agent_definition(agent: &AgentDef) -> rust::Tokens {
             }))
         })
 
+        #[doc = #_(#outbox_status_doc)]
+        async fn outbox_status<'a>(&self, ctx: &#context<'a>) -> #async_result<Option<#outbox_status>> {
+            #agent_impl::outbox_status(self.0.namespace_id, &self.0.external_id, ctx).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+        }
+
         #[doc = #_(#type_doc)]
         #[graphql(name = "type")]
         async fn typ(&self) -> Option<#domain_type_id> {
@@ -983,6 +1026,9 @@ fn gen_query() -> rust::Tokens {
 
     let timeline_order =
         &rust::import("chronicle::api::chronicle_graphql", "TimelineOrder").qualified();
+    let transaction = &rust::import("chronicle::api::chronicle_graphql", "Transaction").qualified();
+    let outbox_item = &rust::import("chronicle::api::chronicle_graphql", "OutboxItem").qualified();
+    let outbox_status = &rust::import("chronicle::common::commands", "OutboxStatus").qualified();
 
     let activities_by_type_doc = include_str!("../../../../domain_docs/activities_by_type.md");
     let activity_by_id_doc = include_str!("../../../../domain_docs/activity_by_id.md");
@@ -991,6 +1037,8 @@ fn gen_query() -> rust::Tokens {
     let agents_by_type_doc = include_str!("../../../../domain_docs/agents_by_type.md");
     let entities_by_type_doc = include_str!("../../../../domain_docs/entities_by_type.md");
     let entity_by_id_doc = include_str!("../../../../domain_docs/entity_by_id.md");
+    let transaction_doc = include_str!("../../../../domain_docs/transaction.md");
+    let outbox_doc = include_str!("../../../../domain_docs/outbox.md");
 
     quote! {
     #[derive(Copy, Clone)]
@@ -1208,6 +1256,32 @@ fn gen_query() -> rust::Tokens {
             .map_err(|e| #async_graphql_error_extensions::extend(&e))?
             .map(map_entity_to_domain_type))
     }
+
+    #[doc = #_(#transaction_doc)]
+    pub async fn transaction<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+        tx_id: String,
+    ) -> #graphql_result<Option<#transaction>> {
+        #query_impl::transaction(ctx, tx_id)
+            .await
+            .map_err(|e| #async_graphql_error_extensions::extend(&e))
+    }
+
+    #[doc = #_(#outbox_doc)]
+    pub async fn outbox<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+        status: Option<#outbox_status>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> #graphql_result<#graphql_connection<i32, #outbox_item, #empty_fields, #empty_fields>> {
+        #query_impl::outbox(ctx, status, after, before, first, last)
+            .await
+            .map_err(|e| #async_graphql_error_extensions::extend(&e))
+    }
     }
     }
 }
@@ -1233,6 +1307,7 @@ fn gen_mutation(domain: &ChronicleDomainDef) -> rust::Tokens {
 
     let acted_on_behalf_of_doc = include_str!("../../../../domain_docs/acted_on_behalf_of.md");
     let define_doc = include_str!("../../../../domain_docs/define.md");
+    let submit_agent_signed_doc = include_str!("../../../../domain_docs/submit_agent_signed.md");
     let end_doc = include_str!("../../../../domain_docs/end_activity.md");
     let had_primary_source_doc = include_str!("../../../../domain_docs/had_primary_source.md");
     let instant_activity_doc = include_str!("../../../../domain_docs/instant_activity.md");
@@ -1561,6 +1636,17 @@ fn gen_mutation(domain: &ChronicleDomainDef) -> rust::Tokens {
         ) -> async_graphql::#graphql_result<#submission> {
             #impls::was_generated_by(ctx, activity.into(), id.into(), namespace).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
         }
+
+        #[doc = #_(#submit_agent_signed_doc)]
+        pub async fn submit_agent_signed<'a>(
+            &self,
+            ctx: &#graphql_context<'a>,
+            agent: #agent_id,
+            payload: String,
+            signature: String,
+        ) -> async_graphql::#graphql_result<#submission> {
+            #impls::submit_agent_signed(ctx, agent.into(), payload, signature).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+        }
     }
     }
 }
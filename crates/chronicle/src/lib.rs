@@ -1,4 +1,5 @@
 #![cfg_attr(feature = "strict", deny(warnings))]
+#![allow(clippy::result_large_err)]
 pub mod bootstrap;
 pub mod codegen;
 /// Re-export dependencies for generated code
@@ -1,4 +1,4 @@
-use std::{path::PathBuf, pin::Pin, sync::Arc};
+use std::{path::PathBuf, pin::Pin, str::FromStr, sync::Arc};
 
 use chrono::{DateTime, Utc};
 use derivative::*;
@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     attributes::Attributes,
+    identity::AgentSignedOperations,
     prov::{
         operations::{ChronicleOperation, DerivationType},
         ActivityId, AgentId, ChronicleIri, ChronicleTransactionId, EntityId, ExternalId,
@@ -15,6 +16,94 @@ use crate::{
     },
 };
 
+/// How far a submitted transaction has progressed towards the ledger
+#[derive(async_graphql::Enum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionStage {
+    Submitted,
+    Committed,
+    Contradicted,
+    Failed,
+}
+
+impl TransactionStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionStage::Submitted => "submitted",
+            TransactionStage::Committed => "committed",
+            TransactionStage::Contradicted => "contradicted",
+            TransactionStage::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for TransactionStage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "submitted" => Ok(TransactionStage::Submitted),
+            "committed" => Ok(TransactionStage::Committed),
+            "contradicted" => Ok(TransactionStage::Contradicted),
+            "failed" => Ok(TransactionStage::Failed),
+            other => Err(format!("unrecognized transaction stage: {other}")),
+        }
+    }
+}
+
+/// Where a transaction queued in the outbox is in its progress towards the ledger. Once
+/// submitted, it is `Committed` or `Contradicted` when the ledger has applied or rejected it.
+#[derive(async_graphql::Enum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutboxStatus {
+    Pending,
+    Submitted,
+    Failed,
+    Committed,
+    Contradicted,
+}
+
+impl OutboxStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxStatus::Pending => "pending",
+            OutboxStatus::Submitted => "submitted",
+            OutboxStatus::Failed => "failed",
+            OutboxStatus::Committed => "committed",
+            OutboxStatus::Contradicted => "contradicted",
+        }
+    }
+}
+
+impl FromStr for OutboxStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(OutboxStatus::Pending),
+            "submitted" => Ok(OutboxStatus::Submitted),
+            "failed" => Ok(OutboxStatus::Failed),
+            "committed" => Ok(OutboxStatus::Committed),
+            "contradicted" => Ok(OutboxStatus::Contradicted),
+            other => Err(format!("unrecognized outbox status: {other}")),
+        }
+    }
+}
+
+/// A transaction queued while the ledger was unreachable, with the effect that has been
+/// applied to the local store ahead of its submission
+#[derive(Debug, Clone)]
+pub struct OutboxItem {
+    pub id: i32,
+    pub subject: ChronicleIri,
+    pub status: OutboxStatus,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    /// The transaction id, once submitted
+    pub tx_id: Option<ChronicleTransactionId>,
+    pub prov: Box<ProvModel>,
+    pub queued: DateTime<Utc>,
+    pub next_attempt: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NamespaceCommand {
     Create { external_id: ExternalId },
@@ -270,6 +359,13 @@ pub struct ImportCommand {
     pub operations: Vec<ChronicleOperation>,
 }
 
+/// Operations signed by an agent, submitted as they were signed. They must all be in the same
+/// namespace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentSignedCommand {
+    pub signed: AgentSignedOperations,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ApiCommand {
     NameSpace(NamespaceCommand),
@@ -279,6 +375,19 @@ pub enum ApiCommand {
     Query(QueryCommand),
     DepthCharge(DepthChargeCommand),
     Import(ImportCommand),
+    AgentSigned(AgentSignedCommand),
+    Outbox(OutboxCommand),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OutboxCommand {
+    List {
+        status: Option<OutboxStatus>,
+    },
+    /// Return a failed transaction to the outbox, to be submitted before those queued behind it
+    Retry {
+        id: i32,
+    },
 }
 
 #[derive(Debug)]
@@ -305,6 +414,22 @@ pub enum ApiResponse {
     },
     /// The api has submitted the depth charge transaction to a ledger
     DepthChargeSubmitted { tx_id: ChronicleTransactionId },
+    /// The command was previously dispatched with the same idempotency key, so was not
+    /// submitted again. `tx_id` is `None` if the original command was already recorded.
+    PriorSubmission {
+        subject: ChronicleIri,
+        tx_id: Option<ChronicleTransactionId>,
+        stage: Option<TransactionStage>,
+    },
+    /// The ledger could not be reached, so the api has validated the command, applied its
+    /// effect locally and queued its transaction for submission
+    Queued {
+        subject: ChronicleIri,
+        prov: Box<ProvModel>,
+        outbox_id: i32,
+    },
+    /// The api has listed the contents of the outbox
+    OutboxItems { items: Vec<OutboxItem> },
 }
 
 impl ApiResponse {
@@ -341,6 +466,30 @@ impl ApiResponse {
         ApiResponse::DepthChargeSubmitted { tx_id }
     }
 
+    pub fn prior_submission(
+        subject: ChronicleIri,
+        tx_id: Option<ChronicleTransactionId>,
+        stage: Option<TransactionStage>,
+    ) -> Self {
+        ApiResponse::PriorSubmission {
+            subject,
+            tx_id,
+            stage,
+        }
+    }
+
+    pub fn queued(subject: impl Into<ChronicleIri>, prov: ProvModel, outbox_id: i32) -> Self {
+        ApiResponse::Queued {
+            subject: subject.into(),
+            prov: Box::new(prov),
+            outbox_id,
+        }
+    }
+
+    pub fn outbox_items(items: Vec<OutboxItem>) -> Self {
+        ApiResponse::OutboxItems { items }
+    }
+
     pub fn import_submitted(prov: ProvModel, tx_id: ChronicleTransactionId) -> Self {
         ApiResponse::ImportSubmitted {
             prov: Box::new(prov),
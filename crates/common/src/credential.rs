@@ -0,0 +1,445 @@
+//! Provenance exported as a W3C Verifiable Credential, signed with the Chronicle key so that
+//! whoever receives it can check where it came from.
+//!
+//! The proof is a detached JWS with an unencoded payload (RFC 7797) over the credential, less
+//! the signature itself, serialized with the JSON Canonicalization Scheme (RFC 8785). Its
+//! algorithm is that of the Chronicle key: ES256K for secp256k1, ES256 for P-256 and EdDSA for
+//! Ed25519.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chronicle_signing::{
+    ChronicleKnownKeyNamesSigner, KeyAlgorithm, SecretError, TaggedVerifyingKey,
+};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::identity::{AuthId, IdentityError, SignedIdentity};
+
+pub const CREDENTIALS_CONTEXT: &str = "https://www.w3.org/2018/credentials/v1";
+pub const CREDENTIAL_TYPE: &str = "ChronicleProvenanceCredential";
+const VERIFIABLE_CREDENTIAL_TYPE: &str = "VerifiableCredential";
+const PROOF_PURPOSE: &str = "assertionMethod";
+const ISSUER_PREFIX: &str = "urn:chronicle:";
+
+#[derive(Error, Debug)]
+pub enum CredentialError {
+    #[error("Signer : {0}")]
+    Signing(#[from] SecretError),
+
+    #[error("Malformed JSON: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+
+    #[error("Invalid base64: {0}")]
+    Base64(#[from] base64::DecodeError),
+
+    #[error("Invalid key: {0}")]
+    Key(SecretError),
+
+    #[error("Malformed credential: {0}")]
+    Malformed(String),
+
+    #[error("Credential signature does not match its contents")]
+    BadSignature,
+
+    #[error("Credential is issued by {issuer}, not by the trusted key")]
+    UntrustedIssuer { issuer: String },
+
+    #[error("Identity of transaction {tx_id}: {source}")]
+    Identity {
+        tx_id: String,
+        source: IdentityError,
+    },
+}
+
+/// A ledger transaction that recorded some of the exported provenance
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ContributingTransaction {
+    pub tx_id: String,
+    pub block_id: Option<String>,
+    pub identity: SignedIdentity,
+    /// The Chronicle key registered when the transaction was committed, as the hex of its
+    /// compressed form, prefixed with its algorithm unless it is secp256k1. The identity must be
+    /// signed with it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvenanceSubject {
+    /// The IRI of the exported activity, agent or entity
+    pub id: String,
+    pub namespace: String,
+    /// Compacted JSON-LD of the subject and its neighbours
+    pub provenance: Value,
+    pub transactions: Vec<ContributingTransaction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialProof {
+    #[serde(rename = "type")]
+    pub typ: String,
+    pub created: DateTime<Utc>,
+    pub proof_purpose: String,
+    pub verification_method: String,
+    pub jws: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvenanceCredential {
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+    #[serde(rename = "type")]
+    pub typ: Vec<String>,
+    pub issuer: String,
+    pub issuance_date: DateTime<Utc>,
+    pub credential_subject: ProvenanceSubject,
+    pub proof: CredentialProof,
+}
+
+/// What a successful verification establishes
+#[derive(Debug, Clone)]
+pub struct VerifiedCredential {
+    pub issuer: TaggedVerifyingKey,
+    /// The identity that submitted each contributing transaction
+    pub identities: Vec<(String, AuthId)>,
+}
+
+/// The JWS algorithm and the proof type of credentials signed with keys of `algorithm`
+fn proof_of(algorithm: KeyAlgorithm) -> (&'static str, &'static str) {
+    match algorithm {
+        KeyAlgorithm::Secp256k1 => ("ES256K", "EcdsaSecp256k1Signature2019"),
+        KeyAlgorithm::P256 => ("ES256", "JsonWebSignature2020"),
+        KeyAlgorithm::Ed25519 => ("EdDSA", "Ed25519Signature2018"),
+    }
+}
+
+fn jws_header(algorithm: KeyAlgorithm) -> String {
+    format!(
+        r#"{{"alg":"{}","b64":false,"crit":["b64"]}}"#,
+        proof_of(algorithm).0
+    )
+}
+
+/// The issuer signing with `key`, such as `urn:chronicle:secp256k1:<hex>`
+fn issuer_of(key: &TaggedVerifyingKey) -> String {
+    format!(
+        "{ISSUER_PREFIX}{}:{}",
+        key.algorithm(),
+        hex::encode(key.to_bytes())
+    )
+}
+
+fn key_from_hex(key: &str) -> Result<TaggedVerifyingKey, CredentialError> {
+    key.parse().map_err(CredentialError::Key)
+}
+
+fn issuer_key(issuer: &str) -> Result<TaggedVerifyingKey, CredentialError> {
+    key_from_hex(
+        issuer
+            .strip_prefix(ISSUER_PREFIX)
+            .ok_or_else(|| CredentialError::Malformed(format!("unknown issuer {issuer}")))?,
+    )
+}
+
+/// The bytes the JWS signs: its header, then the credential without the signature
+fn signing_input(
+    credential: &ProvenanceCredential,
+    algorithm: KeyAlgorithm,
+) -> Result<Vec<u8>, CredentialError> {
+    let mut credential = serde_json::to_value(credential)?;
+    if let Some(proof) = credential.get_mut("proof").and_then(Value::as_object_mut) {
+        proof.remove("jws");
+    }
+
+    let mut input = URL_SAFE_NO_PAD.encode(jws_header(algorithm));
+    input.push('.');
+    input.push_str(&serde_jcs::to_string(&credential)?);
+
+    Ok(input.into_bytes())
+}
+
+impl ProvenanceCredential {
+    /// Sign the provenance of `id` in `namespace` with the Chronicle key
+    pub async fn issue<S: ChronicleKnownKeyNamesSigner>(
+        signer: &S,
+        id: String,
+        namespace: String,
+        provenance: Value,
+        transactions: Vec<ContributingTransaction>,
+    ) -> Result<Self, CredentialError> {
+        let key = signer.chronicle_verifying().await?;
+        let issuer = issuer_of(&key);
+        let now = Utc::now();
+
+        let mut credential = Self {
+            context: vec![CREDENTIALS_CONTEXT.to_owned()],
+            typ: vec![
+                VERIFIABLE_CREDENTIAL_TYPE.to_owned(),
+                CREDENTIAL_TYPE.to_owned(),
+            ],
+            issuer: issuer.clone(),
+            issuance_date: now,
+            credential_subject: ProvenanceSubject {
+                id,
+                namespace,
+                provenance,
+                transactions,
+            },
+            proof: CredentialProof {
+                typ: proof_of(key.algorithm()).1.to_owned(),
+                created: now,
+                proof_purpose: PROOF_PURPOSE.to_owned(),
+                verification_method: issuer,
+                jws: String::new(),
+            },
+        };
+
+        let signature = signer
+            .chronicle_sign(&signing_input(&credential, key.algorithm())?)
+            .await?;
+        credential.proof.jws = format!(
+            "{}..{}",
+            URL_SAFE_NO_PAD.encode(jws_header(key.algorithm())),
+            URL_SAFE_NO_PAD.encode(signature)
+        );
+
+        Ok(credential)
+    }
+
+    /// Check the credential is signed by its issuer, who must hold `trusted` if it is given,
+    /// and that the identity of each contributing transaction is signed by a Chronicle key,
+    /// which is the key registered at the time if the credential records it
+    pub fn verify(
+        &self,
+        trusted: Option<&TaggedVerifyingKey>,
+    ) -> Result<VerifiedCredential, CredentialError> {
+        let issuer = issuer_key(&self.issuer)?;
+        let algorithm = issuer.algorithm();
+        let (alg, proof_type) = proof_of(algorithm);
+
+        if self.proof.typ != proof_type {
+            return Err(CredentialError::Malformed(format!(
+                "unsupported proof type {}",
+                self.proof.typ
+            )));
+        }
+        if self.proof.verification_method != self.issuer {
+            return Err(CredentialError::Malformed(
+                "credential is not signed by its issuer".to_owned(),
+            ));
+        }
+
+        if matches!(trusted, Some(trusted) if trusted != &issuer) {
+            return Err(CredentialError::UntrustedIssuer {
+                issuer: self.issuer.clone(),
+            });
+        }
+
+        let signature = match self.proof.jws.split_once("..") {
+            Some((header, signature))
+                if URL_SAFE_NO_PAD.decode(header)? == jws_header(algorithm).as_bytes() =>
+            {
+                URL_SAFE_NO_PAD.decode(signature)?
+            }
+            _ => {
+                return Err(CredentialError::Malformed(format!(
+                    "proof is not a detached {alg} JWS"
+                )))
+            }
+        };
+        if issuer
+            .verify(&signing_input(self, algorithm)?, &signature)
+            .is_err()
+        {
+            return Err(CredentialError::BadSignature);
+        }
+
+        let identities = self
+            .credential_subject
+            .transactions
+            .iter()
+            .map(|tx| {
+                let identity = match &tx.signing_key {
+                    Some(key) => tx.identity.verify_with(&key_from_hex(key)?),
+                    None => tx.identity.verify(),
+                };
+                identity
+                    .map(|identity| (tx.tx_id.clone(), identity))
+                    .map_err(|source| CredentialError::Identity {
+                        tx_id: tx.tx_id.clone(),
+                        source,
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(VerifiedCredential { issuer, identities })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chronicle_signing::{
+        chronicle_secret_names, ChronicleSecretsOptions, ChronicleSigning, CHRONICLE_NAMESPACE,
+    };
+    use serde_json::json;
+
+    async fn embedded_signing() -> ChronicleSigning {
+        ChronicleSigning::new(
+            chronicle_secret_names(),
+            vec![(
+                CHRONICLE_NAMESPACE.to_string(),
+                ChronicleSecretsOptions::Embedded,
+            )],
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn credential<S: ChronicleKnownKeyNamesSigner + Sync>(
+        signing: &S,
+    ) -> ProvenanceCredential {
+        let identity = AuthId::chronicle().signed_identity(signing).unwrap();
+        let signing_key = signing.chronicle_verifying().await.unwrap().to_string();
+
+        ProvenanceCredential::issue(
+            signing,
+            "chronicle:entity:report".to_owned(),
+            "testns".to_owned(),
+            json!({"@id": "chronicle:entity:report", "value": {"pages": 3}}),
+            vec![ContributingTransaction {
+                tx_id: "tx".to_owned(),
+                block_id: None,
+                identity,
+                signing_key: Some(signing_key),
+            }],
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn issued_credential_verifies_after_round_trip() {
+        let signing = embedded_signing().await;
+        let credential = credential(&signing).await;
+
+        let credential: ProvenanceCredential =
+            serde_json::from_str(&serde_json::to_string_pretty(&credential).unwrap()).unwrap();
+        let key = signing.chronicle_verifying().await.unwrap();
+        let verified = credential.verify(Some(&key)).unwrap();
+
+        assert_eq!(
+            verified.identities,
+            vec![("tx".to_owned(), AuthId::chronicle())]
+        );
+    }
+
+    #[tokio::test]
+    async fn altered_credential_is_rejected() {
+        let signing = embedded_signing().await;
+        let credential = credential(&signing).await;
+
+        let mut altered = credential.clone();
+        altered.credential_subject.provenance = json!({"@id": "chronicle:entity:other"});
+        assert!(matches!(
+            altered.verify(None),
+            Err(CredentialError::BadSignature)
+        ));
+
+        let mut altered = credential.clone();
+        altered.credential_subject.transactions[0].identity.identity =
+            serde_json::to_string(&AuthId::anonymous()).unwrap();
+        assert!(matches!(
+            altered.verify(None),
+            Err(CredentialError::BadSignature)
+        ));
+
+        let other = embedded_signing().await;
+        let key = other.chronicle_verifying().await.unwrap();
+        assert!(matches!(
+            credential.verify(Some(&key)),
+            Err(CredentialError::UntrustedIssuer { .. })
+        ));
+
+        // An identity signed with a key other than the one registered for it
+        let mut altered = credential.clone();
+        altered.credential_subject.transactions[0].identity =
+            AuthId::chronicle().signed_identity(&other).unwrap();
+        let altered = ProvenanceCredential::issue(
+            &signing,
+            altered.credential_subject.id,
+            altered.credential_subject.namespace,
+            altered.credential_subject.provenance,
+            altered.credential_subject.transactions,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            altered.verify(None),
+            Err(CredentialError::Identity {
+                source: IdentityError::UnregisteredKey,
+                ..
+            })
+        ));
+    }
+
+    /// A Chronicle key of an algorithm other than secp256k1, such as Vault Transit holds
+    struct Ed25519Signer(ed25519_dalek::SigningKey);
+
+    #[async_trait::async_trait]
+    impl ChronicleKnownKeyNamesSigner for Ed25519Signer {
+        async fn chronicle_sign(&self, data: &[u8]) -> Result<Vec<u8>, SecretError> {
+            use ed25519_dalek::Signer;
+
+            Ok(self.0.sign(data).to_bytes().to_vec())
+        }
+
+        async fn chronicle_verify(
+            &self,
+            data: &[u8],
+            signature: &[u8],
+        ) -> Result<bool, SecretError> {
+            Ok(self
+                .chronicle_verifying()
+                .await?
+                .verify(data, signature)
+                .is_ok())
+        }
+
+        async fn chronicle_verifying(&self) -> Result<TaggedVerifyingKey, SecretError> {
+            Ok(self.0.verifying_key().into())
+        }
+    }
+
+    #[tokio::test]
+    async fn credentials_are_signed_in_the_algorithm_of_the_chronicle_key() {
+        let signing = Ed25519Signer(ed25519_dalek::SigningKey::from_bytes(&[7; 32]));
+        let credential = credential(&signing).await;
+
+        assert!(credential.issuer.starts_with("urn:chronicle:ed25519:"));
+        assert_eq!(credential.proof.typ, "Ed25519Signature2018");
+
+        let key = signing.chronicle_verifying().await.unwrap();
+        let verified = credential.verify(Some(&key)).unwrap();
+        assert_eq!(verified.issuer, key);
+        assert_eq!(
+            verified.identities,
+            vec![("tx".to_owned(), AuthId::chronicle())]
+        );
+
+        let other = embedded_signing()
+            .await
+            .chronicle_verifying()
+            .await
+            .unwrap();
+        assert!(matches!(
+            credential.verify(Some(&other)),
+            Err(CredentialError::UntrustedIssuer { .. })
+        ));
+    }
+}
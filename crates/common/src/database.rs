@@ -1,4 +1,4 @@
-use diesel::{r2d2::ConnectionManager, Connection, PgConnection};
+use diesel::{r2d2::ConnectionManager, Connection, PgConnection, SqliteConnection};
 use lazy_static::lazy_static;
 use r2d2::Pool;
 use std::{fmt::Display, time::Duration};
@@ -8,19 +8,42 @@ lazy_static! {
     static ref CLIENT: Cli = Cli::default();
 }
 
+/// A connection to the database holding Chronicle's store. Establishing one from a URL tries
+/// each backend in turn, so a `postgresql://` URL connects to PostgreSQL and a file path opens
+/// an SQLite database.
+#[derive(diesel::MultiConnection)]
+pub enum StoreConnection {
+    Postgres(PgConnection),
+    Sqlite(SqliteConnection),
+}
+
+pub type StoreBackend = <StoreConnection as Connection>::Backend;
+
+pub type ConnectionPool = Pool<ConnectionManager<StoreConnection>>;
+
 pub struct TemporaryDatabase<'a> {
     db_uris: Vec<String>,
     _container: Container<'a, Postgres>,
 }
 
 impl<'a> TemporaryDatabase<'a> {
-    pub fn connection_pool(&self) -> Result<Pool<ConnectionManager<PgConnection>>, r2d2::Error> {
-        let db_uri = self
-            .db_uris
+    pub fn connection_pool(&self) -> Result<ConnectionPool, r2d2::Error> {
+        Pool::builder().build(ConnectionManager::<StoreConnection>::new(self.db_uri()))
+    }
+
+    /// A pool whose connections resolve tables in `schema`
+    pub fn connection_pool_in_schema(&self, schema: &str) -> Result<ConnectionPool, r2d2::Error> {
+        Pool::builder().build(ConnectionManager::<StoreConnection>::new(format!(
+            "{}?options=-csearch_path%3D{schema}",
+            self.db_uri()
+        )))
+    }
+
+    fn db_uri(&self) -> &str {
+        self.db_uris
             .iter()
             .find(|db_uri| PgConnection::establish(db_uri).is_ok())
-            .expect("cannot establish connection");
-        Pool::builder().build(ConnectionManager::<PgConnection>::new(db_uri))
+            .expect("cannot establish connection")
     }
 }
 
@@ -47,13 +70,13 @@ impl<'a> Default for TemporaryDatabase<'a> {
 
 #[async_trait::async_trait]
 pub trait DatabaseConnector<X, E> {
-    async fn try_connect(&self) -> Result<(X, Pool<ConnectionManager<PgConnection>>), E>;
+    async fn try_connect(&self) -> Result<(X, ConnectionPool), E>;
     fn should_retry(&self, error: &E) -> bool;
 }
 
 pub async fn get_connection_with_retry<X, E: Display>(
     connector: impl DatabaseConnector<X, E>,
-) -> Result<(X, Pool<ConnectionManager<PgConnection>>), E> {
+) -> Result<(X, ConnectionPool), E> {
     let mut i = 1;
     let mut j = 1;
     loop {
@@ -0,0 +1,587 @@
+use crate::{
+    identity::{AuthId, OpaData},
+    opa::Decision,
+};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tracing::warn;
+use url::Url;
+use uuid::Uuid;
+
+/// The most decisions written to a sink at once
+const MAX_BATCH: usize = 100;
+
+/// How long to wait before writing a batch again after it first fails, doubling after each
+/// failure up to `MAX_RETRY_DELAY`
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum DecisionLogError {
+    #[error("Decision log I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to serialize decisions: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+
+    #[error("Failed to send decisions: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Failed to store decisions: {0}")]
+    Store(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl DecisionLogError {
+    /// Whether writing the same decisions again may succeed. A sink that cannot be reached or
+    /// fails on its side may recover, while decisions it rejects or that cannot be serialized
+    /// will fail the same way every time.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Io(_) | Self::Store(_) => true,
+            Self::SerdeJson(_) => false,
+            Self::Http(error) => match error.status() {
+                Some(status) => {
+                    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                }
+                None => !error.is_builder() && !error.is_decode(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleRevision {
+    pub revision: String,
+}
+
+/// A policy decision, in the format of OPA's decision logs. The bundle is the policy evaluated,
+/// at a revision of its hash.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecisionLogEntry {
+    pub decision_id: String,
+    pub timestamp: DateTime<Utc>,
+    /// The entrypoint evaluated
+    pub path: String,
+    /// The identity the decision was made for
+    pub requested_by: String,
+    pub input: Value,
+    /// Whether the input was allowed, absent if the policy could not be evaluated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub bundles: BTreeMap<String, BundleRevision>,
+    pub metrics: BTreeMap<String, u64>,
+    /// Paths of the input removed before it was logged
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub erased: Vec<String>,
+}
+
+impl DecisionLogEntry {
+    pub fn new(
+        identity: &AuthId,
+        data: &OpaData,
+        policy: &str,
+        hash: &str,
+        path: &str,
+        decision: Result<Decision, String>,
+        elapsed: Duration,
+    ) -> Self {
+        let (result, error) = match decision {
+            Ok(decision) => (Some(decision == Decision::Allow), None),
+            Err(error) => (None, Some(error)),
+        };
+
+        Self {
+            decision_id: Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            path: path.to_owned(),
+            requested_by: identity.to_string(),
+            input: serde_json::json!({ "identity": identity, "data": data }),
+            result,
+            error,
+            bundles: BTreeMap::from([(
+                policy.to_owned(),
+                BundleRevision {
+                    revision: hash.to_owned(),
+                },
+            )]),
+            metrics: BTreeMap::from([(
+                "timer_rego_query_eval_ns".to_owned(),
+                elapsed.as_nanos() as u64,
+            )]),
+            erased: vec![],
+        }
+    }
+
+    pub fn decision(&self) -> Option<Decision> {
+        self.result.map(|allowed| match allowed {
+            true => Decision::Allow,
+            false => Decision::Deny,
+        })
+    }
+
+    /// The policy evaluated and its hash
+    pub fn policy(&self) -> Option<(&str, &str)> {
+        self.bundles
+            .iter()
+            .next()
+            .map(|(policy, bundle)| (policy.as_str(), bundle.revision.as_str()))
+    }
+
+    pub fn eval_ns(&self) -> u64 {
+        self.metrics
+            .get("timer_rego_query_eval_ns")
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Remove the value at the JSON pointer `path` from the input, such as `/identity/claims`
+    pub fn erase(&mut self, path: &str) {
+        let (parent, key) = match path.rsplit_once('/') {
+            Some(split) => split,
+            None => return,
+        };
+        let erased = match self.input.pointer_mut(parent) {
+            Some(Value::Object(object)) => object.remove(key).is_some(),
+            Some(Value::Array(array)) => match key.parse::<usize>() {
+                Ok(index) if index < array.len() => {
+                    array[index] = Value::Null;
+                    true
+                }
+                _ => false,
+            },
+            _ => false,
+        };
+        if erased {
+            self.erased.push(format!("/input{path}"));
+        }
+    }
+}
+
+/// Somewhere decisions are written
+#[async_trait::async_trait]
+pub trait DecisionLogSink: Send + Sync {
+    async fn write(&self, decisions: &[DecisionLogEntry]) -> Result<(), DecisionLogError>;
+}
+
+/// Appends decisions to a file, one JSON object per line
+pub struct FileDecisionLog {
+    path: PathBuf,
+}
+
+impl FileDecisionLog {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl DecisionLogSink for FileDecisionLog {
+    async fn write(&self, decisions: &[DecisionLogEntry]) -> Result<(), DecisionLogError> {
+        let mut lines = vec![];
+        for decision in decisions {
+            serde_json::to_writer(&mut lines, decision)?;
+            lines.push(b'\n');
+        }
+
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?
+                .write_all(&lines)
+        })
+        .await
+        .map_err(std::io::Error::from)??;
+        Ok(())
+    }
+}
+
+/// Posts decisions as a JSON array to an endpoint that accepts OPA's decision logs
+pub struct HttpDecisionLog {
+    client: reqwest::Client,
+    url: Url,
+}
+
+impl HttpDecisionLog {
+    pub fn new(url: Url) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl DecisionLogSink for HttpDecisionLog {
+    async fn write(&self, decisions: &[DecisionLogEntry]) -> Result<(), DecisionLogError> {
+        self.client
+            .post(self.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(decisions)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecisionLogOptions {
+    /// The fraction of allowed decisions logged. Denials and failed evaluations are always logged.
+    pub sample: f64,
+    /// JSON pointers into the input of values left out of the log
+    pub redact: Vec<String>,
+    /// The most decisions waiting to be written. Once it is full, further decisions are dropped
+    /// and counted until the sink catches up.
+    pub queue: usize,
+}
+
+impl Default for DecisionLogOptions {
+    fn default() -> Self {
+        Self {
+            sample: 1.0,
+            redact: vec![],
+            queue: 10_000,
+        }
+    }
+}
+
+/// Logs decisions to a sink from a background task, so that evaluating a policy never waits on
+/// the sink. Decisions made while a batch is written go in the next one. A batch the sink fails
+/// to write for a [transient](DecisionLogError::is_transient) reason is retried until it
+/// succeeds, while decisions made meanwhile queue up to the configured limit. A batch the sink
+/// rejects is written again in halves, so that only the decisions it rejects on their own are
+/// dropped and counted.
+#[derive(Clone)]
+pub struct DecisionLogger {
+    sender: mpsc::Sender<DecisionLogEntry>,
+    options: Arc<DecisionLogOptions>,
+    dropped: Arc<AtomicU64>,
+    rejected: Arc<AtomicU64>,
+}
+
+impl fmt::Debug for DecisionLogger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecisionLogger")
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DecisionLogger {
+    pub fn spawn<S: DecisionLogSink + 'static>(sink: S, options: DecisionLogOptions) -> Self {
+        let (sender, mut receiver) = mpsc::channel(options.queue.max(1));
+        let rejected = Arc::new(AtomicU64::new(0));
+
+        tokio::spawn({
+            let rejected = rejected.clone();
+            async move {
+                while let Some(decision) = receiver.recv().await {
+                    let mut batch = vec![decision];
+                    while batch.len() < MAX_BATCH {
+                        match receiver.try_recv() {
+                            Ok(decision) => batch.push(decision),
+                            Err(_) => break,
+                        }
+                    }
+
+                    write_batch(&sink, batch, &rejected).await;
+                }
+            }
+        });
+
+        Self {
+            sender,
+            options: Arc::new(options),
+            dropped: Arc::new(AtomicU64::new(0)),
+            rejected,
+        }
+    }
+
+    /// The number of decisions dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// The number of decisions dropped because the sink rejected them
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Redact and log `decision`, unless it is allowed and sampling passes it over
+    pub fn log(&self, mut decision: DecisionLogEntry) {
+        if decision.result == Some(true)
+            && self.options.sample < 1.0
+            && rand::random::<f64>() >= self.options.sample
+        {
+            return;
+        }
+
+        for path in &self.options.redact {
+            decision.erase(path);
+        }
+
+        match self.sender.try_send(decision) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                warn!(dropped, "OPA decision log queue is full, dropping decision");
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => warn!("OPA decision log has stopped"),
+        }
+    }
+}
+
+/// Write `batch` to `sink`, retrying transient failures and splitting a rejected batch until the
+/// decisions rejected on their own are found and dropped
+async fn write_batch<S: DecisionLogSink>(
+    sink: &S,
+    batch: Vec<DecisionLogEntry>,
+    rejected: &AtomicU64,
+) {
+    let mut batches = vec![batch];
+    while let Some(mut batch) = batches.pop() {
+        let mut delay = RETRY_DELAY;
+        loop {
+            match sink.write(&batch).await {
+                Ok(()) => break,
+                Err(error) if error.is_transient() => {
+                    warn!(
+                        ?error,
+                        decisions = batch.len(),
+                        retry_in = ?delay,
+                        "Failed to log OPA decisions"
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                }
+                Err(error) if batch.len() > 1 => {
+                    warn!(
+                        ?error,
+                        decisions = batch.len(),
+                        "OPA decisions were rejected, logging them in halves"
+                    );
+                    let second = batch.split_off(batch.len() / 2);
+                    batches.push(second);
+                    batches.push(batch);
+                    break;
+                }
+                Err(error) => {
+                    let rejected = rejected.fetch_add(1, Ordering::Relaxed) + 1;
+                    warn!(?error, rejected, "OPA decision was rejected, dropping it");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::JwtClaims;
+    use std::collections::BTreeSet;
+
+    struct ChannelSink(mpsc::UnboundedSender<DecisionLogEntry>);
+
+    #[async_trait::async_trait]
+    impl DecisionLogSink for ChannelSink {
+        async fn write(&self, decisions: &[DecisionLogEntry]) -> Result<(), DecisionLogError> {
+            for decision in decisions {
+                self.0.send(decision.clone()).unwrap();
+            }
+            Ok(())
+        }
+    }
+
+    /// Fails its first `failures` writes, then writes to its channel
+    struct FlakySink {
+        failures: AtomicU64,
+        sink: ChannelSink,
+    }
+
+    #[async_trait::async_trait]
+    impl DecisionLogSink for FlakySink {
+        async fn write(&self, decisions: &[DecisionLogEntry]) -> Result<(), DecisionLogError> {
+            let failing = self
+                .failures
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |failures| {
+                    failures.checked_sub(1)
+                })
+                .is_ok();
+            if failing {
+                return Err(std::io::Error::from(std::io::ErrorKind::BrokenPipe).into());
+            }
+            self.sink.write(decisions).await
+        }
+    }
+
+    /// Rejects any batch holding a decision whose path is `rejected`
+    struct RejectingSink(ChannelSink);
+
+    #[async_trait::async_trait]
+    impl DecisionLogSink for RejectingSink {
+        async fn write(&self, decisions: &[DecisionLogEntry]) -> Result<(), DecisionLogError> {
+            if decisions.iter().any(|decision| decision.path == "rejected") {
+                return Err(serde_json::from_str::<Value>("rejected")
+                    .unwrap_err()
+                    .into());
+            }
+            self.0.write(decisions).await
+        }
+    }
+
+    fn jwt_user() -> AuthId {
+        let claims = JwtClaims(
+            serde_json::json!({ "sub": "abcdef", "email": "abcdef@example.com" })
+                .as_object()
+                .unwrap()
+                .to_owned(),
+        );
+        AuthId::from_jwt_claims(&claims, &BTreeSet::from(["sub".to_string()])).unwrap()
+    }
+
+    fn decision(allowed: bool) -> DecisionLogEntry {
+        let identity = jwt_user();
+        DecisionLogEntry::new(
+            &identity,
+            &OpaData::operation(&identity, &Value::Null, &Value::Null),
+            "allow_transactions",
+            "abc123",
+            "allow_transactions/allowed_users",
+            Ok(if allowed {
+                Decision::Allow
+            } else {
+                Decision::Deny
+            }),
+            Duration::from_micros(5),
+        )
+    }
+
+    #[tokio::test]
+    async fn denials_are_logged_and_redacted() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let logger = DecisionLogger::spawn(
+            ChannelSink(sender),
+            DecisionLogOptions {
+                sample: 0.0,
+                redact: vec!["/identity/claims".to_owned(), "/data/missing".to_owned()],
+                ..Default::default()
+            },
+        );
+
+        logger.log(decision(true));
+        logger.log(decision(false));
+
+        let logged = receiver.recv().await.unwrap();
+        assert_eq!(logged.decision(), Some(Decision::Deny));
+        assert_eq!(logged.requested_by, jwt_user().to_string());
+        assert_eq!(logged.policy(), Some(("allow_transactions", "abc123")));
+        assert_eq!(logged.eval_ns(), 5000);
+        assert_eq!(logged.erased, vec!["/input/identity/claims"]);
+        assert!(logged.input.pointer("/identity/claims").is_none());
+        assert!(logged.input.pointer("/identity/id").is_some());
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn failed_batches_are_retried() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let logger = DecisionLogger::spawn(
+            FlakySink {
+                failures: AtomicU64::new(2),
+                sink: ChannelSink(sender),
+            },
+            DecisionLogOptions::default(),
+        );
+
+        logger.log(decision(false));
+
+        let logged = receiver.recv().await.unwrap();
+        assert_eq!(logged.decision(), Some(Decision::Deny));
+    }
+
+    #[tokio::test]
+    async fn rejected_decisions_are_dropped_without_holding_up_the_rest() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let logger = DecisionLogger::spawn(RejectingSink(ChannelSink(sender)), Default::default());
+
+        let mut rejected = decision(false);
+        rejected.path = "rejected".to_owned();
+
+        // Queued together, so written as one batch
+        logger.log(decision(false));
+        logger.log(rejected);
+        logger.log(decision(false));
+
+        for _ in 0..2 {
+            let logged = receiver.recv().await.unwrap();
+            assert_eq!(logged.path, "allow_transactions/allowed_users");
+        }
+        assert_eq!(logger.rejected(), 1);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn decisions_beyond_the_queue_are_dropped() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let logger = DecisionLogger::spawn(
+            FlakySink {
+                failures: AtomicU64::new(u64::MAX),
+                sink: ChannelSink(sender),
+            },
+            DecisionLogOptions {
+                queue: 2,
+                ..Default::default()
+            },
+        );
+
+        // The first is taken from the queue and retried, the next two wait behind it
+        logger.log(decision(false));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        for _ in 0..4 {
+            logger.log(decision(false));
+        }
+
+        assert_eq!(logger.dropped(), 2);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn file_log_is_json_lines() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("decisions.jsonl");
+        let sink = FileDecisionLog::new(&path);
+
+        sink.write(&[decision(true)]).await.unwrap();
+        sink.write(&[decision(false), decision(true)])
+            .await
+            .unwrap();
+
+        let logged = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<DecisionLogEntry>(line).unwrap())
+            .map(|decision| decision.result)
+            .collect::<Vec<_>>();
+        assert_eq!(logged, vec![Some(true), Some(false), Some(true)]);
+    }
+}
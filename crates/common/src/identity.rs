@@ -1,12 +1,9 @@
 use std::{collections::BTreeSet, fmt};
 
-use crate::prov::AgentId;
+use crate::prov::{operations::ChronicleOperation, AgentId, ProcessorError};
 
-use chronicle_signing::{ChronicleKnownKeyNamesSigner, SecretError};
-use k256::{
-    ecdsa::VerifyingKey,
-    sha2::{Digest, Sha512},
-};
+use chronicle_signing::{ChronicleKnownKeyNamesSigner, SecretError, TaggedVerifyingKey};
+use k256::sha2::{Digest, Sha512};
 use serde_json::{Map, Value};
 use thiserror::Error;
 use tracing::warn;
@@ -27,6 +24,24 @@ pub enum IdentityError {
 
     #[error("Signing error: {0}")]
     Signing(#[from] k256::ecdsa::Error),
+
+    #[error("Identity is not signed")]
+    Unsigned,
+
+    #[error("Identity signature does not match the identity")]
+    BadSignature,
+
+    #[error("Identity is not signed with the Chronicle key registered for it")]
+    UnregisteredKey,
+
+    #[error("Agent {0} has no registered key")]
+    NoAgentKey(AgentId),
+
+    #[error("Invalid public key for agent {0}")]
+    InvalidAgentKey(AgentId),
+
+    #[error("Agent signed an operation outside of the namespace {0} it signed for")]
+    OutsideSignedNamespace(String),
 }
 
 /// Contains the scalar ID and identity claims for a user established via JWT
@@ -68,6 +83,13 @@ impl std::fmt::Debug for JwtClaims {
     }
 }
 
+/// An agent that signed the operations it submitted with the key registered for it
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct AgentKeyId {
+    pub id: AgentId,
+    pub public_key: String,
+}
+
 /// Chronicle identity object for authorization
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 #[serde(rename_all = "lowercase", tag = "type")]
@@ -75,6 +97,7 @@ pub enum AuthId {
     Anonymous,
     Chronicle,
     JWT(JwtId),
+    Agent(AgentKeyId),
 }
 
 impl fmt::Display for AuthId {
@@ -83,6 +106,7 @@ impl fmt::Display for AuthId {
             Self::Anonymous => write!(f, "Anonymous"),
             Self::Chronicle => write!(f, "Chronicle"),
             Self::JWT(jwt_id) => write!(f, "{}", jwt_id.id),
+            Self::Agent(agent) => write!(f, "{}", agent.id),
         }
     }
 }
@@ -216,14 +240,14 @@ impl OpaData {
 pub struct SignedIdentity {
     pub identity: String,
     pub signature: Option<Vec<u8>>,
-    pub verifying_key: Option<VerifyingKey>,
+    pub verifying_key: Option<TaggedVerifyingKey>,
 }
 
 impl SignedIdentity {
     fn new(
         id: &AuthId,
         signature: Vec<u8>,
-        verifying_key: VerifyingKey,
+        verifying_key: TaggedVerifyingKey,
     ) -> Result<Self, IdentityError> {
         Ok(Self {
             identity: serde_json::to_string(&id)?,
@@ -239,6 +263,87 @@ impl SignedIdentity {
             verifying_key: None,
         }
     }
+
+    /// Check the identity was signed with its verifying key, and return it
+    pub fn verify(&self) -> Result<AuthId, IdentityError> {
+        let (signature, verifying_key) = match (&self.signature, &self.verifying_key) {
+            (Some(signature), Some(verifying_key)) => (signature, verifying_key),
+            _ => return Err(IdentityError::Unsigned),
+        };
+
+        verifying_key
+            .verify(self.identity.as_bytes(), signature)
+            .map_err(|_| IdentityError::BadSignature)?;
+
+        Ok(serde_json::from_str(&self.identity)?)
+    }
+
+    /// As [`verify`](Self::verify), where the identity must also have been signed with `key`
+    pub fn verify_with(&self, key: &TaggedVerifyingKey) -> Result<AuthId, IdentityError> {
+        if self.verifying_key.as_ref() != Some(key) {
+            return Err(IdentityError::UnregisteredKey);
+        }
+
+        self.verify()
+    }
+}
+
+/// What an agent signs: the operations, as a JSON-LD array in the form `chronicle import` reads,
+/// the IRI of the namespace they must all be in, and a nonce the agent never signs with again.
+/// The transaction processor records the nonces it has seen, so signed operations cannot be
+/// submitted twice.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AgentSignedPayload {
+    pub namespace: String,
+    pub nonce: String,
+    pub operations: Vec<Value>,
+}
+
+/// Operations signed by an agent. The signature is over `payload`, an [`AgentSignedPayload`] as
+/// JSON, exactly as it was signed.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AgentSignedOperations {
+    pub agent: AgentId,
+    pub payload: String,
+    pub signature: Vec<u8>,
+}
+
+impl AgentSignedOperations {
+    /// Check the signature with the key registered for the agent, in the algorithm tagged form
+    /// of [`TaggedVerifyingKey`], returning the identity of the agent that signed
+    pub fn verify(&self, public_key: &str) -> Result<AuthId, IdentityError> {
+        let verifying_key = public_key
+            .parse::<TaggedVerifyingKey>()
+            .map_err(|_| IdentityError::InvalidAgentKey(self.agent.clone()))?;
+
+        verifying_key
+            .verify(self.payload.as_bytes(), &self.signature)
+            .map_err(|_| IdentityError::BadSignature)?;
+
+        Ok(AuthId::Agent(AgentKeyId {
+            id: self.agent.clone(),
+            public_key: public_key.to_owned(),
+        }))
+    }
+
+    pub fn signed_payload(&self) -> Result<AgentSignedPayload, serde_json::Error> {
+        serde_json::from_str(&self.payload)
+    }
+
+    /// The signed operations, which must all be in the signed namespace
+    pub async fn operations(&self) -> Result<Vec<ChronicleOperation>, ProcessorError> {
+        let payload = self.signed_payload()?;
+
+        let mut operations = vec![];
+        for operation in &payload.operations {
+            let operation = ChronicleOperation::from_json(operation).await?;
+            if operation.namespace().to_string() != payload.namespace {
+                return Err(IdentityError::OutsideSignedNamespace(payload.namespace).into());
+            }
+            operations.push(operation);
+        }
+        Ok(operations)
+    }
 }
 
 impl TryFrom<&SignedIdentity> for AuthId {
@@ -311,6 +416,92 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn agent_signed_operations_verify_with_registered_key() {
+        use crate::prov::{operations::AgentExists, to_json_ld::ToJson, NamespaceId};
+        use k256::ecdsa::{signature::Signer, Signature, SigningKey};
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let (key, other_key) = (
+            SigningKey::random(StdRng::from_entropy()),
+            SigningKey::random(StdRng::from_entropy()),
+        );
+        let public_key = hex::encode(key.verifying_key().to_bytes());
+
+        let namespace = NamespaceId::from_external_id("testns", uuid::Uuid::nil());
+        let operation = ChronicleOperation::AgentExists(AgentExists {
+            namespace: namespace.clone(),
+            external_id: "alice".into(),
+        });
+        let mut signed_payload = AgentSignedPayload {
+            namespace: namespace.to_string(),
+            nonce: "1".to_owned(),
+            operations: vec![operation.to_json().compact_stable_order().await.unwrap()],
+        };
+        let payload = serde_json::to_string(&signed_payload).unwrap();
+        let signature: Signature = key.sign(payload.as_bytes());
+
+        let mut signed = AgentSignedOperations {
+            agent: AgentId::from_external_id("alice"),
+            payload,
+            signature: signature.as_ref().to_vec(),
+        };
+
+        assert_eq!(
+            signed.verify(&public_key).unwrap(),
+            AuthId::Agent(AgentKeyId {
+                id: AgentId::from_external_id("alice"),
+                public_key,
+            })
+        );
+        assert_eq!(signed.operations().await.unwrap(), vec![operation]);
+
+        let other_public_key = hex::encode(other_key.verifying_key().to_bytes());
+        assert!(matches!(
+            signed.verify(&other_public_key),
+            Err(IdentityError::BadSignature)
+        ));
+
+        signed.payload = signed.payload.replace("alice", "mallory");
+        assert!(matches!(
+            signed.verify(&hex::encode(key.verifying_key().to_bytes())),
+            Err(IdentityError::BadSignature)
+        ));
+
+        // Operations outside the signed namespace are refused, whoever signed them
+        signed_payload.namespace =
+            NamespaceId::from_external_id("otherns", uuid::Uuid::nil()).to_string();
+        signed.payload = serde_json::to_string(&signed_payload).unwrap();
+        assert!(matches!(
+            signed.operations().await,
+            Err(ProcessorError::Identity(
+                IdentityError::OutsideSignedNamespace(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn agent_signed_operations_verify_with_ed25519_keys() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let key = SigningKey::from_bytes(&[3; 32]);
+        let public_key = format!("ed25519:{}", hex::encode(key.verifying_key().to_bytes()));
+        let payload = "[]".to_owned();
+
+        let signed = AgentSignedOperations {
+            agent: AgentId::from_external_id("alice"),
+            signature: key.sign(payload.as_bytes()).to_bytes().to_vec(),
+            payload,
+        };
+
+        assert!(signed.verify(&public_key).is_ok());
+        // The same bytes read as a secp256k1 key are not a key at all
+        assert!(matches!(
+            signed.verify(&hex::encode(key.verifying_key().to_bytes())),
+            Err(IdentityError::InvalidAgentKey(_))
+        ));
+    }
+
     #[test]
     fn test_auth_id_deserialization() {
         let serialized = r#"{"type":"anonymous"}"#;
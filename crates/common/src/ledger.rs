@@ -228,14 +228,16 @@ impl LedgerAddress {
         })
     }
 
-    fn namespace(ns: &NamespaceId) -> Self {
+    /// The address of a namespace
+    pub fn namespace(ns: &NamespaceId) -> Self {
         Self {
             namespace: None,
             resource: ns.clone().into(),
         }
     }
 
-    fn in_namespace(ns: &NamespaceId, resource: impl Into<ChronicleIri>) -> Self {
+    /// The address of a resource within a namespace
+    pub fn in_namespace(ns: &NamespaceId, resource: impl Into<ChronicleIri>) -> Self {
         Self {
             namespace: Some(ns.clone()),
             resource: resource.into(),
@@ -359,7 +361,7 @@ where
             .into_iter()
             .filter_map(|(addr, data)| {
                 if data.version > 0 {
-                    data.value.map(|value| (StateOutput::new(addr, value)))
+                    data.value.map(|value| StateOutput::new(addr, value))
                 } else {
                     None
                 }
@@ -569,7 +571,7 @@ impl ChronicleOperation {
     ) -> Result<serde_json::Value, ProcessorError> {
         for input in input {
             let graph: serde_json::Value = serde_json::from_str(&input.data)?;
-            trace!(input_model=%serde_json::to_string_pretty(&graph).map_or_else(|e| format!("error: {e}"), |x| x));
+            trace!(input_model=%serde_json::to_string_pretty(&graph).unwrap_or_else(|e| format!("error: {e}")));
             let resource = serde_json::json!({
                 "@graph": [graph],
             });
@@ -1,4 +1,5 @@
 #![cfg_attr(feature = "strict", deny(warnings))]
+#![allow(clippy::result_large_err)]
 #[macro_use]
 extern crate serde_derive;
 #[macro_use]
@@ -7,7 +8,9 @@ extern crate iref_enum;
 pub mod attributes;
 pub mod commands;
 pub mod context;
+pub mod credential;
 pub mod database;
+pub mod decision_log;
 pub mod identity;
 pub mod import;
 pub mod ledger;
@@ -1,6 +1,5 @@
 mod graphlql_scalars;
 use async_graphql::OneofObject;
-pub use graphlql_scalars::*;
 use tracing::trace;
 
 use std::{fmt::Display, str::FromStr};
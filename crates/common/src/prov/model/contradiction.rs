@@ -109,6 +109,19 @@ impl Contradiction {
                 .collect(),
         }
     }
+
+    /// The resource whose recorded state the operation contradicted
+    pub fn id(&self) -> &ChronicleIri {
+        &self.id
+    }
+
+    pub fn namespace(&self) -> &NamespaceId {
+        &self.namespace
+    }
+
+    pub fn details(&self) -> &[ContradictionDetail] {
+        &self.contradiction
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
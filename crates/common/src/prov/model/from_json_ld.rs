@@ -39,7 +39,7 @@ impl Loader<IriBuf, ()> for ContextLoader {
         &'b mut self,
         vocabulary: &'b mut (impl Sync + Send + IriVocabularyMut<Iri = IriBuf>),
         url: IriBuf,
-    ) -> BoxFuture<Result<RemoteDocument<IriBuf, Self::Error, Self::Output>, Self::Error>>
+    ) -> BoxFuture<'b, Result<RemoteDocument<IriBuf, Self::Error, Self::Output>, Self::Error>>
     where
         IriBuf: 'b,
     {
@@ -210,7 +210,7 @@ impl ProvModel {
                 map.get(Chronicle::Value.as_iri().as_str())
             {
                 if array.len() == 1 {
-                    let o = array.get(0).unwrap();
+                    let o = array.first().unwrap();
                     let serde_object = &o["@value"];
 
                     if let serde_json::Value::Object(object) = serde_object {
@@ -587,9 +587,11 @@ trait Operation {
     fn optional_activity(&self) -> Option<ActivityId>;
     fn activity(&self) -> ActivityId;
     fn optional_role(&self) -> Option<Role>;
+    #[allow(dead_code)]
     fn identity(&self) -> Option<IdentityId>;
     fn key(&self) -> String;
     fn start_time(&self) -> String;
+    #[allow(dead_code)]
     fn locator(&self) -> Option<String>;
     fn end_time(&self) -> String;
     fn entity(&self) -> EntityId;
@@ -624,10 +626,7 @@ impl Operation for Node<IriBuf, BlankIdBuf, ()> {
 
     fn optional_activity(&self) -> Option<ActivityId> {
         let mut name_objects = self.get(&id_from_iri(&ChronicleOperations::ActivityName));
-        let object = match name_objects.next() {
-            Some(object) => object,
-            None => return None,
-        };
+        let object = name_objects.next()?;
         Some(ActivityId::from_external_id(object.as_str().unwrap()))
     }
 
@@ -720,10 +719,7 @@ impl Operation for Node<IriBuf, BlankIdBuf, ()> {
 
     fn optional_role(&self) -> Option<Role> {
         let mut name_objects = self.get(&id_from_iri(&ChronicleOperations::Role));
-        let object = match name_objects.next() {
-            Some(object) => object,
-            None => return None,
-        };
+        let object = name_objects.next()?;
         Some(Role::from(object.as_str().unwrap()))
     }
 
@@ -735,10 +731,7 @@ impl Operation for Node<IriBuf, BlankIdBuf, ()> {
 
     fn identity(&self) -> Option<IdentityId> {
         let mut id_objects = self.get(&id_from_iri(&ChronicleOperations::Identity));
-        let id = match id_objects.next() {
-            Some(id) => id,
-            None => return None,
-        };
+        let id = id_objects.next()?;
         Some(
             IdentityId::try_from(
                 IriRefBuf::from_string(id.as_str().unwrap().to_owned())
@@ -753,10 +746,7 @@ impl Operation for Node<IriBuf, BlankIdBuf, ()> {
     fn locator(&self) -> Option<String> {
         let mut objects = self.get(&id_from_iri(&ChronicleOperations::Locator));
 
-        let locator = match objects.next() {
-            Some(object) => object,
-            None => return None,
-        };
+        let locator = objects.next()?;
 
         Some(locator.as_str().unwrap().to_owned())
     }
@@ -1,5 +1,5 @@
 mod contradiction;
-pub use contradiction::Contradiction;
+pub use contradiction::{Contradiction, ContradictionDetail};
 pub mod transaction;
 pub use transaction::ChronicleTransaction;
 
@@ -163,6 +163,7 @@ pub struct Agent {
 pub struct Identity {
     pub id: IdentityId,
     pub namespaceid: NamespaceId,
+    /// The key as registered, read by [`chronicle_signing::TaggedVerifyingKey`]
     pub public_key: String,
 }
 
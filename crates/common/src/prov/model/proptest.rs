@@ -349,7 +349,7 @@ fn prov_from_json_ld(json: serde_json::Value) -> ProvModel {
 
 proptest! {
    #![proptest_config(ProptestConfig {
-        max_shrink_iters: std::u32::MAX, verbose: 0, .. ProptestConfig::default()
+        max_shrink_iters: u32::MAX, verbose: 0, .. ProptestConfig::default()
     })]
     #[test]
     fn operations(operations in operation_seq()) {
@@ -388,7 +388,7 @@ proptest! {
         // operation
 
         if let Some((_op,Contradiction {id: _,namespace: _,contradiction})) = contradiction {
-          let _contradiction = contradiction.get(0).unwrap();
+          let _contradiction = contradiction.first().unwrap();
         }
 
         // Now assert that the final prov object matches what we would expect from the input operations
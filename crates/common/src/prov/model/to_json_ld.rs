@@ -1032,8 +1032,10 @@ trait Operate {
     fn new_operation(op: ChronicleOperations) -> Self;
     fn new_type(id: OperationValue, op: ChronicleOperations) -> Self;
     fn new_value(id: OperationValue) -> Self;
+    #[allow(dead_code)]
     fn new_id(id: OperationValue) -> Self;
     fn has_value(&mut self, value: OperationValue, op: ChronicleOperations);
+    #[allow(dead_code)]
     fn has_id(&mut self, id: OperationValue, op: ChronicleOperations);
     fn attributes_object(&mut self, attributes: &Attributes);
     fn derivation(&mut self, typ: &DerivationType);
@@ -169,6 +169,7 @@ impl ActsOnBehalfOf {
 pub struct RegisterKey {
     pub namespace: NamespaceId,
     pub id: AgentId,
+    /// Hex encoded, prefixed with `ed25519:` or `p256:` for keys other than secp256k1
     pub publickey: String,
 }
 
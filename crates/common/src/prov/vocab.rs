@@ -298,7 +298,7 @@ mod test {
 
     proptest! {
     #![proptest_config(ProptestConfig {
-            max_shrink_iters: std::u32::MAX, verbose: 0, .. ProptestConfig::default()
+            max_shrink_iters: u32::MAX, verbose: 0, .. ProptestConfig::default()
     })]
         #[test]
         fn namespace(external_id in ".*") {
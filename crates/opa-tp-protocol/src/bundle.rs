@@ -0,0 +1,493 @@
+//! OPA's signed bundle format. A signed bundle carries a `.signatures.json` file holding a JWT
+//! whose claims list every other file in the bundle with a hash of its contents, so checking
+//! the JWT against a trusted public key and the files against their hashes shows the bundle is
+//! the one its signer built.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use openssl::{
+    bn::BigNum,
+    ecdsa::EcdsaSig,
+    error::ErrorStack,
+    hash::{hash, MessageDigest},
+    pkey::{Id, PKey, PKeyRef, Private, Public},
+    rsa::Padding,
+    sign::{RsaPssSaltlen, Signer, Verifier},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// The file of a bundle holding its signatures
+pub const SIGNATURES_FILE: &str = ".signatures.json";
+
+/// The key id `opa sign` and `opa build` sign with unless given another
+pub const DEFAULT_KEY_ID: &str = "default";
+
+#[derive(Debug, Error)]
+pub enum BundleVerificationError {
+    #[error("Cannot read bundle: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Bundle is not signed")]
+    Unsigned,
+
+    #[error("Malformed .signatures.json: {0}")]
+    MalformedSignatures(#[source] serde_json::Error),
+
+    #[error("Bundle must have exactly one signature, but has {0}")]
+    SignatureCount(usize),
+
+    #[error("Malformed bundle signature")]
+    MalformedToken,
+
+    #[error("Bundle is signed with key {0}, not the verification key")]
+    UnknownKey(String),
+
+    #[error("Unsupported bundle signing algorithm {0}")]
+    UnsupportedAlgorithm(String),
+
+    #[error("Bundle signature does not verify with the verification key")]
+    BadSignature,
+
+    #[error("Unsupported file hash algorithm {0}")]
+    UnsupportedHash(String),
+
+    #[error("Malformed JSON in {0}: {1}")]
+    MalformedFile(String, #[source] serde_json::Error),
+
+    #[error("{0} has changed since the bundle was signed")]
+    FileModified(String),
+
+    #[error("{0} is not covered by the bundle signature")]
+    FileUnsigned(String),
+
+    #[error("{0} is signed but missing from the bundle")]
+    FileMissing(String),
+
+    #[error("{0} appears more than once in the bundle")]
+    DuplicateFile(String),
+
+    #[error("{0} is not a regular file")]
+    NotAFile(String),
+
+    #[error("Key error: {0}")]
+    Key(#[from] ErrorStack),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Signatures {
+    signatures: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TokenHeader {
+    alg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kid: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    files: Vec<SignedFile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keyid: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SignedFile {
+    name: String,
+    hash: String,
+    #[serde(default = "default_hash_algorithm")]
+    algorithm: String,
+}
+
+fn default_hash_algorithm() -> String {
+    "SHA-256".to_owned()
+}
+
+/// A JWS algorithm, with the size of each ECDSA signature component
+#[derive(Clone, Copy)]
+enum Algorithm {
+    Rsa(MessageDigest),
+    RsaPss(MessageDigest),
+    Ecdsa(MessageDigest, usize),
+}
+
+impl Algorithm {
+    fn from_name(alg: &str) -> Result<Self, BundleVerificationError> {
+        Ok(match alg {
+            "RS256" => Self::Rsa(MessageDigest::sha256()),
+            "RS384" => Self::Rsa(MessageDigest::sha384()),
+            "RS512" => Self::Rsa(MessageDigest::sha512()),
+            "PS256" => Self::RsaPss(MessageDigest::sha256()),
+            "PS384" => Self::RsaPss(MessageDigest::sha384()),
+            "PS512" => Self::RsaPss(MessageDigest::sha512()),
+            "ES256" => Self::Ecdsa(MessageDigest::sha256(), 32),
+            "ES384" => Self::Ecdsa(MessageDigest::sha384(), 48),
+            "ES512" => Self::Ecdsa(MessageDigest::sha512(), 66),
+            other => {
+                return Err(BundleVerificationError::UnsupportedAlgorithm(
+                    other.to_owned(),
+                ))
+            }
+        })
+    }
+
+    /// The algorithm `opa sign` would use with `key`
+    fn for_key(key: &PKeyRef<Private>) -> Result<(&'static str, Self), BundleVerificationError> {
+        match key.id() {
+            Id::RSA => Ok(("RS256", Self::Rsa(MessageDigest::sha256()))),
+            Id::EC => {
+                let name = match key.ec_key()?.group().degree() {
+                    256 => "ES256",
+                    384 => "ES384",
+                    521 => "ES512",
+                    degree => {
+                        return Err(BundleVerificationError::UnsupportedAlgorithm(format!(
+                            "ECDSA with a {degree} bit curve"
+                        )))
+                    }
+                };
+                Ok((name, Self::from_name(name)?))
+            }
+            id => Err(BundleVerificationError::UnsupportedAlgorithm(format!(
+                "{id:?} keys"
+            ))),
+        }
+    }
+
+    /// Whether `key` is of the type and size this algorithm signs with
+    fn fits(self, key: &PKeyRef<Public>) -> Result<bool, ErrorStack> {
+        Ok(match self {
+            Self::Rsa(_) | Self::RsaPss(_) => key.id() == Id::RSA,
+            Self::Ecdsa(_, size) => {
+                key.id() == Id::EC
+                    && matches!(
+                        (size, key.ec_key()?.group().degree()),
+                        (32, 256) | (48, 384) | (66, 521)
+                    )
+            }
+        })
+    }
+
+    fn sign(self, key: &PKey<Private>, message: &[u8]) -> Result<Vec<u8>, ErrorStack> {
+        match self {
+            Self::Rsa(digest) => Signer::new(digest, key)?.sign_oneshot_to_vec(message),
+            Self::RsaPss(digest) => {
+                let mut signer = Signer::new(digest, key)?;
+                signer.set_rsa_padding(Padding::PKCS1_PSS)?;
+                signer.set_rsa_mgf1_md(digest)?;
+                signer.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+                signer.sign_oneshot_to_vec(message)
+            }
+            // JWS signatures are the fixed size components, rather than their DER encoding
+            Self::Ecdsa(digest, size) => {
+                let der = Signer::new(digest, key)?.sign_oneshot_to_vec(message)?;
+                let signature = EcdsaSig::from_der(&der)?;
+                let mut jws = signature.r().to_vec_padded(size as i32)?;
+                jws.extend(signature.s().to_vec_padded(size as i32)?);
+                Ok(jws)
+            }
+        }
+    }
+
+    fn verify(
+        self,
+        key: &PKey<Public>,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, ErrorStack> {
+        match self {
+            Self::Rsa(digest) => Verifier::new(digest, key)?.verify_oneshot(signature, message),
+            Self::RsaPss(digest) => {
+                let mut verifier = Verifier::new(digest, key)?;
+                verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
+                verifier.set_rsa_mgf1_md(digest)?;
+                verifier.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+                verifier.verify_oneshot(signature, message)
+            }
+            Self::Ecdsa(digest, size) => {
+                if signature.len() != 2 * size {
+                    return Ok(false);
+                }
+                let der = EcdsaSig::from_private_components(
+                    BigNum::from_slice(&signature[..size])?,
+                    BigNum::from_slice(&signature[size..])?,
+                )?
+                .to_der()?;
+                Verifier::new(digest, key)?.verify_oneshot(&der, message)
+            }
+        }
+    }
+}
+
+/// The name a bundle's signature gives a file at `path` in its archive
+fn file_name(path: &str) -> &str {
+    let path = path.trim_start_matches('/');
+    path.strip_prefix("./").unwrap_or(path)
+}
+
+/// The files of a gzipped tar bundle, with their headers, in the order they appear. Bundles may
+/// only hold regular files, each named once, as OPA would otherwise read a different bundle from
+/// the one that was verified.
+fn read_files(
+    bundle: &[u8],
+) -> Result<Vec<(tar::Header, String, Vec<u8>)>, BundleVerificationError> {
+    use std::io::Read;
+
+    let mut archive = tar::Archive::new(GzDecoder::new(bundle));
+    let mut names = BTreeSet::new();
+    let mut files = vec![];
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = file_name(&entry.path()?.to_string_lossy()).to_owned();
+        if !entry.header().entry_type().is_file() {
+            return Err(BundleVerificationError::NotAFile(name));
+        }
+        if !names.insert(name.clone()) {
+            return Err(BundleVerificationError::DuplicateFile(name));
+        }
+        let mut contents = vec![];
+        entry.read_to_end(&mut contents)?;
+        files.push((entry.header().clone(), name, contents));
+    }
+    Ok(files)
+}
+
+/// Write `value` as OPA does to hash it, with object keys sorted and no whitespace
+fn write_canonical_json(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(object) => {
+            let mut keys: Vec<&String> = object.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical_string(key, out);
+                out.push(':');
+                write_canonical_json(&object[key], out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical_json(item, out);
+            }
+            out.push(']');
+        }
+        Value::String(string) => write_canonical_string(string, out),
+        primitive => out.push_str(&primitive.to_string()),
+    }
+}
+
+/// Write a JSON string escaped as Go's encoder escapes it with HTML escaping off
+fn write_canonical_string(string: &str, out: &mut String) {
+    out.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' || c == '\u{2028}' || c == '\u{2029}' => {
+                out.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// The hex encoded hash of a bundle file. JSON files, and the manifest, are hashed in
+/// canonical form so that reformatting them does not change their hash.
+fn file_hash(
+    name: &str,
+    contents: &[u8],
+    algorithm: &str,
+) -> Result<String, BundleVerificationError> {
+    let digest = match algorithm {
+        "MD5" => MessageDigest::md5(),
+        "SHA-1" => MessageDigest::sha1(),
+        "SHA-224" => MessageDigest::sha224(),
+        "SHA-256" => MessageDigest::sha256(),
+        "SHA-384" => MessageDigest::sha384(),
+        "SHA-512" => MessageDigest::sha512(),
+        other => return Err(BundleVerificationError::UnsupportedHash(other.to_owned())),
+    };
+
+    let digest = if name.ends_with(".json") || name.rsplit('/').next() == Some(".manifest") {
+        let value: Value = serde_json::from_slice(contents)
+            .map_err(|e| BundleVerificationError::MalformedFile(name.to_owned(), e))?;
+        let mut canonical = String::new();
+        write_canonical_json(&value, &mut canonical);
+        hash(digest, canonical.as_bytes())?
+    } else {
+        hash(digest, contents)?
+    };
+
+    Ok(hex::encode(digest))
+}
+
+/// Checks bundles are signed with a trusted public key
+#[derive(Clone, Debug)]
+pub struct BundleVerifier {
+    key_id: String,
+    key: PKey<Public>,
+}
+
+impl BundleVerifier {
+    /// Trust bundles signed under `key_id` with the private key of the PEM encoded public key
+    pub fn from_pem(key_id: &str, pem: &[u8]) -> Result<Self, BundleVerificationError> {
+        Ok(Self {
+            key_id: key_id.to_owned(),
+            key: PKey::public_key_from_pem(pem)?,
+        })
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// The claims of a bundle's JWT, once its signature is checked. A token naming no key is
+    /// taken to be signed with ours.
+    fn verify_token(&self, token: &str) -> Result<Claims, BundleVerificationError> {
+        let mut parts = token.split('.');
+        let (header, claims, signature) =
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(header), Some(claims), Some(signature), None) => (header, claims, signature),
+                _ => return Err(BundleVerificationError::MalformedToken),
+            };
+        let decode = |part: &str| {
+            URL_SAFE_NO_PAD
+                .decode(part)
+                .map_err(|_| BundleVerificationError::MalformedToken)
+        };
+
+        let signing_input = format!("{header}.{claims}");
+        let header: TokenHeader = serde_json::from_slice(&decode(header)?)
+            .map_err(|_| BundleVerificationError::MalformedToken)?;
+        let claims: Claims = serde_json::from_slice(&decode(claims)?)
+            .map_err(|_| BundleVerificationError::MalformedToken)?;
+
+        if let Some(key_id) = claims.keyid.as_ref().or(header.kid.as_ref()) {
+            if *key_id != self.key_id {
+                return Err(BundleVerificationError::UnknownKey(key_id.clone()));
+            }
+        }
+
+        let algorithm = Algorithm::from_name(&header.alg)?;
+        if !algorithm.fits(&self.key)?
+            || !algorithm
+                .verify(&self.key, signing_input.as_bytes(), &decode(signature)?)
+                .unwrap_or(false)
+        {
+            return Err(BundleVerificationError::BadSignature);
+        }
+
+        Ok(claims)
+    }
+
+    /// Check a gzipped tar bundle is signed with our key, and that its files are exactly those
+    /// it was signed with
+    pub fn verify(&self, bundle: &[u8]) -> Result<(), BundleVerificationError> {
+        let mut signatures = None;
+        let mut files = BTreeMap::new();
+        for (_, name, contents) in read_files(bundle)? {
+            if name == SIGNATURES_FILE {
+                signatures = Some(contents);
+            } else {
+                files.insert(name, contents);
+            }
+        }
+
+        let signatures: Signatures =
+            serde_json::from_slice(&signatures.ok_or(BundleVerificationError::Unsigned)?)
+                .map_err(BundleVerificationError::MalformedSignatures)?;
+        let token = match signatures.signatures.as_slice() {
+            [token] => token,
+            tokens => return Err(BundleVerificationError::SignatureCount(tokens.len())),
+        };
+
+        for file in self.verify_token(token)?.files {
+            let name = file_name(&file.name);
+            let contents = files
+                .remove(name)
+                .ok_or_else(|| BundleVerificationError::FileMissing(name.to_owned()))?;
+            if !file_hash(name, &contents, &file.algorithm)?.eq_ignore_ascii_case(&file.hash) {
+                return Err(BundleVerificationError::FileModified(name.to_owned()));
+            }
+        }
+
+        match files.into_keys().next() {
+            Some(name) => Err(BundleVerificationError::FileUnsigned(name)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Sign a gzipped tar bundle under `key_id` with a PEM encoded private key, as `opa sign` does,
+/// replacing any signature it already has. RSA keys sign with RS256, and EC keys with the ES
+/// algorithm for their curve.
+pub fn sign(
+    bundle: &[u8],
+    key_id: &str,
+    private_pem: &[u8],
+) -> Result<Vec<u8>, BundleVerificationError> {
+    let key = PKey::private_key_from_pem(private_pem)?;
+    let (alg, algorithm) = Algorithm::for_key(&key)?;
+
+    let files: Vec<_> = read_files(bundle)?
+        .into_iter()
+        .filter(|(_, name, _)| name != SIGNATURES_FILE)
+        .collect();
+    let claims = Claims {
+        files: files
+            .iter()
+            .map(|(_, name, contents)| {
+                Ok(SignedFile {
+                    name: name.clone(),
+                    hash: file_hash(name, contents, "SHA-256")?,
+                    algorithm: default_hash_algorithm(),
+                })
+            })
+            .collect::<Result<_, BundleVerificationError>>()?,
+        keyid: Some(key_id.to_owned()),
+    };
+    let header = TokenHeader {
+        alg: alg.to_owned(),
+        kid: None,
+    };
+
+    let encode = |part: &[u8]| URL_SAFE_NO_PAD.encode(part);
+    let signing_input = format!(
+        "{}.{}",
+        encode(&serde_json::to_vec(&header).map_err(BundleVerificationError::MalformedSignatures)?),
+        encode(&serde_json::to_vec(&claims).map_err(BundleVerificationError::MalformedSignatures)?)
+    );
+    let signature = algorithm.sign(&key, signing_input.as_bytes())?;
+    let signatures = serde_json::to_vec(&Signatures {
+        signatures: vec![format!("{signing_input}.{}", encode(&signature))],
+    })
+    .map_err(BundleVerificationError::MalformedSignatures)?;
+
+    let mut archive = tar::Builder::new(GzEncoder::new(vec![], Compression::default()));
+    let mut header = tar::Header::new_gnu();
+    header.set_size(signatures.len() as u64);
+    header.set_mode(0o644);
+    archive.append_data(&mut header, SIGNATURES_FILE, &*signatures)?;
+    for (header, _, contents) in files {
+        archive.append(&header, &*contents)?;
+    }
+
+    Ok(archive.into_inner()?.finish()?)
+}
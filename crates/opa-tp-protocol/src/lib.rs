@@ -7,6 +7,7 @@ use async_stl_client::{
 use state::OpaOperationEvent;
 use transaction::OpaSubmitTransaction;
 pub mod address;
+pub mod bundle;
 pub mod events;
 pub mod state;
 pub mod submission;
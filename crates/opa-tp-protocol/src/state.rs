@@ -1,12 +1,46 @@
+use chronicle_signing::{KeyAlgorithm, SecretError, TaggedVerifyingKey};
+use k256::sha2::{Digest, Sha256};
+use prost::Message;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-use crate::address::{hash_and_append, HasSawtoothAddress};
+use crate::{
+    address::{hash_and_append, HasSawtoothAddress},
+    bundle::{BundleVerificationError, BundleVerifier},
+    messages,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct KeyRegistration {
-    // Der encoded public key
+    // PEM encoded public key
     pub key: String,
     pub version: u64,
+    // Left out for secp256k1, so registrations made before keys were tagged are unchanged
+    #[serde(default, skip_serializing_if = "KeyAlgorithm::is_secp256k1")]
+    pub algorithm: KeyAlgorithm,
+}
+
+impl KeyRegistration {
+    /// Register `key`, tagged with the algorithm its PEM names
+    pub fn new(key: String, version: u64) -> Result<Self, SecretError> {
+        let algorithm = TaggedVerifyingKey::from_public_key_pem(&key)?.algorithm();
+
+        Ok(Self {
+            key,
+            version,
+            algorithm,
+        })
+    }
+
+    /// The registered key, which must be of the algorithm it is tagged with
+    pub fn verifying_key(&self) -> Result<TaggedVerifyingKey, SecretError> {
+        let key = TaggedVerifyingKey::from_public_key_pem(&self.key)?;
+        if key.algorithm() != self.algorithm {
+            return Err(SecretError::InvalidPublicKey);
+        }
+
+        Ok(key)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -30,6 +64,26 @@ pub fn policy_meta_address(id: impl AsRef<str>) -> String {
     hash_and_append(format!("opa:policy:meta:{}", id.as_ref()))
 }
 
+/// Where the history of a policy is kept
+pub fn policy_versions_address(id: impl AsRef<str>) -> String {
+    hash_and_append(format!("opa:policy:versions:{}", id.as_ref()))
+}
+
+/// Where the bundle of a version of a policy is kept, by its hash, so that it can be addressed
+/// before its version number is known
+pub fn policy_version_address(id: impl AsRef<str>, hash: impl AsRef<str>) -> String {
+    hash_and_append(format!(
+        "opa:policy:binary:{}:{}",
+        id.as_ref(),
+        hash.as_ref()
+    ))
+}
+
+/// Where the data document read with a policy is kept
+pub fn policy_data_address(id: impl AsRef<str>) -> String {
+    hash_and_append(format!("opa:policy:data:{}", id.as_ref()))
+}
+
 pub fn key_address(id: impl AsRef<str>) -> String {
     hash_and_append(format!("opa:keys:{}", id.as_ref()))
 }
@@ -47,10 +101,307 @@ impl HasSawtoothAddress for PolicyMeta {
     }
 }
 
+/// Keys of the data Chronicle passes to policies, which documents cannot replace
+const RESERVED_DATA_KEYS: [&str; 3] = ["type", "identity", "context"];
+
+#[derive(Debug, Error)]
+pub enum PolicyDataError {
+    #[error("Policy data is not JSON: {0}")]
+    Malformed(#[from] serde_json::Error),
+
+    #[error("Policy data must be a JSON object")]
+    NotAnObject,
+
+    #[error("Policy data cannot set data.{0}, which Chronicle sets")]
+    ReservedKey(String),
+}
+
+/// Read a data document, a JSON object whose keys policies see as `data.*` alongside the data
+/// Chronicle passes them
+pub fn policy_data_document(
+    document: &[u8],
+) -> Result<serde_json::Map<String, serde_json::Value>, PolicyDataError> {
+    match serde_json::from_slice(document)? {
+        serde_json::Value::Object(document) => {
+            match RESERVED_DATA_KEYS
+                .iter()
+                .find(|key| document.contains_key(**key))
+            {
+                Some(key) => Err(PolicyDataError::ReservedKey(key.to_string())),
+                None => Ok(document),
+            }
+        }
+        _ => Err(PolicyDataError::NotAnObject),
+    }
+}
+
+/// The data document of a policy as it was set, whose content is kept at its own address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyDataMeta {
+    pub id: String,
+    pub hash: String,
+    pub data_address: String,
+}
+
+impl HasSawtoothAddress for PolicyDataMeta {
+    fn get_address(&self) -> String {
+        policy_data_address(&self.id)
+    }
+}
+
+/// A policy bundle as it was set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyVersion {
+    pub version: u64,
+    pub hash: String,
+    pub size: u64,
+    // PEM encoded key that signed the operation setting it
+    pub signer: String,
+    // The transaction that set it, from which its block can be found, as the transaction
+    // processor does not know which block it is applied in
+    pub transaction_id: String,
+}
+
+/// Every version of a policy set since its history was first kept, oldest first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyVersions {
+    pub id: String,
+    pub active: u64,
+    pub versions: Vec<PolicyVersion>,
+}
+
+impl PolicyVersions {
+    pub fn version(&self, version: u64) -> Option<&PolicyVersion> {
+        self.versions.iter().find(|v| v.version == version)
+    }
+
+    pub fn next_version(&self) -> u64 {
+        self.versions.last().map_or(1, |v| v.version + 1)
+    }
+}
+
+impl HasSawtoothAddress for PolicyVersions {
+    fn get_address(&self) -> String {
+        policy_versions_address(&self.id)
+    }
+}
+
+/// Where the policies of namespaces that do not use the one in Chronicle's settings are kept
+pub fn namespace_policies_address() -> String {
+    hash_and_append("opa:namespace_policies")
+}
+
+/// Where the key policy bundles must be signed with is kept
+pub fn bundle_key_address() -> String {
+    hash_and_append("opa:bundle_key")
+}
+
+/// The key policy bundles must be signed with, under `key_id`, once one is set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleKey {
+    pub key_id: String,
+    // PEM encoded public key
+    pub key: String,
+}
+
+impl BundleKey {
+    pub fn verifier(&self) -> Result<BundleVerifier, BundleVerificationError> {
+        BundleVerifier::from_pem(&self.key_id, self.key.as_bytes())
+    }
+}
+
+impl HasSawtoothAddress for BundleKey {
+    fn get_address(&self) -> String {
+        bundle_key_address()
+    }
+}
+
+pub fn governance_address() -> String {
+    hash_and_append("opa:governance")
+}
+
+pub fn proposals_address() -> String {
+    hash_and_append("opa:proposals")
+}
+
+/// The administrators, `threshold` of whom must approve an operation once governance is set.
+/// Until then, operations are signed by the root key alone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Governance {
+    // PEM encoded public keys
+    pub keys: Vec<String>,
+    pub threshold: u64,
+}
+
+impl Governance {
+    pub fn verifying_keys(&self) -> Result<Vec<TaggedVerifyingKey>, SecretError> {
+        self.keys
+            .iter()
+            .map(|key| TaggedVerifyingKey::from_public_key_pem(key))
+            .collect()
+    }
+
+    /// Whether the PEM encoded `key` is one of the administrators', however it is encoded
+    pub fn is_administrator(&self, key: &str) -> bool {
+        match (
+            TaggedVerifyingKey::from_public_key_pem(key),
+            self.verifying_keys(),
+        ) {
+            (Ok(key), Ok(keys)) => keys.contains(&key),
+            _ => false,
+        }
+    }
+}
+
+impl HasSawtoothAddress for Governance {
+    fn get_address(&self) -> String {
+        governance_address()
+    }
+}
+
+/// An operation awaiting the approval of the administrators
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proposal {
+    pub id: String,
+    // Hex of the encoded operation
+    pub operation: String,
+    pub addresses: Vec<String>,
+    // PEM encoded keys of the administrators who have approved it, the proposer first
+    pub approvals: Vec<String>,
+}
+
+impl Proposal {
+    /// Proposals are identified by the hash of their sequence number and encoded operation, so
+    /// an approval of one proposal cannot be replayed to approve a later one of the same operation
+    pub fn id_of(sequence: u64, operation: &[u8]) -> String {
+        hex::encode(
+            Sha256::new()
+                .chain_update(sequence.to_be_bytes())
+                .chain_update(operation)
+                .finalize(),
+        )
+    }
+
+    pub fn operation(&self) -> Option<messages::signed_operation::payload::Operation> {
+        let operation = hex::decode(&self.operation).ok()?;
+        messages::signed_operation::Payload::decode(&*operation)
+            .ok()?
+            .operation
+    }
+
+    /// Approvals by those who are still administrators
+    pub fn approvals_by(&self, governance: &Governance) -> u64 {
+        self.approvals
+            .iter()
+            .filter(|key| governance.is_administrator(key))
+            .count() as u64
+    }
+
+    pub fn is_approved_by(&self, key: &str) -> bool {
+        match TaggedVerifyingKey::from_public_key_pem(key) {
+            Ok(key) => self.approvals.iter().any(|approval| {
+                TaggedVerifyingKey::from_public_key_pem(approval)
+                    .ok()
+                    .as_ref()
+                    == Some(&key)
+            }),
+            Err(_) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proposals {
+    pub proposals: Vec<Proposal>,
+    /// The number of proposals ever made, which is the sequence number of the next
+    #[serde(default)]
+    pub proposed: u64,
+}
+
+impl Proposals {
+    pub fn proposal(&self, id: &str) -> Option<&Proposal> {
+        self.proposals.iter().find(|proposal| proposal.id == id)
+    }
+
+    /// Whether the hex encoded `operation` is already awaiting approval
+    pub fn is_proposed(&self, operation: &str) -> bool {
+        self.proposals
+            .iter()
+            .any(|proposal| proposal.operation == operation)
+    }
+}
+
+impl HasSawtoothAddress for Proposals {
+    fn get_address(&self) -> String {
+        proposals_address()
+    }
+}
+
+/// The policy the operations of namespaces matching `namespace` are checked against. A namespace
+/// ending in `*` matches those whose names start with what comes before it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespacePolicy {
+    pub namespace: String,
+    pub policy_id: String,
+    pub entrypoint: String,
+}
+
+impl NamespacePolicy {
+    /// How closely the policy matches `namespace`, if it does at all. A name matches more closely
+    /// than any pattern, and a longer pattern more closely than a shorter one.
+    fn matches(&self, namespace: &str) -> Option<usize> {
+        match self.namespace.strip_suffix('*') {
+            Some(prefix) => namespace.starts_with(prefix).then_some(prefix.len()),
+            None => (self.namespace == namespace).then_some(usize::MAX),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespacePolicies {
+    pub policies: Vec<NamespacePolicy>,
+}
+
+impl NamespacePolicies {
+    /// The policy that most closely matches `namespace`, if any do
+    pub fn policy_for(&self, namespace: &str) -> Option<&NamespacePolicy> {
+        self.policies
+            .iter()
+            .filter_map(|policy| {
+                policy
+                    .matches(namespace)
+                    .map(|closeness| (closeness, policy))
+            })
+            .max_by_key(|(closeness, _)| *closeness)
+            .map(|(_, policy)| policy)
+    }
+
+    /// Set the policy of a namespace or pattern, replacing any it had
+    pub fn set(&mut self, policy: NamespacePolicy) {
+        self.remove(&policy.namespace);
+        self.policies.push(policy);
+    }
+
+    pub fn remove(&mut self, namespace: &str) {
+        self.policies.retain(|policy| policy.namespace != namespace);
+    }
+}
+
+impl HasSawtoothAddress for NamespacePolicies {
+    fn get_address(&self) -> String {
+        namespace_policies_address()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OpaOperationEvent {
     PolicyUpdate(PolicyMeta),
+    PolicyDataUpdate(PolicyDataMeta),
+    NamespacePoliciesUpdate(NamespacePolicies),
+    BundleKeyUpdate(BundleKey),
     KeyUpdate(Keys),
+    GovernanceUpdate(Governance),
+    ProposalUpdate(Proposal),
 
     Error(String),
 }
@@ -72,3 +423,33 @@ impl From<PolicyMeta> for OpaOperationEvent {
         Self::PolicyUpdate(v)
     }
 }
+
+impl From<PolicyDataMeta> for OpaOperationEvent {
+    fn from(v: PolicyDataMeta) -> Self {
+        Self::PolicyDataUpdate(v)
+    }
+}
+
+impl From<NamespacePolicies> for OpaOperationEvent {
+    fn from(v: NamespacePolicies) -> Self {
+        Self::NamespacePoliciesUpdate(v)
+    }
+}
+
+impl From<BundleKey> for OpaOperationEvent {
+    fn from(v: BundleKey) -> Self {
+        Self::BundleKeyUpdate(v)
+    }
+}
+
+impl From<Governance> for OpaOperationEvent {
+    fn from(v: Governance) -> Self {
+        Self::GovernanceUpdate(v)
+    }
+}
+
+impl From<Proposal> for OpaOperationEvent {
+    fn from(v: Proposal) -> Self {
+        Self::ProposalUpdate(v)
+    }
+}
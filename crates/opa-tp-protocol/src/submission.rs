@@ -80,6 +80,31 @@ enum BuildingMessage {
     RegisterKey(messages::SignedOperation),
     RotateKey(messages::SignedOperation),
     SetPolicy(messages::SignedOperation),
+    ActivatePolicyVersion(messages::SignedOperation),
+    SetPolicyData(messages::SignedOperation),
+    SetNamespacePolicy(messages::SignedOperation),
+    SetBundleKey(messages::SignedOperation),
+    SetGovernance(messages::SignedOperation),
+    Propose(messages::SignedOperation),
+    Approve(messages::SignedOperation),
+}
+
+/// Sign `operation` with the OPA key
+async fn signed_operation(
+    operation: messages::signed_operation::payload::Operation,
+    signer: &ChronicleSigning,
+) -> Result<messages::SignedOperation, SecretError> {
+    let operation = messages::signed_operation::Payload {
+        operation: Some(operation),
+    };
+    let signature = signer.opa_sign(&operation.encode_to_vec()).await?;
+    let key: PublicKey = signer.opa_verifying().await?.into();
+
+    Ok(messages::SignedOperation {
+        payload: Some(operation),
+        signature,
+        verifying_key: key.to_public_key_pem(LineEnding::CRLF).unwrap(),
+    })
 }
 
 pub struct SubmissionBuilder {
@@ -189,6 +214,163 @@ impl SubmissionBuilder {
         })
     }
 
+    pub async fn activate_policy_version(
+        id: &str,
+        version: u64,
+        signer: &ChronicleSigning,
+    ) -> Result<Self, SecretError> {
+        let operation = messages::signed_operation::Payload {
+            operation: Some(
+                messages::signed_operation::payload::Operation::ActivatePolicyVersion(
+                    messages::ActivatePolicyVersion {
+                        id: id.to_owned(),
+                        version,
+                    },
+                ),
+            ),
+        };
+        let signature = signer.opa_sign(&operation.encode_to_vec()).await?;
+        let key: PublicKey = signer.opa_verifying().await?.into();
+
+        let signed_operation = messages::SignedOperation {
+            payload: Some(operation),
+            signature,
+            verifying_key: key.to_public_key_pem(LineEnding::CRLF).unwrap(),
+        };
+
+        Ok(Self {
+            message: Some(BuildingMessage::ActivatePolicyVersion(signed_operation)),
+        })
+    }
+
+    /// Set the data `document`, a JSON object, read with the policy `id`
+    pub async fn set_policy_data(
+        id: &str,
+        document: Vec<u8>,
+        signer: &ChronicleSigning,
+    ) -> Result<Self, SecretError> {
+        let signed_operation = signed_operation(
+            messages::signed_operation::payload::Operation::SetPolicyData(
+                messages::SetPolicyData {
+                    id: id.to_owned(),
+                    document,
+                },
+            ),
+            signer,
+        )
+        .await?;
+
+        Ok(Self {
+            message: Some(BuildingMessage::SetPolicyData(signed_operation)),
+        })
+    }
+
+    /// Check the operations of `namespace`, a name or a prefix ending in `*`, against the policy
+    /// `id` with `entrypoint`, or remove its policy if `id` is empty
+    pub async fn set_namespace_policy(
+        namespace: &str,
+        id: &str,
+        entrypoint: &str,
+        signer: &ChronicleSigning,
+    ) -> Result<Self, SecretError> {
+        let signed_operation = signed_operation(
+            messages::signed_operation::payload::Operation::SetNamespacePolicy(
+                messages::SetNamespacePolicy {
+                    namespace: namespace.to_owned(),
+                    policy_id: id.to_owned(),
+                    entrypoint: entrypoint.to_owned(),
+                },
+            ),
+            signer,
+        )
+        .await?;
+
+        Ok(Self {
+            message: Some(BuildingMessage::SetNamespacePolicy(signed_operation)),
+        })
+    }
+
+    /// Only accept policy bundles signed under `key_id` with the private key of `public_key`,
+    /// PEM encoded
+    pub async fn set_bundle_key(
+        key_id: &str,
+        public_key: String,
+        signer: &ChronicleSigning,
+    ) -> Result<Self, SecretError> {
+        let signed_operation = signed_operation(
+            messages::signed_operation::payload::Operation::SetBundleKey(messages::SetBundleKey {
+                key_id: key_id.to_owned(),
+                public_key,
+            }),
+            signer,
+        )
+        .await?;
+
+        Ok(Self {
+            message: Some(BuildingMessage::SetBundleKey(signed_operation)),
+        })
+    }
+
+    /// Require `threshold` of the administrators with `public_keys`, PEM encoded, to approve
+    /// operations
+    pub async fn set_governance(
+        public_keys: Vec<String>,
+        threshold: u64,
+        signer: &ChronicleSigning,
+    ) -> Result<Self, SecretError> {
+        let signed_operation = signed_operation(
+            messages::signed_operation::payload::Operation::SetGovernance(
+                messages::SetGovernance {
+                    public_keys,
+                    threshold,
+                },
+            ),
+            signer,
+        )
+        .await?;
+
+        Ok(Self {
+            message: Some(BuildingMessage::SetGovernance(signed_operation)),
+        })
+    }
+
+    /// Propose `operation`, whose transaction uses `addresses`, approving it with the OPA key
+    pub async fn propose(
+        operation: &messages::signed_operation::Payload,
+        addresses: Vec<String>,
+        signer: &ChronicleSigning,
+    ) -> Result<Self, SecretError> {
+        let signed_operation = signed_operation(
+            messages::signed_operation::payload::Operation::Propose(messages::Propose {
+                operation: operation.encode_to_vec(),
+                addresses,
+            }),
+            signer,
+        )
+        .await?;
+
+        Ok(Self {
+            message: Some(BuildingMessage::Propose(signed_operation)),
+        })
+    }
+
+    pub async fn approve(
+        proposal_id: &str,
+        signer: &ChronicleSigning,
+    ) -> Result<Self, SecretError> {
+        let signed_operation = signed_operation(
+            messages::signed_operation::payload::Operation::Approve(messages::Approve {
+                proposal_id: proposal_id.to_owned(),
+            }),
+            signer,
+        )
+        .await?;
+
+        Ok(Self {
+            message: Some(BuildingMessage::Approve(signed_operation)),
+        })
+    }
+
     pub fn build(mut self, span_id: u64) -> messages::Submission {
         let mut submission = messages::Submission::default();
         match self.message.take().unwrap() {
@@ -201,9 +383,20 @@ impl SubmissionBuilder {
             BuildingMessage::SetPolicy(message) => {
                 submission.payload = Some(messages::submission::Payload::SignedOperation(message));
             }
+            BuildingMessage::ActivatePolicyVersion(message) => {
+                submission.payload = Some(messages::submission::Payload::SignedOperation(message));
+            }
             BuildingMessage::RegisterKey(message) => {
                 submission.payload = Some(messages::submission::Payload::SignedOperation(message));
             }
+            BuildingMessage::SetPolicyData(message)
+            | BuildingMessage::SetNamespacePolicy(message)
+            | BuildingMessage::SetBundleKey(message)
+            | BuildingMessage::SetGovernance(message)
+            | BuildingMessage::Propose(message)
+            | BuildingMessage::Approve(message) => {
+                submission.payload = Some(messages::submission::Payload::SignedOperation(message));
+            }
         };
         submission.span_id = span_id;
         submission.version = PROTOCOL_VERSION.to_string();
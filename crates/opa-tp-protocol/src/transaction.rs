@@ -5,13 +5,20 @@ use async_stl_client::{
     sawtooth::MessageBuilder,
 };
 use chronicle_signing::{BatcherKnownKeyNamesSigner, ChronicleSigning, SecretError};
-use k256::ecdsa::VerifyingKey;
+use k256::{
+    ecdsa::VerifyingKey,
+    sha2::{Digest, Sha256},
+};
 use prost::Message;
 
 use crate::{
     async_stl_client::sawtooth::TransactionPayload,
-    messages::Submission,
-    state::{key_address, policy_address, policy_meta_address},
+    messages::{self, Submission},
+    state::{
+        bundle_key_address, governance_address, key_address, namespace_policies_address,
+        policy_address, policy_data_address, policy_meta_address, policy_version_address,
+        policy_versions_address, proposals_address,
+    },
 };
 
 #[derive(Debug, Clone)]
@@ -21,6 +28,45 @@ pub enum OpaSubmitTransaction {
     RegisterKey(Submission, ChronicleSigning, String, bool),
     RotateKey(Submission, ChronicleSigning, String),
     SetPolicy(Submission, ChronicleSigning, String),
+    // The policy id and the hash of the version to activate
+    ActivatePolicyVersion(Submission, ChronicleSigning, String, String),
+    SetPolicyData(Submission, ChronicleSigning, String),
+    SetNamespacePolicy(Submission, ChronicleSigning),
+    SetBundleKey(Submission, ChronicleSigning),
+    SetGovernance(Submission, ChronicleSigning),
+    // The addresses of the proposed operation's transaction
+    Propose(Submission, ChronicleSigning, Vec<String>),
+    Approve(Submission, ChronicleSigning, Vec<String>),
+}
+
+/// The hash of the bundle a `SetPolicy` submission sets, which addresses its version
+fn set_policy_hash(submission: &Submission) -> Option<String> {
+    match signed_operation(submission)?.operation.as_ref()? {
+        messages::signed_operation::payload::Operation::SetPolicy(set_policy) => {
+            Some(hex::encode(Sha256::digest(&set_policy.policy)))
+        }
+        _ => None,
+    }
+}
+
+/// The policy a `SetNamespacePolicy` submission sets for a namespace, unless it removes one
+fn namespace_policy_id(submission: &Submission) -> Option<&str> {
+    match signed_operation(submission)?.operation.as_ref()? {
+        messages::signed_operation::payload::Operation::SetNamespacePolicy(set) => {
+            Some(set.policy_id.as_str()).filter(|id| !id.is_empty())
+        }
+        _ => None,
+    }
+}
+
+fn signed_operation(submission: &Submission) -> Option<&messages::signed_operation::Payload> {
+    match &submission.payload {
+        Some(messages::submission::Payload::SignedOperation(messages::SignedOperation {
+            payload: Some(payload),
+            ..
+        })) => Some(payload),
+        _ => None,
+    }
 }
 
 impl OpaSubmitTransaction {
@@ -70,6 +116,105 @@ impl OpaSubmitTransaction {
             name.as_ref().to_owned(),
         )
     }
+
+    /// Activate the version of the policy with `hash`, as found in its history
+    pub fn activate_policy_version(
+        name: impl AsRef<str>,
+        hash: impl AsRef<str>,
+        submission: Submission,
+        sawtooth_signer: &ChronicleSigning,
+    ) -> Self {
+        Self::ActivatePolicyVersion(
+            submission,
+            sawtooth_signer.to_owned(),
+            name.as_ref().to_owned(),
+            hash.as_ref().to_owned(),
+        )
+    }
+
+    pub fn set_policy_data(
+        name: impl AsRef<str>,
+        submission: Submission,
+        sawtooth_signer: &ChronicleSigning,
+    ) -> Self {
+        Self::SetPolicyData(
+            submission,
+            sawtooth_signer.to_owned(),
+            name.as_ref().to_owned(),
+        )
+    }
+
+    pub fn set_namespace_policy(
+        submission: Submission,
+        sawtooth_signer: &ChronicleSigning,
+    ) -> Self {
+        Self::SetNamespacePolicy(submission, sawtooth_signer.to_owned())
+    }
+
+    pub fn set_bundle_key(submission: Submission, sawtooth_signer: &ChronicleSigning) -> Self {
+        Self::SetBundleKey(submission, sawtooth_signer.to_owned())
+    }
+
+    pub fn set_governance(submission: Submission, sawtooth_signer: &ChronicleSigning) -> Self {
+        Self::SetGovernance(submission, sawtooth_signer.to_owned())
+    }
+
+    /// Propose an operation, whose own transaction would use `addresses`
+    pub fn propose(
+        submission: Submission,
+        sawtooth_signer: &ChronicleSigning,
+        addresses: Vec<String>,
+    ) -> Self {
+        Self::Propose(submission, sawtooth_signer.to_owned(), addresses)
+    }
+
+    /// Approve a proposal, with the addresses it was proposed with
+    pub fn approve(
+        submission: Submission,
+        sawtooth_signer: &ChronicleSigning,
+        addresses: Vec<String>,
+    ) -> Self {
+        Self::Approve(submission, sawtooth_signer.to_owned(), addresses)
+    }
+
+    pub fn submission(&self) -> &Submission {
+        match self {
+            Self::BootstrapRoot(submission, _) => submission,
+            Self::RotateRoot(submission, _) => submission,
+            Self::RegisterKey(submission, _, _, _) => submission,
+            Self::RotateKey(submission, _, _) => submission,
+            Self::SetPolicy(submission, _, _) => submission,
+            Self::ActivatePolicyVersion(submission, _, _, _) => submission,
+            Self::SetPolicyData(submission, _, _) => submission,
+            Self::SetNamespacePolicy(submission, _) => submission,
+            Self::SetBundleKey(submission, _) => submission,
+            Self::SetGovernance(submission, _) => submission,
+            Self::Propose(submission, _, _) => submission,
+            Self::Approve(submission, _, _) => submission,
+        }
+    }
+
+    /// The signed operation submitted, if it is not the bootstrap of the root key
+    pub fn operation(&self) -> Option<&messages::signed_operation::Payload> {
+        signed_operation(self.submission())
+    }
+
+    fn signer(&self) -> &ChronicleSigning {
+        match self {
+            Self::BootstrapRoot(_, signer) => signer,
+            Self::RotateRoot(_, signer) => signer,
+            Self::RegisterKey(_, signer, _, _) => signer,
+            Self::RotateKey(_, signer, _) => signer,
+            Self::SetPolicy(_, signer, _) => signer,
+            Self::ActivatePolicyVersion(_, signer, _, _) => signer,
+            Self::SetPolicyData(_, signer, _) => signer,
+            Self::SetNamespacePolicy(_, signer) => signer,
+            Self::SetBundleKey(_, signer) => signer,
+            Self::SetGovernance(_, signer) => signer,
+            Self::Propose(_, signer, _) => signer,
+            Self::Approve(_, signer, _) => signer,
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -79,14 +224,7 @@ impl TransactionPayload for OpaSubmitTransaction {
     /// Envelope a payload of `ChronicleOperations` and `SignedIdentity` in a `Submission` protocol buffer,
     /// along with placeholders for protocol version info and a tracing span id.
     async fn to_bytes(&self) -> Result<Vec<u8>, Infallible> {
-        Ok(match self {
-            Self::BootstrapRoot(submission, _) => submission,
-            Self::RotateRoot(submission, _) => submission,
-            Self::RegisterKey(submission, _, _, _) => submission,
-            Self::RotateKey(submission, _, _) => submission,
-            Self::SetPolicy(submission, _, _) => submission,
-        }
-        .encode_to_vec())
+        Ok(self.submission().encode_to_vec())
     }
 }
 
@@ -95,64 +233,72 @@ impl LedgerTransaction for OpaSubmitTransaction {
     type Error = SecretError;
 
     async fn sign(&self, bytes: Arc<Vec<u8>>) -> Result<Vec<u8>, Self::Error> {
-        let signer = match self {
-            Self::BootstrapRoot(_, signer) => signer,
-            Self::RotateRoot(_, signer) => signer,
-            Self::RegisterKey(_, signer, _, _) => signer,
-            Self::RotateKey(_, signer, _) => signer,
-            Self::SetPolicy(_, signer, _) => signer,
-        };
-        signer.batcher_sign(&bytes).await
+        self.signer().batcher_sign(&bytes).await
     }
 
     async fn verifying_key(&self) -> Result<VerifyingKey, Self::Error> {
-        let signer = match self {
-            Self::BootstrapRoot(_, signer) => signer,
-            Self::RotateRoot(_, signer) => signer,
-            Self::RegisterKey(_, signer, _, _) => signer,
-            Self::RotateKey(_, signer, _) => signer,
-            Self::SetPolicy(_, signer, _) => signer,
-        };
-
-        signer.batcher_verifying().await
+        self.signer().batcher_verifying().await
     }
 
     fn addresses(&self) -> Vec<String> {
+        // Every operation is authorized by the root key or by governance
+        let mut addresses = vec![key_address("root"), governance_address()];
         match self {
-            Self::BootstrapRoot(_, _) => {
-                vec![key_address("root")]
-            }
-            Self::RotateRoot(_, _) => {
-                vec![key_address("root")]
-            }
+            Self::BootstrapRoot(_, _) | Self::RotateRoot(_, _) | Self::SetGovernance(_, _) => {}
             Self::RegisterKey(_, _, name, _) => {
-                vec![key_address("root"), key_address(name.clone())]
+                addresses.push(key_address(name));
             }
             Self::RotateKey(_, _, name) => {
-                vec![key_address("root"), key_address(name.clone())]
+                addresses.push(key_address(name));
+            }
+            // Bundles are checked against the bundle key, if one is set
+            Self::SetPolicy(submission, _, name) => {
+                addresses.extend([
+                    bundle_key_address(),
+                    policy_meta_address(name),
+                    policy_address(name),
+                    policy_versions_address(name),
+                ]);
+                addresses.extend(
+                    set_policy_hash(submission).map(|hash| policy_version_address(name, hash)),
+                );
+            }
+            Self::ActivatePolicyVersion(_, _, name, hash) => {
+                addresses.extend([
+                    bundle_key_address(),
+                    policy_meta_address(name),
+                    policy_address(name),
+                    policy_versions_address(name),
+                    policy_version_address(name, hash),
+                ]);
+            }
+            Self::SetPolicyData(_, _, name) => {
+                addresses.push(policy_data_address(name));
+            }
+            Self::SetBundleKey(_, _) => {
+                addresses.push(bundle_key_address());
+            }
+            Self::SetNamespacePolicy(submission, _) => {
+                addresses.push(namespace_policies_address());
+                addresses.extend(namespace_policy_id(submission).map(policy_meta_address));
             }
-            Self::SetPolicy(_, _, name) => {
-                vec![
-                    key_address("root"),
-                    policy_meta_address(name.clone()),
-                    policy_address(name.clone()),
-                ]
+            Self::Propose(_, _, operation_addresses) | Self::Approve(_, _, operation_addresses) => {
+                addresses.push(proposals_address());
+                for address in operation_addresses {
+                    if !addresses.contains(address) {
+                        addresses.push(address.clone());
+                    }
+                }
             }
         }
+        addresses
     }
 
     async fn as_sawtooth_tx(
         &self,
         message_builder: &MessageBuilder,
     ) -> Result<(async_stl_client::messages::Transaction, TransactionId), Self::Error> {
-        let signer = match self {
-            Self::BootstrapRoot(_, signer) => signer,
-            Self::RotateRoot(_, signer) => signer,
-            Self::RegisterKey(_, signer, _, _) => signer,
-            Self::RotateKey(_, signer, _) => signer,
-            Self::SetPolicy(_, signer, _) => signer,
-        }
-        .clone();
+        let signer = self.signer().clone();
 
         message_builder
             .make_sawtooth_transaction(
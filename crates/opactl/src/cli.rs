@@ -167,6 +167,271 @@ fn set_policy() -> Command {
     )
 }
 
+fn set_data() -> Command {
+    wait_args(
+        Command::new("set-data")
+            .about("Set the data document read with the policy with id, requires access to root private key")
+            .arg(
+                Arg::new("id")
+                    .short('i')
+                    .long("id")
+                    .num_args(1)
+                    .value_hint(ValueHint::Unknown)
+                    .value_parser(NonEmptyStringValueParser::new())
+                    .default_value("default")
+                    .help("The id of the policy"),
+            )
+            .arg(
+                Arg::new("data")
+                    .short('d')
+                    .long("data")
+                    .num_args(1)
+                    .required(true)
+                    .value_hint(ValueHint::Url)
+                    .value_parser(StringValueParser::new())
+                    .help("A path or url to a JSON object, whose keys the policy reads as data.*"),
+            )
+            .arg(batcher_key()),
+    )
+}
+
+fn set_namespace_policy() -> Command {
+    wait_args(
+        Command::new("set-namespace-policy")
+            .about("Check a namespace's operations against a policy other than Chronicle's, requires access to root private key")
+            .arg(
+                Arg::new("namespace")
+                    .short('n')
+                    .long("namespace")
+                    .num_args(1)
+                    .required(true)
+                    .value_hint(ValueHint::Unknown)
+                    .value_parser(NonEmptyStringValueParser::new())
+                    .help("The name of the namespace, or a prefix of names ending in *"),
+            )
+            .arg(
+                Arg::new("id")
+                    .short('i')
+                    .long("id")
+                    .num_args(1)
+                    .value_hint(ValueHint::Unknown)
+                    .value_parser(NonEmptyStringValueParser::new())
+                    .required_unless_present("remove")
+                    .requires("entrypoint")
+                    .help("The id of the policy"),
+            )
+            .arg(
+                Arg::new("entrypoint")
+                    .short('e')
+                    .long("entrypoint")
+                    .num_args(1)
+                    .value_hint(ValueHint::Unknown)
+                    .value_parser(NonEmptyStringValueParser::new())
+                    .requires("id")
+                    .help("The entrypoint of the policy, such as allow_transactions.allowed_users"),
+            )
+            .arg(
+                Arg::new("remove")
+                    .long("remove")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with_all(["id", "entrypoint"])
+                    .help("Check the namespace's operations against Chronicle's policy again"),
+            )
+            .arg(batcher_key()),
+    )
+}
+
+fn list_namespace_policies() -> Command {
+    Command::new("list-namespace-policies").about(
+        "List the namespaces whose operations are checked against a policy other than Chronicle's",
+    )
+}
+
+fn list_policy_versions() -> Command {
+    Command::new("list-policy-versions")
+        .about("List the versions of a policy that have been set, and which is active")
+        .arg(
+            Arg::new("id")
+                .short('i')
+                .long("id")
+                .num_args(1)
+                .value_hint(ValueHint::Unknown)
+                .value_parser(NonEmptyStringValueParser::new())
+                .default_value("default")
+                .help("The id of the policy, if not specified then the default policy is listed"),
+        )
+}
+
+fn activate_policy_version() -> Command {
+    wait_args(
+        Command::new("activate-policy-version")
+            .about("Make an earlier version of a policy the active one, requires access to root private key")
+            .arg(
+                Arg::new("id")
+                    .short('i')
+                    .long("id")
+                    .num_args(1)
+                    .value_hint(ValueHint::Unknown)
+                    .value_parser(NonEmptyStringValueParser::new())
+                    .default_value("default")
+                    .help("The id of the policy"),
+            )
+            .arg(
+                Arg::new("version")
+                    .short('v')
+                    .long("version")
+                    .num_args(1)
+                    .required(true)
+                    .value_parser(clap::value_parser!(u64).range(1..))
+                    .help("The version to activate, as listed by list-policy-versions"),
+            )
+            .arg(batcher_key()),
+    )
+}
+
+fn set_bundle_key() -> Command {
+    wait_args(
+        Command::new("set-bundle-key")
+            .about("Only accept policy bundles signed with the private key of a public key, requires access to root private key")
+            .arg(
+                Arg::new("key")
+                    .short('k')
+                    .long("key")
+                    .num_args(1)
+                    .required(true)
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .value_hint(ValueHint::FilePath)
+                    .help("The path of the PEM-encoded public key bundles must be signed with"),
+            )
+            .arg(
+                Arg::new("key-id")
+                    .long("key-id")
+                    .num_args(1)
+                    .value_hint(ValueHint::Unknown)
+                    .value_parser(NonEmptyStringValueParser::new())
+                    .default_value("default")
+                    .help("The key id signed bundles must name, if they name one"),
+            )
+            .arg(batcher_key()),
+    )
+}
+
+fn set_governance() -> Command {
+    wait_args(
+        Command::new("set-governance")
+            .about("Require a threshold of administrators to approve operations, rather than the root key")
+            .arg(
+                Arg::new("administrator")
+                    .short('k')
+                    .long("administrator")
+                    .required(true)
+                    .num_args(1)
+                    .action(ArgAction::Append)
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .value_hint(ValueHint::FilePath)
+                    .help("The path of an administrator's PEM-encoded public key, may be given more than once"),
+            )
+            .arg(
+                Arg::new("threshold")
+                    .short('m')
+                    .long("threshold")
+                    .required(true)
+                    .num_args(1)
+                    .value_parser(clap::value_parser!(u64).range(1..))
+                    .help("The number of administrators who must approve an operation"),
+            )
+            .arg(batcher_key()),
+    )
+}
+
+fn propose() -> Command {
+    Command::new("propose")
+        .about(
+            "Propose an operation for the administrators to approve, approving it with the opa key",
+        )
+        .subcommand_required(true)
+        .subcommand(register_key())
+        .subcommand(set_policy())
+        .subcommand(activate_policy_version())
+        .subcommand(set_data())
+        .subcommand(set_namespace_policy())
+        .subcommand(set_bundle_key())
+        .subcommand(set_governance())
+}
+
+fn approve() -> Command {
+    wait_args(
+        Command::new("approve")
+            .about("Approve a proposed operation with the opa key, applying it once enough administrators have")
+            .arg(
+                Arg::new("proposal")
+                    .short('p')
+                    .long("proposal")
+                    .required(true)
+                    .num_args(1)
+                    .value_hint(ValueHint::Unknown)
+                    .value_parser(NonEmptyStringValueParser::new())
+                    .help("The id of the proposal, as listed by list-proposals"),
+            )
+            .arg(batcher_key()),
+    )
+}
+
+fn list_proposals() -> Command {
+    Command::new("list-proposals").about("List the proposals awaiting approval")
+}
+
+fn test_policy() -> Command {
+    Command::new("test-policy")
+        .about("Evaluate a policy against a directory of recorded inputs, reporting those it decides differently than expected")
+        .arg(
+            Arg::new("policy")
+                .short('p')
+                .long("policy")
+                .num_args(1)
+                .value_hint(ValueHint::Url)
+                .value_parser(StringValueParser::new())
+                .conflicts_with("id")
+                .help("A path or url to a policy bundle, if not specified then the bundle is read from the ledger"),
+        )
+        .arg(
+            Arg::new("id")
+                .short('i')
+                .long("id")
+                .num_args(1)
+                .value_hint(ValueHint::Unknown)
+                .value_parser(NonEmptyStringValueParser::new())
+                .default_value("default")
+                .help("The id of the policy on the ledger"),
+        )
+        .arg(
+            Arg::new("rule")
+                .long("rule")
+                .num_args(1)
+                .value_parser(NonEmptyStringValueParser::new())
+                .default_value("allow_transactions")
+                .help("The name of the policy in the bundle"),
+        )
+        .arg(
+            Arg::new("entrypoint")
+                .long("entrypoint")
+                .num_args(1)
+                .value_parser(NonEmptyStringValueParser::new())
+                .default_value("allow_transactions.allowed_users")
+                .help("The entrypoint of the policy to evaluate"),
+        )
+        .arg(
+            Arg::new("fixtures")
+                .short('f')
+                .long("fixtures")
+                .required(true)
+                .num_args(1)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::DirPath)
+                .help("A directory of fixtures, such as those recorded by chronicle --opa-capture"),
+        )
+}
+
 fn get_key() -> Command {
     Command::new("get-key")
         .about("Get the currently registered public key")
@@ -216,6 +481,30 @@ fn get_policy() -> Command {
         )
 }
 
+fn get_data() -> Command {
+    Command::new("get-data")
+        .about("Get the data document read with a policy")
+        .arg(
+            Arg::new("id")
+                .short('i')
+                .long("id")
+                .num_args(1)
+                .value_hint(ValueHint::Unknown)
+                .value_parser(NonEmptyStringValueParser::new())
+                .default_value("default")
+                .help("The id of the policy, if not specified then the default policy's data is returned"),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .num_args(0..=1)
+                .value_hint(ValueHint::FilePath)
+                .value_parser(NonEmptyStringValueParser::new())
+                .help("The path to write the data to, if not specified then it is written to stdout"),
+        )
+}
+
 pub const LONG_VERSION: &str = const_format::formatcp!(
     "{}:{}",
     env!("CARGO_PKG_VERSION"),
@@ -261,6 +550,18 @@ pub fn cli() -> Command {
                 .conflicts_with("batcher-key-from-path")
                 .conflicts_with("batcher-key-from-vault"),
         )
+        .arg(
+            Arg::new("batcher-key-from-pkcs11")
+                .long("batcher-key-from-pkcs11")
+                .action(ArgAction::SetTrue)
+                .help("Sign batches on a PKCS#11 token, with the key labelled 'batcher-pk'")
+                .requires("pkcs11-module")
+                .requires("pkcs11-token-label")
+                .requires("pkcs11-pin")
+                .conflicts_with("batcher-key-from-path")
+                .conflicts_with("batcher-key-from-vault")
+                .conflicts_with("batcher-key-generated"),
+        )
         .arg(
             Arg::new("opa-key-from-path")
                 .long("opa-key-from-path")
@@ -300,6 +601,29 @@ pub fn cli() -> Command {
                 .default_value("/")
                 .env("VAULT_MOUNT_PATH"),
         )
+        .arg(
+            Arg::new("pkcs11-module")
+                .long("pkcs11-module")
+                .num_args(1)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath)
+                .help("Path to the PKCS#11 module, such as libsofthsm2.so")
+                .env("PKCS11_MODULE"),
+        )
+        .arg(
+            Arg::new("pkcs11-token-label")
+                .long("pkcs11-token-label")
+                .num_args(1)
+                .help("Label of the PKCS#11 token holding the keys")
+                .env("PKCS11_TOKEN_LABEL"),
+        )
+        .arg(
+            Arg::new("pkcs11-pin")
+                .long("pkcs11-pin")
+                .num_args(1)
+                .help("User PIN for the PKCS#11 token")
+                .env("PKCS11_PIN"),
+        )
         .arg(
             Arg::new("sawtooth-address")
                 .short('a')
@@ -318,6 +642,18 @@ pub fn cli() -> Command {
         .subcommand(set_policy())
         .subcommand(get_key())
         .subcommand(get_policy())
+        .subcommand(list_policy_versions())
+        .subcommand(activate_policy_version())
+        .subcommand(set_data())
+        .subcommand(get_data())
+        .subcommand(set_namespace_policy())
+        .subcommand(list_namespace_policies())
+        .subcommand(set_bundle_key())
+        .subcommand(set_governance())
+        .subcommand(propose())
+        .subcommand(approve())
+        .subcommand(list_proposals())
+        .subcommand(test_policy())
 }
 
 // Chronicle secret store needs to know what secret names are used in advance,
@@ -364,6 +700,9 @@ pub(crate) async fn configure_signing(
     let batcher_key_from_vault = root_matches
         .get_one("batcher-key-from-vault")
         .is_some_and(|x| *x);
+    let batcher_key_from_pkcs11 = root_matches
+        .get_one("batcher-key-from-pkcs11")
+        .is_some_and(|x| *x);
     let batcher_secret_options = if batcher_key_from_path {
         ChronicleSecretsOptions::stored_at_path(keystore_path)
     } else if batcher_key_from_vault {
@@ -372,6 +711,14 @@ pub(crate) async fn configure_signing(
             matches.get_one("vault-token").cloned().unwrap(),
             matches.get_one("vault-mount-path").cloned().unwrap(),
         )
+    } else if batcher_key_from_pkcs11 {
+        ChronicleSecretsOptions::held_by_pkcs11(
+            root_matches.get_one::<PathBuf>("pkcs11-module").unwrap(),
+            root_matches
+                .get_one::<String>("pkcs11-token-label")
+                .unwrap(),
+            root_matches.get_one::<String>("pkcs11-pin").unwrap(),
+        )
     } else {
         ChronicleSecretsOptions::generate_in_memory()
     };
@@ -2,10 +2,15 @@ use async_stl_client::{
     sawtooth::MessageBuilder,
     zmq_client::{HighestBlockValidatorSelector, ZmqRequestResponseSawtoothChannel},
 };
-use chronicle_signing::{OpaKnownKeyNamesSigner, SecretError, OPA_PK};
+use chronicle_signing::{
+    ChronicleSigning, OpaKnownKeyNamesSigner, SecretError, TaggedVerifyingKey, OPA_PK,
+};
 use clap::ArgMatches;
 use cli::{configure_signing, Wait};
-use common::import::{load_bytes_from_url, FromUrlError};
+use common::{
+    import::{load_bytes_from_url, FromUrlError},
+    opa::{test_policy, CliPolicyLoader, PolicyFixtureError, PolicyLoaderError},
+};
 use futures::{channel::oneshot, Future, FutureExt, StreamExt};
 use k256::{
     pkcs8::{EncodePrivateKey, LineEnding},
@@ -15,16 +20,32 @@ use opa_tp_protocol::{
     address::{FAMILY, VERSION},
     async_stl_client::{
         error::SawtoothCommunicationError,
-        ledger::{LedgerReader, LedgerWriter, TransactionId},
+        ledger::{
+            BlockId, FromBlock, LedgerReader, LedgerTransaction, LedgerWriter, Position,
+            TransactionId,
+        },
+    },
+    bundle::{BundleVerificationError, BundleVerifier},
+    state::{
+        key_address, namespace_policies_address, policy_address, policy_data_address,
+        policy_data_document, policy_versions_address, proposals_address, Keys, NamespacePolicies,
+        OpaOperationEvent, PolicyDataError, PolicyVersion, PolicyVersions, Proposals,
     },
-    state::{key_address, policy_address, Keys, OpaOperationEvent},
     submission::SubmissionBuilder,
     transaction::OpaSubmitTransaction,
     OpaLedger,
 };
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Deserialize};
 use serde_derive::Serialize;
-use std::{fs::File, io::Write, net::ToSocketAddrs, path::PathBuf, str::from_utf8, time::Duration};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::Write,
+    net::ToSocketAddrs,
+    path::PathBuf,
+    str::from_utf8,
+    time::Duration,
+};
 use thiserror::Error;
 use url::Url;
 
@@ -69,6 +90,27 @@ pub enum OpaCtlError {
 
     #[error("Missing Argument")]
     MissingArgument(String),
+
+    #[error("Policy {0} has no version {1}")]
+    NoPolicyVersion(String, u64),
+
+    #[error("No proposal {0} awaiting approval")]
+    NoProposal(String),
+
+    #[error("Policy: {0}")]
+    Policy(#[from] PolicyLoaderError),
+
+    #[error("Policy fixtures: {0}")]
+    PolicyFixture(#[from] PolicyFixtureError),
+
+    #[error("Policy decided {0} fixtures differently than expected")]
+    PolicyTestFailed(usize),
+
+    #[error("Policy data: {0}")]
+    PolicyData(#[from] PolicyDataError),
+
+    #[error("Bundle key: {0}")]
+    BundleKey(#[from] BundleVerificationError),
 }
 
 impl UFE for OpaCtlError {}
@@ -249,6 +291,191 @@ async fn handle_wait<
     }
 }
 
+/// A policy version as listed, with the block its transaction was committed in, if it was found
+#[derive(Serialize)]
+struct ListedPolicyVersion<'a> {
+    #[serde(flatten)]
+    version: &'a PolicyVersion,
+    block_id: Option<String>,
+    block_num: Option<u64>,
+}
+
+/// Find the block each of `transactions` was committed in from the operation events on the
+/// ledger, as the transaction processor that applies a transaction cannot know its block
+async fn transaction_blocks<
+    R: LedgerReader<Event = OpaOperationEvent, Error = SawtoothCommunicationError> + Send + Sync,
+>(
+    reader: &R,
+    mut transactions: BTreeSet<String>,
+) -> Result<BTreeMap<String, (BlockId, Position)>, OpaCtlError> {
+    let mut blocks = BTreeMap::new();
+    if transactions.is_empty() {
+        return Ok(blocks);
+    }
+
+    let (height, _) = reader.block_height().await?;
+    let mut events = reader
+        .state_updates("opa/operation", FromBlock::First, Some(height.0 + 1))
+        .await?;
+
+    while let Some((_, tx, block_id, position, _)) = events.next().await {
+        if transactions.remove(&tx.to_string()) {
+            blocks.insert(tx.to_string(), (block_id, position));
+            if transactions.is_empty() {
+                break;
+            }
+        }
+    }
+
+    Ok(blocks)
+}
+
+/// Read the state entry at `address`, if there is one
+async fn state_entry<
+    R: LedgerReader<Event = OpaOperationEvent, Error = SawtoothCommunicationError> + Send + Sync,
+    T: DeserializeOwned,
+>(
+    reader: &R,
+    address: &str,
+) -> Result<Option<T>, OpaCtlError> {
+    match reader.get_state_entry(address).await {
+        Ok(entry) => Ok(Some(serde_json::from_slice(&entry)?)),
+        Err(SawtoothCommunicationError::ResourceNotFound) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Build the transaction for an operation that can either be submitted by the root key or
+/// proposed to the administrators
+async fn operation<
+    R: LedgerReader<Event = OpaOperationEvent, Error = SawtoothCommunicationError> + Send + Sync,
+>(
+    command: &str,
+    matches: &ArgMatches,
+    command_matches: &ArgMatches,
+    reader: &R,
+    span_id: u64,
+) -> Result<(ChronicleSigning, OpaSubmitTransaction), OpaCtlError> {
+    match command {
+        "register-key" => {
+            let signing = configure_signing(vec!["new-key"], matches, command_matches).await?;
+            let new_key = &command_matches
+                .get_one::<String>("new-key")
+                .ok_or_else(|| OpaCtlError::MissingArgument("new-key".to_owned()))?;
+            let id = command_matches.get_one::<String>("id").unwrap();
+            let overwrite_existing = command_matches.get_flag("overwrite");
+            let register_key =
+                SubmissionBuilder::register_key(id, new_key, &signing, overwrite_existing)
+                    .await?
+                    .build(span_id);
+            let transaction =
+                OpaSubmitTransaction::register_key(id, register_key, &signing, overwrite_existing);
+            Ok((signing, transaction))
+        }
+        "set-policy" => {
+            let signing = configure_signing(vec![], matches, command_matches).await?;
+            let policy: &String = command_matches.get_one("policy").unwrap();
+
+            let policy = load_bytes_from_url(policy).await?;
+
+            let id = command_matches.get_one::<String>("id").unwrap();
+
+            let bootstrap = SubmissionBuilder::set_policy(id, policy, &signing)
+                .await?
+                .build(span_id);
+            let transaction = OpaSubmitTransaction::set_policy(id, bootstrap, &signing);
+            Ok((signing, transaction))
+        }
+        "activate-policy-version" => {
+            let signing = configure_signing(vec![], matches, command_matches).await?;
+            let id = command_matches.get_one::<String>("id").unwrap();
+            let version = *command_matches.get_one::<u64>("version").unwrap();
+
+            // The transaction must name the address of the version's bundle, so find its hash
+            let hash = state_entry::<_, PolicyVersions>(reader, &policy_versions_address(id))
+                .await?
+                .and_then(|versions| versions.version(version).map(|v| v.hash.clone()))
+                .ok_or_else(|| OpaCtlError::NoPolicyVersion(id.to_owned(), version))?;
+
+            let activate = SubmissionBuilder::activate_policy_version(id, version, &signing)
+                .await?
+                .build(span_id);
+            let transaction =
+                OpaSubmitTransaction::activate_policy_version(id, hash, activate, &signing);
+            Ok((signing, transaction))
+        }
+        "set-data" => {
+            let signing = configure_signing(vec![], matches, command_matches).await?;
+            let data: &String = command_matches.get_one("data").unwrap();
+
+            let document = load_bytes_from_url(data).await?;
+            // Fail here rather than in the transaction processor
+            policy_data_document(&document)?;
+
+            let id = command_matches.get_one::<String>("id").unwrap();
+
+            let set_data = SubmissionBuilder::set_policy_data(id, document, &signing)
+                .await?
+                .build(span_id);
+            let transaction = OpaSubmitTransaction::set_policy_data(id, set_data, &signing);
+            Ok((signing, transaction))
+        }
+        "set-namespace-policy" => {
+            let signing = configure_signing(vec![], matches, command_matches).await?;
+            let namespace = command_matches.get_one::<String>("namespace").unwrap();
+            // Removing a namespace's policy sets it to an empty id
+            let id = command_matches
+                .get_one::<String>("id")
+                .map(String::as_str)
+                .unwrap_or_default();
+            let entrypoint = command_matches
+                .get_one::<String>("entrypoint")
+                .map(String::as_str)
+                .unwrap_or_default();
+
+            let set_namespace_policy =
+                SubmissionBuilder::set_namespace_policy(namespace, id, entrypoint, &signing)
+                    .await?
+                    .build(span_id);
+            let transaction =
+                OpaSubmitTransaction::set_namespace_policy(set_namespace_policy, &signing);
+            Ok((signing, transaction))
+        }
+        "set-bundle-key" => {
+            let signing = configure_signing(vec![], matches, command_matches).await?;
+            let key_id = command_matches.get_one::<String>("key-id").unwrap();
+            let key = std::fs::read_to_string(command_matches.get_one::<PathBuf>("key").unwrap())?;
+            // Fail here rather than in the transaction processor
+            BundleVerifier::from_pem(key_id, key.as_bytes())?;
+
+            let set_bundle_key = SubmissionBuilder::set_bundle_key(key_id, key, &signing)
+                .await?
+                .build(span_id);
+            let transaction = OpaSubmitTransaction::set_bundle_key(set_bundle_key, &signing);
+            Ok((signing, transaction))
+        }
+        "set-governance" => {
+            let signing = configure_signing(vec![], matches, command_matches).await?;
+            let keys = command_matches
+                .get_many::<PathBuf>("administrator")
+                .unwrap()
+                .map(std::fs::read_to_string)
+                .collect::<Result<Vec<_>, _>>()?;
+            for key in &keys {
+                TaggedVerifyingKey::from_public_key_pem(key)?;
+            }
+            let threshold = *command_matches.get_one::<u64>("threshold").unwrap();
+
+            let set_governance = SubmissionBuilder::set_governance(keys, threshold, &signing)
+                .await?
+                .build(span_id);
+            let transaction = OpaSubmitTransaction::set_governance(set_governance, &signing);
+            Ok((signing, transaction))
+        }
+        command => unreachable!("{command} is not an operation"),
+    }
+}
+
 async fn dispatch_args<
     W: LedgerWriter<Transaction = OpaSubmitTransaction, Error = SawtoothCommunicationError>
         + Send
@@ -315,25 +542,6 @@ async fn dispatch_args<
             )
             .await?)
         }
-        Some(("register-key", command_matches)) => {
-            let signing = configure_signing(vec!["new-key"], &matches, command_matches).await?;
-            let new_key = &command_matches
-                .get_one::<String>("new-key")
-                .ok_or_else(|| OpaCtlError::MissingArgument("new-key".to_owned()))?;
-            let id = command_matches.get_one::<String>("id").unwrap();
-            let overwrite_existing = command_matches.get_flag("overwrite");
-            let register_key =
-                SubmissionBuilder::register_key(id, new_key, &signing, overwrite_existing)
-                    .await?
-                    .build(span_id);
-            Ok(handle_wait(
-                command_matches,
-                reader,
-                writer,
-                OpaSubmitTransaction::register_key(id, register_key, &signing, overwrite_existing),
-            )
-            .await?)
-        }
         Some(("rotate-key", command_matches)) => {
             let signing =
                 configure_signing(vec!["current-key", "new-key"], &matches, command_matches)
@@ -357,25 +565,166 @@ async fn dispatch_args<
             )
             .await?)
         }
-        Some(("set-policy", command_matches)) => {
+        Some((
+            command @ ("register-key"
+            | "set-policy"
+            | "activate-policy-version"
+            | "set-data"
+            | "set-namespace-policy"
+            | "set-bundle-key"
+            | "set-governance"),
+            command_matches,
+        )) => {
+            let (_, transaction) =
+                operation(command, &matches, command_matches, &reader, span_id).await?;
+            Ok(handle_wait(command_matches, reader, writer, transaction).await?)
+        }
+        Some(("propose", propose_matches)) => {
+            let (command, command_matches) = propose_matches.subcommand().unwrap();
+            let (signing, transaction) =
+                operation(command, &matches, command_matches, &reader, span_id).await?;
+
+            let addresses = transaction.addresses();
+            let propose = SubmissionBuilder::propose(
+                transaction
+                    .operation()
+                    .expect("proposed operations are signed"),
+                addresses.clone(),
+                &signing,
+            )
+            .await?
+            .build(span_id);
+            Ok(handle_wait(
+                command_matches,
+                reader,
+                writer,
+                OpaSubmitTransaction::propose(propose, &signing, addresses),
+            )
+            .await?)
+        }
+        Some(("approve", command_matches)) => {
             let signing = configure_signing(vec![], &matches, command_matches).await?;
-            let policy: &String = command_matches.get_one("policy").unwrap();
-
-            let policy = load_bytes_from_url(policy).await?;
+            let id = command_matches.get_one::<String>("proposal").unwrap();
 
-            let id = command_matches.get_one::<String>("id").unwrap();
+            // Approvals use the addresses of the proposed operation
+            let proposal = state_entry::<_, Proposals>(&reader, &proposals_address())
+                .await?
+                .and_then(|proposals| proposals.proposal(id).cloned())
+                .ok_or_else(|| OpaCtlError::NoProposal(id.to_owned()))?;
 
-            let bootstrap = SubmissionBuilder::set_policy(id, policy, &signing)
+            let approve = SubmissionBuilder::approve(id, &signing)
                 .await?
                 .build(span_id);
             Ok(handle_wait(
                 command_matches,
                 reader,
                 writer,
-                OpaSubmitTransaction::set_policy(id, bootstrap, &signing),
+                OpaSubmitTransaction::approve(approve, &signing, proposal.addresses),
             )
             .await?)
         }
+        Some(("list-proposals", _)) => {
+            match state_entry::<_, Proposals>(&reader, &proposals_address()).await? {
+                Some(proposals) if !proposals.proposals.is_empty() => {
+                    println!("{}", serde_json::to_string_pretty(&proposals)?)
+                }
+                _ => print!("No proposals awaiting approval"),
+            }
+
+            Ok((Waited::NoWait, reader))
+        }
+        Some(("list-namespace-policies", _)) => {
+            match state_entry::<_, NamespacePolicies>(&reader, &namespace_policies_address())
+                .await?
+            {
+                Some(policies) if !policies.policies.is_empty() => {
+                    println!("{}", serde_json::to_string_pretty(&policies)?)
+                }
+                _ => print!("No namespace policies found"),
+            }
+
+            Ok((Waited::NoWait, reader))
+        }
+        Some(("list-policy-versions", matches)) => {
+            let id = matches.get_one::<String>("id").unwrap();
+
+            match state_entry::<_, PolicyVersions>(&reader, &policy_versions_address(id)).await? {
+                Some(versions) => {
+                    let blocks = transaction_blocks(
+                        &reader,
+                        versions
+                            .versions
+                            .iter()
+                            .map(|version| version.transaction_id.clone())
+                            .collect(),
+                    )
+                    .await?;
+
+                    let listed = versions
+                        .versions
+                        .iter()
+                        .map(|version| {
+                            let block = blocks.get(&version.transaction_id);
+                            ListedPolicyVersion {
+                                version,
+                                block_id: block.map(|(block_id, _)| block_id.to_string()),
+                                block_num: block.map(|(_, position)| position.0),
+                            }
+                        })
+                        .collect::<Vec<_>>();
+
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&serde_json::json!({
+                            "id": versions.id,
+                            "active": versions.active,
+                            "versions": listed,
+                        }))?
+                    );
+                }
+                None => print!("No policy versions found"),
+            }
+
+            Ok((Waited::NoWait, reader))
+        }
+        Some(("test-policy", matches)) => {
+            let bundle = match matches.get_one::<String>("policy") {
+                Some(policy) => load_bytes_from_url(policy).await?,
+                None => {
+                    reader
+                        .get_state_entry(&policy_address(matches.get_one::<String>("id").unwrap()))
+                        .await?
+                }
+            };
+            let loader = CliPolicyLoader::from_policy_bytes(
+                matches.get_one::<String>("rule").unwrap(),
+                matches.get_one::<String>("entrypoint").unwrap(),
+                &bundle,
+            )?;
+
+            let outcomes =
+                test_policy(&loader, matches.get_one::<PathBuf>("fixtures").unwrap()).await?;
+            for outcome in &outcomes {
+                match &outcome.decision {
+                    Ok(decision) if outcome.passed() => {
+                        println!("PASS {} ({decision})", outcome.name)
+                    }
+                    Ok(decision) => println!(
+                        "FAIL {} (expected {}, got {decision})",
+                        outcome.name, outcome.expected
+                    ),
+                    Err(e) => println!("FAIL {} ({e})", outcome.name),
+                }
+            }
+
+            let failed = outcomes.iter().filter(|outcome| !outcome.passed()).count();
+            println!("{} passed, {failed} failed", outcomes.len() - failed);
+            if failed > 0 {
+                return Err(OpaCtlError::PolicyTestFailed(failed));
+            }
+
+            Ok((Waited::NoWait, reader))
+        }
         Some(("get-key", matches)) => {
             let key: Vec<u8> = reader
                 .get_state_entry(&key_address(matches.get_one::<String>("id").unwrap()))
@@ -415,6 +764,29 @@ async fn dispatch_args<
 
             Ok((Waited::NoWait, reader))
         }
+        Some(("get-data", matches)) => {
+            let data: Result<Vec<u8>, _> = reader
+                .get_state_entry(&policy_data_address(
+                    matches.get_one::<String>("id").unwrap(),
+                ))
+                .await;
+
+            if let Err(SawtoothCommunicationError::ResourceNotFound) = data {
+                print!("No policy data found");
+                return Ok((Waited::NoWait, reader));
+            }
+
+            let data = data?;
+
+            if let Some(path) = matches.get_one::<String>("output") {
+                let mut file = File::create(path)?;
+                file.write_all(&data)?;
+            } else {
+                print!("{}", from_utf8(&data)?);
+            }
+
+            Ok((Waited::NoWait, reader))
+        }
         _ => Ok((Waited::NoWait, reader)),
     }
 }
@@ -1316,7 +1688,9 @@ pub mod test {
 
         insta::assert_yaml_snapshot!(opa_tp.readable_state(), {
           ".**.date" => "[date]",
-          ".**.key" => "[pem]"
+          ".**.key" => "[pem]",
+          ".**.signer" => "[pem]",
+          ".**.transaction_id" => "[hash]"
         } ,@r###"
         ---
         - - 7ed19313e8ece6c4f5551b9bd1090797ad25c6d85f7b523b2214d4fe448372279aa95c
@@ -1331,6 +1705,17 @@ pub mod test {
           - hash: 6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d
             id: test
             policy_address: 7ed1931c262a4be700b69974438a35ae56a07ce96778b276c8a061dc254d9862c7ecff
+        - - 7ed193b3cf8269a68eaea1f351f5a88094c90002ee84bd1458b51c0218c2555e10783f
+          - active: 1
+            id: test
+            versions:
+              - hash: 6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d
+                signer: "[pem]"
+                size: 1
+                transaction_id: "[hash]"
+                version: 1
+        - - 7ed193ff8d426033694da78242e89701724ad1174dd6d662ba08251b76a9afe1af93fd
+          - - 0
         "###);
 
         policy.write_all(&[1]).unwrap();
@@ -1361,7 +1746,9 @@ pub mod test {
 
         insta::assert_yaml_snapshot!(opa_tp.readable_state(), {
           ".**.date" => "[date]",
-          ".**.key" => "[pem]"
+          ".**.key" => "[pem]",
+          ".**.signer" => "[pem]",
+          ".**.transaction_id" => "[hash]"
         } ,@r###"
         ---
         - - 7ed19313e8ece6c4f5551b9bd1090797ad25c6d85f7b523b2214d4fe448372279aa95c
@@ -1377,6 +1764,25 @@ pub mod test {
           - hash: b413f47d13ee2fe6c845b2ee141af81de858df4ec549a58b7970bb96645bc8d2
             id: test
             policy_address: 7ed1931c262a4be700b69974438a35ae56a07ce96778b276c8a061dc254d9862c7ecff
+        - - 7ed193ad0259cad3b313286a5cd9f27f207cdc66cdc1837c57f864040a04400ea9c1d6
+          - - 0
+            - 1
+        - - 7ed193b3cf8269a68eaea1f351f5a88094c90002ee84bd1458b51c0218c2555e10783f
+          - active: 2
+            id: test
+            versions:
+              - hash: 6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d
+                signer: "[pem]"
+                size: 1
+                transaction_id: "[hash]"
+                version: 1
+              - hash: b413f47d13ee2fe6c845b2ee141af81de858df4ec549a58b7970bb96645bc8d2
+                signer: "[pem]"
+                size: 2
+                transaction_id: "[hash]"
+                version: 2
+        - - 7ed193ff8d426033694da78242e89701724ad1174dd6d662ba08251b76a9afe1af93fd
+          - - 0
         "###);
     }
 }
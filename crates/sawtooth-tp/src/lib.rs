@@ -1,4 +1,5 @@
 //! Library exports for use in test and embedding contexts.
+#![allow(clippy::result_large_err)]
 pub mod abstract_tp;
 mod opa;
 pub mod tp;
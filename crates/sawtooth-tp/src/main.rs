@@ -1,3 +1,4 @@
+#![allow(clippy::result_large_err)]
 mod abstract_tp;
 mod tp;
 use ::chronicle_telemetry::ConsoleLogging;
@@ -53,6 +54,12 @@ async fn main() {
                 .default_value("pretty")
                 .help("Log to console using RUST_LOG environment"),
         )
+        .arg(
+            Arg::new("explain-denials")
+                .long("explain-denials")
+                .takes_value(false)
+                .help("Include the reasons OPA policies give for denying operations in rejections"),
+        )
         .get_matches();
 
     telemetry(
@@ -79,11 +86,14 @@ async fn main() {
             "Starting Chronicle Transaction Processor on {:?}",
             matches.get_one::<String>("connect")
         );
-        let handler = match ChronicleTransactionHandler::new(bootstrap_policy, bootstrap_entrypoint)
-        {
-            Ok(handler) => handler,
-            Err(e) => panic!("Error initializing TransactionHandler: {e}"),
-        };
+        let mut handler =
+            match ChronicleTransactionHandler::new(bootstrap_policy, bootstrap_entrypoint) {
+                Ok(handler) => handler,
+                Err(e) => panic!("Error initializing TransactionHandler: {e}"),
+            };
+        if matches.contains_id("explain-denials") {
+            handler = handler.explaining_denials();
+        }
         let mut processor = TransactionProcessor::new({
             if let Some(connect) = matches.get_one::<String>("connect") {
                 connect
@@ -1,5 +1,6 @@
 use chronicle_protocol::settings::sawtooth_settings_address;
-use common::opa::{CliPolicyLoader, ExecutorContext};
+use common::opa::{CliPolicyLoader, ExecutorContext, PolicyLoader};
+use opa_tp_protocol::state::NamespacePolicies;
 use protobuf::Message;
 use sawtooth_sdk::{
     messages::setting::Setting,
@@ -11,10 +12,21 @@ use std::{
 };
 use tracing::{debug, info, warn};
 
+/// The hash of a policy loaded from the chain, the data document it was loaded with, and its
+/// context
+type CachedPolicy = (String, Option<Vec<u8>>, ExecutorContext);
+
+/// The policies transactions are checked against. The on-chain policy is read from the state
+/// each transaction sees, so a transaction is checked against the version in effect at its
+/// block, and a policy set with `opactl set-policy` applies from the next transaction on. So
+/// are the policies set for namespaces with `opactl set-namespace-policy`.
 #[derive(Debug)]
 pub struct TpOpa {
     pub embedded: ExecutorContext,
-    pub on_chain: Arc<Mutex<HashMap<String, (String, ExecutorContext)>>>,
+    // By policy name and entrypoint
+    pub on_chain: Arc<Mutex<HashMap<(String, String), CachedPolicy>>>,
+    // Whether denials carry the reasons policies give for them
+    pub explain: bool,
 }
 
 impl TpOpa {
@@ -28,12 +40,24 @@ impl TpOpa {
                 )
                 .map_err(|e| ApplyError::InternalError(e.to_string()))?
             },
+            explain: false,
         })
     }
 
-    pub fn executor_context(
+    /// Explain denials by the embedded policy and policies loaded from the chain
+    pub fn explaining_denials(self) -> Self {
+        Self {
+            embedded: self.embedded.explaining_denials(),
+            explain: true,
+            ..self
+        }
+    }
+
+    /// The context the operations of a transaction on `namespaces` are checked in
+    pub fn executor_context<'a>(
         &self,
         ctx: &mut dyn TransactionContext,
+        namespaces: impl IntoIterator<Item = &'a str>,
     ) -> Result<ExecutorContext, ApplyError> {
         let policy_name_settings_entry =
             ctx.get_state_entry(&sawtooth_settings_address("chronicle.opa.policy_name"))?;
@@ -73,69 +97,126 @@ impl TpOpa {
                         ApplyError::InternalError("Invalid setting entry".to_string())
                     })?;
 
-                let policy_meta_address =
-                    opa_tp_protocol::state::policy_meta_address(&policy_name.value);
-
-                let policy_meta: Vec<u8> =
-                    ctx.get_state_entry(&policy_meta_address)?.ok_or_else(|| {
+                let context = self
+                    .on_chain_context(ctx, &policy_name.value, &policy_entrypoint.value)?
+                    .ok_or_else(|| {
                         ApplyError::InternalError(format!(
                             "Failed to load policy metadata for policy '{}' from '{}'",
-                            policy_name.value, policy_meta_address
-                        ))
-                    })?;
-
-                let policy_meta: opa_tp_protocol::state::PolicyMeta =
-                    serde_json::from_slice(&policy_meta).map_err(|_e| {
-                        ApplyError::InternalError(format!(
-                            "Cannot parse policy meta for {}",
-                            policy_name.value
+                            policy_name.value,
+                            opa_tp_protocol::state::policy_meta_address(&policy_name.value)
                         ))
                     })?;
 
-                debug!(policy_from_submission_meta = ?policy_meta);
-
-                // Check if we have the policy loaded as an executor context and the
-                // loaded policy version against the current policy
-                // version. If either the policy is not loaded or the policy version is nor
-                // current, load the policy from the chain and cache it
-                if let Some((hash, executor_context)) =
-                    self.on_chain.lock().unwrap().get(&policy_name.value)
+                // Only the policies of the namespaces the transaction operates on are loaded
+                let policies = match ctx
+                    .get_state_entry(&opa_tp_protocol::state::namespace_policies_address())?
                 {
-                    if *hash == policy_meta.hash {
-                        return Ok(executor_context.clone());
+                    Some(policies) => serde_json::from_slice::<NamespacePolicies>(&policies)
+                        .map_err(|_e| {
+                            ApplyError::InternalError("Cannot parse namespace policies".to_string())
+                        })?,
+                    None => return Ok(context),
+                };
+
+                let mut contexts = HashMap::new();
+                for namespace in namespaces {
+                    let policy = match policies.policy_for(namespace) {
+                        Some(policy) => policy,
+                        None => continue,
+                    };
+                    let key = (policy.policy_id.clone(), policy.entrypoint.clone());
+                    if contexts.contains_key(&key) {
+                        continue;
                     }
+                    let namespace_context = self
+                        .on_chain_context(ctx, &policy.policy_id, &policy.entrypoint)?
+                        .ok_or_else(|| {
+                            ApplyError::InvalidTransaction(format!(
+                                "Namespace '{namespace}' is checked against policy '{}', which has not been set",
+                                policy.policy_id
+                            ))
+                        })?;
+                    contexts.insert(key, namespace_context);
                 }
 
-                // Load the policy from the chain
-                let policy_bytes = ctx
-                    .get_state_entry(&opa_tp_protocol::state::policy_address(&policy_name.value))?
-                    .ok_or_else(|| {
-                        ApplyError::InternalError(format!(
-                            "Failed to load policy for policy {}",
-                            policy_name.value
-                        ))
-                    })?;
-
-                let loader = CliPolicyLoader::from_policy_bytes(
-                    &policy_name.value,
-                    &policy_entrypoint.value,
-                    &policy_bytes,
-                )
-                .map_err(|e| ApplyError::InternalError(e.to_string()))?;
-
-                let ctx = ExecutorContext::from_loader(&loader)
-                    .map_err(|e| ApplyError::InternalError(e.to_string()))?;
-
-                self.on_chain
-                    .lock()
-                    .unwrap()
-                    .insert(policy_name.value.clone(), (policy_meta.hash, ctx.clone()));
-
-                Ok(ctx)
+                Ok(context.with_namespace_policies(policies, contexts))
             }
             _ => Err(ApplyError::InternalError(
                 "Opa policy settings are invalid".to_string(),
             )),
         }
     }
+
+    /// The context of the on-chain policy `policy_name` with `entrypoint`, loaded from the state
+    /// `ctx` sees unless the policy and its data are as cached. There is none if the policy has
+    /// not been set.
+    fn on_chain_context(
+        &self,
+        ctx: &mut dyn TransactionContext,
+        policy_name: &str,
+        entrypoint: &str,
+    ) -> Result<Option<ExecutorContext>, ApplyError> {
+        let policy_meta_address = opa_tp_protocol::state::policy_meta_address(policy_name);
+
+        let policy_meta: Vec<u8> = match ctx.get_state_entry(&policy_meta_address)? {
+            Some(policy_meta) => policy_meta,
+            None => return Ok(None),
+        };
+
+        let policy_meta: opa_tp_protocol::state::PolicyMeta = serde_json::from_slice(&policy_meta)
+            .map_err(|_e| {
+                ApplyError::InternalError(format!("Cannot parse policy meta for {policy_name}"))
+            })?;
+
+        debug!(policy_from_submission_meta = ?policy_meta);
+
+        let policy_data =
+            ctx.get_state_entry(&opa_tp_protocol::state::policy_data_address(policy_name))?;
+
+        // Check if we have the policy loaded as an executor context and the
+        // loaded policy version and data against the current ones. If either the
+        // policy is not loaded or it is not current, load the policy from the chain
+        // and cache it
+        let key = (policy_name.to_owned(), entrypoint.to_owned());
+        if let Some((hash, data, executor_context)) = self.on_chain.lock().unwrap().get(&key) {
+            if *hash == policy_meta.hash && *data == policy_data {
+                return Ok(Some(executor_context.clone()));
+            }
+        }
+
+        // Load the policy from the chain
+        let policy_bytes = ctx
+            .get_state_entry(&opa_tp_protocol::state::policy_address(policy_name))?
+            .ok_or_else(|| {
+                ApplyError::InternalError(format!("Failed to load policy for policy {policy_name}"))
+            })?;
+
+        let mut loader = CliPolicyLoader::from_policy_bytes(policy_name, entrypoint, &policy_bytes)
+            .map_err(|e| ApplyError::InternalError(e.to_string()))?;
+        if let Some(document) = &policy_data {
+            loader.set_policy_data(Some(
+                opa_tp_protocol::state::policy_data_document(document)
+                    .map_err(|e| ApplyError::InternalError(e.to_string()))?,
+            ));
+        }
+
+        let mut ctx = ExecutorContext::from_loader(&loader)
+            .map_err(|e| ApplyError::InternalError(e.to_string()))?;
+        if self.explain {
+            ctx = ctx.explaining_denials();
+        }
+
+        info!(
+            policy = %policy_name,
+            %entrypoint,
+            hash = %policy_meta.hash,
+            "Loaded on-chain policy"
+        );
+        self.on_chain
+            .lock()
+            .unwrap()
+            .insert(key, (policy_meta.hash, policy_data, ctx.clone()));
+
+        Ok(Some(ctx))
+    }
 }
@@ -1,15 +1,24 @@
-use chronicle_protocol::protocol::{
-    chronicle_committed, chronicle_contradicted, chronicle_identity_from_submission,
-    chronicle_operations_from_submission_v1, chronicle_operations_from_submission_v2,
-    deserialize_submission, messages::Submission,
+use chronicle_protocol::{
+    protocol::{
+        chronicle_committed, chronicle_contradicted, chronicle_identity_from_submission,
+        chronicle_keys_rotated, chronicle_operations_from_submission_v1,
+        chronicle_operations_from_submission_v2, deserialize_submission,
+        messages::{submission::BodyVariant, KeyRotationMessageV1, Submission},
+    },
+    settings::sawtooth_settings_address,
+    state::{
+        agent_nonce_address, chronicle_keys_address, ChronicleKeys, SignedKeyRotation,
+        BOOTSTRAP_KEY_SETTING,
+    },
 };
+use chronicle_signing::TaggedVerifyingKey;
 use common::{
-    identity::{AuthId, OpaData, SignedIdentity},
-    ledger::{OperationState, StateOutput, SubmissionError},
+    identity::{AgentSignedOperations, AuthId, IdentityError, OpaData, SignedIdentity},
+    ledger::{LedgerAddress, OperationState, StateOutput, SubmissionError},
     opa::ExecutorContext,
     prov::{
         operations::ChronicleOperation, to_json_ld::ToJson, ChronicleTransaction,
-        ChronicleTransactionId, ProcessorError, ProvModel,
+        ChronicleTransactionId, ExternalIdPart, ProcessorError, ProvModel, PublicKeyPart,
     },
 };
 use prost::Message;
@@ -18,7 +27,7 @@ use std::collections::{BTreeMap, HashSet};
 use chronicle_protocol::address::{SawtoothAddress, FAMILY, PREFIX, VERSION};
 
 use sawtooth_sdk::{
-    messages::processor::TpProcessRequest,
+    messages::{processor::TpProcessRequest, setting::Setting},
     processor::handler::{ApplyError, TransactionContext, TransactionHandler},
 };
 use tracing::{error, info, instrument, trace};
@@ -45,6 +54,14 @@ impl ChronicleTransactionHandler {
             opa_executor: TpOpa::new(policy, entrypoint)?,
         })
     }
+
+    /// Reject operations that policies deny with the reasons they give
+    pub fn explaining_denials(self) -> Self {
+        Self {
+            opa_executor: self.opa_executor.explaining_denials(),
+            ..self
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -128,14 +145,19 @@ impl TP for ChronicleTransactionHandler {
             }
             "2" => {
                 use transaction::v2::ChronicleTransaction;
-                let ops = chronicle_operations_from_submission_v2(
-                    match submission.body_variant.unwrap() {
-                        chronicle_protocol::protocol::messages::submission::BodyVariant::Body(
-                            body,
-                        ) => body.payload,
-                    },
-                )
-                .await
+                let ops = match submission.body_variant.unwrap() {
+                    BodyVariant::Body(body) => {
+                        chronicle_operations_from_submission_v2(body.payload).await
+                    }
+                    BodyVariant::AgentSigned(signed) => {
+                        AgentSignedOperations::from(signed).operations().await
+                    }
+                    BodyVariant::KeyRotation(_) => {
+                        return Err(ApplyError::InternalError(
+                            "key rotation has no operations".to_string(),
+                        ))
+                    }
+                }
                 .map_err(|e| ApplyError::InternalError(e.to_string()))?;
                 let tx = ChronicleTransaction::new(ops, identity);
                 Ok(tx.to_current())
@@ -182,7 +204,7 @@ impl TP for ChronicleTransactionHandler {
         // Now apply operations to the model
         for operation in operations.tx {
             Self::enforce_opa(
-                opa_executor.clone(),
+                opa_executor.for_namespace(operation.namespace().external_id_part().as_str()),
                 &operations.identity,
                 &operation,
                 &state,
@@ -320,6 +342,173 @@ impl TP for ChronicleTransactionHandler {
     }
 }
 
+fn registered_keys(
+    context: &mut dyn TransactionContext,
+) -> Result<Option<ChronicleKeys>, ApplyError> {
+    context
+        .get_state_entry(&chronicle_keys_address())?
+        .map(|entry| serde_json::from_slice(&entry))
+        .transpose()
+        .map_err(|e| ApplyError::InternalError(e.to_string()))
+}
+
+/// The key set on chain that may register the first Chronicle keys
+fn bootstrap_key(
+    context: &mut dyn TransactionContext,
+) -> Result<Option<TaggedVerifyingKey>, ApplyError> {
+    let setting =
+        match context.get_state_entry(&sawtooth_settings_address(BOOTSTRAP_KEY_SETTING))? {
+            Some(setting) => setting,
+            None => return Ok(None),
+        };
+    let setting: Setting = protobuf::Message::parse_from_bytes(&setting)
+        .map_err(|_e| ApplyError::InternalError("Invalid setting entry".to_string()))?;
+
+    setting
+        .get_entries()
+        .iter()
+        .find(|entry| entry.key == BOOTSTRAP_KEY_SETTING)
+        .map(|entry| {
+            entry.value.trim().parse().map_err(|_| {
+                ApplyError::InvalidTransaction(format!(
+                    "{BOOTSTRAP_KEY_SETTING} is not a hex encoded public key"
+                ))
+            })
+        })
+        .transpose()
+}
+
+/// Once Chronicle keys are registered, identities must be signed with the current one
+fn check_identity_key(
+    keys: Option<&ChronicleKeys>,
+    identity: &SignedIdentity,
+) -> Result<(), ApplyError> {
+    let keys = match keys {
+        Some(keys) => keys,
+        None => return Ok(()),
+    };
+
+    let current = keys
+        .current
+        .verifying_key()
+        .map_err(|e| ApplyError::InternalError(e.to_string()))?;
+
+    identity.verify_with(&current).map(|_| ()).map_err(|e| {
+        ApplyError::InvalidTransaction(format!(
+            "{e}, the current Chronicle key is version {}",
+            keys.current.version
+        ))
+    })
+}
+
+/// Operations signed by an agent must be signed with the key currently registered for it, and
+/// submitted with the identity of the agent so that OPA sees who signed them. Each nonce an
+/// agent signs with is recorded, so the same signed operations cannot be applied again.
+fn check_agent_signature(
+    context: &mut dyn TransactionContext,
+    transaction_id: &str,
+    signed: AgentSignedOperations,
+    operations: &ChronicleTransaction,
+) -> Result<(), ApplyError> {
+    let namespace = match operations.tx.first() {
+        Some(operation) => operation.namespace().clone(),
+        None => {
+            return Err(ApplyError::InvalidTransaction(
+                "Agent signed no operations".to_string(),
+            ))
+        }
+    };
+    if operations.tx.iter().any(|op| op.namespace() != &namespace) {
+        return Err(ApplyError::InvalidTransaction(
+            "Agent signed operations must all be in one namespace".to_string(),
+        ));
+    }
+
+    let no_agent_key = || {
+        ApplyError::InvalidTransaction(IdentityError::NoAgentKey(signed.agent.clone()).to_string())
+    };
+    let address = SawtoothAddress::from(&LedgerAddress::in_namespace(
+        &namespace,
+        signed.agent.clone(),
+    ));
+    let entry = context
+        .get_state_entry(&address.to_string())?
+        .ok_or_else(no_agent_key)?;
+
+    let mut agent = ProvModel::default();
+    futures::executor::block_on(agent.apply_json_ld_bytes(&entry))
+        .map_err(|e| ApplyError::InternalError(e.to_string()))?;
+    let public_key = agent
+        .has_identity
+        .get(&(namespace, signed.agent.clone()))
+        .map(|(_, identity)| identity.public_key_part().to_owned())
+        .ok_or_else(no_agent_key)?;
+
+    let signer = signed
+        .verify(&public_key)
+        .map_err(|e| ApplyError::InvalidTransaction(e.to_string()))?;
+
+    if AuthId::try_from(&operations.identity).ok().as_ref() != Some(&signer) {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Operations signed by {signer} must be submitted with its identity"
+        )));
+    }
+
+    let payload = signed
+        .signed_payload()
+        .map_err(|e| ApplyError::InvalidTransaction(e.to_string()))?;
+    if payload.nonce.is_empty() {
+        return Err(ApplyError::InvalidTransaction(
+            "Agent signed operations must have a nonce".to_string(),
+        ));
+    }
+    let nonce_address = agent_nonce_address(&payload.namespace, &signed.agent, &payload.nonce);
+    if context.get_state_entry(&nonce_address)?.is_some() {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Nonce {} has already been used by {}",
+            payload.nonce, signed.agent
+        )));
+    }
+    context.set_state_entry(nonce_address, transaction_id.as_bytes().to_vec())?;
+
+    Ok(())
+}
+
+#[instrument(skip(request, context, message))]
+fn apply_key_rotation(
+    request: &TpProcessRequest,
+    context: &mut dyn TransactionContext,
+    span: u64,
+    message: KeyRotationMessageV1,
+) -> Result<(), ApplyError> {
+    let registered = registered_keys(context)?;
+    // Only needed for the first rotation
+    let bootstrap = match registered {
+        Some(_) => None,
+        None => bootstrap_key(context)?,
+    };
+    let keys = SignedKeyRotation::from(message)
+        .apply(registered, bootstrap.as_ref())
+        .map_err(|e| ApplyError::InvalidTransaction(e.to_string()))?;
+
+    info!(version = keys.current.version, "Rotated Chronicle key");
+
+    context.set_state_entry(
+        chronicle_keys_address(),
+        serde_json::to_vec(&keys).map_err(|e| ApplyError::InternalError(e.to_string()))?,
+    )?;
+
+    let ev = chronicle_keys_rotated(span, &keys)
+        .map_err(|e| ApplyError::InternalError(e.to_string()))?;
+    context.add_event(
+        "chronicle/prov-update".to_string(),
+        vec![("transaction_id".to_owned(), request.signature.clone())],
+        &ev.encode_to_vec(),
+    )?;
+
+    Ok(())
+}
+
 impl TransactionHandler for ChronicleTransactionHandler {
     fn family_name(&self) -> String {
         self.family_name.clone()
@@ -350,17 +539,32 @@ impl TransactionHandler for ChronicleTransactionHandler {
         context: &mut dyn TransactionContext,
     ) -> Result<(), ApplyError> {
         let submission = Self::tp_parse(request)?;
+        if let Some(BodyVariant::KeyRotation(rotation)) = &submission.body_variant {
+            return apply_key_rotation(request, context, submission.span_id, rotation.clone());
+        }
         let submission_clone = submission.clone();
 
-        let opa_exec_context = self.opa_executor.executor_context(context)?;
-
         let operations =
             futures::executor::block_on(
                 async move { Self::tp_operations(submission.clone()).await },
             )?;
 
+        let namespaces = operations
+            .tx
+            .iter()
+            .map(|op| op.namespace().external_id_part().to_string())
+            .collect::<HashSet<_>>();
+        let opa_exec_context = self
+            .opa_executor
+            .executor_context(context, namespaces.iter().map(String::as_str))?;
+
         info!(transaction_id = %request.signature, operation_count = %operations.tx.len());
 
+        check_identity_key(registered_keys(context)?.as_ref(), &operations.identity)?;
+        if let Some(BodyVariant::AgentSigned(signed)) = submission_clone.body_variant.clone() {
+            check_agent_signature(context, &request.signature, signed.into(), &operations)?;
+        }
+
         let state = Self::tp_state(context, &operations)?;
         let effects = futures::executor::block_on(async move {
             Self::tp(
@@ -393,27 +597,36 @@ pub mod test {
         async_stl_client::{ledger::LedgerTransaction, sawtooth::MessageBuilder},
         messages::ChronicleSubmitTransaction,
         protocol::messages::Submission,
+        settings::sawtooth_settings_address,
+        state::{ChronicleKeys, SignedKeyRotation, BOOTSTRAP_KEY_SETTING},
     };
     use chronicle_signing::{
-        chronicle_secret_names, ChronicleSecretsOptions, ChronicleSigning, BATCHER_NAMESPACE,
-        CHRONICLE_NAMESPACE,
+        chronicle_secret_names, ChronicleKnownKeyNamesSigner, ChronicleSecretsOptions,
+        ChronicleSigning, TaggedVerifyingKey, BATCHER_NAMESPACE, CHRONICLE_NAMESPACE,
     };
     use chrono::{NaiveDateTime, TimeZone, Utc};
     use common::{
-        identity::{AuthId, SignedIdentity},
+        identity::{AgentKeyId, AgentSignedOperations, AgentSignedPayload, AuthId, SignedIdentity},
+        k256::ecdsa::{signature::Signer, Signature, SigningKey},
         prov::{
             operations::{
-                ActsOnBehalfOf, AgentExists, ChronicleOperation, CreateNamespace, EndActivity,
-                StartActivity,
+                ActivityExists, ActsOnBehalfOf, AgentExists, ChronicleOperation, CreateNamespace,
+                EndActivity, RegisterKey, StartActivity,
             },
+            to_json_ld::ToJson,
             ActivityId, AgentId, ChronicleTransaction, DelegationId, ExternalId, ExternalIdPart,
             NamespaceId, Role,
         },
     };
     use prost::Message;
+    use rand::{rngs::StdRng, SeedableRng};
 
     use sawtooth_sdk::{
-        messages::{processor::TpProcessRequest, transaction::TransactionHeader},
+        messages::{
+            processor::TpProcessRequest,
+            setting::{Setting, Setting_Entry},
+            transaction::TransactionHeader,
+        },
         processor::handler::{ContextError, TransactionContext, TransactionHandler},
     };
     use serde_json::Value;
@@ -530,18 +743,14 @@ pub mod test {
         }
     }
 
-    #[derive(Debug, Clone)]
-    struct SameUuid;
-
     fn uuid() -> Uuid {
         Uuid::parse_str("5a0ab5b8-eeb7-4812-9fe3-6dd69bd20cea").unwrap()
     }
 
     fn create_namespace_id_helper(tag: Option<i32>) -> NamespaceId {
-        let external_id = if tag.is_none() || tag == Some(0) {
-            "testns".to_string()
-        } else {
-            format!("testns{}", tag.unwrap())
+        let external_id = match tag {
+            None | Some(0) => "testns".to_string(),
+            Some(tag) => format!("testns{tag}"),
         };
         NamespaceId::from_external_id(external_id, uuid())
     }
@@ -621,6 +830,8 @@ pub mod test {
             tx,
             signer: secrets.clone(),
             policy_name: None,
+            key_rotation: None,
+            agent_signed: None,
         };
 
         let message_builder = MessageBuilder::new_deterministic("TEST", "1.0");
@@ -793,4 +1004,361 @@ pub mod test {
             .tx;
         assert_eq!(expected_operations, actual_operations);
     }
+
+    async fn process_request(submit_tx: ChronicleSubmitTransaction) -> TpProcessRequest {
+        let message_builder = MessageBuilder::new_deterministic("TEST", "1.0");
+        let (tx, _id) = submit_tx.as_sawtooth_tx(&message_builder).await.unwrap();
+
+        let header =
+            <TransactionHeader as protobuf::Message>::parse_from_bytes(&tx.header).unwrap();
+
+        let mut request = TpProcessRequest::default();
+        request.set_header(header);
+        request.set_payload(tx.payload);
+        request.set_signature("TRANSACTION_SIGNATURE".to_string());
+        request
+    }
+
+    /// The setting that lets `key` make the first key rotation
+    fn bootstrap_key_setting(key: &TaggedVerifyingKey) -> (String, Vec<u8>) {
+        let setting = Setting {
+            entries: vec![Setting_Entry {
+                key: BOOTSTRAP_KEY_SETTING.to_string(),
+                value: key.to_string(),
+                ..Default::default()
+            }]
+            .into(),
+            ..Default::default()
+        };
+
+        (
+            sawtooth_settings_address(BOOTSTRAP_KEY_SETTING),
+            protobuf::Message::write_to_bytes(&setting).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn identities_must_be_signed_with_the_current_key() {
+        let signing = |chronicle_key: ChronicleSecretsOptions| async move {
+            ChronicleSigning::new(
+                chronicle_secret_names(),
+                vec![
+                    (CHRONICLE_NAMESPACE.to_string(), chronicle_key),
+                    (
+                        BATCHER_NAMESPACE.to_string(),
+                        ChronicleSecretsOptions::test_keys(),
+                    ),
+                ],
+            )
+            .await
+            .unwrap()
+        };
+        let previous = signing(ChronicleSecretsOptions::test_keys()).await;
+        let new = signing(ChronicleSecretsOptions::generate_in_memory()).await;
+
+        let rotation = SignedKeyRotation::new(&previous, &new, None).await.unwrap();
+        let rotate = process_request(ChronicleSubmitTransaction::rotate_key(
+            rotation,
+            previous.clone(),
+        ))
+        .await;
+
+        let mut create_namespace = vec![];
+        for signing in [&previous, &new] {
+            let tx = ChronicleTransaction::new(
+                vec![create_namespace_helper(None)],
+                AuthId::chronicle().signed_identity(signing).unwrap(),
+            );
+            create_namespace.push(
+                process_request(ChronicleSubmitTransaction::new(tx, signing.clone(), None)).await,
+            );
+        }
+
+        let (policy, entrypoint) = ("allow_transactions", "allow_transactions.allowed_users");
+        let bootstrap = bootstrap_key_setting(&previous.chronicle_verifying().await.unwrap());
+
+        tokio::task::spawn_blocking(move || {
+            let mut context = TestTransactionContext::new();
+            context.state.borrow_mut().insert(bootstrap.0, bootstrap.1);
+            let handler = ChronicleTransactionHandler::new(policy, entrypoint).unwrap();
+
+            // Until a key is registered, any will do
+            handler.apply(&create_namespace[0], &mut context).unwrap();
+            handler.apply(&rotate, &mut context).unwrap();
+
+            let event = chronicle_protocol::sawtooth::Event::decode(
+                &*context.events.borrow().last().unwrap().2,
+            )
+            .unwrap();
+            let keys: ChronicleKeys = serde_json::from_str(&event.keys).unwrap();
+            assert_eq!(keys.current.version, 1);
+            assert_eq!(keys.expired[0].version, 0);
+
+            assert!(handler.apply(&create_namespace[0], &mut context).is_err());
+            handler.apply(&create_namespace[1], &mut context).unwrap();
+
+            // The rotation cannot be replayed
+            assert!(handler.apply(&rotate, &mut context).is_err());
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn first_rotation_by_a_stranger_is_rejected() {
+        let signing = |chronicle_key: ChronicleSecretsOptions| async move {
+            ChronicleSigning::new(
+                chronicle_secret_names(),
+                vec![
+                    (CHRONICLE_NAMESPACE.to_string(), chronicle_key),
+                    (
+                        BATCHER_NAMESPACE.to_string(),
+                        ChronicleSecretsOptions::test_keys(),
+                    ),
+                ],
+            )
+            .await
+            .unwrap()
+        };
+        let bootstrap = signing(ChronicleSecretsOptions::test_keys()).await;
+        let stranger = signing(ChronicleSecretsOptions::generate_in_memory()).await;
+        let new = signing(ChronicleSecretsOptions::generate_in_memory()).await;
+
+        let rotate = process_request(ChronicleSubmitTransaction::rotate_key(
+            SignedKeyRotation::new(&stranger, &new, None).await.unwrap(),
+            stranger.clone(),
+        ))
+        .await;
+        let setting = bootstrap_key_setting(&bootstrap.chronicle_verifying().await.unwrap());
+
+        let (policy, entrypoint) = ("allow_transactions", "allow_transactions.allowed_users");
+
+        tokio::task::spawn_blocking(move || {
+            let mut context = TestTransactionContext::new();
+            let handler = ChronicleTransactionHandler::new(policy, entrypoint).unwrap();
+
+            // Without a bootstrap key no one can make the first rotation
+            assert!(handler.apply(&rotate, &mut context).is_err());
+
+            context.state.borrow_mut().insert(setting.0, setting.1);
+            assert!(handler.apply(&rotate, &mut context).is_err());
+            assert!(context
+                .state
+                .borrow()
+                .get(&chronicle_protocol::state::chronicle_keys_address())
+                .is_none());
+        })
+        .await
+        .unwrap();
+    }
+
+    /// The payload an agent signs to submit `operation` with `nonce`
+    async fn signed_payload(operation: &ChronicleOperation, nonce: &str) -> String {
+        serde_json::to_string(&AgentSignedPayload {
+            namespace: operation.namespace().to_string(),
+            nonce: nonce.to_owned(),
+            operations: vec![operation.to_json().compact_stable_order().await.unwrap()],
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn agent_signed_operations_must_be_signed_with_the_agent_key() {
+        let signing = ChronicleSigning::new(
+            chronicle_secret_names(),
+            vec![
+                (
+                    CHRONICLE_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::test_keys(),
+                ),
+                (
+                    BATCHER_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::test_keys(),
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+        let (key, other_key) = (
+            SigningKey::random(StdRng::from_entropy()),
+            SigningKey::random(StdRng::from_entropy()),
+        );
+        let agent = AgentId::from_external_id("test_agent");
+        let public_key = hex::encode(key.verifying_key().to_bytes());
+
+        let register_key = process_request(ChronicleSubmitTransaction::new(
+            ChronicleTransaction::new(
+                vec![
+                    create_namespace_helper(None),
+                    agent_exists_helper(),
+                    ChronicleOperation::RegisterKey(RegisterKey {
+                        namespace: create_namespace_id_helper(None),
+                        id: agent.clone(),
+                        publickey: public_key.clone(),
+                    }),
+                ],
+                AuthId::chronicle().signed_identity(&signing).unwrap(),
+            ),
+            signing.clone(),
+            None,
+        ))
+        .await;
+
+        let operation = ChronicleOperation::ActivityExists(ActivityExists {
+            namespace: create_namespace_id_helper(None),
+            external_id: "signed_activity".into(),
+        });
+        let payload = signed_payload(&operation, "1").await;
+
+        let mut requests = vec![];
+        for (signing_key, identity) in [
+            (
+                &key,
+                AuthId::Agent(AgentKeyId {
+                    id: agent.clone(),
+                    public_key: public_key.clone(),
+                }),
+            ),
+            (
+                &other_key,
+                AuthId::Agent(AgentKeyId {
+                    id: agent.clone(),
+                    public_key: public_key.clone(),
+                }),
+            ),
+            // Correctly signed, but submitted as someone else
+            (&key, AuthId::chronicle()),
+        ] {
+            let signature: Signature = signing_key.sign(payload.as_bytes());
+            requests.push(
+                process_request(ChronicleSubmitTransaction::agent_signed(
+                    ChronicleTransaction::new(
+                        vec![operation.clone()],
+                        identity.signed_identity(&signing).unwrap(),
+                    ),
+                    AgentSignedOperations {
+                        agent: agent.clone(),
+                        payload: payload.clone(),
+                        signature: signature.as_ref().to_vec(),
+                    },
+                    signing.clone(),
+                    None,
+                ))
+                .await,
+            );
+        }
+
+        let (policy, entrypoint) = ("allow_transactions", "allow_transactions.allowed_users");
+
+        tokio::task::spawn_blocking(move || {
+            let mut context = TestTransactionContext::new();
+            let handler = ChronicleTransactionHandler::new(policy, entrypoint).unwrap();
+
+            // The agent has no key yet
+            assert!(handler.apply(&requests[0], &mut context).is_err());
+
+            handler.apply(&register_key, &mut context).unwrap();
+            handler.apply(&requests[0], &mut context).unwrap();
+
+            let event = chronicle_protocol::sawtooth::Event::decode(
+                &*context.events.borrow().last().unwrap().2,
+            )
+            .unwrap();
+            assert!(event.delta.contains("signed%5Factivity"));
+
+            assert!(handler.apply(&requests[1], &mut context).is_err());
+            assert!(handler.apply(&requests[2], &mut context).is_err());
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn agent_signed_operations_cannot_be_replayed() {
+        let signing = ChronicleSigning::new(
+            chronicle_secret_names(),
+            vec![
+                (
+                    CHRONICLE_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::test_keys(),
+                ),
+                (
+                    BATCHER_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::test_keys(),
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+        let key = SigningKey::random(StdRng::from_entropy());
+        let agent = AgentId::from_external_id("test_agent");
+        let public_key = hex::encode(key.verifying_key().to_bytes());
+
+        let register_key = process_request(ChronicleSubmitTransaction::new(
+            ChronicleTransaction::new(
+                vec![
+                    create_namespace_helper(None),
+                    agent_exists_helper(),
+                    ChronicleOperation::RegisterKey(RegisterKey {
+                        namespace: create_namespace_id_helper(None),
+                        id: agent.clone(),
+                        publickey: public_key.clone(),
+                    }),
+                ],
+                AuthId::chronicle().signed_identity(&signing).unwrap(),
+            ),
+            signing.clone(),
+            None,
+        ))
+        .await;
+
+        let operation = ChronicleOperation::ActivityExists(ActivityExists {
+            namespace: create_namespace_id_helper(None),
+            external_id: "signed_activity".into(),
+        });
+        let identity = AuthId::Agent(AgentKeyId {
+            id: agent.clone(),
+            public_key,
+        })
+        .signed_identity(&signing)
+        .unwrap();
+
+        // Each submission of the signed operations is a transaction of its own, so only the
+        // nonce tells a replay apart
+        let mut requests = vec![];
+        for nonce in ["1", "1", "2"] {
+            let payload = signed_payload(&operation, nonce).await;
+            let signature: Signature = key.sign(payload.as_bytes());
+            requests.push(
+                process_request(ChronicleSubmitTransaction::agent_signed(
+                    ChronicleTransaction::new(vec![operation.clone()], identity.clone()),
+                    AgentSignedOperations {
+                        agent: agent.clone(),
+                        payload,
+                        signature: signature.as_ref().to_vec(),
+                    },
+                    signing.clone(),
+                    None,
+                ))
+                .await,
+            );
+        }
+
+        let (policy, entrypoint) = ("allow_transactions", "allow_transactions.allowed_users");
+
+        tokio::task::spawn_blocking(move || {
+            let mut context = TestTransactionContext::new();
+            let handler = ChronicleTransactionHandler::new(policy, entrypoint).unwrap();
+
+            handler.apply(&register_key, &mut context).unwrap();
+            handler.apply(&requests[0], &mut context).unwrap();
+
+            assert!(handler.apply(&requests[1], &mut context).is_err());
+            assert!(handler.apply(&requests[0], &mut context).is_err());
+
+            handler.apply(&requests[2], &mut context).unwrap();
+        })
+        .await
+        .unwrap();
+    }
 }
@@ -15,3 +15,9 @@ allow_defines {
 }
 
 default deny_all = false
+
+default allow_except_hidden_data = false
+allow_except_hidden_data {
+  common_rules.allowed_users
+  not common_rules.reads_hidden_data
+}
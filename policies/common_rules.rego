@@ -3,7 +3,7 @@ package common_rules
 import future.keywords.in
 import input
 
-allowed := {"chronicle", "anonymous", "jwt"}
+allowed := {"chronicle", "anonymous", "jwt", "agent"}
 
 allowed_users {
   input.type in allowed
@@ -13,3 +13,8 @@ allow_defines {
   data.context.operation in ["Mutation", "Submission"]
   startswith(data.context.state[0], "define")
 }
+
+reads_hidden_data {
+  data.context.operation == "ReadData"
+  startswith(data.context.state.id, "hidden")
+}